@@ -1,10 +1,48 @@
+pub mod appearance;
 pub mod assistant;
+pub mod audit;
+pub mod backup;
+pub mod catalog;
 pub mod chat;
+pub mod convert;
+pub mod diagnostics;
+pub mod download;
+pub mod editor;
+pub mod exec;
+pub mod executor;
+pub mod footnote;
+pub mod gguf;
+pub mod guardrails;
+pub mod hardware;
+pub mod keymap;
+pub mod knowledge;
+pub mod locale;
+pub mod mcp;
+pub mod pool;
+pub mod profile;
+pub mod remote;
+pub mod secret;
+pub mod settings;
+pub mod setup;
+pub mod template;
+pub mod theme;
+pub mod tools;
+pub mod trace;
+pub mod usage;
+pub mod web;
+pub mod window;
+
+#[cfg(test)]
+mod snapshot;
 
 pub use chat::Chat;
+pub use settings::Settings;
 
 use std::io;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs;
 use tokio::task;
 
 #[derive(Debug, Clone, thiserror::Error)]
@@ -19,10 +57,43 @@ pub enum Error {
     ExecutorFailed(&'static str),
     #[error("deserialization failed: {0}")]
     DecodingFailed(Arc<serde_json::Error>),
+    #[error("settings are invalid: {0}")]
+    InvalidSettings(Arc<toml::de::Error>),
+    #[error("settings serialization failed: {0}")]
+    SettingsEncodingFailed(Arc<toml::ser::Error>),
+    #[error("import failed: {0}")]
+    ImportFailed(&'static str),
+    #[error("archive failed: {0}")]
+    ArchiveFailed(Arc<zip::result::ZipError>),
+    #[cfg(feature = "sqlite")]
+    #[error("database failed: {0}")]
+    DatabaseFailed(Arc<rusqlite::Error>),
+    #[error("keyring failed: {0}")]
+    KeyringFailed(Arc<keyring::Error>),
+    #[error("invalid backup: {0}")]
+    InvalidBackup(&'static str),
+    #[error("unknown template variable: {{{{{0}}}}}")]
+    UnknownVariable(String),
     #[error("task join failed: {0}")]
     JoinFailed(Arc<task::JoinError>),
+    #[error("invalid model file: {0}")]
+    InvalidModelFile(&'static str),
+    #[error("conversion failed: {0}")]
+    ConversionFailed(&'static str),
+    #[error("download failed: {0}")]
+    DownloadFailed(&'static str),
+    #[error("checksum mismatch: {0} is corrupted")]
+    ChecksumMismatch(String),
+    #[error("the model does not fit in memory")]
+    OutOfMemory,
+    #[error("MCP server failed: {0}")]
+    McpFailed(String),
+    #[error("invalid llama-server flags: {0}")]
+    InvalidFlags(String),
     #[error("no suitable executor was found: neither llama-server nor docker are installed")]
     NoExecutorAvailable,
+    #[error("rate-limited by Hugging Face")]
+    RateLimited(Option<Duration>),
 }
 
 impl From<reqwest::Error> for Error {
@@ -37,14 +108,65 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<zip::result::ZipError> for Error {
+    fn from(error: zip::result::ZipError) -> Self {
+        Self::ArchiveFailed(Arc::new(error))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::DatabaseFailed(Arc::new(error))
+    }
+}
+
+impl From<keyring::Error> for Error {
+    fn from(error: keyring::Error) -> Self {
+        Self::KeyringFailed(Arc::new(error))
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(error: serde_json::Error) -> Self {
         Self::DecodingFailed(Arc::new(error))
     }
 }
 
+impl From<toml::de::Error> for Error {
+    fn from(error: toml::de::Error) -> Self {
+        Self::InvalidSettings(Arc::new(error))
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(error: toml::ser::Error) -> Self {
+        Self::SettingsEncodingFailed(Arc::new(error))
+    }
+}
+
 impl From<task::JoinError> for Error {
     fn from(error: task::JoinError) -> Self {
         Self::JoinFailed(Arc::new(error))
     }
 }
+
+async fn data_dir() -> Result<PathBuf, io::Error> {
+    let directory = data_path();
+
+    fs::create_dir_all(&directory).await?;
+
+    Ok(directory)
+}
+
+fn data_path() -> PathBuf {
+    dirs_next::data_local_dir()
+        .unwrap_or(PathBuf::from("."))
+        .join("icebreaker")
+}
+
+pub async fn available_space() -> Result<u64, Error> {
+    let directory = data_dir().await?;
+
+    Ok(task::spawn_blocking(move || fs2::available_space(directory)).await??)
+}
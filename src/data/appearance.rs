@@ -0,0 +1,125 @@
+use iced::Font;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// How the app and its chats are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Appearance {
+    pub icons: Icons,
+    pub icon_scale: Scale,
+    /// The scale of the whole interface.
+    pub scale: Scale,
+    /// The font of the messages in a chat.
+    pub chat_font: ChatFont,
+    /// The size of the text of messages.
+    pub text_size: TextSize,
+}
+
+/// A set of icons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Icons {
+    #[default]
+    Standard,
+    /// Bold, monochrome symbols that stand out against any background.
+    HighContrast,
+}
+
+impl Icons {
+    pub const ALL: &'static [Self] = &[Self::Standard, Self::HighContrast];
+}
+
+impl fmt::Display for Icons {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Icons::Standard => "Standard",
+            Icons::HighContrast => "High contrast",
+        })
+    }
+}
+
+/// The size of icons, as a percentage of their default size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Scale(pub u16);
+
+impl Scale {
+    pub const ALL: &'static [Self] = &[Self(75), Self(100), Self(125), Self(150), Self(200)];
+
+    pub fn apply(self, size: f32) -> f32 {
+        size * f32::from(self.0) / 100.0
+    }
+
+    pub fn factor(self) -> f64 {
+        f64::from(self.0) / 100.0
+    }
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self(100)
+    }
+}
+
+impl fmt::Display for Scale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+/// The font family of the messages in a chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatFont {
+    #[default]
+    Proportional,
+    Monospace,
+}
+
+impl ChatFont {
+    pub const ALL: &'static [Self] = &[Self::Proportional, Self::Monospace];
+
+    pub fn font(self) -> Font {
+        match self {
+            ChatFont::Proportional => Font::DEFAULT,
+            ChatFont::Monospace => Font::MONOSPACE,
+        }
+    }
+}
+
+impl fmt::Display for ChatFont {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChatFont::Proportional => "Proportional",
+            ChatFont::Monospace => "Monospace",
+        })
+    }
+}
+
+/// The size of the text of messages, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TextSize(pub u16);
+
+impl TextSize {
+    pub const ALL: &'static [Self] = &[Self(12), Self(14), Self(16), Self(18), Self(20), Self(24)];
+
+    /// The size, shifted by the given amount of pixels.
+    pub fn offset(self, pixels: i16) -> f32 {
+        (f32::from(self.0) + f32::from(pixels)).max(8.0)
+    }
+}
+
+impl Default for TextSize {
+    fn default() -> Self {
+        Self(16)
+    }
+}
+
+impl fmt::Display for TextSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} px", self.0)
+    }
+}
@@ -1,3 +1,13 @@
+use crate::data::audit;
+use crate::data::convert;
+use crate::data::download;
+use crate::data::executor::{self, Executor};
+use crate::data::gguf;
+use crate::data::profile::Profile;
+use crate::data::remote::{self, Remote};
+use crate::data::secret::Secret;
+use crate::data::tools::Tool;
+use crate::data::trace;
 use crate::data::Error;
 
 use futures::channel::mpsc;
@@ -5,28 +15,44 @@ use futures::{FutureExt, SinkExt, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tokio::fs;
-use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt};
+use tokio::io::{self, AsyncBufReadExt};
 use tokio::process;
 
+use std::collections::VecDeque;
 use std::fmt;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct Assistant {
     file: File,
-    _server: Arc<Server>,
+    context_size: Option<u64>,
+    tool_calling: bool,
+    raw_completion: bool,
+    summarizer: Option<Box<Assistant>>,
+    host: String,
+    port: u64,
+    server: Arc<Server>,
+    logs: Logs,
 }
 
 impl Assistant {
-    const LLAMA_CPP_CONTAINER_CPU: &'static str = "ghcr.io/ggerganov/llama.cpp:server-b4600";
-    const LLAMA_CPP_CONTAINER_CUDA: &'static str = "ghcr.io/ggerganov/llama.cpp:server-cuda-b4600";
-    const LLAMA_CPP_CONTAINER_ROCM: &'static str = "ghcr.io/hecrj/icebreaker:server-rocm-b4600";
-
     const MODELS_DIR: &'static str = "./models";
-    const HOST_PORT: u64 = 8080;
 
-    pub fn boot(file: File, backend: Backend) -> impl Stream<Item = Result<BootEvent, Error>> {
+    /// The context size of `llama-server` when none is given.
+    const DEFAULT_CONTEXT_SIZE: u32 = 4096;
+    pub const HOST_PORT: u64 = 8080;
+
+    /// The port of a second assistant booted to compare replies with.
+    pub const COMPARISON_PORT: u64 = 8082;
+
+    /// Boots an assistant whose server listens on the given port.
+    pub fn boot(
+        file: File,
+        launch: Launch,
+        port: u64,
+    ) -> impl Stream<Item = Result<BootEvent, Error>> {
         #[derive(Clone)]
         struct Sender(mpsc::Sender<BootEvent>);
 
@@ -44,10 +70,33 @@ impl Assistant {
             }
         }
 
-        iced::stream::try_channel(1, move |sender| async move {
+        let Launch {
+            backend,
+            inference,
+            extra_flags,
+            executor,
+        } = launch;
+
+        let span = tracing::info_span!("boot", model = %file.name, %backend, port);
+
+        let stream = iced::stream::try_channel(1, move |sender| async move {
             let mut sender = Sender(sender);
+            let extra_arguments = parse_flags(&extra_flags)?;
+
+            let inference = match Profile::find(&file, backend).await {
+                Some(profile) => {
+                    let inference = inference.or(profile);
+
+                    sender
+                        .log(format!("Applying tuned {backend} profile..."))
+                        .await;
+
+                    inference
+                }
+                None => inference,
+            };
 
-            fs::create_dir_all(Self::MODELS_DIR).await?;
+            fs::create_dir_all(Assistant::MODELS_DIR).await?;
 
             let model_path = format!(
                 "{directory}/{filename}",
@@ -64,38 +113,49 @@ impl Assistant {
                     ))
                     .await;
 
-                let metadata = reqwest::get(format!(
-                    "https://huggingface.co/{model}/raw/main/{filename}",
-                    model = file.model.0,
-                    filename = file.name
-                ))
-                .await?
-                .text()
-                .await?;
-
-                let size: u64 = metadata
-                    .lines()
-                    .next_back()
-                    .unwrap_or_default()
-                    .split_whitespace()
-                    .next_back()
-                    .unwrap_or_default()
-                    .parse()
-                    .unwrap_or_default();
-
                 let file_metadata = fs::metadata(&model_path).await?;
 
-                if size == file_metadata.len() {
-                    sender.log(format!("File sizes match! {size} bytes")).await;
+                // Hugging Face knows nothing about local files, even when
+                // their id matches a real repository
+                let pointer = if file.is_local().await {
+                    None
                 } else {
-                    sender
-                        .log(format!(
-                            "Invalid file size. Deleting {filename}...",
-                            filename = file.name
-                        ))
-                        .await;
+                    Some(file.pointer().await)
+                };
+
+                match pointer {
+                    Some(Ok(pointer)) if pointer.size == file_metadata.len() => {
+                        sender
+                            .log(format!(
+                                "File sizes match! {size} bytes",
+                                size = pointer.size
+                            ))
+                            .await;
+                    }
+                    Some(Ok(_)) => {
+                        sender
+                            .log(format!(
+                                "Invalid file size. Deleting {filename}...",
+                                filename = file.name
+                            ))
+                            .await;
+
+                        fs::remove_file(&model_path).await?;
+                    }
+                    // Offline, or for local files, only the header of the
+                    // file can be checked
+                    pointer => {
+                        let reason = match pointer {
+                            Some(Err(error)) => format!("Could not verify online: {error}"),
+                            _ => "Local file".to_owned(),
+                        };
+
+                        sender.log(format!("{reason}. Reading header...")).await;
+
+                        let _ = gguf::Metadata::read(PathBuf::from(&model_path)).await?;
 
-                    fs::remove_file(&model_path).await?;
+                        sender.log("Header is valid!".to_owned()).await;
+                    }
                 }
             }
 
@@ -107,203 +167,285 @@ impl Assistant {
                     ))
                     .await;
 
-                let mut model = io::BufWriter::new(fs::File::create(&model_path).await?);
+                let mut attempts = 0;
 
-                let mut download = {
-                    let url = format!(
-                        "https://huggingface.co\
-                            /{id}/resolve/main/\
-                            {filename}?download=true",
-                        id = file.model.0,
-                        filename = file.name
-                    );
+                loop {
+                    attempts += 1;
 
-                    reqwest::get(url)
-                }
-                .await?;
+                    sender
+                        .log(format!("Downloading {file}...", file = file.name))
+                        .await;
 
-                let model_size = download.content_length();
-                let mut downloaded = 0;
-                let mut progress = 0;
-                let start = Instant::now();
+                    let mut download = download::run(file.clone()).boxed();
+                    let mut progress = 0;
 
-                sender
-                    .log(format!("Downloading {file}...", file = file.name))
-                    .await;
+                    let result = async {
+                        while let Some(event) = download.next().await.transpose()? {
+                            let download = match event {
+                                download::Event::Progressed(download) => download,
+                                download::Event::Verifying => {
+                                    sender.progress("Verifying checksum...", 100).await;
+                                    sender.log("Verifying SHA256 checksum...".to_owned()).await;
 
-                while let Some(chunk) = download.chunk().await? {
-                    downloaded += chunk.len() as u64;
+                                    continue;
+                                }
+                                download::Event::Finished => {
+                                    sender.log("Download complete!".to_owned()).await;
+
+                                    continue;
+                                }
+                                download::Event::Converting | download::Event::Quantizing => {
+                                    continue;
+                                }
+                            };
 
-                    let speed = downloaded as f32 / start.elapsed().as_secs_f32();
+                            let Some(model_size) = download.total else {
+                                continue;
+                            };
 
-                    if let Some(model_size) = model_size {
-                        let new_progress =
-                            (100.0 * downloaded as f32 / model_size as f32).round() as u64;
+                            let new_progress = download.percent().round() as u64;
 
-                        if new_progress > progress {
-                            progress = new_progress;
+                            if new_progress > progress {
+                                progress = new_progress;
+
+                                sender.progress("Downloading model...", progress).await;
+
+                                if progress % 5 == 0 {
+                                    sender
+                                        .log(format!(
+                                            "=> {progress}% {downloaded:.2}GB of {model_size:.2}GB @ {speed:.2} MB/s",
+                                            downloaded = download.downloaded as f32 / 10f32.powi(9),
+                                            model_size = model_size as f32 / 10f32.powi(9),
+                                            speed = download.speed / 10f32.powi(6),
+                                        ))
+                                        .await;
+                                }
+                            }
+                        }
 
-                            sender.progress("Downloading model...", progress).await;
+                        Ok::<_, Error>(())
+                    }
+                    .await;
 
-                            if progress % 5 == 0 {
-                                sender
+                    match result {
+                        Err(Error::ChecksumMismatch(_)) if attempts < 2 => {
+                            sender
                                 .log(format!(
-                                    "=> {progress}% {downloaded:.2}GB of {model_size:.2}GB @ {speed:.2} MB/s",
-                                    downloaded = downloaded as f32 / 10f32.powi(9),
-                                    model_size = model_size as f32 / 10f32.powi(9),
-                                    speed = speed / 10f32.powi(6),
+                                    "Checksum mismatch! {filename} is corrupted. Downloading again...",
+                                    filename = file.name
                                 ))
                                 .await;
-                            }
                         }
+                        result => break result?,
                     }
+                }
+            }
+
+            let inference = match gguf::Metadata::read(PathBuf::from(&model_path)).await {
+                Ok(metadata) => {
+                    sender.log(format!("Model: {}", metadata.summary())).await;
+
+                    // Never go past the context the model was trained with
+                    inference.or(Inference {
+                        context_size: metadata.context_length().map(|context_length| {
+                            context_length.min(Self::DEFAULT_CONTEXT_SIZE.into()) as u32
+                        }),
+                        ..Inference::default()
+                    })
+                }
+                Err(error) => {
+                    sender
+                        .log(format!("Could not read the model metadata: {error}"))
+                        .await;
 
-                    model.write_all(&chunk).await?;
+                    inference
                 }
+            };
 
-                model.flush().await?;
+            if !extra_flags.trim().is_empty() {
+                sender.log(format!("Extra flags: {extra_flags}")).await;
             }
 
-            sender.progress("Detecting executor...", 0).await;
-
-            let (server, stdout, stderr) = if let Ok(version) =
-                process::Command::new("llama-server")
-                    .arg("--version")
-                    .output()
-                    .await
-            {
+            // A previous assistant may still be shutting down
+            if Self::is_healthy("localhost", port).await {
                 sender
-                    .log("Local llama-server binary found!".to_owned())
+                    .progress("Waiting for previous assistant...", 0)
                     .await;
 
-                let mut lines = version.stdout.lines();
+                let _ = tokio::time::timeout(Duration::from_secs(10), async {
+                    while Self::is_healthy("localhost", port).await {
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                    }
+                })
+                .await;
+            }
 
-                while let Some(line) = lines.next_line().await? {
-                    sender.log(line).await;
-                }
+            sender.progress("Detecting executor...", 0).await;
 
-                sender.progress("Launching assistant...", 99).await;
+            let (server, stdout, stderr) = match executor.locate(backend).await? {
+                executor::Program::Binary(executable) => {
+                    sender
+                        .log(format!("{} binary found!", executable.display()))
+                        .await;
 
-                sender
-                    .log(format!(
-                        "Launching {model} with local llama-server...",
-                        model = file.model.name(),
-                    ))
-                    .await;
+                    let version = process::Command::new(&executable)
+                        .arg("--version")
+                        .output()
+                        .await?;
 
-                let mut server = Self::launch_with_executable("llama-server", &file, backend)?;
-                let stdout = server.stdout.take();
-                let stderr = server.stderr.take();
+                    let mut lines = version.stdout.lines();
 
-                (Server::Process(server), stdout, stderr)
-            } else if let Ok(_docker) = process::Command::new("docker")
-                .arg("version")
-                .output()
-                .await
-            {
-                sender
-                    .log(format!(
-                        "Launching {model} with Docker...",
-                        model = file.model.name(),
-                    ))
-                    .await;
+                    while let Some(line) = lines.next_line().await? {
+                        sender.log(line).await;
+                    }
 
-                sender.progress("Preparing container...", 0).await;
+                    sender.progress("Launching assistant...", 99).await;
 
-                let command = match backend {
-                    Backend::Cpu => {
-                        format!(
-                            "create --rm -p {port}:80 -v {volume}:/models \
-                            {container} --model /models/{filename} \
-                            --port 80 --host 0.0.0.0",
-                            filename = file.name,
-                            container = Self::LLAMA_CPP_CONTAINER_CPU,
-                            port = Self::HOST_PORT,
-                            volume = Self::MODELS_DIR,
-                        )
+                    sender
+                        .log(format!(
+                            "Launching {model} with local llama-server...",
+                            model = file.model.name(),
+                        ))
+                        .await;
+
+                    sender.log(format!("Using the {backend} backend")).await;
+
+                    let mut server = Self::launch_with_executable(
+                        &executable,
+                        &file,
+                        backend,
+                        inference,
+                        &extra_arguments,
+                        port,
+                    )?;
+                    let stdout = server.stdout.take();
+                    let stderr = server.stderr.take();
+
+                    (Server::Process(server), stdout, stderr)
+                }
+                executor::Program::Docker => {
+                    sender
+                        .log(format!(
+                            "Launching {model} with Docker...",
+                            model = file.model.name(),
+                        ))
+                        .await;
+
+                    sender.progress("Preparing container...", 0).await;
+
+                    if backend == Backend::Metal {
+                        sender
+                            .log("Metal is not available in containers; using the CPU.".to_owned())
+                            .await;
                     }
-                    Backend::Cuda => {
-                        format!(
-                            "create --rm --gpus all -p {port}:80 -v {volume}:/models \
+
+                    let command = match backend {
+                        Backend::Cpu | Backend::Metal => {
+                            format!(
+                                "create --rm -p {port}:80 -v {volume}:/models \
                             {container} --model /models/{filename} \
-                            --port 80 --host 0.0.0.0 --gpu-layers 40",
-                            filename = file.name,
-                            container = Self::LLAMA_CPP_CONTAINER_CUDA,
-                            port = Self::HOST_PORT,
-                            volume = Self::MODELS_DIR,
-                        )
-                    }
-                    Backend::Rocm => {
-                        format!(
-                            "create --rm -p {port}:80 -v {volume}:/models \
+                            --port 80 --host 0.0.0.0 {flags}",
+                                filename = file.name,
+                                container = executor.image(backend),
+                                flags = inference.flags(Backend::Cpu, 40),
+                                volume = Self::MODELS_DIR,
+                            )
+                        }
+                        Backend::Cuda => {
+                            format!(
+                                "create --rm --gpus all -p {port}:80 -v {volume}:/models \
+                            {container} --model /models/{filename} \
+                            --port 80 --host 0.0.0.0 {flags}",
+                                filename = file.name,
+                                container = executor.image(backend),
+                                flags = inference.flags(backend, 40),
+                                volume = Self::MODELS_DIR,
+                            )
+                        }
+                        Backend::Rocm => {
+                            format!(
+                                "create --rm -p {port}:80 -v {volume}:/models \
                             --device=/dev/kfd --device=/dev/dri \
                             --security-opt seccomp=unconfined --group-add video \
                             {container} --model /models/{filename} \
-                            --port 80 --host 0.0.0.0 --gpu-layers 40",
-                            filename = file.name,
-                            container = Self::LLAMA_CPP_CONTAINER_ROCM,
-                            port = Self::HOST_PORT,
-                            volume = Self::MODELS_DIR,
-                        )
-                    }
-                };
+                            --port 80 --host 0.0.0.0 {flags}",
+                                filename = file.name,
+                                container = executor.image(backend),
+                                flags = inference.flags(backend, 40),
+                                volume = Self::MODELS_DIR,
+                            )
+                        }
+                        Backend::Vulkan | Backend::Sycl => {
+                            format!(
+                                "create --rm -p {port}:80 -v {volume}:/models \
+                            --device=/dev/dri \
+                            {container} --model /models/{filename} \
+                            --port 80 --host 0.0.0.0 {flags}",
+                                filename = file.name,
+                                container = executor.image(backend),
+                                flags = inference.flags(backend, 40),
+                                volume = Self::MODELS_DIR,
+                            )
+                        }
+                    };
 
-                let mut docker = process::Command::new("docker")
-                    .args(Self::parse_args(&command))
-                    .kill_on_drop(true)
-                    .stdout(std::process::Stdio::piped())
-                    .stderr(std::process::Stdio::piped())
-                    .spawn()?;
+                    let mut docker = process::Command::new("docker")
+                        .args(Self::parse_args(&command))
+                        .args(&extra_arguments)
+                        .kill_on_drop(true)
+                        .stdout(std::process::Stdio::piped())
+                        .stderr(std::process::Stdio::piped())
+                        .spawn()?;
 
-                let notify_progress = {
-                    let mut sender = sender.clone();
+                    let notify_progress = {
+                        let mut sender = sender.clone();
 
-                    let output = io::BufReader::new(docker.stderr.take().expect("piped stderr"));
+                        let output =
+                            io::BufReader::new(docker.stderr.take().expect("piped stderr"));
 
-                    async move {
-                        let mut lines = output.lines();
+                        async move {
+                            let mut lines = output.lines();
 
-                        while let Ok(Some(log)) = lines.next_line().await {
-                            sender.log(log).await;
+                            while let Ok(Some(log)) = lines.next_line().await {
+                                sender.log(log).await;
+                            }
                         }
-                    }
-                };
+                    };
 
-                let _handle = tokio::task::spawn(notify_progress);
+                    let _handle = tokio::task::spawn(notify_progress);
 
-                let container = {
-                    let output = io::BufReader::new(docker.stdout.take().expect("piped stdout"));
+                    let container = {
+                        let output =
+                            io::BufReader::new(docker.stdout.take().expect("piped stdout"));
 
-                    let mut lines = output.lines();
+                        let mut lines = output.lines();
 
-                    lines
-                        .next_line()
-                        .await?
-                        .ok_or_else(|| Error::DockerFailed("no container id returned by docker"))?
-                };
+                        lines.next_line().await?.ok_or_else(|| {
+                            Error::DockerFailed("no container id returned by docker")
+                        })?
+                    };
 
-                if !docker.wait().await?.success() {
-                    return Err(Error::DockerFailed("failed to create container"));
-                }
+                    if !docker.wait().await?.success() {
+                        return Err(Error::DockerFailed("failed to create container"));
+                    }
 
-                sender.progress("Launching assistant...", 99).await;
+                    sender.progress("Launching assistant...", 99).await;
 
-                let server = Server::Container(container.clone());
+                    let server = Server::Container(container.clone());
 
-                let _start = process::Command::new("docker")
-                    .args(["start", &container])
-                    .output()
-                    .await?;
+                    let _start = process::Command::new("docker")
+                        .args(["start", &container])
+                        .output()
+                        .await?;
 
-                let mut logs = process::Command::new("docker")
-                    .args(["logs", "-f", &container])
-                    .stdout(std::process::Stdio::piped())
-                    .stderr(std::process::Stdio::piped())
-                    .spawn()?;
+                    let mut logs = process::Command::new("docker")
+                        .args(["logs", "-f", &container])
+                        .stdout(std::process::Stdio::piped())
+                        .stderr(std::process::Stdio::piped())
+                        .spawn()?;
 
-                (server, logs.stdout.take(), logs.stderr.take())
-            } else {
-                return Err(Error::NoExecutorAvailable);
+                    (server, logs.stdout.take(), logs.stderr.take())
+                }
             };
 
             let mut lines = {
@@ -319,17 +461,30 @@ impl Assistant {
                 )
             };
 
+            let logs = Logs::default();
+
             let log_output = {
                 let mut sender = sender.clone();
+                let logs = logs.clone();
+                let lines = &mut lines;
 
                 async move {
+                    let mut is_out_of_memory = false;
+
                     while let Some(line) = lines.next().await {
                         if let Ok(log) = line {
+                            is_out_of_memory |= Self::is_out_of_memory(&log);
+
+                            logs.push(log.clone());
                             sender.log(log).await;
                         }
                     }
 
-                    return false;
+                    if is_out_of_memory {
+                        Err(Error::OutOfMemory)
+                    } else {
+                        Err(Error::ExecutorFailed("llama-server exited unexpectedly"))
+                    }
                 }
                 .boxed()
             };
@@ -338,48 +493,218 @@ impl Assistant {
                 loop {
                     tokio::time::sleep(Duration::from_secs(1)).await;
 
-                    if let Ok(response) = reqwest::get(format!(
-                        "http://localhost:{port}/health",
-                        port = Self::HOST_PORT
-                    ))
-                    .await
-                    {
-                        if response.error_for_status().is_ok() {
-                            return true;
-                        }
+                    if Self::is_healthy("localhost", port).await {
+                        return Ok(());
                     }
                 }
             }
             .boxed();
 
-            if futures::future::select(log_output, check_health)
+            futures::future::select(log_output, check_health)
                 .await
                 .factor_first()
-                .0
-            {
+                .0?;
+
+            // Keep reading the output of the server for as long as it runs
+            let _reader = tokio::task::spawn({
+                let logs = logs.clone();
+
+                async move {
+                    while let Some(line) = lines.next().await {
+                        if let Ok(log) = line {
+                            logs.push(log);
+                        }
+                    }
+                }
+            });
+
+            let _ = Profile::record(file.clone(), backend, inference).await;
+
+            let context_size = Self::fetch_context_size("localhost", port).await;
+
+            if let Some(context_size) = context_size {
                 sender
-                    .finish(Assistant {
-                        file,
-                        _server: Arc::new(server),
-                    })
+                    .log(format!("Context size: {context_size} tokens"))
                     .await;
+            }
+
+            sender
+                .finish(Assistant {
+                    file,
+                    context_size,
+                    tool_calling: inference.tool_calling.unwrap_or_default(),
+                    raw_completion: false,
+                    summarizer: None,
+                    host: "localhost".to_owned(),
+                    port,
+                    server: Arc::new(server),
+                    logs,
+                })
+                .await;
+
+            Ok(())
+        });
 
-                return Ok(());
+        trace::stream(span, stream)
+    }
+
+    /// Connects to the llama-server of the given [`Remote`] instead of
+    /// launching one; a tunnel forwards it to the given local port.
+    ///
+    /// The remote server runs its own model, which may not be the given file.
+    pub fn connect(
+        file: File,
+        remote: Remote,
+        inference: Inference,
+        port: u64,
+    ) -> impl Stream<Item = Result<BootEvent, Error>> {
+        let span = tracing::info_span!("connect", model = %file.name, host = %remote.address());
+
+        let stream = iced::stream::try_channel(1, move |mut sender| async move {
+            let _ = sender
+                .send(BootEvent::Progressed {
+                    stage: "Connecting to remote server...",
+                    percent: 0,
+                })
+                .await;
+
+            let _ = sender
+                .send(BootEvent::Logged(format!(
+                    "Connecting to {address}...",
+                    address = remote.address()
+                )))
+                .await;
+
+            let connection = remote.connect(port).await?;
+
+            if let Some(model) = connection.model().await {
+                let _ = sender
+                    .send(BootEvent::Logged(format!(
+                        "Remote server is running {model}"
+                    )))
+                    .await;
             }
 
-            Err(Error::ExecutorFailed("llama-server exited unexpectedly"))
-        })
+            let context_size = Self::fetch_context_size(&connection.host, connection.port).await;
+
+            let _ = sender
+                .send(BootEvent::Finished(Assistant {
+                    file,
+                    context_size,
+                    tool_calling: inference.tool_calling.unwrap_or_default(),
+                    raw_completion: false,
+                    summarizer: None,
+                    host: connection.host.clone(),
+                    port: connection.port,
+                    server: Arc::new(Server::Remote(connection)),
+                    logs: Logs::default(),
+                }))
+                .await;
+
+            Ok(())
+        });
+
+        trace::stream(span, stream)
+    }
+
+    fn is_out_of_memory(log: &str) -> bool {
+        const PATTERNS: &[&str] = &[
+            "out of memory",
+            "failed to allocate",
+            "unable to allocate",
+            "cudamalloc failed",
+            "erroroutofdevicememory",
+        ];
+
+        let log = log.to_lowercase();
+
+        PATTERNS.iter().any(|pattern| log.contains(pattern))
+    }
+
+    /// Counts the tokens of the given text with the model tokenizer.
+    pub async fn count_tokens(&self, text: &str) -> Result<u64, Error> {
+        #[derive(Deserialize)]
+        struct Response {
+            tokens: Vec<serde_json::Value>,
+        }
+
+        let response: Response = reqwest::Client::new()
+            .post(format!(
+                "http://{host}:{port}/tokenize",
+                host = self.host,
+                port = self.port
+            ))
+            .json(&json!({ "content": text }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response.tokens.len() as u64)
+    }
+
+    async fn fetch_context_size(host: &str, port: u64) -> Option<u64> {
+        let props: serde_json::Value = reqwest::get(format!("http://{host}:{port}/props"))
+            .await
+            .ok()?
+            .json()
+            .await
+            .ok()?;
+
+        props["default_generation_settings"]["n_ctx"].as_u64()
     }
 
     pub fn complete<'a>(
         &'a self,
         system_prompt: &'a str,
         messages: &'a [Message],
+        sampling: Sampling,
     ) -> impl Stream<Item = Result<Token, Error>> + 'a {
-        iced::stream::try_channel(1, move |mut sender| async move {
+        self.complete_with_tools(system_prompt, messages, &[], sampling, &[])
+    }
+
+    /// Completes the conversation, letting the model call the given tools.
+    ///
+    /// The turns of any previous tool calls are sent after the messages.
+    pub fn complete_with_tools<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        messages: &'a [Message],
+        turns: &'a [ToolTurn],
+        sampling: Sampling,
+        tools: &'a [Tool],
+    ) -> impl Stream<Item = Result<Token, Error>> + 'a {
+        let span = tracing::info_span!(
+            "completion",
+            model = self.name(),
+            messages = messages.len(),
+            prompt_tokens = tracing::field::Empty,
+            completion_tokens = tracing::field::Empty,
+        );
+
+        let stream = iced::stream::try_channel(1, move |mut sender| async move {
             let client = reqwest::Client::new();
 
-            let request = {
+            let request = if self.raw_completion {
+                client
+                    .post(format!(
+                        "http://{host}:{port}/completion",
+                        host = self.host,
+                        port = self.port
+                    ))
+                    .json(&json!({
+                        "prompt": transcript(system_prompt, messages),
+                        "stream": true,
+                        "cache_prompt": true,
+                        "temperature": sampling.temperature,
+                        "top_p": sampling.top_p,
+                        "top_k": sampling.top_k,
+                        "repeat_penalty": sampling.repeat_penalty,
+                        "n_predict": sampling.max_tokens.map_or(-1, i64::from),
+                        "stop": ["\nUser:"],
+                    }))
+            } else {
                 let messages: Vec<_> = [("system", system_prompt)]
                     .into_iter()
                     .chain(messages.iter().map(|message| match message {
@@ -392,24 +717,35 @@ impl Assistant {
                             "content": content
                         })
                     })
+                    .chain(turns.iter().map(ToolTurn::to_json))
                     .collect();
 
+                let tools: Vec<_> = tools.iter().map(|tool| tool.definition()).collect();
+
                 client
                     .post(format!(
-                        "http://localhost:{port}/v1/chat/completions",
-                        port = Self::HOST_PORT
+                        "http://{host}:{port}/v1/chat/completions",
+                        host = self.host,
+                        port = self.port
                     ))
                     .json(&json!({
                         "model": format!("{model}", model = self.name()),
                         "messages": messages,
                         "stream": true,
                         "cache_prompt": true,
+                        "temperature": sampling.temperature,
+                        "top_p": sampling.top_p,
+                        "top_k": sampling.top_k,
+                        "repeat_penalty": sampling.repeat_penalty,
+                        "max_tokens": sampling.max_tokens,
+                        "tools": (!tools.is_empty()).then_some(tools),
                     }))
             };
 
             let mut response = request.send().await?.error_for_status()?;
             let mut buffer = Vec::new();
             let mut is_reasoning = None;
+            let mut tool_calls: Vec<ToolCall> = Vec::new();
 
             while let Some(chunk) = response.chunk().await? {
                 buffer.extend(chunk);
@@ -428,7 +764,18 @@ impl Assistant {
                     if let Ok(data) = std::str::from_utf8(line) {
                         #[derive(Deserialize)]
                         struct Data {
+                            #[serde(default)]
                             choices: Vec<Choice>,
+                            /// The text of a raw completion
+                            content: Option<String>,
+                            timings: Option<Timings>,
+                        }
+
+                        #[derive(Deserialize)]
+                        struct Timings {
+                            prompt_n: u64,
+                            predicted_n: u64,
+                            predicted_ms: f64,
                         }
 
                         #[derive(Deserialize)]
@@ -439,6 +786,21 @@ impl Assistant {
                         #[derive(Deserialize)]
                         struct Delta {
                             content: Option<String>,
+                            #[serde(default)]
+                            tool_calls: Vec<ToolCallDelta>,
+                        }
+
+                        #[derive(Deserialize)]
+                        struct ToolCallDelta {
+                            index: usize,
+                            id: Option<String>,
+                            function: Option<FunctionDelta>,
+                        }
+
+                        #[derive(Deserialize)]
+                        struct FunctionDelta {
+                            name: Option<String>,
+                            arguments: Option<String>,
                         }
 
                         if data == "data: [DONE]" {
@@ -449,28 +811,68 @@ impl Assistant {
                             data.trim().strip_prefix("data: ").unwrap_or(data),
                         )?;
 
-                        if let Some(choice) = data.choices.first_mut() {
-                            if let Some(content) = &mut choice.delta.content {
-                                match is_reasoning {
-                                    None if content.contains("<think>") => {
-                                        is_reasoning = Some(true);
-                                        *content = content.replace("<think>", "");
-                                    }
-                                    Some(true) if content.contains("</think>") => {
-                                        is_reasoning = Some(false);
-                                        *content = content.replace("</think>", "");
-                                    }
-                                    _ => {}
+                        let content = if let Some(choice) = data.choices.first_mut() {
+                            // Tool calls arrive in fragments, keyed by their index
+                            for delta in choice.delta.tool_calls.drain(..) {
+                                if tool_calls.len() <= delta.index {
+                                    tool_calls.resize_with(delta.index + 1, ToolCall::default);
+                                }
+
+                                let call = &mut tool_calls[delta.index];
+
+                                if let Some(id) = delta.id {
+                                    call.id = id;
                                 }
 
-                                let _ = sender
-                                    .send(if is_reasoning.unwrap_or_default() {
-                                        Token::Reasoning(content.clone())
-                                    } else {
-                                        Token::Talking(content.clone())
-                                    })
-                                    .await;
+                                if let Some(function) = delta.function {
+                                    call.name.push_str(&function.name.unwrap_or_default());
+                                    call.arguments
+                                        .push_str(&function.arguments.unwrap_or_default());
+                                }
+                            }
+
+                            choice.delta.content.take()
+                        } else {
+                            data.content.take().filter(|content| !content.is_empty())
+                        };
+
+                        if let Some(mut content) = content {
+                            match is_reasoning {
+                                None if content.contains("<think>") => {
+                                    is_reasoning = Some(true);
+                                    content = content.replace("<think>", "");
+                                }
+                                Some(true) if content.contains("</think>") => {
+                                    is_reasoning = Some(false);
+                                    content = content.replace("</think>", "");
+                                }
+                                _ => {}
                             }
+
+                            let _ = sender
+                                .send(if is_reasoning.unwrap_or_default() {
+                                    Token::Reasoning(content)
+                                } else {
+                                    Token::Talking(content)
+                                })
+                                .await;
+                        }
+
+                        // llama-server reports timings in the final chunk
+                        if let Some(timings) = data.timings {
+                            tracing::Span::current()
+                                .record("prompt_tokens", timings.prompt_n)
+                                .record("completion_tokens", timings.predicted_n);
+
+                            let _ = sender
+                                .send(Token::Usage(Usage {
+                                    prompt_tokens: timings.prompt_n,
+                                    completion_tokens: timings.predicted_n,
+                                    generation_time: Duration::from_secs_f64(
+                                        timings.predicted_ms.max(0.0) / 1000.0,
+                                    ),
+                                }))
+                                .await;
                         }
                     };
                 }
@@ -478,34 +880,179 @@ impl Assistant {
                 buffer = last_line.to_vec();
             }
 
+            if !tool_calls.is_empty() {
+                let _ = sender.send(Token::ToolCalls(tool_calls)).await;
+            }
+
             Ok(())
-        })
+        });
+
+        trace::stream(span, stream)
+    }
+
+    /// Fills in the text between the given prefix and suffix.
+    ///
+    /// Only models trained for fill-in-the-middle support it, like most
+    /// code models.
+    pub fn infill(
+        &self,
+        prefix: String,
+        suffix: String,
+        sampling: Sampling,
+    ) -> impl Stream<Item = Result<String, Error>> {
+        let host = self.host.clone();
+        let port = self.port;
+
+        let span = tracing::info_span!("infill", model = self.name());
+
+        let stream = iced::stream::try_channel(1, move |mut sender| async move {
+            #[derive(Deserialize)]
+            struct Data {
+                content: String,
+            }
+
+            let mut response = reqwest::Client::new()
+                .post(format!("http://{host}:{port}/infill"))
+                .json(&json!({
+                    "input_prefix": prefix,
+                    "input_suffix": suffix,
+                    "stream": true,
+                    "cache_prompt": true,
+                    "temperature": sampling.temperature,
+                    "top_p": sampling.top_p,
+                    "top_k": sampling.top_k,
+                    "repeat_penalty": sampling.repeat_penalty,
+                    "n_predict": sampling.max_tokens.map_or(-1, i64::from),
+                }))
+                .send()
+                .await?
+                .error_for_status()?;
+
+            let mut buffer = Vec::new();
+
+            while let Some(chunk) = response.chunk().await? {
+                buffer.extend(chunk);
+
+                while let Some(end) = buffer.iter().position(|byte| *byte == 0x0A) {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+
+                    let Some(data) = std::str::from_utf8(&line)
+                        .ok()
+                        .and_then(|line| line.trim().strip_prefix("data: "))
+                    else {
+                        continue;
+                    };
+
+                    let data: Data = serde_json::from_str(data)?;
+
+                    if !data.content.is_empty() {
+                        let _ = sender.send(data.content).await;
+                    }
+                }
+            }
+
+            Ok(())
+        });
+
+        trace::stream(span, stream)
+    }
+
+    /// Whether the assistant was launched with native function calling.
+    pub fn can_call_tools(&self) -> bool {
+        self.tool_calling && !self.raw_completion
+    }
+
+    /// Makes the assistant continue a plain transcript of the conversation,
+    /// instead of using the chat template of the model.
+    ///
+    /// Base models are not tuned for chatting and often have no template.
+    pub fn with_raw_completion(self, raw_completion: bool) -> Self {
+        Self {
+            raw_completion,
+            ..self
+        }
+    }
+
+    /// Pairs the assistant with another one that takes care of auxiliary
+    /// tasks, like suggesting titles.
+    pub fn with_summarizer(self, summarizer: Option<Assistant>) -> Self {
+        Self {
+            summarizer: summarizer.map(Box::new),
+            ..self
+        }
+    }
+
+    /// The assistant taking care of auxiliary tasks; itself, unless paired.
+    pub fn summarizer(&self) -> &Assistant {
+        self.summarizer.as_deref().unwrap_or(self)
     }
 
     pub fn file(&self) -> &File {
         &self.file
     }
 
+    pub fn context_size(&self) -> Option<u64> {
+        self.context_size
+    }
+
+    pub fn port(&self) -> u64 {
+        self.port
+    }
+
+    /// The id of the `llama-server` process, if it runs directly on this
+    /// computer instead of in a container or remotely.
+    pub fn process_id(&self) -> Option<u32> {
+        match self.server.as_ref() {
+            Server::Process(process) => process.id(),
+            Server::Container(_) | Server::Remote(_) => None,
+        }
+    }
+
+    /// Whether the server of the assistant still answers its health checks.
+    pub async fn check_health(self) -> bool {
+        Self::is_healthy(&self.host, self.port).await
+    }
+
+    async fn is_healthy(host: &str, port: u64) -> bool {
+        let health = reqwest::Client::new()
+            .get(format!("http://{host}:{port}/health"))
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await;
+
+        health.is_ok_and(|response| response.status().is_success())
+    }
+
+    /// The output of the server, if it runs on this computer.
+    pub fn logs(&self) -> &Logs {
+        &self.logs
+    }
+
+    /// Whether the assistant runs on this computer.
+    pub fn is_local(&self) -> bool {
+        !matches!(self.server.as_ref(), Server::Remote(_))
+    }
+
     pub fn name(&self) -> &str {
         self.file.model.name()
     }
 
     fn launch_with_executable(
-        executable: &'static str,
+        executable: &Path,
         file: &File,
         backend: Backend,
+        inference: Inference,
+        extra_arguments: &[String],
+        port: u64,
     ) -> Result<process::Child, Error> {
-        let gpu_flags = match backend {
-            Backend::Cpu => "",
-            Backend::Cuda | Backend::Rocm => "--gpu-layers 80",
-        };
-
         let server = process::Command::new(executable)
             .args(Self::parse_args(&format!(
                 "--model models/{filename} \
-                    --port 8080 --host 0.0.0.0 {gpu_flags}",
+                    --port {port} --host 0.0.0.0 {flags}",
                 filename = file.name,
+                flags = inference.flags(backend, 80),
             )))
+            .args(extra_arguments)
             .kill_on_drop(true)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
@@ -522,19 +1069,46 @@ impl Assistant {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Backend {
     Cpu,
     Cuda,
     Rocm,
+    /// Any GPU with Vulkan drivers, like Intel Arc cards and most
+    /// integrated GPUs.
+    Vulkan,
+    /// Intel GPUs through oneAPI.
+    Sycl,
+    /// Apple Silicon, whose GPU shares the memory of the system.
+    Metal,
 }
 
 impl Backend {
+    pub const ALL: &'static [Self] = &[
+        Self::Cpu,
+        Self::Cuda,
+        Self::Rocm,
+        Self::Vulkan,
+        Self::Sycl,
+        Self::Metal,
+    ];
+
     pub fn detect(graphics_adapter: &str) -> Self {
+        const SOFTWARE: &[&str] = &["llvmpipe", "softpipe", "swiftshader", "basic render"];
+        const VULKAN: &[&str] = &["intel", "mesa", "adreno", "mali", "radeon"];
+
+        let adapter = graphics_adapter.to_lowercase();
+
         if graphics_adapter.contains("NVIDIA") {
             Self::Cuda
         } else if graphics_adapter.contains("AMD") {
             Self::Rocm
+        } else if graphics_adapter.contains("Apple") {
+            Self::Metal
+        } else if SOFTWARE.iter().any(|renderer| adapter.contains(renderer)) {
+            Self::Cpu
+        } else if VULKAN.iter().any(|vendor| adapter.contains(vendor)) {
+            Self::Vulkan
         } else {
             Self::Cpu
         }
@@ -542,37 +1116,300 @@ impl Backend {
 
     pub fn uses_gpu(self) -> bool {
         match self {
-            Backend::Cuda | Backend::Rocm => true,
+            Backend::Cuda | Backend::Rocm | Backend::Vulkan | Backend::Sycl | Backend::Metal => {
+                true
+            }
             Backend::Cpu => false,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum Message {
-    Assistant {
-        reasoning: Option<Reasoning>,
-        content: String,
-    },
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Backend::Cpu => "CPU",
+            Backend::Cuda => "CUDA",
+            Backend::Rocm => "ROCm",
+            Backend::Vulkan => "Vulkan",
+            Backend::Sycl => "SYCL",
+            Backend::Metal => "Metal",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sampling {
+    pub temperature: f32,
+    pub top_p: f32,
+    pub top_k: u32,
+    pub repeat_penalty: f32,
+    pub max_tokens: Option<u32>,
+    /// The amount of replies generated for every message.
+    pub replies: u32,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            temperature: 0.8,
+            top_p: 0.95,
+            top_k: 40,
+            repeat_penalty: 1.0,
+            max_tokens: None,
+            replies: 1,
+        }
+    }
+}
+
+/// How an assistant is launched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launch {
+    pub backend: Backend,
+    pub inference: Inference,
+    /// Extra flags passed as is to `llama-server`.
+    pub extra_flags: String,
+    pub executor: Executor,
+}
+
+/// The options used to boot a specific file, overriding the detected
+/// backend and the [`Inference`] settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootOptions {
+    pub file: File,
+    pub backend: Option<Backend>,
+    pub gpu_layers: Option<u32>,
+    /// Extra flags passed as is to `llama-server`.
+    #[serde(default)]
+    pub extra_flags: String,
+}
+
+impl BootOptions {
+    pub fn new(file: File) -> Self {
+        Self {
+            file,
+            backend: None,
+            gpu_layers: None,
+            extra_flags: String::new(),
+        }
+    }
+
+    /// Whether the options change nothing.
+    pub fn is_empty(&self) -> bool {
+        self.backend.is_none() && self.gpu_layers.is_none() && self.extra_flags.trim().is_empty()
+    }
+}
+
+/// Server options used when launching an assistant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Inference {
+    /// The index of the GPU that runs the model. All GPUs are used if unset.
+    pub gpu: Option<u32>,
+    /// The number of layers offloaded to the GPU.
+    pub gpu_layers: Option<u32>,
+    /// The context size in tokens. The model default is used if unset.
+    pub context_size: Option<u32>,
+    /// The number of CPU threads used during generation.
+    pub threads: Option<u32>,
+    /// The logical batch size used to process prompts.
+    pub batch_size: Option<u32>,
+    /// Whether to use the chat template of the model, which enables native
+    /// function calling.
+    pub tool_calling: Option<bool>,
+}
+
+impl Inference {
+    /// Fills in the unset options with the ones of the given inference.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            gpu: self.gpu.or(other.gpu),
+            gpu_layers: self.gpu_layers.or(other.gpu_layers),
+            context_size: self.context_size.or(other.context_size),
+            threads: self.threads.or(other.threads),
+            batch_size: self.batch_size.or(other.batch_size),
+            tool_calling: self.tool_calling.or(other.tool_calling),
+        }
+    }
+
+    fn flags(self, backend: Backend, default_gpu_layers: u32) -> String {
+        // More than any model has; llama.cpp offloads what there is
+        const ALL_LAYERS: u32 = 999;
+
+        let mut flags = Vec::new();
+
+        if backend == Backend::Metal {
+            // The GPU shares the memory of the system, so offloading every
+            // layer costs nothing and the memory-mapped weights are used as is
+            flags.push(format!(
+                "--gpu-layers {}",
+                self.gpu_layers.unwrap_or(ALL_LAYERS)
+            ));
+        } else if backend != Backend::Cpu {
+            flags.push(format!(
+                "--gpu-layers {}",
+                self.gpu_layers.unwrap_or(default_gpu_layers)
+            ));
+
+            if let Some(gpu) = self.gpu {
+                flags.push(format!("--split-mode none --main-gpu {gpu}"));
+            }
+        }
+
+        if let Some(context_size) = self.context_size {
+            flags.push(format!("--ctx-size {context_size}"));
+        }
+
+        if let Some(threads) = self.threads {
+            flags.push(format!("--threads {threads}"));
+        }
+
+        if let Some(batch_size) = self.batch_size {
+            flags.push(format!("--batch-size {batch_size}"));
+        }
+
+        if self.tool_calling.unwrap_or_default() {
+            flags.push("--jinja".to_owned());
+        }
+
+        flags.join(" ")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Assistant {
+        reasoning: Option<Reasoning>,
+        content: String,
+        usage: Option<Usage>,
+        alternatives: Vec<Alternative>,
+        /// Whether the reply was cut off while streaming, like when the
+        /// app was closed.
+        interrupted: bool,
+    },
     User(String),
 }
 
+impl Message {
+    pub fn content(&self) -> &str {
+        match self {
+            Message::Assistant { content, .. } | Message::User(content) => content,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Reasoning {
     pub content: String,
     pub duration: Duration,
 }
 
+/// Another reply generated for the same message, which was not kept.
+#[derive(Debug, Clone)]
+pub struct Alternative {
+    pub reasoning: Option<Reasoning>,
+    pub content: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
     Reasoning(String),
     Talking(String),
+    Usage(Usage),
+    ToolCalls(Vec<ToolCall>),
+}
+
+/// A function call requested by the model.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+/// A step of a tool-calling exchange.
+#[derive(Debug, Clone)]
+pub enum ToolTurn {
+    Calls(Vec<ToolCall>),
+    Result { id: String, content: String },
+}
+
+impl ToolTurn {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            ToolTurn::Calls(calls) => json!({
+                "role": "assistant",
+                "content": "",
+                "tool_calls": calls
+                    .iter()
+                    .map(|call| json!({
+                        "id": call.id,
+                        "type": "function",
+                        "function": {
+                            "name": call.name,
+                            "arguments": call.arguments,
+                        }
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+            ToolTurn::Result { id, content } => json!({
+                "role": "tool",
+                "tool_call_id": id,
+                "content": content,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub generation_time: Duration,
+}
+
+impl Usage {
+    pub fn tokens_per_second(&self) -> f32 {
+        if self.generation_time.is_zero() {
+            return 0.0;
+        }
+
+        self.completion_tokens as f32 / self.generation_time.as_secs_f32()
+    }
+}
+
+/// The output of a `llama-server`, kept for as long as it runs.
+#[derive(Debug, Clone, Default)]
+pub struct Logs(Arc<Mutex<VecDeque<String>>>);
+
+impl Logs {
+    /// The most lines kept; older ones are dropped.
+    const LIMIT: usize = 5_000;
+
+    fn push(&self, line: String) {
+        if let Ok(mut lines) = self.0.lock() {
+            if lines.len() == Self::LIMIT {
+                let _ = lines.pop_front();
+            }
+
+            lines.push_back(line);
+        }
+    }
+
+    /// The lines logged so far.
+    pub fn lines(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .map(|lines| lines.iter().cloned().collect())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug)]
 enum Server {
     Container(String),
     Process(process::Child),
+    Remote(remote::Connection),
 }
 
 impl Drop for Server {
@@ -589,6 +1426,7 @@ impl Drop for Server {
                     .spawn();
             }
             Self::Process(_process) => {}
+            Self::Remote(_connection) => {}
         }
     }
 }
@@ -603,10 +1441,387 @@ pub enum BootEvent {
 #[derive(Debug, Clone)]
 pub struct Model {
     id: Id,
+    pub kind: Kind,
     pub last_modified: chrono::DateTime<chrono::Local>,
     pub downloads: Downloads,
     pub likes: Likes,
     pub files: Vec<File>,
+    /// Whether the repository publishes the original safetensors weights,
+    /// which can be converted into a GGUF file.
+    pub convertible: bool,
+    /// The amount of parameters of the model, if known.
+    pub parameters: Option<u64>,
+    pub license: Option<String>,
+    pub architecture: Option<String>,
+}
+
+/// A search of models in Hugging Face.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Query {
+    pub text: String,
+    pub sort: Sort,
+    pub size: Option<Size>,
+    /// A quantization that must be available in the repository.
+    pub quantization: Option<&'static str>,
+    pub license: Option<String>,
+    pub architecture: Option<String>,
+}
+
+impl Query {
+    /// Whether any filter is set.
+    pub fn is_filtered(&self) -> bool {
+        self.size.is_some()
+            || self.quantization.is_some()
+            || self.license.is_some()
+            || self.architecture.is_some()
+    }
+
+    fn matches(&self, model: &Model) -> bool {
+        self.size.is_none_or(|size| {
+            model
+                .parameters
+                .is_some_and(|parameters| size.contains(parameters))
+        }) && self
+            .quantization
+            .is_none_or(|quantization| model.quantizations().contains(&quantization))
+            && self
+                .architecture
+                .as_ref()
+                .is_none_or(|architecture| model.architecture.as_ref() == Some(architecture))
+    }
+}
+
+/// The order of the models found, most first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sort {
+    #[default]
+    Downloads,
+    Likes,
+    LastModified,
+    Parameters,
+}
+
+impl Sort {
+    pub const ALL: &'static [Self] = &[
+        Self::Downloads,
+        Self::Likes,
+        Self::LastModified,
+        Self::Parameters,
+    ];
+
+    /// The sort key of the Hugging Face API; models are sorted by their
+    /// parameters once fetched.
+    fn key(self) -> &'static str {
+        match self {
+            Sort::Downloads | Sort::Parameters => "downloads",
+            Sort::Likes => "likes",
+            Sort::LastModified => "lastModified",
+        }
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Sort::Downloads => "Most downloaded",
+            Sort::Likes => "Most liked",
+            Sort::LastModified => "Recently updated",
+            Sort::Parameters => "Largest",
+        })
+    }
+}
+
+/// A range of parameter counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    /// Less than 3B parameters.
+    Tiny,
+    /// From 3B to 9B parameters.
+    Small,
+    /// From 9B to 35B parameters.
+    Medium,
+    /// More than 35B parameters.
+    Large,
+}
+
+impl Size {
+    pub const ALL: &'static [Self] = &[Self::Tiny, Self::Small, Self::Medium, Self::Large];
+
+    fn contains(self, parameters: u64) -> bool {
+        const BILLION: u64 = 1_000_000_000;
+
+        match self {
+            Size::Tiny => parameters < 3 * BILLION,
+            Size::Small => (3 * BILLION..9 * BILLION).contains(&parameters),
+            Size::Medium => (9 * BILLION..35 * BILLION).contains(&parameters),
+            Size::Large => parameters >= 35 * BILLION,
+        }
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Size::Tiny => "Under 3B",
+            Size::Small => "3B to 9B",
+            Size::Medium => "9B to 35B",
+            Size::Large => "Over 35B",
+        })
+    }
+}
+
+/// Guesses the amount of parameters of a model from the name of its
+/// repository, like `Qwen2.5-0.5B` or `Mixtral-8x7B`.
+fn parameters(name: &str) -> Option<u64> {
+    name.split(['-', '_', ' '])
+        .filter_map(|token| {
+            let token = token.to_lowercase();
+
+            let (number, scale) = if let Some(number) = token.strip_suffix('b') {
+                (number, 1e9)
+            } else if let Some(number) = token.strip_suffix('m') {
+                (number, 1e6)
+            } else {
+                return None;
+            };
+
+            let (experts, number) = match number.split_once('x') {
+                Some((experts, number)) => (experts.parse().ok()?, number),
+                None => (1.0, number),
+            };
+
+            let number: f64 = number.parse().ok()?;
+
+            Some((experts * number * scale) as u64)
+        })
+        .next()
+}
+
+/// Normalizes the name of a model repository by dropping the packaging
+/// details, like the format or the quantization.
+fn family(name: &str) -> String {
+    const NOISE: &[&str] = &["gguf", "ggml", "imatrix", "i1", "quantized", "quants", "hf"];
+
+    let name = name.to_lowercase();
+
+    let tokens: Vec<_> = name
+        .split(['-', ' '])
+        .filter(|token| !token.is_empty() && !NOISE.contains(token))
+        .filter(|token| {
+            !File::QUANTIZATIONS
+                .iter()
+                .any(|quantization| quantization.eq_ignore_ascii_case(token))
+        })
+        .collect();
+
+    let tokens = match tokens.as_slice() {
+        ["meta", rest @ ..] if !rest.is_empty() => rest,
+        tokens => tokens,
+    };
+
+    tokens.join("-")
+}
+
+/// Whether a model was tuned to follow instructions in a conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Instruct,
+    /// A model that only continues text.
+    Base,
+}
+
+impl Kind {
+    /// Guesses the kind of a model from the tags and the name of its
+    /// repository.
+    fn detect(name: &str, tags: &[String]) -> Self {
+        const MARKERS: &[&str] = &[
+            "instruct",
+            "chat",
+            "it",
+            "sft",
+            "dpo",
+            "rlhf",
+            "assistant",
+            "hermes",
+            "r1",
+        ];
+
+        let name = name.to_lowercase();
+
+        let is_instruct = tags.iter().any(|tag| tag == "conversational")
+            || name
+                .split(['-', '_', '.', ' '])
+                .any(|token| MARKERS.contains(&token));
+
+        if is_instruct {
+            Self::Instruct
+        } else {
+            Self::Base
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Instruct => "chat",
+            Kind::Base => "base",
+        })
+    }
+}
+
+/// Writes the conversation as a plain transcript for a raw completion,
+/// ending with the turn of the assistant.
+fn transcript(system_prompt: &str, messages: &[Message]) -> String {
+    let mut transcript = format!("{}\n", system_prompt.trim());
+
+    for message in messages {
+        let (role, content) = match message {
+            Message::User(content) => ("User", content),
+            Message::Assistant { content, .. } => ("Assistant", content),
+        };
+
+        transcript.push_str(&format!("\n{role}: {}\n", content.trim()));
+    }
+
+    transcript.push_str("\nAssistant:");
+    transcript
+}
+
+/// Splits extra `llama-server` flags into arguments, keeping quoted values
+/// together like a shell would.
+///
+/// The flags that the app sets itself, like the model and the port, are
+/// rejected.
+pub fn parse_flags(flags: &str) -> Result<Vec<String>, Error> {
+    const RESERVED: &[&str] = &["-m", "--model", "--port", "--host"];
+
+    let mut arguments = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+
+    for c in flags.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => current.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                let _ = current.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => arguments.extend(current.take()),
+            None => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(Error::InvalidFlags("a quote is never closed".to_owned()));
+    }
+
+    arguments.extend(current);
+
+    if let Some(first) = arguments.first().filter(|first| !first.starts_with('-')) {
+        return Err(Error::InvalidFlags(format!("{first} is not a flag")));
+    }
+
+    if let Some(reserved) = arguments.iter().find(|argument| {
+        let flag = argument.split('=').next().unwrap_or_default();
+
+        RESERVED.contains(&flag)
+    }) {
+        return Err(Error::InvalidFlags(format!("{reserved} is set by the app")));
+    }
+
+    Ok(arguments)
+}
+
+/// The access token sent to Hugging Face, if any.
+static TOKEN: RwLock<String> = RwLock::new(String::new());
+
+/// Authenticates all requests to Hugging Face from now on with the given
+/// access token, unlocking gated models; an empty token logs out.
+pub fn authenticate(token: &str) {
+    if let Ok(mut current) = TOKEN.write() {
+        token.trim().clone_into(&mut current);
+    }
+}
+
+/// Authenticates with the access token stored in the keyring, if any.
+pub async fn log_in() -> Result<(), Error> {
+    let token = Secret::HuggingFaceToken.read().await?;
+
+    authenticate(&token.unwrap_or_default());
+
+    Ok(())
+}
+
+/// Stores the given access token in the keyring and authenticates with it;
+/// an empty token is deleted and logs out.
+///
+/// Returns whether a token is stored.
+pub async fn store_token(token: String) -> Result<bool, Error> {
+    let token = token.trim().to_owned();
+
+    Secret::HuggingFaceToken.write(token.clone()).await?;
+    authenticate(&token);
+
+    Ok(!token.is_empty())
+}
+
+fn is_authenticated() -> bool {
+    TOKEN.read().is_ok_and(|token| !token.is_empty())
+}
+
+/// Builds a `GET` request to Hugging Face, authenticated if there is a token.
+pub fn hugging_face(url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+    let request = reqwest::Client::new().get(url);
+
+    match TOKEN.read() {
+        Ok(token) if !token.is_empty() => request.bearer_auth(token.as_str()),
+        _ => request,
+    }
+}
+
+/// Fails with [`Error::RateLimited`] if Hugging Face rejected the request
+/// for being over quota.
+fn check_rate_limit(response: reqwest::Response) -> Result<reqwest::Response, Error> {
+    if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(retry_after);
+
+    Err(Error::RateLimited(retry_after))
+}
+
+/// Parses a `Retry-After` header, given either in seconds or as a date.
+fn retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+/// Whether the given paths hold the same file: either the very same one,
+/// or one with identical contents.
+async fn is_same_file(a: &Path, b: &Path) -> Result<bool, Error> {
+    if fs::canonicalize(a).await? == fs::canonicalize(b).await? {
+        return Ok(true);
+    }
+
+    if fs::metadata(a).await?.len() != fs::metadata(b).await?.len() {
+        return Ok(false);
+    }
+
+    Ok(download::checksum(a).await? == download::checksum(b).await?)
 }
 
 impl Model {
@@ -614,20 +1829,24 @@ impl Model {
     const API_URL: &'static str = "https://huggingface.co/api";
 
     pub async fn list() -> Result<Vec<Self>, Error> {
-        Self::search(String::new()).await
+        Self::search(Query::default()).await
     }
 
-    pub async fn search(query: String) -> Result<Vec<Self>, Error> {
-        let client = reqwest::Client::new();
-
-        let request = client.get(format!("{}/models", Self::API_URL)).query(&[
-            ("search", query.as_ref()),
+    pub async fn search(query: Query) -> Result<Vec<Self>, Error> {
+        let mut request = hugging_face(format!("{}/models", Self::API_URL)).query(&[
+            ("search", query.text.as_str()),
             ("filter", "text-generation"),
             ("filter", "gguf"),
+            ("sort", query.sort.key()),
+            ("direction", "-1"),
             ("limit", "100"),
             ("full", "true"),
         ]);
 
+        if let Some(license) = &query.license {
+            request = request.query(&[("filter", format!("license:{license}"))]);
+        }
+
         #[derive(Deserialize)]
         struct Response {
             id: Id,
@@ -637,6 +1856,15 @@ impl Model {
             likes: Likes,
             gated: Gated,
             siblings: Vec<Sibling>,
+            #[serde(default)]
+            tags: Vec<String>,
+            gguf: Option<Gguf>,
+        }
+
+        #[derive(Deserialize)]
+        struct Gguf {
+            total: Option<u64>,
+            architecture: Option<String>,
         }
 
         #[derive(Deserialize, PartialEq, Eq)]
@@ -651,18 +1879,63 @@ impl Model {
             rfilename: String,
         }
 
-        let response = request.send().await?;
-        let mut models: Vec<Response> = response.json().await?;
+        let response = check_rate_limit(request.send().await?)?;
+        let endpoint = response.url().to_string();
+        let bytes = response.bytes().await?;
+
+        audit::record(
+            audit::Purpose::ModelSearch,
+            endpoint,
+            Some(bytes.len() as u64),
+        )
+        .await;
+
+        let mut models: Vec<Response> = serde_json::from_slice(&bytes)?;
 
-        models.retain(|model| model.gated == Gated::Bool(false));
+        // Repositories without GGUF files are only found by their exact id
+        let id = query.text.trim();
 
-        Ok(models
+        if id.contains('/') && !models.iter().any(|model| model.id.0 == id) {
+            let url = format!("{}/models/{id}", Self::API_URL);
+            let response = check_rate_limit(hugging_face(&url).send().await?)?;
+
+            if response.status().is_success() {
+                let bytes = response.bytes().await?;
+
+                audit::record(audit::Purpose::ModelSearch, url, Some(bytes.len() as u64)).await;
+
+                models.extend(serde_json::from_slice(&bytes).ok());
+            }
+        }
+
+        // Gated models can only be downloaded with an access token
+        if !is_authenticated() {
+            models.retain(|model| model.gated == Gated::Bool(false));
+        }
+
+        let mut models: Vec<_> = models
             .into_iter()
             .map(|model| Self {
+                kind: Kind::detect(model.id.name(), &model.tags),
+                parameters: model
+                    .gguf
+                    .as_ref()
+                    .and_then(|gguf| gguf.total)
+                    .or_else(|| parameters(model.id.name())),
+                license: model
+                    .tags
+                    .iter()
+                    .find_map(|tag| tag.strip_prefix("license:"))
+                    .map(str::to_owned),
+                architecture: model.gguf.and_then(|gguf| gguf.architecture),
                 id: model.id.clone(),
                 last_modified: model.last_modified,
                 downloads: model.downloads,
                 likes: model.likes,
+                convertible: model
+                    .siblings
+                    .iter()
+                    .any(|file| file.rfilename.ends_with(".safetensors")),
                 files: model
                     .siblings
                     .into_iter()
@@ -673,18 +1946,28 @@ impl Model {
                     })
                     .collect(),
             })
-            .collect())
+            .filter(|model| query.matches(model))
+            .collect();
+
+        if query.sort == Sort::Parameters {
+            models.sort_by_key(|model| std::cmp::Reverse(model.parameters));
+        }
+
+        Ok(models)
     }
 
     pub async fn fetch_readme(self) -> Result<String, Error> {
-        let response = reqwest::get(format!(
+        let url = format!(
             "{url}/{id}/raw/main/README.md",
             url = Self::HF_URL,
             id = self.id.0
-        ))
-        .await?;
+        );
+
+        let readme = hugging_face(&url).send().await?.text().await?;
 
-        Ok(response.text().await?)
+        audit::record(audit::Purpose::ModelCard, url, Some(readme.len() as u64)).await;
+
+        Ok(readme)
     }
 
     pub fn name(&self) -> &str {
@@ -694,6 +1977,38 @@ impl Model {
     pub fn author(&self) -> &str {
         self.id.author()
     }
+
+    /// The base model packaged in the repository, normalized so that the
+    /// uploads of different authors match.
+    pub fn family(&self) -> String {
+        family(self.name())
+    }
+
+    /// The quantizations available in the repository, smallest first.
+    pub fn quantizations(&self) -> Vec<&'static str> {
+        let mut ranks: Vec<_> = self
+            .files
+            .iter()
+            .filter_map(|file| File::split(&file.name).1)
+            .collect();
+
+        ranks.sort_unstable();
+        ranks.dedup();
+
+        ranks
+            .into_iter()
+            .map(|rank| File::QUANTIZATIONS[rank])
+            .collect()
+    }
+
+    /// The GGUF file produced by converting the original weights of the
+    /// model.
+    pub fn converted(&self) -> File {
+        File {
+            model: self.id.clone(),
+            name: format!("{}-{}.gguf", self.name(), convert::QUANTIZATION),
+        }
+    }
 }
 
 impl fmt::Display for Model {
@@ -721,9 +2036,15 @@ impl Id {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
 pub struct Downloads(u64);
 
+impl Downloads {
+    pub fn count(self) -> u64 {
+        self.0
+    }
+}
+
 impl fmt::Display for Downloads {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.0 {
@@ -743,6 +2064,12 @@ impl fmt::Display for Downloads {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 pub struct Likes(u64);
 
+impl Likes {
+    pub fn count(self) -> u64 {
+        self.0
+    }
+}
+
 impl fmt::Display for Likes {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -755,8 +2082,502 @@ pub struct File {
     pub name: String,
 }
 
+impl File {
+    pub const QUANTIZATIONS: &'static [&'static str] = &[
+        "IQ1_S", "IQ1_M", "IQ2_XXS", "IQ2_XS", "IQ2_S", "IQ2_M", "Q2_K", "IQ3_XXS", "IQ3_XS",
+        "IQ3_S", "IQ3_M", "Q3_K_S", "Q3_K_M", "Q3_K_L", "IQ4_XS", "IQ4_NL", "Q4_0", "Q4_K_S",
+        "Q4_K_M", "Q5_0", "Q5_K_S", "Q5_K_M", "Q6_K", "Q8_0", "F16", "BF16", "F32",
+    ];
+
+    /// A placeholder file for chats that were created by another app.
+    pub fn imported(source: &str, model: &str) -> Self {
+        Self {
+            model: Id(format!("{source}/{model}")),
+            name: model.to_owned(),
+        }
+    }
+
+    /// A downloaded file whose model is unknown, named after the file.
+    pub fn local(name: String) -> Self {
+        Self {
+            model: Id(format!("local/{}", Self::split(&name).0)),
+            name,
+        }
+    }
+
+    /// Adds the GGUF file at the given path to the models directory, linking
+    /// to it when possible instead of copying it.
+    ///
+    /// The model is identified by the metadata of the file.
+    pub async fn add(path: PathBuf) -> Result<Self, Error> {
+        let metadata = gguf::Metadata::read(path.clone()).await?;
+
+        let Some(name) = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            return Err(Error::InvalidModelFile("it has no name"));
+        };
+
+        let author = metadata
+            .string("general.organization")
+            .or(metadata.string("general.author"))
+            .unwrap_or("local");
+
+        let model = metadata
+            .string("general.basename")
+            .or(metadata.string("general.name"))
+            .unwrap_or(Self::split(&name).0);
+
+        let file = Self {
+            model: Id(format!("{author}/{model}")),
+            name,
+        };
+
+        let destination = file.path();
+        let path = fs::canonicalize(path).await?;
+
+        if fs::try_exists(&destination).await? {
+            if is_same_file(&path, &destination).await? {
+                return Ok(file);
+            }
+
+            return Err(Error::InvalidModelFile(
+                "a different file with the same name was already added",
+            ));
+        }
+
+        fs::create_dir_all(Assistant::MODELS_DIR).await?;
+
+        #[cfg(unix)]
+        let linked = fs::symlink(&path, &destination).await.is_ok();
+
+        #[cfg(not(unix))]
+        let linked = false;
+
+        if !linked {
+            let _ = fs::copy(&path, &destination).await?;
+        }
+
+        file.mark_local().await?;
+
+        Ok(file)
+    }
+
+    /// Marks the file as added from disk or produced on this computer, so
+    /// it is never checked against Hugging Face; even if its model exists
+    /// there.
+    pub async fn mark_local(&self) -> Result<(), Error> {
+        Ok(fs::write(self.marker_path(), []).await?)
+    }
+
+    /// Whether the file was added from disk or produced on this computer.
+    pub async fn is_local(&self) -> bool {
+        fs::try_exists(self.marker_path()).await.unwrap_or(false)
+    }
+
+    /// The quantization of the file, if known.
+    pub fn quantization(&self) -> Option<&'static str> {
+        Self::split(&self.name)
+            .1
+            .map(|rank| Self::QUANTIZATIONS[rank])
+    }
+
+    /// The quantizations smaller than the one of this file that can be
+    /// produced from it, largest first.
+    ///
+    /// The `IQ` quantizations need an importance matrix, so they are left out.
+    pub fn smaller_quantizations(&self) -> Vec<&'static str> {
+        let Some(rank) = Self::split(&self.name).1 else {
+            return Vec::new();
+        };
+
+        Self::QUANTIZATIONS[..rank]
+            .iter()
+            .rev()
+            .copied()
+            .filter(|quantization| !quantization.starts_with("IQ"))
+            .collect()
+    }
+
+    /// This file with the given quantization, if its own is known.
+    pub fn requantized(&self, quantization: &str) -> Option<File> {
+        let (stem, Some(_rank)) = Self::split(&self.name) else {
+            return None;
+        };
+
+        Some(File {
+            model: self.model.clone(),
+            name: format!("{stem}-{quantization}.gguf"),
+        })
+    }
+
+    pub fn is_downloaded(&self) -> bool {
+        self.path().exists()
+    }
+
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(Assistant::MODELS_DIR).join(&self.name)
+    }
+
+    pub fn partial_path(&self) -> PathBuf {
+        PathBuf::from(Assistant::MODELS_DIR).join(format!("{}.part", self.name))
+    }
+
+    fn marker_path(&self) -> PathBuf {
+        PathBuf::from(Assistant::MODELS_DIR).join(format!("{}.local", self.name))
+    }
+
+    pub fn url(&self) -> String {
+        format!(
+            "https://huggingface.co/{id}/resolve/main/{filename}?download=true",
+            id = self.model.0,
+            filename = self.name
+        )
+    }
+
+    /// Fetches the Git LFS pointer of the file from Hugging Face.
+    pub async fn pointer(&self) -> Result<Pointer, Error> {
+        let url = format!(
+            "https://huggingface.co/{model}/raw/main/{filename}",
+            model = self.model.0,
+            filename = self.name
+        );
+
+        let pointer = hugging_face(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        audit::record(
+            audit::Purpose::Verification,
+            url,
+            Some(pointer.len() as u64),
+        )
+        .await;
+
+        Ok(Pointer::parse(&pointer))
+    }
+
+    /// Lists the original files of the model needed to convert it into
+    /// this file: its safetensors weights, configuration, and tokenizer.
+    pub async fn sources(&self) -> Result<Vec<Source>, Error> {
+        #[derive(Deserialize)]
+        struct Response {
+            siblings: Vec<Sibling>,
+        }
+
+        #[derive(Deserialize)]
+        struct Sibling {
+            rfilename: String,
+            size: Option<u64>,
+        }
+
+        let url = format!("{}/models/{}?blobs=true", Model::API_URL, self.model.0);
+        let bytes = check_rate_limit(hugging_face(&url).send().await?)?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        audit::record(audit::Purpose::ModelSearch, url, Some(bytes.len() as u64)).await;
+
+        let response: Response = serde_json::from_slice(&bytes)?;
+
+        Ok(response
+            .siblings
+            .into_iter()
+            .filter(|sibling| {
+                let name = &sibling.rfilename;
+
+                !name.contains('/')
+                    && (name.ends_with(".safetensors")
+                        || name.ends_with(".json")
+                        || name.ends_with(".txt")
+                        || name.ends_with(".model")
+                        || name.ends_with(".tiktoken"))
+            })
+            .map(|sibling| Source {
+                url: format!(
+                    "https://huggingface.co/{id}/resolve/main/{filename}?download=true",
+                    id = self.model.0,
+                    filename = sibling.rfilename
+                ),
+                name: sibling.rfilename,
+                size: sibling.size,
+            })
+            .collect())
+    }
+
+    /// Lists the quantizations of the same model that are smaller than this
+    /// one, largest first.
+    pub async fn smaller(&self) -> Result<Vec<File>, Error> {
+        #[derive(Deserialize)]
+        struct Response {
+            siblings: Vec<Sibling>,
+        }
+
+        #[derive(Deserialize)]
+        struct Sibling {
+            rfilename: String,
+        }
+
+        let (stem, Some(rank)) = Self::split(&self.name) else {
+            return Ok(Vec::new());
+        };
+
+        let url = format!("{}/models/{}", Model::API_URL, self.model.0);
+        let bytes = check_rate_limit(hugging_face(&url).send().await?)?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        audit::record(audit::Purpose::ModelSearch, url, Some(bytes.len() as u64)).await;
+
+        let response: Response = serde_json::from_slice(&bytes)?;
+
+        let mut files: Vec<_> = response
+            .siblings
+            .into_iter()
+            .filter_map(|sibling| {
+                let (candidate, candidate_rank) = Self::split(&sibling.rfilename);
+
+                (sibling.rfilename.ends_with(".gguf") && candidate.eq_ignore_ascii_case(stem))
+                    .then_some(candidate_rank)
+                    .flatten()
+                    .filter(|candidate_rank| *candidate_rank < rank)
+                    .map(|candidate_rank| (candidate_rank, sibling.rfilename))
+            })
+            .collect();
+
+        files.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+        Ok(files
+            .into_iter()
+            .map(|(_, name)| File {
+                model: self.model.clone(),
+                name,
+            })
+            .collect())
+    }
+
+    pub async fn list_downloaded() -> Result<Vec<String>, Error> {
+        let mut files = Vec::new();
+
+        let Ok(mut entries) = fs::read_dir(Assistant::MODELS_DIR).await else {
+            return Ok(files);
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if name.ends_with(".gguf") {
+                files.push(name);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Finds the downloaded file that best replaces this one.
+    ///
+    /// Only files of the same model are considered; among those, the
+    /// closest quantization wins.
+    pub fn relink(&self, downloaded: &[String]) -> Option<File> {
+        let (stem, quantization) = Self::split(&self.name);
+
+        downloaded
+            .iter()
+            .filter_map(|name| {
+                let (candidate, candidate_quantization) = Self::split(name);
+
+                (candidate.eq_ignore_ascii_case(stem)).then(|| {
+                    let distance = match (quantization, candidate_quantization) {
+                        (Some(a), Some(b)) => a.abs_diff(b),
+                        (None, None) => 0,
+                        _ => Self::QUANTIZATIONS.len(),
+                    };
+
+                    (distance, name)
+                })
+            })
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, name)| File {
+                model: self.model.clone(),
+                name: name.clone(),
+            })
+    }
+
+    fn split(name: &str) -> (&str, Option<usize>) {
+        let name = name.strip_suffix(".gguf").unwrap_or(name);
+
+        Self::QUANTIZATIONS
+            .iter()
+            .enumerate()
+            .find_map(|(rank, quantization)| {
+                let stem = name.get(..name.len().checked_sub(quantization.len())?)?;
+                let suffix = &name[stem.len()..];
+
+                suffix
+                    .eq_ignore_ascii_case(quantization)
+                    .then_some((stem.strip_suffix(['-', '.', '_'])?, Some(rank)))
+            })
+            .unwrap_or((name, None))
+    }
+}
+
 impl fmt::Display for File {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(&self.name)
     }
 }
+
+/// An original file of a model, converted into GGUF locally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub name: String,
+    pub size: Option<u64>,
+    pub url: String,
+}
+
+/// The Git LFS metadata of a model file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+    pub sha256: Option<String>,
+    pub size: u64,
+}
+
+impl Pointer {
+    fn parse(pointer: &str) -> Self {
+        let mut sha256 = None;
+        let mut size = 0;
+
+        for line in pointer.lines() {
+            if let Some(oid) = line.strip_prefix("oid sha256:") {
+                sha256 = Some(oid.trim().to_lowercase());
+            } else if let Some(bytes) = line.strip_prefix("size ") {
+                size = bytes.trim().parse().unwrap_or_default();
+            }
+        }
+
+        Self { sha256, size }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn families_ignore_packaging_details() {
+        let llama = "llama-3.1-8b-instruct";
+
+        assert_eq!(family("Meta-Llama-3.1-8B-Instruct-GGUF"), llama);
+        assert_eq!(family("Llama-3.1-8B-Instruct-Q4_K_M-GGUF"), llama);
+        assert_eq!(family("Meta-Llama-3.1-8B-Instruct-i1-GGUF"), llama);
+        assert_ne!(family("Llama-3.1-70B-Instruct-GGUF"), llama);
+    }
+
+    #[test]
+    fn base_models_are_told_apart() {
+        let tags = |tags: &[&str]| tags.iter().map(|tag| (*tag).to_owned()).collect::<Vec<_>>();
+
+        assert_eq!(
+            Kind::detect("Llama-3.1-8B-Instruct-GGUF", &[]),
+            Kind::Instruct
+        );
+        assert_eq!(Kind::detect("gemma-2-9b-it-GGUF", &[]), Kind::Instruct);
+        assert_eq!(
+            Kind::detect("phi-4-GGUF", &tags(&["gguf", "conversational"])),
+            Kind::Instruct
+        );
+        assert_eq!(
+            Kind::detect("Llama-3.1-8B-GGUF", &tags(&["gguf"])),
+            Kind::Base
+        );
+        assert_eq!(Kind::detect("Mistral-7B-v0.1-GGUF", &[]), Kind::Base);
+    }
+
+    #[test]
+    fn transcripts_end_with_the_assistant() {
+        assert_eq!(
+            transcript("Be brief.", &[Message::User("Hi!".to_owned())]),
+            "Be brief.\n\nUser: Hi!\n\nAssistant:"
+        );
+    }
+
+    #[test]
+    fn parameters_are_read_from_names() {
+        assert_eq!(parameters("Llama-3.1-8B-Instruct"), Some(8_000_000_000));
+        assert_eq!(parameters("Qwen2.5-0.5B-Instruct-GGUF"), Some(500_000_000));
+        assert_eq!(parameters("SmolLM2-135M-Instruct"), Some(135_000_000));
+        assert_eq!(parameters("Mixtral-8x7B-v0.1"), Some(56_000_000_000));
+        assert_eq!(parameters("phi-4"), None);
+    }
+
+    #[test]
+    fn backends_are_detected_from_the_adapter() {
+        assert_eq!(Backend::detect("NVIDIA GeForce RTX 4090"), Backend::Cuda);
+        assert_eq!(Backend::detect("AMD Radeon RX 7900 XTX"), Backend::Rocm);
+        assert_eq!(
+            Backend::detect("Intel(R) Arc(TM) A770 Graphics"),
+            Backend::Vulkan
+        );
+        assert_eq!(
+            Backend::detect("Mesa Intel(R) UHD Graphics 620 (KBL GT2)"),
+            Backend::Vulkan
+        );
+        assert_eq!(
+            Backend::detect("llvmpipe (LLVM 15.0.7, 256 bits)"),
+            Backend::Cpu
+        );
+        assert_eq!(Backend::detect("Apple M2 Pro"), Backend::Metal);
+    }
+
+    #[test]
+    fn extra_flags_are_tokenized() {
+        assert_eq!(
+            parse_flags("--flash-attn  --rope-scaling yarn --alias 'My model'")
+                .expect("flags should parse"),
+            vec![
+                "--flash-attn".to_owned(),
+                "--rope-scaling".to_owned(),
+                "yarn".to_owned(),
+                "--alias".to_owned(),
+                "My model".to_owned(),
+            ]
+        );
+        assert!(parse_flags("   ").is_ok_and(|arguments| arguments.is_empty()));
+        assert!(parse_flags("--alias \"unclosed").is_err());
+        assert!(parse_flags("yarn --flash-attn").is_err());
+        assert!(parse_flags("--port 9000").is_err());
+        assert!(parse_flags("--model=other.gguf").is_err());
+    }
+
+    #[test]
+    fn metal_offloads_every_layer() {
+        let inference = Inference {
+            gpu: Some(1),
+            ..Inference::default()
+        };
+
+        assert_eq!(inference.flags(Backend::Metal, 80), "--gpu-layers 999");
+        assert_eq!(
+            inference.flags(Backend::Cuda, 80),
+            "--gpu-layers 80 --split-mode none --main-gpu 1"
+        );
+    }
+
+    #[test]
+    fn retry_after_is_parsed() {
+        assert_eq!(retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(retry_after("soon"), None);
+
+        let later = (chrono::Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let wait = retry_after(&later).expect("future dates should parse");
+
+        assert!(wait > Duration::from_secs(80) && wait <= Duration::from_secs(90));
+    }
+}
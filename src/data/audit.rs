@@ -0,0 +1,111 @@
+use crate::data::{self, Error};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// An outbound network request made by the application.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub timestamp: chrono::DateTime<chrono::Local>,
+    pub purpose: Purpose,
+    pub endpoint: String,
+    pub received: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Purpose {
+    ModelSearch,
+    ModelCard,
+    Catalog,
+    Verification,
+    Download,
+    WebSearch,
+    Scrape,
+}
+
+impl Purpose {
+    pub const ALL: &'static [Self] = &[
+        Self::ModelSearch,
+        Self::ModelCard,
+        Self::Catalog,
+        Self::Verification,
+        Self::Download,
+        Self::WebSearch,
+        Self::Scrape,
+    ];
+}
+
+impl fmt::Display for Purpose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Purpose::ModelSearch => "Model search",
+            Purpose::ModelCard => "Model card",
+            Purpose::Catalog => "Recommendations",
+            Purpose::Verification => "Verification",
+            Purpose::Download => "Download",
+            Purpose::WebSearch => "Web search",
+            Purpose::Scrape => "Page fetch",
+        })
+    }
+}
+
+async fn path() -> Result<PathBuf, io::Error> {
+    Ok(data::data_dir().await?.join("audit.jsonl"))
+}
+
+/// Appends a request to the audit log.
+///
+/// Failing to write the log never fails the request itself.
+pub async fn record(purpose: Purpose, endpoint: impl Into<String>, received: Option<u64>) {
+    let record = Record {
+        timestamp: chrono::Local::now(),
+        purpose,
+        endpoint: endpoint.into(),
+        received,
+    };
+
+    let result: Result<(), Error> = async {
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path().await?)
+            .await?;
+
+        file.write_all(&line).await?;
+
+        Ok(())
+    }
+    .await;
+
+    if let Err(error) = result {
+        let _ = dbg!(error);
+    }
+}
+
+/// Fetches every recorded request, most recent first.
+pub async fn fetch() -> Result<Vec<Record>, Error> {
+    let Ok(contents) = fs::read_to_string(path().await?).await else {
+        return Ok(Vec::new());
+    };
+
+    Ok(contents
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+pub async fn clear() -> Result<(), Error> {
+    match fs::remove_file(path().await?).await {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,136 @@
+use crate::data::chat;
+use crate::data::{Error, Settings};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::task;
+
+use std::path::{Component, Path, PathBuf};
+
+/// The current version of the backup format.
+const VERSION: u32 = 1;
+
+/// A snapshot of the application data: settings, with their memory and
+/// variables, and chats.
+///
+/// Model files are not included.
+#[derive(Debug, Serialize, Deserialize)]
+struct Archive {
+    version: u32,
+    created_at: chrono::DateTime<chrono::Local>,
+    files: Vec<File>,
+    checksum: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct File {
+    path: PathBuf,
+    contents: String,
+}
+
+/// The settings file, relative to the data directory.
+const SETTINGS: &str = "settings.toml";
+
+/// The directory of the chats, relative to the data directory.
+const CHATS: &str = "chats";
+
+/// Writes a backup archive of the application data to the given path.
+///
+/// Only the settings, without secrets, and the chats are included; caches,
+/// indexes, locks, and managed builds are left out.
+pub async fn create(path: PathBuf) -> Result<(), Error> {
+    let settings = Settings::fetch().await?.without_secrets();
+    let settings = task::spawn_blocking(move || toml::to_string_pretty(&settings)).await??;
+
+    let mut files = vec![File {
+        path: PathBuf::from(SETTINGS),
+        contents: settings,
+    }];
+
+    files.extend(
+        chat::backup()
+            .await?
+            .into_iter()
+            .map(|(name, contents)| File {
+                path: Path::new(CHATS).join(name),
+                contents,
+            }),
+    );
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let archive = Archive {
+        version: VERSION,
+        created_at: chrono::Local::now(),
+        checksum: checksum(&files),
+        files,
+    };
+
+    let bytes = task::spawn_blocking(move || serde_json::to_vec_pretty(&archive)).await??;
+    fs::write(path, bytes).await?;
+
+    Ok(())
+}
+
+/// Restores the application data from the backup archive at the given path.
+///
+/// Existing chats with the same id are overwritten, and the current secrets
+/// are kept. Returns the restored settings.
+pub async fn restore(path: PathBuf) -> Result<Settings, Error> {
+    let bytes = fs::read(path).await?;
+    let archive: Archive = task::spawn_blocking(move || serde_json::from_slice(&bytes)).await??;
+
+    if archive.version > VERSION {
+        return Err(Error::InvalidBackup(
+            "the backup was made by a newer version",
+        ));
+    }
+
+    if archive.checksum != checksum(&archive.files) {
+        return Err(Error::InvalidBackup("the backup is corrupted"));
+    }
+
+    if !archive.files.iter().all(|file| is_relative(&file.path)) {
+        return Err(Error::InvalidBackup("the backup contains invalid paths"));
+    }
+
+    let mut chats = Vec::new();
+
+    // Anything else, like the files swept in by older backups, is skipped
+    for file in archive.files {
+        if file.path == Path::new(SETTINGS) {
+            let restored: Settings =
+                task::spawn_blocking(move || toml::from_str(&file.contents)).await??;
+
+            // Secrets are never backed up, so the current ones are kept
+            let current = Settings::fetch().await.unwrap_or_default();
+
+            restored.with_secrets_of(&current).save().await?;
+        } else if let Ok(name) = file.path.strip_prefix(CHATS) {
+            chats.push((name.to_string_lossy().into_owned(), file.contents));
+        }
+    }
+
+    chat::restore(chats).await?;
+
+    Settings::fetch().await
+}
+
+fn checksum(files: &[File]) -> String {
+    let mut hasher = Sha256::new();
+
+    for file in files {
+        hasher.update(file.path.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(file.contents.as_bytes());
+        hasher.update([0]);
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+fn is_relative(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
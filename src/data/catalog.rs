@@ -0,0 +1,241 @@
+//! A curated catalog of recommended models for newcomers.
+use crate::data::assistant::{File, Id};
+use crate::data::audit;
+use crate::data::hardware::{self, Fit, Memory};
+use crate::data::{self, Error};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// The recommended models, grouped by use case.
+///
+/// A catalog is bundled with the app and updated from the repository, so
+/// recommendations can change between releases.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Catalog {
+    /// The revision of the catalog; newer revisions replace older ones.
+    pub version: u32,
+    pub models: Vec<Entry>,
+}
+
+/// A recommended model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: Id,
+    pub name: String,
+    pub description: String,
+    pub use_case: UseCase,
+    pub tier: Tier,
+    /// The recommended files of the model, smallest first.
+    pub files: Vec<Choice>,
+}
+
+/// A file of a recommended model.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Choice {
+    pub name: String,
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UseCase {
+    Chat,
+    Coding,
+    Reasoning,
+    Writing,
+}
+
+impl UseCase {
+    pub const ALL: &'static [Self] = &[Self::Chat, Self::Coding, Self::Reasoning, Self::Writing];
+}
+
+impl fmt::Display for UseCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UseCase::Chat => "Chat",
+            UseCase::Coding => "Coding",
+            UseCase::Reasoning => "Reasoning",
+            UseCase::Writing => "Writing",
+        })
+    }
+}
+
+/// The hardware a model is meant for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tier {
+    /// Laptops with 8 GB of memory.
+    Light,
+    /// Computers with 16 GB of memory.
+    Standard,
+    /// Workstations with 32 GB of memory or a large GPU.
+    Heavy,
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Tier::Light => "8 GB",
+            Tier::Standard => "16 GB",
+            Tier::Heavy => "32 GB",
+        })
+    }
+}
+
+impl Catalog {
+    const BUNDLED: &'static str = include_str!("../../catalog/models.json");
+
+    const URL: &'static str =
+        "https://raw.githubusercontent.com/hecrj/icebreaker/master/catalog/models.json";
+
+    async fn path() -> Result<PathBuf, io::Error> {
+        Ok(data::data_dir().await?.join("catalog.json"))
+    }
+
+    /// The catalog bundled with the app.
+    pub fn bundled() -> Self {
+        serde_json::from_str(Self::BUNDLED).expect("bundled catalog must be valid")
+    }
+
+    /// Fetches the latest catalog known locally; the bundled one, unless a
+    /// newer one was downloaded before.
+    pub async fn fetch() -> Self {
+        let bundled = Self::bundled();
+
+        let Ok(path) = Self::path().await else {
+            return bundled;
+        };
+
+        let Ok(bytes) = fs::read(path).await else {
+            return bundled;
+        };
+
+        match serde_json::from_slice::<Self>(&bytes) {
+            Ok(catalog) if catalog.version > bundled.version => catalog,
+            _ => bundled,
+        }
+    }
+
+    /// Downloads the catalog from the repository, keeping it for later.
+    pub async fn update() -> Result<Self, Error> {
+        let bytes = reqwest::get(Self::URL)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        audit::record(audit::Purpose::Catalog, Self::URL, Some(bytes.len() as u64)).await;
+
+        let catalog: Self = serde_json::from_slice(&bytes)?;
+
+        fs::write(Self::path().await?, &bytes).await?;
+
+        Ok(catalog)
+    }
+
+    /// The recommended models for the given use case.
+    pub fn entries(&self, use_case: UseCase) -> impl Iterator<Item = &Entry> {
+        self.models
+            .iter()
+            .filter(move |entry| entry.use_case == use_case)
+    }
+}
+
+impl Entry {
+    /// The largest file that fits in the given memory, if any.
+    pub fn suggest(&self, memory: Memory) -> Option<File> {
+        self.files
+            .iter()
+            .rev()
+            .find(|choice| {
+                hardware::estimate(Some(choice.size), None, None)
+                    .is_some_and(|required| memory.fit(required) != Fit::TooLarge)
+            })
+            .map(|choice| self.file(choice))
+    }
+
+    pub fn file(&self, choice: &Choice) -> File {
+        File {
+            model: self.id.clone(),
+            name: choice.name.clone(),
+        }
+    }
+
+    /// The size of the given file, if it is part of the catalog.
+    pub fn size(&self, file: &File) -> Option<u64> {
+        self.files
+            .iter()
+            .find(|choice| choice.name == file.name)
+            .map(|choice| choice.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_catalog_is_valid() {
+        let catalog = Catalog::bundled();
+
+        for use_case in UseCase::ALL {
+            assert!(catalog.entries(*use_case).next().is_some());
+        }
+
+        for entry in &catalog.models {
+            assert!(!entry.files.is_empty(), "{} has no files", entry.name);
+
+            for choice in &entry.files {
+                assert!(
+                    entry.file(choice).quantization().is_some(),
+                    "{} has no known quantization",
+                    choice.name
+                );
+            }
+
+            assert!(
+                entry.files.is_sorted_by_key(|choice| choice.size),
+                "{} files are not sorted",
+                entry.name
+            );
+        }
+    }
+
+    #[test]
+    fn largest_fitting_file_is_suggested() {
+        const GB: u64 = 1_000_000_000;
+
+        let entry: Entry = serde_json::from_str(
+            r#"{
+                "id": "bartowski/Example-8B-GGUF",
+                "name": "Example 8B",
+                "description": "An example.",
+                "use_case": "chat",
+                "tier": "standard",
+                "files": [
+                    { "name": "Example-8B-Q4_K_M.gguf", "size": 4000000000 },
+                    { "name": "Example-8B-Q8_0.gguf", "size": 9000000000 }
+                ]
+            }"#,
+        )
+        .expect("entry should parse");
+
+        let memory = |ram| Memory { ram, vram: None };
+
+        assert_eq!(
+            entry.suggest(memory(16 * GB)).map(|file| file.name),
+            Some("Example-8B-Q8_0.gguf".to_owned())
+        );
+        assert_eq!(
+            entry.suggest(memory(8 * GB)).map(|file| file.name),
+            Some("Example-8B-Q4_K_M.gguf".to_owned())
+        );
+        assert_eq!(entry.suggest(memory(4 * GB)), None);
+    }
+}
@@ -1,17 +1,41 @@
+#[cfg(feature = "sqlite")]
+mod database;
+mod export;
+mod import;
+mod metadata;
 mod schema;
+mod snapshot;
 
-use crate::data::assistant::{self, Assistant, Message};
-use crate::data::chat::schema::Schema;
-use crate::data::Error;
+pub use export::Format;
+pub use metadata::{Language, Metadata};
+pub use snapshot::Snapshot;
+
+#[cfg(feature = "sqlite")]
+use database::{load, remove, saved, store};
 
+use crate::data::assistant::{self, Assistant, Message, ToolTurn};
+use crate::data::chat::schema::Schema;
+use crate::data::guardrails::Guardrails;
+use crate::data::knowledge::{self, Knowledge};
+use crate::data::tools::{self, Tool};
+use crate::data::web;
+use crate::data::{self, Error};
+
+use futures::channel::mpsc;
+use futures::future;
 use futures::{SinkExt, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 use tokio::task;
 use uuid::Uuid;
 
+use std::collections::BTreeMap;
+use std::fmt;
 use std::io;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
@@ -20,9 +44,40 @@ pub struct Chat {
     pub file: assistant::File,
     pub title: Option<String>,
     pub history: Vec<Message>,
+    pub system_prompt: Option<String>,
+    pub guardrails: Guardrails,
+    pub sampling: assistant::Sampling,
+    pub memory: Vec<String>,
+}
+
+/// The contents of a chat to be saved, which gets an [`Id`] once created.
+#[derive(Debug, Clone)]
+pub struct Draft {
+    pub file: assistant::File,
+    pub title: Option<String>,
+    pub history: Vec<Message>,
+    pub system_prompt: Option<String>,
+    pub guardrails: Guardrails,
+    pub sampling: assistant::Sampling,
+    pub memory: Vec<String>,
+}
+
+impl From<Chat> for Draft {
+    fn from(chat: Chat) -> Self {
+        Self {
+            file: chat.file,
+            title: chat.title,
+            history: chat.history,
+            system_prompt: chat.system_prompt,
+            guardrails: chat.guardrails,
+            sampling: chat.sampling,
+            memory: chat.memory,
+        }
+    }
 }
 
 impl Chat {
+    #[cfg(not(feature = "sqlite"))]
     async fn path(id: &Id) -> Result<PathBuf, Error> {
         Ok(storage_dir().await?.join(format!("{}.json", id.0.simple())))
     }
@@ -34,16 +89,26 @@ impl Chat {
     }
 
     pub async fn fetch(id: Id) -> Result<Self, Error> {
-        let bytes = fs::read(Self::path(&id).await?).await?;
-        let schema: Schema = task::spawn_blocking(move || serde_json::from_slice(&bytes)).await??;
+        let chat = Self::read(id).await?;
 
         let _ = LastOpened::update(id).await;
 
+        Ok(chat)
+    }
+
+    /// Reads the chat without opening it.
+    async fn read(id: Id) -> Result<Self, Error> {
+        let schema = load(id).await?;
+
         Ok(Self {
             id,
             file: schema.file,
             title: schema.title,
             history: schema.history.into_iter().map(Message::from).collect(),
+            system_prompt: schema.system_prompt,
+            guardrails: schema.guardrails,
+            sampling: schema.sampling,
+            memory: schema.memory,
         })
     }
 
@@ -53,68 +118,345 @@ impl Chat {
         Self::fetch(id).await
     }
 
-    pub async fn create(
-        file: assistant::File,
-        title: Option<String>,
-        history: Vec<Message>,
-    ) -> Result<Self, Error> {
-        let id = Id(Uuid::new_v4());
-        let chat = Self::save(id, file, title, history).await?;
+    pub async fn create(draft: Draft) -> Result<Self, Error> {
+        let chat = Self::save(Id(Uuid::new_v4()), draft).await?;
 
         LastOpened::update(chat.id).await?;
 
+        let (languages, has_code) = summarize(
+            chat.history
+                .iter()
+                .map(|message| Metadata::compute(message.content())),
+        );
+
         List::push(Entry {
             id: chat.id,
             file: chat.file.clone(),
             title: chat.title.clone(),
+            languages,
+            has_code,
+            tags: Vec::new(),
         })
         .await?;
 
         Ok(chat)
     }
 
-    pub async fn save(
-        id: Id,
-        file: assistant::File,
-        title: Option<String>,
-        history: Vec<Message>,
-    ) -> Result<Self, Error> {
-        if let Ok(current) = Self::fetch(id).await {
-            if current.title != title {
-                let mut list = List::fetch().await?;
+    /// Creates a new chat with the history of the given chat up to and
+    /// including the message at the given index.
+    pub async fn fork(id: Id, index: usize) -> Result<Self, Error> {
+        let mut chat = Self::fetch(id).await?;
+        chat.history.truncate(index + 1);
 
-                if let Some(entry) = list.entries.iter_mut().find(|entry| entry.id == id) {
-                    entry.title = title.clone();
-                }
+        Self::create(Draft {
+            title: chat.title.clone().map(|title| format!("{title} (branch)")),
+            ..Draft::from(chat)
+        })
+        .await
+    }
 
-                list.save().await?;
-            }
-        }
+    pub async fn save(id: Id, draft: Draft) -> Result<Self, Error> {
+        let Draft {
+            file,
+            title,
+            history,
+            system_prompt,
+            guardrails,
+            sampling,
+            memory,
+        } = draft;
 
         let chat = Schema {
             id,
             file,
             title,
             history: history.iter().cloned().map(schema::Message::from).collect(),
+            system_prompt,
+            guardrails,
+            sampling,
+            memory,
         };
 
-        let (bytes, chat) =
-            task::spawn_blocking(move || (serde_json::to_vec_pretty(&chat), chat)).await?;
+        let (languages, has_code) =
+            summarize(chat.history.iter().map(schema::Message::metadata).cloned());
 
-        fs::write(Self::path(&chat.id).await?, bytes?).await?;
+        let title = chat.title.clone();
 
-        Ok(Self {
+        List::update(move |list| {
+            let Some(entry) = list.entries.iter_mut().find(|entry| entry.id == id) else {
+                return false;
+            };
+
+            if entry.title == title && entry.languages == languages && entry.has_code == has_code {
+                return false;
+            }
+
+            entry.title = title;
+            entry.languages = languages;
+            entry.has_code = has_code;
+
+            true
+        })
+        .await?;
+
+        let generation = Writes::queue(id);
+        tokio::time::sleep(Writes::DELAY).await;
+
+        // A newer save of the chat replaces this one
+        if !Writes::is_latest(id, generation) {
+            return Ok(Self::from_schema(chat, history));
+        }
+
+        let (bytes, chat) = task::spawn_blocking(move || (serde_json::to_vec(&chat), chat)).await?;
+
+        let bytes = bytes?;
+        let digest = digest(&bytes);
+
+        // Unchanged chats are not written again
+        let chat = if Writes::is_written(id, digest) {
+            chat
+        } else {
+            let _ = snapshot::take(&chat.id, &bytes).await;
+
+            let chat = store(chat, bytes).await?;
+            Writes::finish(id, digest);
+
+            chat
+        };
+
+        Ok(Self::from_schema(chat, history))
+    }
+
+    fn from_schema(chat: Schema, history: Vec<Message>) -> Self {
+        Self {
             id: chat.id,
             file: chat.file,
             title: chat.title,
             history,
+            system_prompt: chat.system_prompt,
+            guardrails: chat.guardrails,
+            sampling: chat.sampling,
+            memory: chat.memory,
+        }
+    }
+
+    /// Writes a title for the given chat with the assistant, unless it
+    /// already has one.
+    ///
+    /// Returns the new title, if any.
+    pub async fn retitle(assistant: Assistant, id: Id) -> Result<Option<String>, Error> {
+        const SYSTEM_PROMPT: &str =
+            "You write titles for conversations between a user and an assistant.";
+
+        let chat = Self::read(id).await?;
+
+        if chat.title.is_some() || chat.history.is_empty() {
+            return Ok(None);
+        }
+
+        let assistant = assistant.summarizer();
+
+        let sampling = assistant::Sampling {
+            max_tokens: Some(32),
+            ..assistant::Sampling::default()
+        };
+
+        let mut messages = chat.history.clone();
+        messages.push(Message::User(TITLE_REQUEST.to_owned()));
+
+        let _ = fit_context(assistant, SYSTEM_PROMPT, sampling, &mut messages).await?;
+
+        let mut tokens = assistant
+            .complete(SYSTEM_PROMPT, &messages, sampling)
+            .boxed();
+        let mut title = String::new();
+
+        while let Some(token) = tokens.next().await.transpose()? {
+            if let assistant::Token::Talking(token) = token {
+                title.push_str(&token);
+
+                if title.len() > 80 {
+                    title.push_str("...");
+                    break;
+                }
+            }
+        }
+
+        let title = title.trim().trim_matches('"').to_owned();
+
+        if title.is_empty() {
+            return Ok(None);
+        }
+
+        let _ = Self::save(
+            chat.id,
+            Draft {
+                title: Some(title.clone()),
+                ..Draft::from(chat)
+            },
+        )
+        .await?;
+
+        Ok(Some(title))
+    }
+
+    /// Replaces the tags of the given chat.
+    pub async fn tag(id: Id, tags: Vec<String>) -> Result<(), Error> {
+        List::update(move |list| {
+            let Some(entry) = list.entries.iter_mut().find(|entry| entry.id == id) else {
+                return false;
+            };
+
+            entry.tags = tags;
+
+            true
         })
+        .await
+    }
+
+    /// Points chats of missing model files to equivalent downloaded ones.
+    ///
+    /// Returns the amount of chats that were relinked.
+    pub async fn relink() -> Result<usize, Error> {
+        let downloaded = assistant::File::list_downloaded().await?;
+        let list = List::fetch().await?;
+        let mut relinked = Vec::new();
+
+        for entry in &list.entries {
+            if entry.file.is_downloaded() {
+                continue;
+            }
+
+            let Some(file) = entry.file.relink(&downloaded) else {
+                continue;
+            };
+
+            let mut schema = load(entry.id).await?;
+            schema.file = file.clone();
+
+            let (bytes, schema) =
+                task::spawn_blocking(move || (serde_json::to_vec(&schema), schema)).await?;
+
+            let _ = store(schema, bytes?).await?;
+            Writes::forget(entry.id);
+
+            relinked.push((entry.id, file));
+        }
+
+        let count = relinked.len();
+
+        if count > 0 {
+            List::update(move |list| {
+                for entry in &mut list.entries {
+                    if let Some((_, file)) = relinked.iter().find(|(id, _)| *id == entry.id) {
+                        entry.file = file.clone();
+                    }
+                }
+
+                true
+            })
+            .await?;
+        }
+
+        Ok(count)
+    }
+
+    /// Scans every saved chat for the given query.
+    ///
+    /// Titles and message bodies are matched case-insensitively.
+    pub async fn search(query: String) -> Result<Vec<Hit>, Error> {
+        const MAX_HITS_PER_CHAT: usize = 3;
+        const MAX_HITS: usize = 100;
+
+        let needle = query.trim().to_lowercase();
+
+        if needle.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let list = List::fetch().await?;
+        let mut hits = Vec::new();
+
+        #[cfg(feature = "sqlite")]
+        let found = database::search(needle.clone()).await?;
+
+        for entry in list.entries {
+            if hits.len() >= MAX_HITS {
+                break;
+            }
+
+            if let Some((snippet, highlight)) = entry
+                .title
+                .as_deref()
+                .and_then(|title| Hit::snippet(title, &needle))
+            {
+                hits.push(Hit {
+                    id: entry.id,
+                    title: entry.title,
+                    message: None,
+                    snippet,
+                    highlight,
+                });
+
+                continue;
+            }
+
+            #[cfg(not(feature = "sqlite"))]
+            let matches = {
+                let Ok(bytes) = fs::read(Self::path(&entry.id).await?).await else {
+                    continue;
+                };
+
+                let needle = needle.clone();
+
+                task::spawn_blocking(move || {
+                    let Ok(schema) = serde_json::from_slice::<Schema>(&bytes) else {
+                        return Vec::new();
+                    };
+
+                    schema
+                        .history
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(index, message)| {
+                            Hit::snippet(message.content(), &needle)
+                                .map(|(snippet, highlight)| (index, snippet, highlight))
+                        })
+                        .take(MAX_HITS_PER_CHAT)
+                        .collect::<Vec<_>>()
+                })
+                .await?
+            };
+
+            #[cfg(feature = "sqlite")]
+            let matches: Vec<_> = found
+                .iter()
+                .filter(|(id, ..)| *id == entry.id)
+                .filter_map(|(_, index, content)| {
+                    Hit::snippet(content, &needle)
+                        .map(|(snippet, highlight)| (*index, snippet, highlight))
+                })
+                .take(MAX_HITS_PER_CHAT)
+                .collect();
+
+            hits.extend(matches.into_iter().map(|(index, snippet, highlight)| Hit {
+                id: entry.id,
+                title: entry.title.clone(),
+                message: Some(index),
+                snippet,
+                highlight,
+            }));
+        }
+
+        Ok(hits)
     }
 
     pub async fn delete(id: Id) -> Result<(), Error> {
-        fs::remove_file(Self::path(&id).await?).await?;
+        Writes::forget(id);
+        remove(&id).await?;
 
         let _ = List::remove(&id).await;
+        let _ = snapshot::delete(&id).await;
 
         match LastOpened::fetch().await {
             Ok(LastOpened(last_opened)) if id == last_opened => {
@@ -136,6 +478,59 @@ impl Chat {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub id: Id,
+    pub title: Option<String>,
+    pub message: Option<usize>,
+    pub snippet: String,
+    pub highlight: Range<usize>,
+}
+
+impl Hit {
+    fn snippet(content: &str, needle: &str) -> Option<(String, Range<usize>)> {
+        const CONTEXT: usize = 40;
+
+        let lowercase = content.to_lowercase();
+
+        // Lowercasing may change byte offsets of some characters
+        let haystack = if lowercase.len() == content.len() {
+            lowercase
+        } else {
+            content.to_ascii_lowercase()
+        };
+
+        let offset = haystack.find(needle)?;
+        let end = offset + needle.len();
+
+        if !content.is_char_boundary(offset) || !content.is_char_boundary(end) {
+            return None;
+        }
+
+        let mut start = offset.saturating_sub(CONTEXT);
+        while !content.is_char_boundary(start) {
+            start -= 1;
+        }
+
+        let mut finish = (end + CONTEXT).min(content.len());
+        while !content.is_char_boundary(finish) {
+            finish += 1;
+        }
+
+        let prefix = if start > 0 { "..." } else { "" };
+        let suffix = if finish < content.len() { "..." } else { "" };
+
+        let snippet = format!(
+            "{prefix}{}{suffix}",
+            content[start..finish].replace(['\n', '\r'], " ")
+        );
+
+        let highlight = prefix.len() + offset - start..prefix.len() + end - start;
+
+        Some((snippet, highlight))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Event {
     MessageSent(Message),
@@ -145,8 +540,46 @@ pub enum Event {
         content: String,
         new_token: assistant::Token,
     },
+    UsageReported(assistant::Usage),
+    ContextTruncated(usize),
     ExchangeOver,
     TitleChanged(String),
+    StageChanged(Stage),
+    AlternativeChanged {
+        index: usize,
+        alternative: assistant::Alternative,
+    },
+    /// An alternative stopped because of an error; the reply and the other
+    /// alternatives go on.
+    AlternativeFailed {
+        index: usize,
+        error: Error,
+    },
+}
+
+/// The phase a reply is going through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// The conversation is being fitted in the context of the assistant.
+    Queued,
+    /// The knowledge folders are being searched for relevant passages.
+    Retrieving,
+    /// The model is reading the prompt and has not produced any tokens yet.
+    ProcessingPrompt,
+    Reasoning,
+    Answering,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Queued => "Queued",
+            Stage::Retrieving => "Searching knowledge",
+            Stage::ProcessingPrompt => "Processing prompt",
+            Stage::Reasoning => "Reasoning",
+            Stage::Answering => "Answering",
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -168,69 +601,325 @@ impl Content {
     }
 }
 
+/// The maximum amount of tool-calling rounds in a single reply.
+const MAX_TOOL_ROUNDS: usize = 5;
+
+/// The tokens received since the last update of a reply.
+///
+/// Fast models produce tokens much quicker than the screen refreshes, so
+/// tokens are sent together at most once per frame.
+#[derive(Default)]
+struct Batch {
+    pending: Option<assistant::Token>,
+    tokens: usize,
+    flushed_at: Option<Instant>,
+}
+
+impl Batch {
+    const INTERVAL: Duration = Duration::from_millis(16);
+    const MAX_TOKENS: usize = 64;
+
+    /// Adds a token to the batch, returning the tokens that are due.
+    ///
+    /// Only reasoning and talking tokens are batched; a token of a different
+    /// kind than the pending ones is due right away.
+    fn push(&mut self, token: assistant::Token) -> Vec<assistant::Token> {
+        let mut due = Vec::new();
+
+        self.pending = match (self.pending.take(), token) {
+            (
+                Some(assistant::Token::Reasoning(mut pending)),
+                assistant::Token::Reasoning(token),
+            ) => {
+                pending.push_str(&token);
+                Some(assistant::Token::Reasoning(pending))
+            }
+            (Some(assistant::Token::Talking(mut pending)), assistant::Token::Talking(token)) => {
+                pending.push_str(&token);
+                Some(assistant::Token::Talking(pending))
+            }
+            (pending, token) => {
+                due.extend(pending);
+                Some(token)
+            }
+        };
+
+        self.tokens += 1;
+
+        let is_due = self.tokens >= Self::MAX_TOKENS
+            || self
+                .flushed_at
+                .is_none_or(|flushed_at| flushed_at.elapsed() >= Self::INTERVAL);
+
+        if is_due {
+            due.extend(self.take());
+        }
+
+        due
+    }
+
+    /// Takes the pending tokens, if any.
+    fn take(&mut self) -> Option<assistant::Token> {
+        self.tokens = 0;
+        self.flushed_at = Some(Instant::now());
+
+        self.pending.take()
+    }
+}
+
+/// How the replies of a chat are completed.
+#[derive(Debug, Clone, Copy)]
+pub struct Request<'a> {
+    pub system_prompt: &'a str,
+    pub sampling: assistant::Sampling,
+    pub search: &'a web::Search,
+    pub knowledge: Option<&'a Knowledge>,
+    /// Whether the model suggests a title for the chat after replying.
+    pub suggest_titles: bool,
+}
+
 pub fn complete(
     assistant: &Assistant,
+    request: Request<'_>,
     mut messages: Vec<Message>,
 ) -> impl Stream<Item = Result<Event, Error>> {
-    const SYSTEM_PROMPT: &str = "You are a helpful assistant.";
-
     let assistant = assistant.clone();
+    let mut system_prompt = request.system_prompt.to_owned();
+    let sampling = request.sampling;
+    let search = request.search.clone();
+    let knowledge = request.knowledge.cloned();
+    let suggest_titles = request.suggest_titles;
 
-    iced::stream::try_channel(1, |mut sender| async move {
+    iced::stream::try_channel(1, move |mut sender| async move {
         let mut reasoning = String::new();
         let mut reasoning_started_at: Option<Instant> = None;
         let mut reasoning_duration = Duration::ZERO;
+        let mut reasoned = false;
         let mut content = String::new();
+        let turns = messages.len();
+
+        let query = messages.iter().rev().find_map(|message| match message {
+            Message::User(content) => Some(content.clone()),
+            Message::Assistant { .. } => None,
+        });
+
+        let passages = match knowledge.as_ref().zip(query) {
+            Some((knowledge, query)) => {
+                let _ = sender.send(Event::StageChanged(Stage::Retrieving)).await;
+
+                knowledge::retrieve(knowledge, &query).await?
+            }
+            None => Vec::new(),
+        };
+
+        if !passages.is_empty() {
+            system_prompt = format!("{system_prompt}\n\n{}", knowledge::context(&passages));
+        }
+
+        if search.guard && assistant.can_call_tools() {
+            system_prompt = format!("{system_prompt}\n\n{}", web::GUARD_INSTRUCTIONS);
+        }
+
+        let _ = sender.send(Event::StageChanged(Stage::Queued)).await;
+
+        let dropped = fit_context(&assistant, &system_prompt, sampling, &mut messages).await?;
+        let _ = sender.send(Event::ContextTruncated(dropped)).await;
 
         let _ = sender.send(Event::MessageAdded).await;
 
-        {
-            let mut next_message = assistant.complete(SYSTEM_PROMPT, &messages).boxed();
+        let tools = if assistant.can_call_tools() {
+            Tool::available()
+        } else {
+            Vec::new()
+        };
 
-            while let Some(token) = next_message.next().await.transpose()? {
-                match &token {
-                    assistant::Token::Reasoning(token) => {
-                        reasoning.push_str(token);
+        let mut tool_turns = Vec::new();
+
+        if !passages.is_empty() {
+            let step = passages
+                .iter()
+                .enumerate()
+                .map(|(index, passage)| format!("[{}] {}", index + 1, passage.source.display()))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            reasoning.push_str(&format!("Consulted your documents:\n\n{step}"));
+            reasoning_started_at = Some(Instant::now());
+
+            let _ = sender
+                .send(Event::LastMessageChanged {
+                    reasoning: Some(assistant::Reasoning {
+                        content: reasoning.clone(),
+                        duration: reasoning_duration,
+                    }),
+                    content: String::new(),
+                    new_token: assistant::Token::Reasoning(step),
+                })
+                .await;
+        }
+
+        let alternatives = alternatives(
+            &assistant,
+            &system_prompt,
+            &messages,
+            sampling,
+            sender.clone(),
+        );
+
+        let reply = async {
+            for round in 0..=MAX_TOOL_ROUNDS {
+                // Tools are withheld in the last round, so the model has to answer
+                let tools = if round < MAX_TOOL_ROUNDS {
+                    &tools[..]
+                } else {
+                    &[]
+                };
+                let mut calls = Vec::new();
+                let mut stage = Stage::ProcessingPrompt;
+
+                let _ = sender.send(Event::StageChanged(stage)).await;
+
+                {
+                    let mut next_message = assistant
+                        .complete_with_tools(
+                            &system_prompt,
+                            &messages,
+                            &tool_turns,
+                            sampling,
+                            tools,
+                        )
+                        .boxed();
+
+                    let mut batch = Batch::default();
+
+                    while let Some(token) = next_message.next().await.transpose()? {
+                        let new_stage = match &token {
+                            assistant::Token::Reasoning(_) => Some(Stage::Reasoning),
+                            assistant::Token::Talking(_) => Some(Stage::Answering),
+                            assistant::Token::Usage(_) | assistant::Token::ToolCalls(_) => None,
+                        };
+
+                        if let Some(new_stage) = new_stage.filter(|new_stage| *new_stage != stage) {
+                            stage = new_stage;
+
+                            let _ = sender.send(Event::StageChanged(stage)).await;
+                        }
 
-                        if let Some(reasoning_started_at) = reasoning_started_at {
-                            reasoning_duration = reasoning_started_at.elapsed();
-                        } else {
-                            reasoning_started_at = Some(Instant::now());
+                        match &token {
+                            assistant::Token::Reasoning(token) => {
+                                reasoning.push_str(token);
+                                reasoned = true;
+
+                                if let Some(reasoning_started_at) = reasoning_started_at {
+                                    reasoning_duration = reasoning_started_at.elapsed();
+                                } else {
+                                    reasoning_started_at = Some(Instant::now());
+                                }
+                            }
+                            assistant::Token::Talking(token) => {
+                                content.push_str(token);
+                            }
+                            assistant::Token::Usage(usage) => {
+                                let _ = sender.send(Event::UsageReported(*usage)).await;
+
+                                continue;
+                            }
+                            assistant::Token::ToolCalls(new_calls) => {
+                                calls = new_calls.clone();
+
+                                continue;
+                            }
+                        }
+
+                        for new_token in batch.push(token) {
+                            let _ = sender
+                                .send(last_message_changed(
+                                    &reasoning,
+                                    reasoning_started_at.map(|_| reasoning_duration),
+                                    &content,
+                                    new_token,
+                                ))
+                                .await;
                         }
                     }
-                    assistant::Token::Talking(token) => {
-                        content.push_str(token);
+
+                    if let Some(new_token) = batch.take() {
+                        let _ = sender
+                            .send(last_message_changed(
+                                &reasoning,
+                                reasoning_started_at.map(|_| reasoning_duration),
+                                &content,
+                                new_token,
+                            ))
+                            .await;
                     }
                 }
 
-                let _ = sender
-                    .send(Event::LastMessageChanged {
-                        reasoning: reasoning_started_at
-                            .is_some()
-                            .then(|| assistant::Reasoning {
+                if calls.is_empty() {
+                    break;
+                }
+
+                let started_at = *reasoning_started_at.get_or_insert_with(Instant::now);
+                let mut results = Vec::new();
+
+                for call in &calls {
+                    let step = format!("\n\nUsing {} with {}", call.name, call.arguments);
+                    reasoning.push_str(&step);
+                    reasoning_duration = started_at.elapsed();
+
+                    let _ = sender
+                        .send(Event::LastMessageChanged {
+                            reasoning: Some(assistant::Reasoning {
                                 content: reasoning.trim().to_owned(),
                                 duration: reasoning_duration,
                             }),
-                        content: content.trim().to_owned(),
-                        new_token: token,
-                    })
-                    .await;
+                            content: content.trim().to_owned(),
+                            new_token: assistant::Token::Reasoning(step),
+                        })
+                        .await;
+
+                    results.push(ToolTurn::Result {
+                        id: call.id.clone(),
+                        content: tools::call(call, &search).await,
+                    });
+                }
+
+                tool_turns.push(ToolTurn::Calls(calls));
+                tool_turns.extend(results);
             }
-        }
 
-        // Suggest a title after the 1st and 5th messages
-        if messages.len() == 1 || messages.len() == 5 {
+            Ok::<_, Error>(())
+        };
+
+        let (reply, ()) = future::join(reply, alternatives).await;
+        reply?;
+
+        if !suggest_titles {
+            if let Some(Message::User(prompt)) = messages.first().filter(|_| turns == 1) {
+                let _ = sender.send(Event::TitleChanged(first_words(prompt))).await;
+            }
+        } else if turns == 1 || turns == 5 {
+            // Suggest a title after the 1st and 5th messages
             messages.push(Message::Assistant {
                 reasoning: None,
                 content: content.trim().to_owned(),
+                usage: None,
+                alternatives: Vec::new(),
+                interrupted: false,
             });
-            messages.push(Message::User(
-                "Give me a short title for our conversation so far, without considering this interaction. \
-                    Just the title between quotes; don't say anything else."
-                    .to_owned(),
-            ));
-
-            let mut title_suggestion = assistant.complete(SYSTEM_PROMPT, &messages).boxed();
+            messages.push(Message::User(TITLE_REQUEST.to_owned()));
+
+            // Reasoning models take long to think about titles, so their
+            // summarizer suggests them instead, if any
+            let suggester = if !reasoned {
+                &assistant
+            } else {
+                assistant.summarizer()
+            };
+
+            let mut title_suggestion = suggester
+                .complete(&system_prompt, &messages, sampling)
+                .boxed();
             let mut title = String::new();
 
             while let Some(token) = title_suggestion.next().await.transpose()? {
@@ -260,22 +949,363 @@ pub fn complete(
     })
 }
 
+/// The request that makes a model suggest a title for a conversation.
+const TITLE_REQUEST: &str = "Give me a short title for our conversation so far, \
+    without considering this interaction. \
+    Just the title between quotes; don't say anything else.";
+
+/// A title made of the first words of the given prompt.
+fn first_words(prompt: &str) -> String {
+    const WORDS: usize = 8;
+
+    let mut words = prompt.split_whitespace();
+    let mut title = words.by_ref().take(WORDS).collect::<Vec<_>>().join(" ");
+
+    if words.next().is_some() {
+        title.push_str("...");
+    }
+
+    title
+}
+
+/// The [`Event`] of a reply that has changed; the reasoning is only present
+/// once its duration is known.
+fn last_message_changed(
+    reasoning: &str,
+    reasoning_duration: Option<Duration>,
+    content: &str,
+    new_token: assistant::Token,
+) -> Event {
+    Event::LastMessageChanged {
+        reasoning: reasoning_duration.map(|duration| assistant::Reasoning {
+            content: reasoning.trim().to_owned(),
+            duration,
+        }),
+        content: content.trim().to_owned(),
+        new_token,
+    }
+}
+
+/// Generates the other replies requested by the [`Sampling`] while the main
+/// one is being written.
+///
+/// Alternatives are plain completions; they cannot call any tools. An
+/// alternative that fails is reported with [`Event::AlternativeFailed`].
+///
+/// [`Sampling`]: assistant::Sampling
+async fn alternatives(
+    assistant: &Assistant,
+    system_prompt: &str,
+    messages: &[Message],
+    sampling: assistant::Sampling,
+    sender: mpsc::Sender<Event>,
+) {
+    let alternatives = (1..sampling.replies.max(1) as usize).map(|index| {
+        let mut sender = sender.clone();
+
+        async move {
+            let started_at = Instant::now();
+            let mut reasoning = String::new();
+            let mut reasoning_duration = None;
+            let mut content = String::new();
+
+            let mut tokens = assistant
+                .complete(system_prompt, messages, sampling)
+                .boxed();
+
+            let mut batch = Batch::default();
+
+            loop {
+                let token = tokens.next().await.transpose()?;
+                let is_over = token.is_none();
+
+                let is_due = match token {
+                    Some(token) => {
+                        match &token {
+                            assistant::Token::Reasoning(token) => {
+                                reasoning.push_str(token);
+                                reasoning_duration = Some(started_at.elapsed());
+                            }
+                            assistant::Token::Talking(token) => {
+                                content.push_str(token);
+                            }
+                            assistant::Token::Usage(_) | assistant::Token::ToolCalls(_) => continue,
+                        }
+
+                        !batch.push(token).is_empty()
+                    }
+                    None => batch.take().is_some(),
+                };
+
+                if is_due {
+                    let _ = sender
+                        .send(Event::AlternativeChanged {
+                            index: index - 1,
+                            alternative: assistant::Alternative {
+                                reasoning: reasoning_duration.map(|duration| {
+                                    assistant::Reasoning {
+                                        content: reasoning.trim().to_owned(),
+                                        duration,
+                                    }
+                                }),
+                                content: content.trim().to_owned(),
+                            },
+                        })
+                        .await;
+                }
+
+                if is_over {
+                    break;
+                }
+            }
+
+            Ok::<_, Error>(())
+        }
+    });
+
+    let alternatives = alternatives.enumerate().map(|(index, alternative)| {
+        let mut sender = sender.clone();
+
+        async move {
+            if let Err(error) = alternative.await {
+                let _ = sender.send(Event::AlternativeFailed { index, error }).await;
+            }
+        }
+    });
+
+    let _ = future::join_all(alternatives).await;
+}
+
+/// What an assistant is sent to reply to some messages.
+#[derive(Debug, Clone)]
+pub struct Preview {
+    /// The system prompt, with the excerpts of any retrieved documents.
+    pub system_prompt: String,
+    /// The documents the excerpts were retrieved from.
+    pub sources: Vec<PathBuf>,
+    /// The amount of messages sent.
+    pub sent: usize,
+    /// The amount of oldest messages dropped to fit in the context.
+    pub dropped: usize,
+    /// The tools the assistant can call.
+    pub tools: Vec<String>,
+}
+
+/// Prepares the given messages like [`complete`] does, without sending them.
+pub async fn preview(
+    assistant: Assistant,
+    system_prompt: String,
+    sampling: assistant::Sampling,
+    mut messages: Vec<Message>,
+    knowledge: Option<Knowledge>,
+    guard: bool,
+) -> Result<Preview, Error> {
+    let query = messages.iter().rev().find_map(|message| match message {
+        Message::User(content) => Some(content.clone()),
+        Message::Assistant { .. } => None,
+    });
+
+    let passages = match knowledge.as_ref().zip(query) {
+        Some((knowledge, query)) => knowledge::retrieve(knowledge, &query).await?,
+        None => Vec::new(),
+    };
+
+    let mut system_prompt = if passages.is_empty() {
+        system_prompt
+    } else {
+        format!("{system_prompt}\n\n{}", knowledge::context(&passages))
+    };
+
+    if guard && assistant.can_call_tools() {
+        system_prompt = format!("{system_prompt}\n\n{}", web::GUARD_INSTRUCTIONS);
+    }
+
+    let dropped = fit_context(&assistant, &system_prompt, sampling, &mut messages).await?;
+
+    let mut sources: Vec<PathBuf> = Vec::new();
+
+    for passage in passages {
+        if !sources.contains(&passage.source) {
+            sources.push(passage.source);
+        }
+    }
+
+    let tools = if assistant.can_call_tools() {
+        Tool::available()
+            .iter()
+            .map(|tool| tool.name().to_owned())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    Ok(Preview {
+        system_prompt,
+        sources,
+        sent: messages.len(),
+        dropped,
+        tools,
+    })
+}
+
+/// Summarizes the given messages into a recap short enough to leave most of
+/// the context of the assistant free, so a chat can carry over to it.
+pub async fn recap(assistant: Assistant, mut messages: Vec<Message>) -> Result<String, Error> {
+    const SYSTEM_PROMPT: &str = "You summarize conversations between a user and an assistant.";
+
+    // Roughly 3 words every 4 tokens, using an eighth of the context
+    let words = assistant
+        .context_size()
+        .map(|context_size| (context_size / 8 * 3 / 4).clamp(50, 1000))
+        .unwrap_or(200);
+
+    let sampling = assistant::Sampling {
+        max_tokens: Some(words as u32 * 2),
+        ..assistant::Sampling::default()
+    };
+
+    messages.push(Message::User(format!(
+        "Summarize our conversation so far in at most {words} words, \
+        keeping any facts, decisions, and open questions needed to continue it. \
+        Just the summary; don't say anything else."
+    )));
+
+    let _ = fit_context(&assistant, SYSTEM_PROMPT, sampling, &mut messages).await?;
+
+    let mut tokens = assistant
+        .complete(SYSTEM_PROMPT, &messages, sampling)
+        .boxed();
+    let mut recap = String::new();
+
+    while let Some(token) = tokens.next().await.transpose()? {
+        if let assistant::Token::Talking(token) = token {
+            recap.push_str(&token);
+        }
+    }
+
+    Ok(recap.trim().to_owned())
+}
+
+/// Drops the oldest messages until the conversation fits in the context of
+/// the assistant, leaving room for the reply.
+///
+/// Returns the amount of dropped messages.
+async fn fit_context(
+    assistant: &Assistant,
+    system_prompt: &str,
+    sampling: assistant::Sampling,
+    messages: &mut Vec<Message>,
+) -> Result<usize, Error> {
+    // Tokens used by the chat template around every message
+    const MESSAGE_OVERHEAD: u64 = 8;
+
+    let Some(context_size) = assistant.context_size() else {
+        return Ok(0);
+    };
+
+    let reply = sampling
+        .max_tokens
+        .map(u64::from)
+        .unwrap_or(context_size / 4);
+
+    let budget = context_size.saturating_sub(reply);
+
+    // Tokens rarely take less than a byte, so skip counting short conversations
+    let bytes = system_prompt.len()
+        + messages
+            .iter()
+            .map(|message| message.content().len())
+            .sum::<usize>();
+
+    if (bytes as u64) + MESSAGE_OVERHEAD * (messages.len() as u64 + 1) <= budget {
+        return Ok(0);
+    }
+
+    let mut tokens = count_tokens(assistant, system_prompt).await? + MESSAGE_OVERHEAD;
+    let mut kept = 0;
+
+    for message in messages.iter().rev() {
+        let message_tokens = count_tokens(assistant, message.content()).await? + MESSAGE_OVERHEAD;
+
+        if kept > 0 && tokens + message_tokens > budget {
+            break;
+        }
+
+        tokens += message_tokens;
+        kept += 1;
+    }
+
+    let mut dropped = messages.len() - kept;
+
+    // The conversation must start with a user message
+    while dropped + 1 < messages.len() && matches!(messages[dropped], Message::Assistant { .. }) {
+        dropped += 1;
+    }
+
+    let _ = messages.drain(..dropped);
+
+    Ok(dropped)
+}
+
+/// Counts the tokens of the given text with the tokenizer of the assistant.
+///
+/// Counts are remembered by digest, so every send only tokenizes the
+/// messages that were not counted before.
+async fn count_tokens(assistant: &Assistant, text: &str) -> Result<u64, Error> {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    // Forgets every count past this many, instead of tracking their use
+    const LIMIT: usize = 10_000;
+
+    static COUNTS: Mutex<BTreeMap<u64, u64>> = Mutex::new(BTreeMap::new());
+
+    let key = {
+        let file = assistant.file();
+
+        let mut hasher = DefaultHasher::new();
+        file.model.name().hash(&mut hasher);
+        file.name.hash(&mut hasher);
+        text.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let counted = COUNTS.lock().expect("lock token counts").get(&key).copied();
+
+    if let Some(tokens) = counted {
+        return Ok(tokens);
+    }
+
+    let tokens = assistant.count_tokens(text).await?;
+
+    let mut counts = COUNTS.lock().expect("lock token counts");
+
+    if counts.len() >= LIMIT {
+        counts.clear();
+    }
+
+    let _ = counts.insert(key, tokens);
+
+    Ok(tokens)
+}
+
 pub fn send(
     assistant: &Assistant,
+    request: Request<'_>,
     mut history: Vec<Message>,
     message: Content,
 ) -> impl Stream<Item = Result<Event, Error>> {
-    let assistant = assistant.clone();
     let message = message.as_str().to_owned();
 
-    iced::stream::try_channel(1, |mut sender| async move {
-        history.push(Message::User(message.clone()));
+    history.push(Message::User(message.clone()));
 
+    let task = complete(assistant, request, history);
+
+    iced::stream::try_channel(1, move |mut sender| async move {
         let _ = sender
             .send(Event::MessageSent(Message::User(message)))
             .await;
 
-        let mut task = complete(&assistant, history).boxed();
+        let mut task = task.boxed();
 
         while let Some(result) = task.next().await {
             let _ = sender.send(result?).await;
@@ -293,6 +1323,171 @@ pub struct Entry {
     pub id: Id,
     pub file: assistant::File,
     pub title: Option<String>,
+    #[serde(default)]
+    pub languages: Vec<Language>,
+    #[serde(default)]
+    pub has_code: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn summarize(metadata: impl IntoIterator<Item = Metadata>) -> (Vec<Language>, bool) {
+    let mut languages = Vec::new();
+    let mut has_code = false;
+
+    for metadata in metadata {
+        has_code |= metadata.has_code;
+
+        if let Some(language) = metadata.language {
+            if !languages.contains(&language) {
+                languages.push(language);
+            }
+        }
+    }
+
+    (languages, has_code)
+}
+
+/// The files of every chat and their list, by name, to be kept in a
+/// backup.
+#[cfg(feature = "sqlite")]
+pub async fn backup() -> Result<Vec<(String, String)>, Error> {
+    let list = List::fetch().await?;
+    let mut files = Vec::new();
+
+    for entry in &list.entries {
+        let Some(contents) = saved(&entry.id).await else {
+            continue;
+        };
+
+        files.push((
+            format!("{}.json", entry.id.0.simple()),
+            String::from_utf8_lossy(&contents).into_owned(),
+        ));
+    }
+
+    files.push((List::FILE.to_owned(), serde_json::to_string_pretty(&list)?));
+
+    Ok(files)
+}
+
+/// The files of every chat and their list, by name, to be kept in a
+/// backup.
+#[cfg(not(feature = "sqlite"))]
+pub async fn backup() -> Result<Vec<(String, String)>, Error> {
+    let mut files = Vec::new();
+    let mut entries = fs::read_dir(storage_dir().await?).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if !is_backed_up(&name) {
+            continue;
+        }
+
+        let Ok(contents) = fs::read_to_string(entry.path()).await else {
+            continue;
+        };
+
+        files.push((name, contents));
+    }
+
+    Ok(files)
+}
+
+/// Restores the chats of a backup made by [`backup`].
+///
+/// Chats with the same id are overwritten, and the entries of the list are
+/// merged into the current one.
+pub async fn restore(files: Vec<(String, String)>) -> Result<(), Error> {
+    #[cfg(not(feature = "sqlite"))]
+    let directory = storage_dir().await?;
+
+    let mut restored = None;
+
+    for (name, contents) in files {
+        if !is_backed_up(&name) {
+            continue;
+        }
+
+        if name == List::FILE {
+            restored = Some(contents);
+            continue;
+        }
+
+        let chat: Schema = serde_json::from_str(&contents)?;
+        let id = chat.id;
+
+        #[cfg(not(feature = "sqlite"))]
+        write(&directory.join(name), contents.into_bytes()).await?;
+
+        #[cfg(feature = "sqlite")]
+        let _ = store(chat, contents.into_bytes()).await?;
+
+        // The file changed under any pending save of the chat
+        Writes::forget(id);
+    }
+
+    let Some(restored) = restored else {
+        return Ok(());
+    };
+
+    let restored: List = task::spawn_blocking(move || serde_json::from_str(&restored)).await??;
+
+    List::update(move |list| {
+        for entry in restored.entries {
+            match list
+                .entries
+                .iter_mut()
+                .find(|current| current.id == entry.id)
+            {
+                Some(current) => *current = entry,
+                None => list.entries.push(entry),
+            }
+        }
+
+        true
+    })
+    .await
+}
+
+/// Whether the file of the chats directory with the given name is kept in
+/// backups: the list, or a chat.
+fn is_backed_up(name: &str) -> bool {
+    name == List::FILE
+        || name
+            .strip_suffix(".json")
+            .is_some_and(|id| Uuid::try_parse(id).is_ok())
+}
+
+/// Reads the saved chat with the given id.
+#[cfg(not(feature = "sqlite"))]
+async fn load(id: Id) -> Result<Schema, Error> {
+    let bytes = fs::read(Chat::path(&id).await?).await?;
+
+    Ok(task::spawn_blocking(move || serde_json::from_slice(&bytes)).await??)
+}
+
+/// Saves the chat, encoded as the given JSON.
+#[cfg(not(feature = "sqlite"))]
+async fn store(chat: Schema, json: Vec<u8>) -> Result<Schema, Error> {
+    write(&Chat::path(&chat.id).await?, json).await?;
+
+    Ok(chat)
+}
+
+/// The saved chat encoded as JSON, if any.
+#[cfg(not(feature = "sqlite"))]
+async fn saved(id: &Id) -> Option<Vec<u8>> {
+    fs::read(Chat::path(id).await.ok()?).await.ok()
+}
+
+/// Deletes the saved chat.
+#[cfg(not(feature = "sqlite"))]
+async fn remove(id: &Id) -> Result<(), Error> {
+    fs::remove_file(Chat::path(id).await?).await?;
+
+    Ok(())
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -301,10 +1496,19 @@ struct List {
 }
 
 impl List {
+    const FILE: &'static str = "list.json";
+
+    #[cfg(not(feature = "sqlite"))]
     async fn path() -> Result<PathBuf, io::Error> {
-        Ok(storage_dir().await?.join("list.json"))
+        Ok(storage_dir().await?.join(Self::FILE))
+    }
+
+    #[cfg(feature = "sqlite")]
+    async fn fetch() -> Result<Self, Error> {
+        database::list().await
     }
 
+    #[cfg(not(feature = "sqlite"))]
     async fn fetch() -> Result<Self, Error> {
         let path = Self::path().await?;
 
@@ -314,31 +1518,94 @@ impl List {
             return Ok(List::default());
         };
 
-        let list: Self =
-            { task::spawn_blocking(move || serde_json::from_slice(&bytes).ok()).await? }
-                .unwrap_or_default();
+        let list: Option<Self> =
+            task::spawn_blocking(move || serde_json::from_slice(&bytes).ok()).await?;
 
-        Ok(list)
+        match list {
+            Some(list) => Ok(list),
+            None => Self::rebuild().await,
+        }
+    }
+
+    /// Recovers a corrupted list from the chats themselves.
+    #[cfg(not(feature = "sqlite"))]
+    async fn rebuild() -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        let mut files = fs::read_dir(storage_dir().await?).await?;
+
+        while let Some(file) = files.next_entry().await? {
+            let Ok(bytes) = fs::read(file.path()).await else {
+                continue;
+            };
+
+            let Ok(schema) = serde_json::from_slice::<Schema>(&bytes) else {
+                continue;
+            };
+
+            let (languages, has_code) = summarize(
+                schema
+                    .history
+                    .iter()
+                    .map(schema::Message::metadata)
+                    .cloned(),
+            );
+
+            entries.push(Entry {
+                id: schema.id,
+                file: schema.file,
+                title: schema.title,
+                languages,
+                has_code,
+                tags: Vec::new(),
+            });
+        }
+
+        Ok(Self { entries })
     }
 
     async fn push(entry: Entry) -> Result<(), Error> {
-        let mut list = Self::fetch().await.unwrap_or_default();
-        list.entries.insert(0, entry);
+        Self::update(move |list| {
+            list.entries.retain(|candidate| candidate.id != entry.id);
+            list.entries.insert(0, entry);
 
-        list.save().await
+            true
+        })
+        .await
     }
 
     async fn remove(id: &Id) -> Result<(), Error> {
-        let mut list = List::fetch().await?;
-        list.entries.retain(|entry| &entry.id != id);
+        let id = *id;
+
+        Self::update(move |list| {
+            let before = list.entries.len();
+            list.entries.retain(|entry| entry.id != id);
 
-        list.save().await
+            list.entries.len() != before
+        })
+        .await
     }
 
-    async fn save(self) -> Result<(), Error> {
-        let json = task::spawn_blocking(move || serde_json::to_vec_pretty(&self)).await?;
+    /// Applies a change to the latest list on disk.
+    ///
+    /// The list stays locked until the change is saved, so changes made by
+    /// other instances are kept instead of overwritten. The change returns
+    /// whether the list needs to be saved.
+    #[cfg(feature = "sqlite")]
+    async fn update(change: impl FnOnce(&mut Self) -> bool + Send + 'static) -> Result<(), Error> {
+        database::update(change).await
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    async fn update(change: impl FnOnce(&mut Self) -> bool + Send + 'static) -> Result<(), Error> {
+        let _lock = Lock::acquire(storage_dir().await?.join("list.lock")).await?;
+
+        let mut list = Self::fetch().await?;
 
-        fs::write(Self::path().await?, json?).await?;
+        if change(&mut list) {
+            let json = task::spawn_blocking(move || serde_json::to_vec_pretty(&list)).await?;
+
+            write(&Self::path().await?, json?).await?;
+        }
 
         Ok(())
     }
@@ -362,9 +1629,7 @@ impl LastOpened {
     async fn update(id: Id) -> Result<(), Error> {
         let json = serde_json::to_vec(&LastOpened(id))?;
 
-        fs::write(Self::path().await?, json).await?;
-
-        Ok(())
+        write(&Self::path().await?, json).await
     }
 
     async fn delete() -> Result<(), Error> {
@@ -374,13 +1639,222 @@ impl LastOpened {
     }
 }
 
+/// An advisory lock shared by every running instance.
+#[cfg(not(feature = "sqlite"))]
+struct Lock(std::fs::File);
+
+#[cfg(not(feature = "sqlite"))]
+impl Lock {
+    async fn acquire(path: PathBuf) -> Result<Self, Error> {
+        Ok(task::spawn_blocking(move || {
+            use fs2::FileExt;
+
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(path)?;
+
+            file.lock_exclusive()?;
+
+            Ok::<_, io::Error>(Self(file))
+        })
+        .await??)
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs2::FileExt::unlock(&self.0);
+    }
+}
+
+/// The latest saves of every chat, used to coalesce the ones made in quick
+/// succession and to skip the ones that would not change anything.
+static WRITES: Mutex<Vec<Writes>> = Mutex::new(Vec::new());
+
+struct Writes {
+    id: Id,
+    latest: u64,
+    /// The digest of the last bytes written.
+    written: Option<u64>,
+}
+
+impl Writes {
+    /// How long a save waits for newer ones before writing.
+    const DELAY: Duration = Duration::from_millis(300);
+
+    fn queue(id: Id) -> u64 {
+        let mut writes = WRITES.lock().expect("lock chat writes");
+
+        match writes.iter_mut().find(|writes| writes.id == id) {
+            Some(writes) => {
+                writes.latest += 1;
+                writes.latest
+            }
+            None => {
+                writes.push(Self {
+                    id,
+                    latest: 0,
+                    written: None,
+                });
+
+                0
+            }
+        }
+    }
+
+    fn is_latest(id: Id, write: u64) -> bool {
+        WRITES
+            .lock()
+            .expect("lock chat writes")
+            .iter()
+            .any(|writes| writes.id == id && writes.latest == write)
+    }
+
+    fn is_written(id: Id, digest: u64) -> bool {
+        WRITES
+            .lock()
+            .expect("lock chat writes")
+            .iter()
+            .any(|writes| writes.id == id && writes.written == Some(digest))
+    }
+
+    fn finish(id: Id, digest: u64) {
+        if let Some(writes) = WRITES
+            .lock()
+            .expect("lock chat writes")
+            .iter_mut()
+            .find(|writes| writes.id == id)
+        {
+            writes.written = Some(digest);
+        }
+    }
+
+    /// Drops the pending saves of the chat, and what is known about its file.
+    fn forget(id: Id) {
+        WRITES
+            .lock()
+            .expect("lock chat writes")
+            .retain(|writes| writes.id != id);
+    }
+}
+
+fn digest(bytes: &[u8]) -> u64 {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Writes to a temporary file first, so a crash never leaves a file half
+/// written.
+///
+/// Every write gets its own temporary file, so concurrent writes to the
+/// same path never interleave.
+async fn write(path: &Path, bytes: Vec<u8>) -> Result<(), Error> {
+    static TEMPORARIES: AtomicU64 = AtomicU64::new(0);
+
+    let temporary = path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        TEMPORARIES.fetch_add(1, atomic::Ordering::Relaxed)
+    ));
+
+    fs::write(&temporary, bytes).await?;
+    fs::rename(temporary, path).await?;
+
+    Ok(())
+}
+
 async fn storage_dir() -> Result<PathBuf, io::Error> {
-    let directory = dirs_next::data_local_dir()
-        .unwrap_or(PathBuf::from("."))
-        .join("icebreaker")
-        .join("chats");
+    let directory = data::data_dir().await?.join("chats");
 
     fs::create_dir_all(&directory).await?;
 
     Ok(directory)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_fall_back_to_the_first_words() {
+        assert_eq!(
+            first_words("  How do I\nsort a vector? "),
+            "How do I sort a vector?"
+        );
+        assert_eq!(
+            first_words("What is the fastest way to parse a large CSV file in Rust?"),
+            "What is the fastest way to parse a..."
+        );
+    }
+
+    #[test]
+    fn only_chats_and_their_list_are_backed_up() {
+        assert!(is_backed_up("list.json"));
+        assert!(is_backed_up(&format!("{}.json", Uuid::new_v4().simple())));
+
+        assert!(!is_backed_up("list.lock"));
+        assert!(!is_backed_up("last_opened.json"));
+        assert!(!is_backed_up(&format!(
+            "{}.42.0.tmp",
+            Uuid::new_v4().simple()
+        )));
+        assert!(!is_backed_up("../settings.json"));
+    }
+
+    #[test]
+    fn batched_tokens_keep_their_order() {
+        use assistant::Token;
+
+        let mut batch = Batch::default();
+
+        assert_eq!(
+            batch.push(Token::Talking("a".to_owned())),
+            vec![Token::Talking("a".to_owned())]
+        );
+
+        let mut sent = Vec::new();
+        sent.extend(batch.push(Token::Talking("b".to_owned())));
+        sent.extend(batch.push(Token::Talking("c".to_owned())));
+        sent.extend(batch.push(Token::Reasoning("d".to_owned())));
+        sent.extend(batch.take());
+
+        let talking: String = sent
+            .iter()
+            .map_while(|token| match token {
+                Token::Talking(token) => Some(token.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(talking, "bc");
+        assert_eq!(sent.last(), Some(&Token::Reasoning("d".to_owned())));
+        assert_eq!(batch.take(), None);
+    }
+
+    #[test]
+    fn newer_saves_replace_pending_ones() {
+        let id = Id(Uuid::new_v4());
+
+        let first = Writes::queue(id);
+        let second = Writes::queue(id);
+
+        assert!(!Writes::is_latest(id, first));
+        assert!(Writes::is_latest(id, second));
+
+        let digest = digest(b"{}");
+        assert!(!Writes::is_written(id, digest));
+
+        Writes::finish(id, digest);
+        assert!(Writes::is_written(id, digest));
+
+        Writes::forget(id);
+        assert!(!Writes::is_latest(id, second));
+        assert!(!Writes::is_written(id, digest));
+    }
+}
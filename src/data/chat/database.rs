@@ -0,0 +1,519 @@
+//! Chats kept in a SQLite database, instead of a JSON file each.
+//!
+//! The list of chats, their settings, and their messages live in their own
+//! tables, so listing reads a single table and saving a chat only writes
+//! the messages that changed. Messages are indexed for full-text search.
+//!
+//! The first time the database is opened, the chats in JSON files are
+//! copied into it. The files are left in place.
+use crate::data::chat::schema::{self, Schema};
+use crate::data::chat::{storage_dir, summarize, Entry, Id, List};
+use crate::data::Error;
+
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use tokio::task;
+use uuid::Uuid;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The name of the database in the chats directory.
+const FILE: &str = "chats.sqlite";
+
+/// The version of the tables, kept as the `user_version` of the database.
+const VERSION: i32 = 1;
+
+/// The open database and its directory, shared by every operation.
+static CONNECTION: Mutex<Option<(PathBuf, Connection)>> = Mutex::new(None);
+
+const TABLES: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        file TEXT NOT NULL,
+        title TEXT,
+        languages TEXT NOT NULL,
+        has_code INTEGER NOT NULL,
+        tags TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS chats (
+        id TEXT PRIMARY KEY,
+        file TEXT NOT NULL,
+        title TEXT,
+        system_prompt TEXT,
+        guardrails TEXT NOT NULL,
+        sampling TEXT NOT NULL,
+        memory TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS messages (
+        chat TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        message TEXT NOT NULL,
+        PRIMARY KEY (chat, position)
+    );
+
+    CREATE VIRTUAL TABLE IF NOT EXISTS search USING fts5(
+        chat UNINDEXED,
+        position UNINDEXED,
+        content,
+        tokenize = 'trigram'
+    );
+";
+
+/// Reads the saved chat with the given id.
+pub async fn load(id: Id) -> Result<Schema, Error> {
+    run(move |connection| {
+        read(connection, id)?.ok_or_else(|| io::Error::from(io::ErrorKind::NotFound).into())
+    })
+    .await
+}
+
+/// Saves the chat; its JSON is only needed by the files.
+pub async fn store(chat: Schema, _json: Vec<u8>) -> Result<Schema, Error> {
+    run(move |connection| {
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        write(&transaction, &chat)?;
+        transaction.commit()?;
+
+        Ok(chat)
+    })
+    .await
+}
+
+/// The saved chat encoded as JSON, if any.
+pub async fn saved(id: &Id) -> Option<Vec<u8>> {
+    let chat = load(*id).await.ok()?;
+
+    serde_json::to_vec(&chat).ok()
+}
+
+/// Deletes the saved chat.
+pub async fn remove(id: &Id) -> Result<(), Error> {
+    let key = key(id);
+
+    run(move |connection| {
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let _ = transaction.execute("DELETE FROM chats WHERE id = ?1", [&key])?;
+        let _ = transaction.execute("DELETE FROM messages WHERE chat = ?1", [&key])?;
+        let _ = transaction.execute("DELETE FROM search WHERE chat = ?1", [&key])?;
+
+        transaction.commit()?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Reads the list of chats.
+pub async fn list() -> Result<List, Error> {
+    run(|connection| {
+        Ok(List {
+            entries: entries(connection)?,
+        })
+    })
+    .await
+}
+
+/// Applies a change to the list of chats, which returns whether the list
+/// needs to be saved.
+///
+/// The database stays locked until the change is saved.
+pub async fn update(change: impl FnOnce(&mut List) -> bool + Send + 'static) -> Result<(), Error> {
+    run(move |connection| {
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let mut list = List {
+            entries: entries(&transaction)?,
+        };
+
+        if change(&mut list) {
+            replace(&transaction, &list.entries)?;
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Finds the messages that may contain the given lowercase query, with the
+/// chat and the index of each one.
+pub async fn search(needle: String) -> Result<Vec<(Id, usize, String)>, Error> {
+    run(move |connection| {
+        // The trigram index needs at least three characters
+        let (query, pattern) = if needle.chars().count() >= 3 {
+            (
+                "SELECT chat, position, content FROM search WHERE search MATCH ?1",
+                format!("\"{}\"", needle.replace('"', "\"\"")),
+            )
+        } else {
+            (
+                "SELECT chat, position, content FROM search WHERE instr(lower(content), ?1) > 0",
+                needle,
+            )
+        };
+
+        let mut statement = connection.prepare(query)?;
+
+        let rows = statement.query_map([pattern], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, usize>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+
+        let mut matches = Vec::new();
+
+        for row in rows {
+            let (chat, position, content) = row?;
+
+            if let Ok(id) = Uuid::try_parse(&chat) {
+                matches.push((Id(id), position, content));
+            }
+        }
+
+        matches.sort_by_key(|(_, position, _)| *position);
+
+        Ok(matches)
+    })
+    .await
+}
+
+async fn run<T: Send + 'static>(
+    f: impl FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    let directory = storage_dir().await?;
+
+    task::spawn_blocking(move || {
+        let mut connection = CONNECTION.lock().expect("lock chats database");
+
+        if connection
+            .as_ref()
+            .is_none_or(|(opened, _)| *opened != directory)
+        {
+            *connection = Some((directory.clone(), open(&directory)?));
+        }
+
+        let (_, connection) = connection.as_mut().expect("chats database should be open");
+
+        f(connection)
+    })
+    .await?
+}
+
+/// Opens the database in the given directory, creating its tables and
+/// copying the chats in JSON files into it the first time.
+fn open(directory: &Path) -> Result<Connection, Error> {
+    let mut connection = Connection::open(directory.join(FILE))?;
+    connection.busy_timeout(Duration::from_secs(5))?;
+
+    if version(&connection)? >= VERSION {
+        return Ok(connection);
+    }
+
+    let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    // Another instance may have migrated the chats in the meantime
+    if version(&transaction)? < VERSION {
+        transaction.execute_batch(TABLES)?;
+        migrate(&transaction, directory)?;
+        transaction.pragma_update(None, "user_version", VERSION)?;
+    }
+
+    transaction.commit()?;
+
+    Ok(connection)
+}
+
+fn version(connection: &Connection) -> Result<i32, Error> {
+    Ok(connection.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// Copies the chats in JSON files, and their list, into the database.
+fn migrate(connection: &Connection, directory: &Path) -> Result<(), Error> {
+    let list = std::fs::read(directory.join(List::FILE))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<List>(&bytes).ok());
+
+    let mut entries = Vec::new();
+
+    for file in std::fs::read_dir(directory)? {
+        let file = file?;
+        let name = file.file_name().to_string_lossy().into_owned();
+
+        if name
+            .strip_suffix(".json")
+            .is_none_or(|id| Uuid::try_parse(id).is_err())
+        {
+            continue;
+        }
+
+        let Ok(bytes) = std::fs::read(file.path()) else {
+            continue;
+        };
+
+        let Ok(chat) = serde_json::from_slice::<Schema>(&bytes) else {
+            continue;
+        };
+
+        write(connection, &chat)?;
+
+        let (languages, has_code) =
+            summarize(chat.history.iter().map(schema::Message::metadata).cloned());
+
+        entries.push(Entry {
+            id: chat.id,
+            file: chat.file,
+            title: chat.title,
+            languages,
+            has_code,
+            tags: Vec::new(),
+        });
+    }
+
+    // A missing or corrupted list is recovered from the chats themselves
+    replace(connection, &list.map_or(entries, |list| list.entries))
+}
+
+fn read(connection: &Connection, id: Id) -> Result<Option<Schema>, Error> {
+    let key = key(&id);
+
+    let chat = connection
+        .query_row(
+            "SELECT file, title, system_prompt, guardrails, sampling, memory
+            FROM chats WHERE id = ?1",
+            [&key],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            },
+        )
+        .optional()?;
+
+    let Some((file, title, system_prompt, guardrails, sampling, memory)) = chat else {
+        return Ok(None);
+    };
+
+    let mut history = Vec::new();
+    let mut statement =
+        connection.prepare("SELECT message FROM messages WHERE chat = ?1 ORDER BY position")?;
+
+    for message in statement.query_map([&key], |row| row.get::<_, String>(0))? {
+        history.push(serde_json::from_str(&message?)?);
+    }
+
+    Ok(Some(Schema {
+        id,
+        file: serde_json::from_str(&file)?,
+        title,
+        history,
+        system_prompt,
+        guardrails: serde_json::from_str(&guardrails)?,
+        sampling: serde_json::from_str(&sampling)?,
+        memory: serde_json::from_str(&memory)?,
+    }))
+}
+
+/// Saves the chat, writing only the messages that changed.
+fn write(connection: &Connection, chat: &Schema) -> Result<(), Error> {
+    let key = key(&chat.id);
+
+    let _ = connection.execute(
+        "INSERT INTO chats (id, file, title, system_prompt, guardrails, sampling, memory)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ON CONFLICT (id) DO UPDATE SET
+            file = excluded.file,
+            title = excluded.title,
+            system_prompt = excluded.system_prompt,
+            guardrails = excluded.guardrails,
+            sampling = excluded.sampling,
+            memory = excluded.memory",
+        params![
+            key,
+            serde_json::to_string(&chat.file)?,
+            chat.title,
+            chat.system_prompt,
+            serde_json::to_string(&chat.guardrails)?,
+            serde_json::to_string(&chat.sampling)?,
+            serde_json::to_string(&chat.memory)?,
+        ],
+    )?;
+
+    let saved = {
+        let mut statement =
+            connection.prepare("SELECT message FROM messages WHERE chat = ?1 ORDER BY position")?;
+
+        let messages = statement
+            .query_map([&key], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        messages
+    };
+
+    for (position, message) in chat.history.iter().enumerate() {
+        let json = serde_json::to_string(message)?;
+
+        if saved.get(position) == Some(&json) {
+            continue;
+        }
+
+        let _ = connection.execute(
+            "INSERT OR REPLACE INTO messages (chat, position, message) VALUES (?1, ?2, ?3)",
+            params![key, position, json],
+        )?;
+
+        let _ = connection.execute(
+            "DELETE FROM search WHERE chat = ?1 AND position = ?2",
+            params![key, position],
+        )?;
+
+        let _ = connection.execute(
+            "INSERT INTO search (chat, position, content) VALUES (?1, ?2, ?3)",
+            params![key, position, message.content()],
+        )?;
+    }
+
+    let length = chat.history.len();
+
+    let _ = connection.execute(
+        "DELETE FROM messages WHERE chat = ?1 AND position >= ?2",
+        params![key, length],
+    )?;
+
+    let _ = connection.execute(
+        "DELETE FROM search WHERE chat = ?1 AND position >= ?2",
+        params![key, length],
+    )?;
+
+    Ok(())
+}
+
+fn entries(connection: &Connection) -> Result<Vec<Entry>, Error> {
+    let mut statement = connection.prepare(
+        "SELECT id, file, title, languages, has_code, tags FROM entries ORDER BY position",
+    )?;
+
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, bool>(4)?,
+            row.get::<_, String>(5)?,
+        ))
+    })?;
+
+    let mut entries = Vec::new();
+
+    for row in rows {
+        let (id, file, title, languages, has_code, tags) = row?;
+
+        let Ok(id) = Uuid::try_parse(&id) else {
+            continue;
+        };
+
+        entries.push(Entry {
+            id: Id(id),
+            file: serde_json::from_str(&file)?,
+            title,
+            languages: serde_json::from_str(&languages)?,
+            has_code,
+            tags: serde_json::from_str(&tags)?,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn replace(connection: &Connection, entries: &[Entry]) -> Result<(), Error> {
+    let _ = connection.execute("DELETE FROM entries", [])?;
+
+    let mut statement = connection.prepare(
+        "INSERT OR REPLACE INTO entries (id, position, file, title, languages, has_code, tags)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?;
+
+    for (position, entry) in entries.iter().enumerate() {
+        let _ = statement.execute(params![
+            key(&entry.id),
+            position,
+            serde_json::to_string(&entry.file)?,
+            entry.title,
+            serde_json::to_string(&entry.languages)?,
+            entry.has_code,
+            serde_json::to_string(&entry.tags)?,
+        ])?;
+    }
+
+    Ok(())
+}
+
+fn key(id: &Id) -> String {
+    id.0.simple().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::assistant;
+    use crate::data::guardrails::Guardrails;
+
+    fn chat(id: Id, messages: &[&str]) -> Schema {
+        Schema {
+            id,
+            file: assistant::File::imported("test", "model"),
+            title: Some("Test".to_owned()),
+            history: messages
+                .iter()
+                .map(|content| {
+                    schema::Message::from(assistant::Message::User((*content).to_owned()))
+                })
+                .collect(),
+            system_prompt: None,
+            guardrails: Guardrails::default(),
+            sampling: assistant::Sampling::default(),
+            memory: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn chats_are_saved_and_searched() -> Result<(), Error> {
+        let connection = Connection::open_in_memory()?;
+        connection.execute_batch(TABLES)?;
+
+        let id = Id(Uuid::new_v4());
+
+        write(
+            &connection,
+            &chat(id, &["Hello there", "How do I sort a vector?"]),
+        )?;
+        write(&connection, &chat(id, &["Hello there"]))?;
+
+        let saved = read(&connection, id)?.expect("read saved chat");
+        assert_eq!(saved.history.len(), 1);
+        assert_eq!(saved.history[0].content(), "Hello there");
+
+        let hits: Vec<String> = connection
+            .prepare("SELECT content FROM search WHERE search MATCH '\"ello\"'")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+
+        assert_eq!(hits, vec!["Hello there".to_owned()]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use crate::data::assistant::Message;
+use crate::data::chat::Chat;
+use crate::data::footnote;
+use crate::data::Error;
+
+use serde_json::json;
+use tokio::fs;
+
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Json,
+}
+
+impl Format {
+    pub const ALL: &'static [Self] = &[Self::Markdown, Self::Json];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Markdown => "Markdown",
+            Format::Json => "JSON",
+        })
+    }
+}
+
+impl Chat {
+    pub fn export(&self, format: Format) -> String {
+        match format {
+            Format::Markdown => self.to_markdown(),
+            Format::Json => self.to_json(),
+        }
+    }
+
+    pub async fn export_to(self, format: Format, path: PathBuf) -> Result<(), Error> {
+        fs::write(path, self.export(format)).await?;
+
+        Ok(())
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut markdown = format!(
+            "# {title}\n\n_{model} ({file})_\n",
+            title = self.title.as_deref().unwrap_or("Untitled chat"),
+            model = self.file.model.name(),
+            file = self.file.name,
+        );
+
+        if let Some(system_prompt) = &self.system_prompt {
+            markdown.push_str("\n## System prompt\n\n");
+            markdown.push_str(&quote(system_prompt));
+        }
+
+        // Footnote labels are only unique within a message
+        for (index, message) in self.history.iter().enumerate() {
+            let prefix = format!("m{}", index + 1);
+
+            match message {
+                Message::User(content) => {
+                    markdown.push_str("\n## User\n\n");
+                    markdown.push_str(footnote::namespace(content, &prefix).trim());
+                    markdown.push('\n');
+                }
+                Message::Assistant {
+                    reasoning, content, ..
+                } => {
+                    markdown.push_str("\n## Assistant\n\n");
+
+                    if let Some(reasoning) = reasoning {
+                        markdown.push_str(&format!(
+                            "<details>\n<summary>Thought for {seconds}s</summary>\n\n{quote}\n</details>\n\n",
+                            seconds = reasoning.duration.as_secs(),
+                            quote = quote(&reasoning.content),
+                        ));
+                    }
+
+                    markdown.push_str(footnote::namespace(content, &prefix).trim());
+                    markdown.push('\n');
+                }
+            }
+        }
+
+        markdown
+    }
+
+    fn to_json(&self) -> String {
+        let messages: Vec<_> = self
+            .history
+            .iter()
+            .map(|message| match message {
+                Message::User(content) => json!({
+                    "role": "user",
+                    "content": content,
+                }),
+                Message::Assistant {
+                    reasoning,
+                    content,
+                    usage,
+                    ..
+                } => json!({
+                    "role": "assistant",
+                    "content": content,
+                    "reasoning": reasoning.as_ref().map(|reasoning| &reasoning.content),
+                    "usage": usage.map(|usage| json!({
+                        "prompt_tokens": usage.prompt_tokens,
+                        "completion_tokens": usage.completion_tokens,
+                        "tokens_per_second": usage.tokens_per_second(),
+                    })),
+                }),
+            })
+            .collect();
+
+        let chat = json!({
+            "title": self.title,
+            "model": self.file.model,
+            "file": self.file.name,
+            "system_prompt": self.system_prompt,
+            "messages": messages,
+        });
+
+        serde_json::to_string_pretty(&chat).unwrap_or_default()
+    }
+}
+
+fn quote(text: &str) -> String {
+    text.trim()
+        .lines()
+        .map(|line| format!("> {line}\n"))
+        .collect()
+}
@@ -0,0 +1,268 @@
+use crate::data::assistant::{self, Message};
+use crate::data::chat::{summarize, Chat, Draft, Entry, Id, List, Metadata};
+use crate::data::guardrails::Guardrails;
+use crate::data::Error;
+
+use serde_json::Value;
+use tokio::fs;
+use tokio::task;
+use uuid::Uuid;
+
+use std::path::PathBuf;
+
+struct Conversation {
+    title: Option<String>,
+    file: assistant::File,
+    history: Vec<Message>,
+}
+
+impl Chat {
+    /// Imports the conversations exported by ChatGPT, Ollama, or LM Studio.
+    ///
+    /// Returns the amount of chats that were imported.
+    pub async fn import(path: PathBuf) -> Result<usize, Error> {
+        let bytes = fs::read(path).await?;
+
+        let conversations = task::spawn_blocking(move || {
+            let json: Value = serde_json::from_slice(&bytes)?;
+
+            parse(&json)
+        })
+        .await??;
+
+        let mut imported = 0;
+
+        for conversation in conversations {
+            if conversation.history.is_empty() {
+                continue;
+            }
+
+            let chat = Self::save(
+                Id(Uuid::new_v4()),
+                Draft {
+                    file: conversation.file,
+                    title: conversation.title,
+                    history: conversation.history,
+                    system_prompt: None,
+                    guardrails: Guardrails::default(),
+                    sampling: assistant::Sampling::default(),
+                    memory: Vec::new(),
+                },
+            )
+            .await?;
+
+            let (languages, has_code) = summarize(
+                chat.history
+                    .iter()
+                    .map(|message| Metadata::compute(message.content())),
+            );
+
+            List::push(Entry {
+                id: chat.id,
+                file: chat.file,
+                title: chat.title,
+                languages,
+                has_code,
+                tags: Vec::new(),
+            })
+            .await?;
+
+            imported += 1;
+        }
+
+        Ok(imported)
+    }
+}
+
+fn parse(json: &Value) -> Result<Vec<Conversation>, Error> {
+    let conversations = match json {
+        Value::Array(items) => items.as_slice(),
+        _ => std::slice::from_ref(json),
+    };
+
+    conversations
+        .iter()
+        .map(|conversation| {
+            if conversation.get("mapping").is_some() {
+                chatgpt(conversation)
+            } else if conversation.get("messages").is_some() {
+                if conversation.get("name").is_some() {
+                    lm_studio(conversation)
+                } else {
+                    ollama(conversation)
+                }
+            } else {
+                Err(Error::ImportFailed("unsupported conversation format"))
+            }
+        })
+        .collect()
+}
+
+/// Parses a conversation of the `conversations.json` file in a ChatGPT export.
+///
+/// Messages form a tree; only the branch leading to the current node is kept.
+fn chatgpt(conversation: &Value) -> Result<Conversation, Error> {
+    let mapping = conversation
+        .get("mapping")
+        .and_then(Value::as_object)
+        .ok_or(Error::ImportFailed("ChatGPT conversation has no messages"))?;
+
+    let mut node = conversation.get("current_node").and_then(Value::as_str);
+    let mut history = Vec::new();
+    let mut model = None;
+
+    while let Some(current) = node.and_then(|id| mapping.get(id)) {
+        if let Some(message) = current.get("message") {
+            let role = message.pointer("/author/role").and_then(Value::as_str);
+
+            let content: String = message
+                .pointer("/content/parts")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if model.is_none() {
+                model = message
+                    .pointer("/metadata/model_slug")
+                    .and_then(Value::as_str);
+            }
+
+            if let Some(message) = self::message(role, content) {
+                history.push(message);
+            }
+        }
+
+        node = current.get("parent").and_then(Value::as_str);
+    }
+
+    history.reverse();
+
+    Ok(Conversation {
+        title: title(conversation.get("title")),
+        file: assistant::File::imported("chatgpt", model.unwrap_or("unknown")),
+        history,
+    })
+}
+
+/// Parses an Ollama chat, as sent to its `/api/chat` endpoint.
+fn ollama(conversation: &Value) -> Result<Conversation, Error> {
+    let history = messages(conversation)?
+        .iter()
+        .filter_map(|message| {
+            self::message(
+                message.get("role").and_then(Value::as_str),
+                text(message.get("content")),
+            )
+        })
+        .collect();
+
+    Ok(Conversation {
+        title: title(conversation.get("title")),
+        file: assistant::File::imported(
+            "ollama",
+            conversation
+                .get("model")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown"),
+        ),
+        history,
+    })
+}
+
+/// Parses a conversation file of LM Studio.
+///
+/// Messages may have multiple versions; only the selected one is kept.
+fn lm_studio(conversation: &Value) -> Result<Conversation, Error> {
+    let history = messages(conversation)?
+        .iter()
+        .filter_map(|message| {
+            let message = match message.get("versions").and_then(Value::as_array) {
+                Some(versions) => {
+                    let selected = message
+                        .get("currentlySelected")
+                        .and_then(Value::as_u64)
+                        .unwrap_or(0);
+
+                    versions.get(selected as usize)?
+                }
+                None => message,
+            };
+
+            let content = match message.get("steps").and_then(Value::as_array) {
+                Some(steps) => steps
+                    .iter()
+                    .map(|step| text(step.get("content")))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                None => text(message.get("content")),
+            };
+
+            self::message(message.get("role").and_then(Value::as_str), content)
+        })
+        .collect();
+
+    let model = conversation
+        .pointer("/lastUsedModel/identifier")
+        .or_else(|| conversation.pointer("/lastUsedModel/indexedModelIdentifier"))
+        .and_then(Value::as_str)
+        .unwrap_or("unknown");
+
+    Ok(Conversation {
+        title: title(conversation.get("name")),
+        file: assistant::File::imported("lmstudio", model),
+        history,
+    })
+}
+
+fn messages(conversation: &Value) -> Result<&Vec<Value>, Error> {
+    conversation
+        .get("messages")
+        .and_then(Value::as_array)
+        .ok_or(Error::ImportFailed("conversation messages are not a list"))
+}
+
+fn message(role: Option<&str>, content: String) -> Option<Message> {
+    let content = content.trim();
+
+    if content.is_empty() {
+        return None;
+    }
+
+    match role? {
+        "user" => Some(Message::User(content.to_owned())),
+        "assistant" => Some(Message::Assistant {
+            reasoning: None,
+            content: content.to_owned(),
+            usage: None,
+            alternatives: Vec::new(),
+            interrupted: false,
+        }),
+        _ => None,
+    }
+}
+
+fn text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| match part {
+                Value::String(text) => Some(text.as_str()),
+                _ => part.get("text").and_then(Value::as_str),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+fn title(title: Option<&Value>) -> Option<String> {
+    title
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .map(str::to_owned)
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub words: usize,
+    pub language: Option<Language>,
+    pub has_code: bool,
+}
+
+impl Metadata {
+    pub fn compute(content: &str) -> Self {
+        let mut prose = String::new();
+        let mut has_code = false;
+
+        for (i, block) in content.split("```").enumerate() {
+            if i % 2 == 0 {
+                prose.push_str(block);
+                prose.push('\n');
+            } else {
+                has_code = true;
+            }
+        }
+
+        Self {
+            words: content.split_whitespace().count(),
+            language: Language::detect(&prose),
+            has_code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Language(String);
+
+impl Language {
+    pub fn detect(text: &str) -> Option<Self> {
+        let info = whatlang::detect(text)?;
+
+        info.is_reliable()
+            .then(|| Self(info.lang().code().to_owned()))
+    }
+
+    pub fn name(&self) -> &str {
+        whatlang::Lang::from_code(self.0.as_str())
+            .map(whatlang::Lang::eng_name)
+            .unwrap_or(&self.0)
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
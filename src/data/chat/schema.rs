@@ -1,5 +1,6 @@
 use crate::data::assistant;
-use crate::data::chat::Id;
+use crate::data::chat::{Id, Metadata};
+use crate::data::guardrails::Guardrails;
 
 use futures::never::Never;
 use serde::de::{self, Deserializer, Error, MapAccess, Visitor};
@@ -16,31 +17,67 @@ pub struct Schema {
     pub file: assistant::File,
     pub title: Option<String>,
     pub history: Vec<Message>,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub guardrails: Guardrails,
+    #[serde(default)]
+    pub sampling: assistant::Sampling,
+    #[serde(default)]
+    pub memory: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Message {
-    User(String),
+    #[serde(deserialize_with = "string_or_struct")]
+    User(UserMessage),
     #[serde(deserialize_with = "string_or_struct")]
     Assistant(AssistantMessage),
 }
 
+impl Message {
+    pub fn metadata(&self) -> &Metadata {
+        match self {
+            Message::User(message) => &message.metadata,
+            Message::Assistant(message) => &message.metadata,
+        }
+    }
+
+    pub fn content(&self) -> &str {
+        match self {
+            Message::User(message) => &message.content,
+            Message::Assistant(message) => &message.content,
+        }
+    }
+}
+
 impl From<assistant::Message> for Message {
     fn from(message: assistant::Message) -> Self {
         match message {
-            assistant::Message::User(content) => Message::User(content),
-            assistant::Message::Assistant { reasoning, content } => {
-                Message::Assistant(AssistantMessage {
-                    reasoning: reasoning
-                        .as_ref()
-                        .map(|reasoning| reasoning.content.clone())
-                        .unwrap_or_default(),
-                    reasoning_time: reasoning
-                        .map(|reasoning| reasoning.duration)
-                        .unwrap_or_default(),
-                    content,
-                })
-            }
+            assistant::Message::User(content) => Message::User(UserMessage {
+                metadata: Metadata::compute(&content),
+                content,
+            }),
+            assistant::Message::Assistant {
+                reasoning,
+                content,
+                usage,
+                alternatives,
+                interrupted,
+            } => Message::Assistant(AssistantMessage {
+                usage,
+                metadata: Metadata::compute(&content),
+                reasoning: reasoning
+                    .as_ref()
+                    .map(|reasoning| reasoning.content.clone())
+                    .unwrap_or_default(),
+                reasoning_time: reasoning
+                    .map(|reasoning| reasoning.duration)
+                    .unwrap_or_default(),
+                content,
+                alternatives: alternatives.into_iter().map(Alternative::from).collect(),
+                interrupted,
+            }),
         }
     }
 }
@@ -48,28 +85,98 @@ impl From<assistant::Message> for Message {
 impl From<Message> for assistant::Message {
     fn from(message: Message) -> Self {
         match message {
-            Message::User(content) => assistant::Message::User(content),
+            Message::User(message) => assistant::Message::User(message.content),
             Message::Assistant(message) => assistant::Message::Assistant {
-                reasoning: if message.reasoning.is_empty() {
-                    None
-                } else {
-                    Some(assistant::Reasoning {
-                        content: message.reasoning,
-                        duration: message.reasoning_time,
-                    })
-                },
+                reasoning: reasoning(message.reasoning, message.reasoning_time),
                 content: message.content,
+                usage: message.usage,
+                alternatives: message
+                    .alternatives
+                    .into_iter()
+                    .map(assistant::Alternative::from)
+                    .collect(),
+                interrupted: message.interrupted,
             },
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserMessage {
+    content: String,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+impl FromStr for UserMessage {
+    type Err = Never;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            content: s.to_owned(),
+            metadata: Metadata::compute(s),
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AssistantMessage {
     reasoning: String,
     #[serde(default)]
     reasoning_time: Duration,
     content: String,
+    #[serde(default)]
+    metadata: Metadata,
+    #[serde(default)]
+    usage: Option<assistant::Usage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<Alternative>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    interrupted: bool,
+}
+
+/// A discarded reply, kept for later reference.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Alternative {
+    #[serde(default)]
+    reasoning: String,
+    #[serde(default)]
+    reasoning_time: Duration,
+    content: String,
+}
+
+impl From<assistant::Alternative> for Alternative {
+    fn from(alternative: assistant::Alternative) -> Self {
+        Self {
+            reasoning: alternative
+                .reasoning
+                .as_ref()
+                .map(|reasoning| reasoning.content.clone())
+                .unwrap_or_default(),
+            reasoning_time: alternative
+                .reasoning
+                .map(|reasoning| reasoning.duration)
+                .unwrap_or_default(),
+            content: alternative.content,
+        }
+    }
+}
+
+impl From<Alternative> for assistant::Alternative {
+    fn from(alternative: Alternative) -> Self {
+        Self {
+            reasoning: reasoning(alternative.reasoning, alternative.reasoning_time),
+            content: alternative.content,
+        }
+    }
+}
+
+fn reasoning(content: String, duration: Duration) -> Option<assistant::Reasoning> {
+    if content.is_empty() {
+        None
+    } else {
+        Some(assistant::Reasoning { content, duration })
+    }
 }
 
 impl FromStr for AssistantMessage {
@@ -80,6 +187,10 @@ impl FromStr for AssistantMessage {
             reasoning: String::new(),
             reasoning_time: Duration::default(),
             content: s.to_owned(),
+            metadata: Metadata::compute(s),
+            usage: None,
+            alternatives: Vec::new(),
+            interrupted: false,
         })
     }
 }
@@ -127,3 +238,101 @@ where
 
     deserializer.deserialize_any(StringOrStruct(PhantomData))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::snapshot;
+
+    fn encode(schema: &Schema) -> String {
+        serde_json::to_string_pretty(schema).expect("schema should encode")
+    }
+
+    fn decode(json: &str) -> Schema {
+        serde_json::from_str(json).expect("schema should decode")
+    }
+
+    #[test]
+    fn legacy_chats_are_migrated() {
+        let schema = decode(&snapshot::read("chat/legacy.json"));
+
+        assert_eq!(schema.system_prompt, None);
+        assert!(schema.guardrails.is_empty());
+        assert_eq!(schema.sampling, assistant::Sampling::default());
+        assert!(schema.memory.is_empty());
+        assert_eq!(schema.history.len(), 2);
+        assert_eq!(schema.history[0].metadata().words, 10);
+
+        snapshot::assert("chat/legacy.migrated.json", &encode(&schema));
+    }
+
+    #[test]
+    fn chats_round_trip() {
+        let history = vec![
+            assistant::Message::User("Write a haiku about winter.".to_owned()),
+            assistant::Message::Assistant {
+                reasoning: Some(assistant::Reasoning {
+                    content: "Five, seven, five syllables.".to_owned(),
+                    duration: Duration::from_millis(1_500),
+                }),
+                content:
+                    "Silent falling snow\nblankets the sleeping village\nsmoke curls from chimneys"
+                        .to_owned(),
+                usage: Some(assistant::Usage {
+                    prompt_tokens: 24,
+                    completion_tokens: 17,
+                    generation_time: Duration::from_millis(850),
+                }),
+                alternatives: vec![assistant::Alternative {
+                    reasoning: None,
+                    content: "Snow hides the garden\nthe kettle sings by the stove\nwinter holds its breath"
+                        .to_owned(),
+                }],
+                interrupted: true,
+            },
+        ];
+
+        let legacy = decode(&snapshot::read("chat/legacy.json"));
+
+        let schema = Schema {
+            id: legacy.id,
+            file: legacy.file,
+            title: Some("Winter haiku".to_owned()),
+            history: history.into_iter().map(Message::from).collect(),
+            system_prompt: Some("You are a poet.".to_owned()),
+            guardrails: Guardrails {
+                refused_topics: "politics".to_owned(),
+                tone: "Calm".to_owned(),
+            },
+            sampling: assistant::Sampling {
+                temperature: 1.25,
+                max_tokens: Some(256),
+                replies: 2,
+                ..assistant::Sampling::default()
+            },
+            memory: vec!["The user lives in Oslo.".to_owned()],
+        };
+
+        let encoded = encode(&schema);
+        snapshot::assert("chat/current.json", &encoded);
+
+        let decoded = decode(&encoded);
+        assert_eq!(encode(&decoded), encoded);
+
+        let history: Vec<assistant::Message> = decoded
+            .history
+            .into_iter()
+            .map(assistant::Message::from)
+            .collect();
+
+        assert!(matches!(
+            &history[1],
+            assistant::Message::Assistant {
+                reasoning: Some(reasoning),
+                usage: Some(_),
+                alternatives,
+                ..
+            } if reasoning.duration == Duration::from_millis(1_500) && alternatives.len() == 1
+        ));
+    }
+}
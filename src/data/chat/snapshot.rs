@@ -0,0 +1,140 @@
+use crate::data::assistant::Message;
+use crate::data::chat::schema::Schema;
+use crate::data::chat::{saved, storage_dir, Chat, Draft, Id};
+use crate::data::Error;
+
+use tokio::fs;
+use tokio::task;
+
+use std::io;
+use std::path::PathBuf;
+
+/// An earlier version of a chat.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub id: Id,
+    pub saved_at: chrono::DateTime<chrono::Local>,
+    pub messages: usize,
+    pub last_message: Option<String>,
+    path: PathBuf,
+}
+
+impl Snapshot {
+    /// The amount of snapshots kept for every chat.
+    const LIMIT: usize = 10;
+}
+
+impl Chat {
+    /// Lists the snapshots of the given chat, most recent first.
+    pub async fn snapshots(id: Id) -> Result<Vec<Snapshot>, Error> {
+        let mut files = match fs::read_dir(directory(&id).await?).await {
+            Ok(files) => files,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut snapshots = Vec::new();
+
+        while let Some(file) = files.next_entry().await? {
+            let path = file.path();
+
+            let Some(saved_at) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str()?.parse().ok())
+                .and_then(chrono::DateTime::from_timestamp_millis)
+            else {
+                continue;
+            };
+
+            let bytes = fs::read(&path).await?;
+            let schema: Schema =
+                task::spawn_blocking(move || serde_json::from_slice(&bytes)).await??;
+
+            let history: Vec<Message> = schema.history.into_iter().map(Message::from).collect();
+
+            snapshots.push(Snapshot {
+                id,
+                saved_at: saved_at.with_timezone(&chrono::Local),
+                messages: history.len(),
+                last_message: history.last().map(|message| message.content().to_owned()),
+                path,
+            });
+        }
+
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.saved_at));
+
+        Ok(snapshots)
+    }
+
+    /// Replaces the chat with the given snapshot.
+    ///
+    /// The current version is kept as a new snapshot.
+    pub async fn restore(snapshot: Snapshot) -> Result<Self, Error> {
+        let bytes = fs::read(&snapshot.path).await?;
+        let schema: Schema = task::spawn_blocking(move || serde_json::from_slice(&bytes)).await??;
+
+        Self::save(
+            snapshot.id,
+            Draft {
+                file: schema.file,
+                title: schema.title,
+                history: schema.history.into_iter().map(Message::from).collect(),
+                system_prompt: schema.system_prompt,
+                guardrails: schema.guardrails,
+                sampling: schema.sampling,
+                memory: schema.memory,
+            },
+        )
+        .await
+    }
+}
+
+/// Keeps the saved version of a chat before it is replaced with the given
+/// bytes.
+pub(super) async fn take(id: &Id, replacement: &[u8]) -> Result<(), Error> {
+    let Some(current) = saved(id).await else {
+        return Ok(());
+    };
+
+    if current == replacement {
+        return Ok(());
+    }
+
+    let directory = directory(id).await?;
+    fs::create_dir_all(&directory).await?;
+
+    fs::write(
+        directory.join(format!("{}.json", chrono::Local::now().timestamp_millis())),
+        current,
+    )
+    .await?;
+
+    let mut files = fs::read_dir(&directory).await?;
+    let mut snapshots = Vec::new();
+
+    while let Some(file) = files.next_entry().await? {
+        snapshots.push(file.path());
+    }
+
+    snapshots.sort();
+
+    for outdated in snapshots.iter().rev().skip(Snapshot::LIMIT) {
+        fs::remove_file(outdated).await?;
+    }
+
+    Ok(())
+}
+
+pub(super) async fn delete(id: &Id) -> Result<(), Error> {
+    match fs::remove_dir_all(directory(id).await?).await {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+async fn directory(id: &Id) -> Result<PathBuf, io::Error> {
+    Ok(storage_dir()
+        .await?
+        .join("snapshots")
+        .join(id.0.simple().to_string()))
+}
@@ -0,0 +1,399 @@
+//! Conversion of models only published as safetensors into GGUF files,
+//! using the tooling of llama.cpp.
+use crate::data::assistant::{self, File};
+use crate::data::audit;
+use crate::data::download::{Event, Progress};
+use crate::data::trace;
+use crate::data::Error;
+
+use futures::channel::mpsc;
+use futures::{SinkExt, Stream};
+use tokio::fs;
+use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt};
+use tokio::process;
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+/// The quantization of converted models; small, yet close to the original.
+pub const QUANTIZATION: &str = "Q4_K_M";
+
+/// Where a clone of the llama.cpp repository is looked up, besides `PATH`.
+const LLAMA_CPP_DIR: &str = "./llama.cpp";
+
+const QUANTIZER: &str = "llama-quantize";
+
+/// The llama.cpp tools needed to convert a model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tooling {
+    convert: PathBuf,
+    quantize: PathBuf,
+}
+
+impl Tooling {
+    /// Finds the conversion script and `llama-quantize` in `PATH` or in a
+    /// clone of llama.cpp next to the app.
+    pub fn detect() -> Option<Self> {
+        Some(Self {
+            convert: find("convert_hf_to_gguf.py")?,
+            quantize: find(QUANTIZER)?,
+        })
+    }
+}
+
+/// Returns true if `llama-quantize` is available to requantize files.
+pub fn can_requantize() -> bool {
+    find(QUANTIZER).is_some()
+}
+
+/// Downloads the original weights of the model of the given file and
+/// converts them into it.
+///
+/// The original files are kept until the conversion succeeds, so a retry
+/// only downloads the ones missing.
+pub fn run(file: File) -> impl Stream<Item = Result<Event, Error>> {
+    const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+    let span = tracing::info_span!("convert", model = %file.name, bytes = tracing::field::Empty);
+
+    let stream = iced::stream::try_channel(1, move |mut sender| async move {
+        let Some(tooling) = Tooling::detect() else {
+            return Err(Error::ConversionFailed(
+                "convert_hf_to_gguf.py and llama-quantize of llama.cpp were not found",
+            ));
+        };
+
+        let sources = file.sources().await?;
+
+        if !sources
+            .iter()
+            .any(|source| source.name.ends_with(".safetensors"))
+        {
+            return Err(Error::ConversionFailed(
+                "the model has no safetensors weights",
+            ));
+        }
+
+        let directory = directory(&file);
+        fs::create_dir_all(&directory).await?;
+
+        let total = sources
+            .iter()
+            .map(|source| source.size)
+            .sum::<Option<u64>>();
+        let start = Instant::now();
+        let mut downloaded = 0;
+        let mut received = 0;
+        let mut last_report = start;
+
+        for source in &sources {
+            let path = directory.join(&source.name);
+
+            if let Some(size) = source.size.filter(|_| path.exists()) {
+                downloaded += size;
+                continue;
+            }
+
+            let mut response = assistant::hugging_face(&source.url)
+                .send()
+                .await?
+                .error_for_status()?;
+
+            audit::record(
+                audit::Purpose::Download,
+                &source.url,
+                response.content_length(),
+            )
+            .await;
+
+            let partial = directory.join(format!("{}.part", source.name));
+            let mut output = io::BufWriter::new(fs::File::create(&partial).await?);
+
+            while let Some(chunk) = response.chunk().await? {
+                output.write_all(&chunk).await?;
+
+                downloaded += chunk.len() as u64;
+                received += chunk.len() as u64;
+
+                if last_report.elapsed() >= REPORT_INTERVAL {
+                    last_report = Instant::now();
+
+                    let _ = sender
+                        .send(Event::Progressed(Progress {
+                            downloaded,
+                            total,
+                            speed: received as f32 / start.elapsed().as_secs_f32(),
+                        }))
+                        .await;
+                }
+            }
+
+            output.flush().await?;
+            drop(output);
+
+            fs::rename(&partial, &path).await?;
+        }
+
+        tracing::Span::current().record("bytes", received);
+
+        let _ = sender.send(Event::Converting).await;
+
+        let converted = directory.join("model-F16.gguf");
+
+        execute(
+            process::Command::new(python())
+                .arg(&tooling.convert)
+                .arg(&directory)
+                .args(["--outtype", "f16", "--outfile"])
+                .arg(&converted),
+            "convert_hf_to_gguf.py could not convert the model",
+        )
+        .await?;
+
+        let _ = sender.send(Event::Quantizing).await;
+
+        let estimated = estimate(fs::metadata(&converted).await?.len(), "F16", QUANTIZATION);
+
+        quantize(
+            &tooling.quantize,
+            &converted,
+            &file,
+            QUANTIZATION,
+            estimated,
+            &mut sender,
+        )
+        .await?;
+
+        fs::remove_dir_all(&directory).await?;
+
+        let _ = sender.send(Event::Finished).await;
+
+        Ok(())
+    });
+
+    trace::stream(span, stream)
+}
+
+/// Quantizes the downloaded source file into the given smaller file.
+pub fn requantize(source: File, target: File) -> impl Stream<Item = Result<Event, Error>> {
+    let span = tracing::info_span!("requantize", model = %target.name);
+
+    let stream = iced::stream::try_channel(1, move |mut sender| async move {
+        let Some(quantizer) = find(QUANTIZER) else {
+            return Err(Error::ConversionFailed(
+                "llama-quantize of llama.cpp was not found",
+            ));
+        };
+
+        let (Some(from), Some(to)) = (source.quantization(), target.quantization()) else {
+            return Err(Error::ConversionFailed(
+                "the quantization of the file is unknown",
+            ));
+        };
+
+        let _ = sender.send(Event::Quantizing).await;
+
+        let estimated = estimate(fs::metadata(source.path()).await?.len(), from, to);
+
+        quantize(
+            &quantizer,
+            &source.path(),
+            &target,
+            to,
+            estimated,
+            &mut sender,
+        )
+        .await?;
+
+        let _ = sender.send(Event::Finished).await;
+
+        Ok(())
+    });
+
+    trace::stream(span, stream)
+}
+
+/// Estimates the size of a file quantized with `to`, given the size of the
+/// same weights quantized with `from`.
+pub fn estimate(size: u64, from: &str, to: &str) -> Option<u64> {
+    Some((size as f64 * bits_per_weight(to)? / bits_per_weight(from)?) as u64)
+}
+
+/// The average bits per weight of a quantization, as reported by
+/// llama.cpp for a 7B model.
+pub fn bits_per_weight(quantization: &str) -> Option<f64> {
+    Some(match quantization {
+        "IQ1_S" => 1.56,
+        "IQ1_M" => 1.75,
+        "IQ2_XXS" => 2.06,
+        "IQ2_XS" => 2.31,
+        "IQ2_S" => 2.5,
+        "IQ2_M" => 2.7,
+        "Q2_K" => 3.35,
+        "IQ3_XXS" => 3.06,
+        "IQ3_XS" => 3.3,
+        "IQ3_S" => 3.44,
+        "IQ3_M" => 3.66,
+        "Q3_K_S" => 3.5,
+        "Q3_K_M" => 3.91,
+        "Q3_K_L" => 4.27,
+        "IQ4_XS" => 4.25,
+        "IQ4_NL" => 4.5,
+        "Q4_0" => 4.55,
+        "Q4_K_S" => 4.58,
+        "Q4_K_M" => 4.89,
+        "Q5_0" => 5.54,
+        "Q5_K_S" => 5.54,
+        "Q5_K_M" => 5.7,
+        "Q6_K" => 6.56,
+        "Q8_0" => 8.5,
+        "F16" | "BF16" => 16.0,
+        "F32" => 32.0,
+        _ => return None,
+    })
+}
+
+/// Runs `llama-quantize` into the given file, reporting progress as the
+/// share of the estimated size written.
+async fn quantize(
+    quantizer: &Path,
+    input: &Path,
+    output: &File,
+    quantization: &str,
+    estimated: Option<u64>,
+    sender: &mut mpsc::Sender<Event>,
+) -> Result<(), Error> {
+    let mut process = process::Command::new(quantizer)
+        .arg("--allow-requantize")
+        .arg(input)
+        .arg(output.partial_path())
+        .arg(quantization)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let start = Instant::now();
+
+    if let Some(stdout) = process.stdout.take() {
+        let mut lines = io::BufReader::new(stdout).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            let (Some((tensor, tensors)), Some(estimated)) = (tensor(&line), estimated) else {
+                continue;
+            };
+
+            let written = estimated * tensor / tensors.max(1);
+
+            let _ = sender
+                .send(Event::Progressed(Progress {
+                    downloaded: written,
+                    total: Some(estimated),
+                    speed: written as f32 / start.elapsed().as_secs_f32(),
+                }))
+                .await;
+        }
+    }
+
+    if !process.wait().await?.success() {
+        let _ = fs::remove_file(output.partial_path()).await;
+
+        return Err(Error::ConversionFailed(
+            "llama-quantize could not quantize the model",
+        ));
+    }
+
+    output.mark_local().await?;
+    fs::rename(output.partial_path(), output.path()).await?;
+
+    Ok(())
+}
+
+/// Parses the `[  12/ 291]` prefix that `llama-quantize` prints for every
+/// tensor it quantizes.
+fn tensor(line: &str) -> Option<(u64, u64)> {
+    let (current, total) = line
+        .trim_start()
+        .strip_prefix('[')?
+        .split_once(']')?
+        .0
+        .split_once('/')?;
+
+    Some((current.trim().parse().ok()?, total.trim().parse().ok()?))
+}
+
+/// Deletes the original files downloaded for the conversion, if any.
+pub async fn cancel(file: File) -> Result<(), Error> {
+    let _ = fs::remove_file(file.partial_path()).await;
+
+    match fs::remove_dir_all(directory(&file)).await {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+/// The directory keeping the original files of the model of the given file.
+fn directory(file: &File) -> PathBuf {
+    file.path().with_extension("sources")
+}
+
+async fn execute(command: &mut process::Command, failure: &'static str) -> Result<(), Error> {
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::ConversionFailed(failure))
+    }
+}
+
+fn python() -> &'static str {
+    if cfg!(windows) {
+        "python"
+    } else {
+        "python3"
+    }
+}
+
+fn find(name: &str) -> Option<PathBuf> {
+    let candidates = [name.to_owned(), format!("{name}.exe")];
+
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .chain([PathBuf::from(LLAMA_CPP_DIR)])
+        .flat_map(|directory| candidates.clone().map(|name| directory.join(name)))
+        .find(|path| Path::is_file(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantized_sizes_are_estimated() {
+        const GB: u64 = 1_000_000_000;
+
+        assert_eq!(estimate(16 * GB, "F16", "Q8_0"), Some(8_500_000_000));
+        assert_eq!(estimate(8 * GB, "Q4_K_M", "Q4_K_M"), Some(8 * GB));
+        assert_eq!(estimate(8 * GB, "Q4_K_M", "UNKNOWN"), None);
+
+        assert_eq!(
+            tensor("[  12/ 291]  blk.1.attn_k.weight - [ 4096, 1024], type = q4_K"),
+            Some((12, 291))
+        );
+        assert_eq!(
+            tensor("llama_model_quantize_impl: model size = 4685 MB"),
+            None
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use crate::data::assistant::Backend;
+use crate::data::executor::Executor;
+use crate::data::{Error, Settings};
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::io;
+use std::sync::Mutex;
+
+/// The most recent errors, kept in memory for environment reports.
+static RECENT_ERRORS: Mutex<VecDeque<Failure>> = Mutex::new(VecDeque::new());
+
+const MAX_RECENT_ERRORS: usize = 10;
+
+/// The most recent lines logged by the app, kept in memory for
+/// diagnostics bundles.
+static RECENT_LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+const MAX_RECENT_LOGS: usize = 2_000;
+
+/// A [`tracing`] writer that keeps the most recent lines logged by the app.
+#[derive(Debug, Clone, Copy)]
+pub struct Sink;
+
+impl io::Write for Sink {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        if let Ok(mut logs) = RECENT_LOGS.lock() {
+            logs.extend(String::from_utf8_lossy(buffer).lines().map(str::to_owned));
+
+            while logs.len() > MAX_RECENT_LOGS {
+                let _ = logs.pop_front();
+            }
+        }
+
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl tracing_subscriber::fmt::MakeWriter<'_> for Sink {
+    type Writer = Sink;
+
+    fn make_writer(&self) -> Self::Writer {
+        *self
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Failure {
+    at: chrono::DateTime<chrono::Local>,
+    summary: String,
+}
+
+/// Remembers the given error for the next environment report.
+pub fn record(error: &Error) {
+    let Ok(mut errors) = RECENT_ERRORS.lock() else {
+        return;
+    };
+
+    tracing::error!("{error}");
+
+    errors.push_back(Failure {
+        at: chrono::Local::now(),
+        summary: error.to_string(),
+    });
+
+    while errors.len() > MAX_RECENT_ERRORS {
+        let _ = errors.pop_front();
+    }
+}
+
+/// The machine running Icebreaker.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    pub system: String,
+    pub cpu: String,
+    pub memory: u64,
+    pub graphics_adapter: String,
+    pub graphics_backend: String,
+}
+
+/// Builds a markdown report of the environment, ready to be pasted
+/// into a bug report.
+///
+/// Paths inside the home directory are redacted.
+pub async fn report(environment: Environment, executor: Executor) -> String {
+    let backend = Backend::detect(&environment.graphics_adapter);
+    let server = executor.describe(backend).await;
+
+    let errors: Vec<_> = RECENT_ERRORS
+        .lock()
+        .map(|errors| errors.iter().cloned().collect())
+        .unwrap_or_default();
+
+    let report = format_report(&environment, backend, &server, &errors);
+
+    match dirs_next::home_dir() {
+        Some(home) => redact(&report, &home.to_string_lossy()),
+        None => report,
+    }
+}
+
+/// Packs an environment report, the recent logs of the app and its
+/// server, and the given settings into a zip archive, ready to be attached
+/// to a bug report.
+///
+/// Secrets are removed from the settings and paths inside the home
+/// directory are redacted.
+pub async fn bundle(
+    environment: Environment,
+    settings: Settings,
+    server_logs: Vec<String>,
+) -> Result<Vec<u8>, Error> {
+    let report = report(environment, settings.executor.clone()).await;
+    let settings = toml::to_string_pretty(&settings.redacted())?;
+
+    let logs = RECENT_LOGS
+        .lock()
+        .map(|logs| logs.iter().cloned().collect::<Vec<_>>().join("\n"))
+        .unwrap_or_default();
+
+    let home = dirs_next::home_dir()
+        .map(|home| home.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    archive(&[
+        ("report.md", report),
+        ("icebreaker.log", redact(&logs, &home)),
+        ("llama-server.log", redact(&server_logs.join("\n"), &home)),
+        ("settings.toml", redact(&settings, &home)),
+    ])
+}
+
+fn format_report(
+    environment: &Environment,
+    backend: Backend,
+    server: &str,
+    errors: &[Failure],
+) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(report, "### Environment\n");
+    let _ = writeln!(report, "- Icebreaker: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "- OS: {}", environment.system);
+    let _ = writeln!(report, "- CPU: {}", environment.cpu);
+    let _ = writeln!(
+        report,
+        "- Memory: {:.1} GB",
+        environment.memory as f64 / 1_000_000_000.0
+    );
+    let _ = writeln!(
+        report,
+        "- GPU: {} ({})",
+        environment.graphics_adapter, environment.graphics_backend
+    );
+    let _ = writeln!(report, "- Backend: {backend}");
+    let _ = writeln!(report, "- llama-server: {server}");
+    let _ = writeln!(report, "\n### Recent errors\n");
+
+    if errors.is_empty() {
+        let _ = writeln!(report, "None");
+    }
+
+    for error in errors {
+        let _ = writeln!(
+            report,
+            "- `{at}` {summary}",
+            at = error.at.format("%Y-%m-%d %H:%M:%S"),
+            summary = error.summary.replace('\n', " ")
+        );
+    }
+
+    report
+}
+
+/// Replaces every mention of the home directory, which usually contains
+/// the name of the user.
+fn redact(report: &str, home: &str) -> String {
+    if home.is_empty() {
+        return report.to_owned();
+    }
+
+    report.replace(home, "~")
+}
+
+/// Packs the given files into a zip archive, without compression.
+fn archive(files: &[(&str, String)]) -> Result<Vec<u8>, Error> {
+    use std::io::Write;
+
+    let mut archive = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for (name, contents) in files {
+        archive.start_file(*name, options)?;
+        archive.write_all(contents.as_bytes())?;
+    }
+
+    Ok(archive.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_are_redacted() {
+        let environment = Environment {
+            system: "Linux 6.1 (Arch Linux)".to_owned(),
+            cpu: "AMD Ryzen 7".to_owned(),
+            memory: 32_000_000_000,
+            graphics_adapter: "NVIDIA GeForce RTX 4090".to_owned(),
+            graphics_backend: "Vulkan".to_owned(),
+        };
+
+        let errors = [Failure {
+            at: chrono::Local::now(),
+            summary: "io operation failed: /home/alice/.local/share/icebreaker missing".to_owned(),
+        }];
+
+        let report = redact(
+            &format_report(&environment, Backend::Cuda, "version: 4600", &errors),
+            "/home/alice",
+        );
+
+        assert!(report.contains("- Backend: CUDA"));
+        assert!(report.contains("- Memory: 32.0 GB"));
+        assert!(report.contains("~/.local/share/icebreaker missing"));
+        assert!(!report.contains("alice"));
+    }
+
+    #[test]
+    fn bundles_are_zip_archives() {
+        use std::io::Read;
+
+        let archive = archive(&[
+            ("report.md", "### Environment".to_owned()),
+            ("settings.toml", String::new()),
+        ])
+        .expect("archive should be written");
+
+        let mut archive =
+            zip::ZipArchive::new(io::Cursor::new(archive)).expect("archive should be read");
+
+        assert_eq!(archive.len(), 2);
+
+        let mut report = String::new();
+        let _ = archive
+            .by_name("report.md")
+            .expect("report should be archived")
+            .read_to_string(&mut report)
+            .expect("report should be decompressed");
+
+        assert_eq!(report, "### Environment");
+    }
+}
@@ -0,0 +1,447 @@
+use crate::data::assistant::{self, File};
+use crate::data::audit;
+use crate::data::trace;
+use crate::data::Error;
+
+use futures::channel::mpsc;
+use futures::future::{self, FutureExt};
+use futures::{SinkExt, Stream};
+use reqwest::header;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::{self, AsyncSeekExt, AsyncWriteExt};
+use tokio::task;
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU64};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Progressed(Progress),
+    Verifying,
+    /// The original weights of a model are being converted into GGUF.
+    Converting,
+    /// The converted weights are being quantized.
+    Quantizing,
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub downloaded: u64,
+    pub total: Option<u64>,
+    pub speed: f32,
+}
+
+impl Progress {
+    pub fn percent(&self) -> f32 {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| 100.0 * self.downloaded as f32 / total as f32)
+            .unwrap_or_default()
+    }
+}
+
+/// Files at least this large are downloaded in parallel chunks, if the
+/// server supports ranges.
+const CHUNKED_AFTER: u64 = 64 * 1024 * 1024;
+
+/// The amount of chunks a file is split into.
+const CHUNKS: u64 = 4;
+
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Downloads the file into the models directory.
+///
+/// Bytes are written to a partial file first. If a partial file already
+/// exists, the download resumes from where it stopped. Large files are
+/// split into chunks that download at the same time.
+///
+/// Once complete, the file is verified against the SHA256 digest in its
+/// Git LFS metadata. A corrupted file is deleted and the download fails.
+pub fn run(file: File) -> impl Stream<Item = Result<Event, Error>> {
+    let span = tracing::info_span!("download", model = %file.name, bytes = tracing::field::Empty);
+
+    let stream = iced::stream::try_channel(1, move |mut sender| async move {
+        let path = file.path();
+        let partial = file.partial_path();
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).await?;
+        }
+
+        let downloaded = if let Some(chunks) = Chunks::load(&partial).await {
+            fetch(&file, &partial, chunks, &mut sender).await?
+        } else {
+            let mut downloaded = fs::metadata(&partial)
+                .await
+                .map(|metadata| metadata.len())
+                .unwrap_or_default();
+
+            let mut request = assistant::hugging_face(file.url());
+
+            if downloaded > 0 {
+                request = request.header(header::RANGE, format!("bytes={downloaded}-"));
+            }
+
+            let mut response = request.send().await?;
+
+            audit::record(
+                audit::Purpose::Download,
+                file.url(),
+                response.content_length(),
+            )
+            .await;
+
+            if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+                // The partial file is already complete
+                let _ = sender.send(Event::Verifying).await;
+                verify(&file, &partial).await?;
+
+                fs::rename(&partial, &path).await?;
+                let _ = sender.send(Event::Finished).await;
+
+                return Ok(());
+            }
+
+            response = response.error_for_status()?;
+
+            if response.status() != StatusCode::PARTIAL_CONTENT {
+                downloaded = 0;
+            }
+
+            let total = response.content_length().map(|length| length + downloaded);
+
+            let accepts_ranges = response
+                .headers()
+                .get(header::ACCEPT_RANGES)
+                .is_some_and(|ranges| ranges == "bytes");
+
+            if let Some(total) =
+                total.filter(|total| downloaded == 0 && accepts_ranges && *total >= CHUNKED_AFTER)
+            {
+                drop(response);
+
+                fetch(&file, &partial, Chunks::plan(total, CHUNKS), &mut sender).await?
+            } else {
+                let mut output = io::BufWriter::new(
+                    fs::OpenOptions::new()
+                        .create(true)
+                        .write(true)
+                        .append(downloaded > 0)
+                        .truncate(downloaded == 0)
+                        .open(&partial)
+                        .await?,
+                );
+
+                let start = Instant::now();
+                let mut received = 0;
+                let mut last_report = start;
+
+                while let Some(chunk) = response.chunk().await? {
+                    output.write_all(&chunk).await?;
+
+                    downloaded += chunk.len() as u64;
+                    received += chunk.len() as u64;
+
+                    if last_report.elapsed() >= REPORT_INTERVAL {
+                        last_report = Instant::now();
+
+                        let _ = sender
+                            .send(Event::Progressed(Progress {
+                                downloaded,
+                                total,
+                                speed: received as f32 / start.elapsed().as_secs_f32(),
+                            }))
+                            .await;
+                    }
+                }
+
+                output.flush().await?;
+
+                downloaded
+            }
+        };
+
+        let _ = sender.send(Event::Verifying).await;
+        verify(&file, &partial).await?;
+
+        fs::rename(&partial, &path).await?;
+        tracing::Span::current().record("bytes", downloaded);
+
+        let _ = sender.send(Event::Finished).await;
+
+        Ok(())
+    });
+
+    trace::stream(span, stream)
+}
+
+/// The progress of a file downloaded in parallel chunks.
+///
+/// It is saved next to the partial file, so the download can resume
+/// after a pause.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Chunks {
+    total: u64,
+    chunks: Vec<Chunk>,
+}
+
+/// A range of bytes of a file, with the amount already written.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Chunk {
+    start: u64,
+    end: u64,
+    written: u64,
+}
+
+impl Chunks {
+    /// Splits a file of the given size into the given amount of chunks.
+    fn plan(total: u64, count: u64) -> Self {
+        let size = total.div_ceil(count.max(1)).max(1);
+
+        Self {
+            total,
+            chunks: (0..count)
+                .map(|index| Chunk {
+                    start: (index * size).min(total),
+                    end: ((index + 1) * size).min(total),
+                    written: 0,
+                })
+                .filter(|chunk| chunk.start < chunk.end)
+                .collect(),
+        }
+    }
+
+    fn downloaded(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.written).sum()
+    }
+
+    fn path(partial: &Path) -> PathBuf {
+        partial.with_extension("chunks")
+    }
+
+    /// Loads the chunks of the given partial file, if it was being
+    /// downloaded in chunks.
+    async fn load(partial: &Path) -> Option<Self> {
+        if !fs::try_exists(partial).await.unwrap_or(false) {
+            return None;
+        }
+
+        let bytes = fs::read(Self::path(partial)).await.ok()?;
+
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    async fn save(&self, partial: &Path) -> Result<(), Error> {
+        fs::write(Self::path(partial), serde_json::to_vec(self)?).await?;
+
+        Ok(())
+    }
+}
+
+/// Downloads the rest of every chunk at the same time, each one into its
+/// place in the partial file.
+///
+/// Returns the size of the file.
+async fn fetch(
+    file: &File,
+    partial: &Path,
+    mut chunks: Chunks,
+    sender: &mut mpsc::Sender<Event>,
+) -> Result<u64, Error> {
+    fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(partial)
+        .await?
+        .set_len(chunks.total)
+        .await?;
+
+    chunks.save(partial).await?;
+
+    let written: Vec<_> = chunks
+        .chunks
+        .iter()
+        .map(|chunk| AtomicU64::new(chunk.written))
+        .collect();
+
+    let pending = chunks.chunks.clone();
+    let download = future::try_join_all(
+        pending
+            .into_iter()
+            .zip(&written)
+            .map(|(chunk, written)| fetch_chunk(file, partial, chunk, written)),
+    );
+    let mut download = std::pin::pin!(download);
+
+    let start = Instant::now();
+    let resumed = chunks.downloaded();
+
+    loop {
+        let report = tokio::time::sleep(REPORT_INTERVAL).boxed();
+
+        match future::select(download.as_mut(), report).await {
+            future::Either::Left((result, _)) => {
+                let _ = result?;
+                break;
+            }
+            future::Either::Right(_) => {
+                for (chunk, written) in chunks.chunks.iter_mut().zip(&written) {
+                    chunk.written = written.load(atomic::Ordering::Relaxed);
+                }
+
+                chunks.save(partial).await?;
+
+                let downloaded = chunks.downloaded();
+
+                let _ = sender
+                    .send(Event::Progressed(Progress {
+                        downloaded,
+                        total: Some(chunks.total),
+                        speed: downloaded.saturating_sub(resumed) as f32
+                            / start.elapsed().as_secs_f32(),
+                    }))
+                    .await;
+            }
+        }
+    }
+
+    fs::remove_file(Chunks::path(partial)).await?;
+
+    Ok(chunks.total)
+}
+
+/// Downloads the rest of a chunk, counting the bytes written.
+///
+/// Bytes are only counted once flushed, so the saved progress never gets
+/// ahead of the partial file.
+async fn fetch_chunk(
+    file: &File,
+    partial: &Path,
+    chunk: Chunk,
+    written: &AtomicU64,
+) -> Result<(), Error> {
+    let from = chunk.start + chunk.written;
+
+    if from >= chunk.end {
+        return Ok(());
+    }
+
+    let mut response = assistant::hugging_face(file.url())
+        .header(header::RANGE, format!("bytes={from}-{}", chunk.end - 1))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    audit::record(
+        audit::Purpose::Download,
+        file.url(),
+        response.content_length(),
+    )
+    .await;
+
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(Error::DownloadFailed(
+            "the server ignored the requested range",
+        ));
+    }
+
+    let mut output = fs::OpenOptions::new().write(true).open(partial).await?;
+    let _ = output.seek(io::SeekFrom::Start(from)).await?;
+
+    let mut position = from;
+
+    while let Some(bytes) = response.chunk().await? {
+        let bytes = &bytes[..bytes.len().min((chunk.end - position) as usize)];
+
+        output.write_all(bytes).await?;
+        output.flush().await?;
+
+        position += bytes.len() as u64;
+        let _ = written.fetch_add(bytes.len() as u64, atomic::Ordering::Relaxed);
+
+        if position == chunk.end {
+            break;
+        }
+    }
+
+    if position < chunk.end {
+        return Err(Error::DownloadFailed(
+            "the connection closed before the end",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Deletes any partial data of the file.
+pub async fn cancel(file: File) -> Result<(), Error> {
+    let partial = file.partial_path();
+
+    for path in [Chunks::path(&partial), partial] {
+        match fs::remove_file(path).await {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error.into()),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes the SHA256 digest of the file at the given path.
+pub async fn checksum(path: impl AsRef<Path>) -> Result<String, Error> {
+    let path = path.as_ref().to_path_buf();
+
+    let digest = task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+
+        let _ = std::io::copy(&mut file, &mut hasher)?;
+
+        Ok::<_, std::io::Error>(hasher.finalize())
+    })
+    .await??;
+
+    Ok(format!("{digest:x}"))
+}
+
+async fn verify(file: &File, partial: &Path) -> Result<(), Error> {
+    let Some(expected) = file.pointer().await?.sha256 else {
+        return Ok(());
+    };
+
+    if checksum(partial).await? != expected {
+        fs::remove_file(partial).await?;
+
+        return Err(Error::ChecksumMismatch(file.name.clone()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_cover_the_whole_file() {
+        let chunks = Chunks::plan(10, 4);
+
+        assert_eq!(
+            chunks
+                .chunks
+                .iter()
+                .map(|chunk| (chunk.start, chunk.end))
+                .collect::<Vec<_>>(),
+            vec![(0, 3), (3, 6), (6, 9), (9, 10)]
+        );
+
+        assert_eq!(Chunks::plan(2, 4).chunks.len(), 2);
+        assert_eq!(Chunks::plan(0, 4).chunks, Vec::new());
+    }
+}
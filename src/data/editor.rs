@@ -0,0 +1,98 @@
+//! Editing replies in an external editor.
+use crate::data::Error;
+
+use tokio::fs;
+use tokio::process;
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::SystemTime;
+
+/// A temporary file opened in an external editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Draft {
+    path: PathBuf,
+    modified: SystemTime,
+}
+
+impl Draft {
+    /// Writes the given content to a temporary file and opens it with the
+    /// given editor command, followed by the path of the file.
+    ///
+    /// The default app for the file is used if the command is empty.
+    pub async fn open(
+        command: String,
+        language: Option<String>,
+        content: String,
+    ) -> Result<Self, Error> {
+        let path = std::env::temp_dir().join(format!(
+            "icebreaker-{}.{}",
+            uuid::Uuid::new_v4().simple(),
+            extension(language.as_deref())
+        ));
+
+        fs::write(&path, content).await?;
+
+        let mut args = command.split_whitespace();
+
+        match args.next() {
+            Some(program) => {
+                let _editor = process::Command::new(program)
+                    .args(args)
+                    .arg(&path)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+            }
+            None => open::that_detached(&path)?,
+        }
+
+        let modified = fs::metadata(&path).await?.modified()?;
+
+        Ok(Self { path, modified })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the draft again, if it was saved since it was last read.
+    pub async fn read(self) -> Result<(Self, Option<String>), Error> {
+        let modified = fs::metadata(&self.path).await?.modified()?;
+
+        if modified == self.modified {
+            return Ok((self, None));
+        }
+
+        let content = fs::read_to_string(&self.path).await?;
+
+        Ok((Self { modified, ..self }, Some(content)))
+    }
+
+    /// Deletes the temporary file of the draft.
+    pub async fn discard(self) -> Result<(), Error> {
+        Ok(fs::remove_file(self.path).await?)
+    }
+}
+
+/// The file extension for code in the given language, so editors can
+/// highlight it.
+fn extension(language: Option<&str>) -> String {
+    let Some(language) = language.map(str::to_lowercase) else {
+        return "md".to_owned();
+    };
+
+    match language.as_str() {
+        "python" | "python3" => "py".to_owned(),
+        "rust" => "rs".to_owned(),
+        "javascript" => "js".to_owned(),
+        "typescript" => "ts".to_owned(),
+        "shell" | "bash" | "zsh" => "sh".to_owned(),
+        "markdown" => "md".to_owned(),
+        _ if !language.is_empty() && language.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            language
+        }
+        _ => "txt".to_owned(),
+    }
+}
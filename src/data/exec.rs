@@ -0,0 +1,213 @@
+use crate::data::Error;
+
+use tokio::fs;
+use tokio::process;
+use tokio::time;
+
+use std::fmt;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+/// The languages of the code blocks that can be run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Shell,
+    Python,
+}
+
+impl Language {
+    /// Finds the language of a code block from its fence info.
+    pub fn parse(info: &str) -> Option<Self> {
+        match info.to_lowercase().as_str() {
+            "sh" | "bash" | "shell" | "zsh" => Some(Self::Shell),
+            "py" | "python" | "python3" => Some(Self::Python),
+            _ => None,
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Language::Shell => "main.sh",
+            Language::Python => "main.py",
+        }
+    }
+
+    fn interpreter(self) -> &'static str {
+        match self {
+            Language::Shell => "sh",
+            Language::Python => "python3",
+        }
+    }
+
+    fn image(self) -> &'static str {
+        match self {
+            Language::Shell => "alpine:3",
+            Language::Python => "python:3-alpine",
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Language::Shell => "shell",
+            Language::Python => "Python",
+        })
+    }
+}
+
+/// Where code is run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sandbox {
+    /// A container without network access and a read-only copy of the code.
+    Docker,
+    /// A subprocess in an empty temporary directory with a cleared
+    /// environment.
+    Process,
+}
+
+impl Sandbox {
+    pub async fn detect() -> Self {
+        let docker = process::Command::new("docker")
+            .arg("version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+
+        if docker.is_ok_and(|status| status.success()) {
+            Self::Docker
+        } else {
+            Self::Process
+        }
+    }
+}
+
+impl fmt::Display for Sandbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Sandbox::Docker => "an isolated Docker container",
+            Sandbox::Process => "a subprocess on your machine",
+        })
+    }
+}
+
+/// The captured result of running a code block.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: Option<i32>,
+    pub timed_out: bool,
+    pub duration: Duration,
+}
+
+impl Output {
+    pub fn is_success(&self) -> bool {
+        !self.timed_out && self.status == Some(0)
+    }
+}
+
+/// The maximum time code may run before it is killed.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs the given code in the given sandbox and captures its output.
+pub async fn run(language: Language, code: String, sandbox: Sandbox) -> Result<Output, Error> {
+    let directory =
+        std::env::temp_dir().join(format!("icebreaker-exec-{}", uuid::Uuid::new_v4().simple()));
+
+    fs::create_dir_all(&directory).await?;
+    fs::write(directory.join(language.file_name()), code).await?;
+
+    let mut command = match sandbox {
+        Sandbox::Docker => {
+            let mut command = process::Command::new("docker");
+
+            command.args(["run", "--rm", "--network", "none", "--memory", "512m"]);
+            command
+                .arg("-v")
+                .arg(format!("{}:/code:ro", directory.display()));
+            command.args(["-w", "/code", language.image()]);
+
+            // The container outlives the client when it is killed, so it
+            // enforces the timeout on its own too.
+            command.arg("timeout").arg(TIMEOUT.as_secs().to_string());
+            command.args([language.interpreter(), language.file_name()]);
+
+            command
+        }
+        Sandbox::Process => {
+            let mut command = process::Command::new(language.interpreter());
+
+            command
+                .arg(language.file_name())
+                .current_dir(&directory)
+                .env_clear()
+                .envs(std::env::var_os("PATH").map(|path| ("PATH", path)));
+
+            command
+        }
+    };
+
+    // Its own process group, so a timeout also kills whatever it spawned
+    #[cfg(unix)]
+    command.process_group(0);
+
+    let started_at = Instant::now();
+
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+
+    let timeout = match sandbox {
+        // Leaves room to pull the image the first time
+        Sandbox::Docker => TIMEOUT + Duration::from_secs(60),
+        Sandbox::Process => TIMEOUT,
+    };
+
+    let result = match child {
+        Ok(child) => {
+            let id = child.id();
+
+            match time::timeout(timeout, child.wait_with_output()).await {
+                Ok(Ok(output)) => Ok(Output {
+                    stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                    stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                    status: output.status.code(),
+                    timed_out: false,
+                    duration: started_at.elapsed(),
+                }),
+                Ok(Err(error)) => Err(error.into()),
+                Err(_elapsed) => {
+                    #[cfg(unix)]
+                    if let Some(id) = id {
+                        kill_group(id);
+                    }
+
+                    Ok(Output {
+                        stdout: String::new(),
+                        stderr: String::new(),
+                        status: None,
+                        timed_out: true,
+                        duration: started_at.elapsed(),
+                    })
+                }
+            }
+        }
+        Err(error) => Err(error.into()),
+    };
+
+    let _ = fs::remove_dir_all(&directory).await;
+
+    result
+}
+
+/// Kills every process in the group led by the child with the given id.
+#[cfg(unix)]
+fn kill_group(id: u32) {
+    // SAFETY: `killpg` only sends a signal
+    let _ = unsafe { libc::killpg(id as libc::pid_t, libc::SIGKILL) };
+}
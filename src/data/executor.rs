@@ -0,0 +1,372 @@
+//! The programs that run `llama-server`: a binary on the system, a build
+//! managed by the app, or a Docker container.
+use crate::data::assistant::Backend;
+use crate::data::audit;
+use crate::data::download;
+use crate::data::{self, Error};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::process;
+use tokio::task;
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The llama.cpp build the app is made for.
+pub const BUILD: &str = "b4600";
+
+/// The SHA256 digests of the release archives of [`BUILD`], by name.
+///
+/// They must be updated together with [`BUILD`], from the archives
+/// published in its release. Archives without a digest are never
+/// installed, and the managed build stays hidden on systems without one.
+const CHECKSUMS: &[(&str, &str)] = &[];
+
+/// The executor that launches assistants.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Executor {
+    pub kind: Kind,
+    /// The build of the llama.cpp images; the [`BUILD`] of the app if empty.
+    pub docker_tag: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// The first one available, in the order below.
+    #[default]
+    Automatic,
+    /// The `llama-server` binary on the `PATH`.
+    System,
+    /// The llama.cpp release downloaded by the app.
+    Managed,
+    Docker,
+}
+
+impl Kind {
+    pub const ALL: &'static [Self] = &[Self::Automatic, Self::System, Self::Managed, Self::Docker];
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Automatic => "Automatic",
+            Kind::System => "System llama-server",
+            Kind::Managed => "Managed build",
+            Kind::Docker => "Docker",
+        })
+    }
+}
+
+/// A program ready to launch `llama-server`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Program {
+    Binary(PathBuf),
+    Docker,
+}
+
+/// The executors found on the system, with their versions.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Availability {
+    pub system: Option<String>,
+    pub managed: Option<String>,
+    pub docker: Option<String>,
+}
+
+impl Availability {
+    pub async fn detect(backend: Backend) -> Self {
+        let managed = match managed_server(backend).await {
+            Some(server) => version(&server).await,
+            None => None,
+        };
+
+        Self {
+            system: version(Path::new("llama-server")).await,
+            managed,
+            docker: run(
+                Path::new("docker"),
+                &["version", "--format", "{{.Server.Version}}"],
+            )
+            .await
+            .map(|version| format!("Docker {version}")),
+        }
+    }
+}
+
+impl Executor {
+    /// Finds the program that launches `llama-server` for the given
+    /// backend, if available.
+    pub async fn locate(&self, backend: Backend) -> Result<Program, Error> {
+        let system = || async {
+            version(Path::new("llama-server"))
+                .await
+                .map(|_| Program::Binary(PathBuf::from("llama-server")))
+        };
+
+        let docker = || async {
+            run(Path::new("docker"), &["version"])
+                .await
+                .map(|_| Program::Docker)
+        };
+
+        let managed = || async { managed_server(backend).await.map(Program::Binary) };
+
+        match self.kind {
+            Kind::Automatic => {
+                if let Some(program) = system().await {
+                    Ok(program)
+                } else if let Some(program) = managed().await {
+                    Ok(program)
+                } else {
+                    docker().await.ok_or(Error::NoExecutorAvailable)
+                }
+            }
+            Kind::System => system()
+                .await
+                .ok_or(Error::ExecutorFailed("llama-server is not on the PATH")),
+            Kind::Managed => managed().await.ok_or(Error::ExecutorFailed(
+                "the managed build is not installed for this backend",
+            )),
+            Kind::Docker => docker()
+                .await
+                .ok_or(Error::ExecutorFailed("docker is not installed")),
+        }
+    }
+
+    /// Checks that the executor can launch `llama-server` for the given
+    /// backend, describing what would run.
+    pub async fn test(self, backend: Backend) -> Result<String, Error> {
+        match self.locate(backend).await? {
+            Program::Binary(server) => {
+                let version = version(&server)
+                    .await
+                    .ok_or(Error::ExecutorFailed("llama-server does not run"))?;
+
+                Ok(format!("{} works: {version}", server.display()))
+            }
+            Program::Docker => {
+                let image = self.image(backend);
+
+                if run(Path::new("docker"), &["image", "inspect", &image])
+                    .await
+                    .is_some()
+                {
+                    Ok(format!("Docker works: {image} is ready"))
+                } else {
+                    Ok(format!(
+                        "Docker works: {image} will be pulled on the next boot"
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Describes the llama.cpp server build that launches new assistants.
+    pub async fn describe(&self, backend: Backend) -> String {
+        match self.locate(backend).await {
+            Ok(Program::Binary(server)) => version(&server)
+                .await
+                .unwrap_or_else(|| "unknown version".to_owned()),
+            Ok(Program::Docker) => format!("Docker ({image})", image = self.image(backend)),
+            Err(_) => "none found".to_owned(),
+        }
+    }
+
+    /// The Docker image of the llama.cpp server for the given backend.
+    pub fn image(&self, backend: Backend) -> String {
+        let (repository, variant) = match backend {
+            // Containers cannot reach the GPU of a Mac
+            Backend::Cpu | Backend::Metal => ("ggerganov/llama.cpp", "server"),
+            Backend::Cuda => ("ggerganov/llama.cpp", "server-cuda"),
+            Backend::Rocm => ("hecrj/icebreaker", "server-rocm"),
+            Backend::Vulkan => ("ggerganov/llama.cpp", "server-vulkan"),
+            Backend::Sycl => ("ggerganov/llama.cpp", "server-intel"),
+        };
+
+        let tag = match self.docker_tag.trim() {
+            "" => BUILD,
+            tag => tag,
+        };
+
+        format!("ghcr.io/{repository}:{variant}-{tag}")
+    }
+}
+
+/// Whether the app can install the managed build for the given backend;
+/// that is, whether its release archive has a pinned digest.
+pub fn is_installable(backend: Backend) -> bool {
+    release(backend).is_some_and(|asset| checksum(&asset).is_some())
+}
+
+/// Downloads, verifies, and unpacks the llama.cpp release for the given
+/// backend, returning the path of its `llama-server`.
+pub async fn install(backend: Backend) -> Result<PathBuf, Error> {
+    let asset = release(backend).ok_or(Error::ExecutorFailed(
+        "there is no managed build for this system and backend",
+    ))?;
+
+    let expected = checksum(&asset).ok_or(Error::ExecutorFailed(
+        "the managed build for this system and backend has no known checksum",
+    ))?;
+
+    let url = format!("https://github.com/ggerganov/llama.cpp/releases/download/{BUILD}/{asset}");
+
+    let bytes = reqwest::get(&url)
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    audit::record(audit::Purpose::Download, &url, Some(bytes.len() as u64)).await;
+
+    let directory = managed_directory(&asset).await?;
+    fs::create_dir_all(&directory).await?;
+
+    let archive = directory.join(&asset);
+    fs::write(&archive, &bytes).await?;
+
+    if download::checksum(&archive).await? != expected {
+        fs::remove_file(&archive).await?;
+
+        return Err(Error::ChecksumMismatch(asset));
+    }
+
+    let unpacked = {
+        let directory = directory.clone();
+
+        task::spawn_blocking(move || {
+            zip::ZipArchive::new(io::Cursor::new(bytes))?.extract(directory)
+        })
+        .await?
+    };
+
+    let _ = fs::remove_file(&archive).await;
+    unpacked?;
+
+    managed_server(backend).await.ok_or(Error::ExecutorFailed(
+        "the managed build has no llama-server",
+    ))
+}
+
+/// The pinned SHA256 digest of the given release archive, if any.
+fn checksum(asset: &str) -> Option<&'static str> {
+    CHECKSUMS
+        .iter()
+        .find(|(name, _)| *name == asset)
+        .map(|(_, digest)| *digest)
+}
+
+/// The name of the release archive of llama.cpp for this system and the
+/// given backend, if there is one.
+fn release(backend: Backend) -> Option<String> {
+    let platform = match (std::env::consts::OS, std::env::consts::ARCH, backend) {
+        ("linux", "x86_64", Backend::Vulkan) => "ubuntu-vulkan-x64",
+        ("linux", "x86_64", Backend::Cpu) => "ubuntu-x64",
+        ("macos", "aarch64", _) => "macos-arm64",
+        ("macos", "x86_64", _) => "macos-x64",
+        ("windows", "x86_64", Backend::Cuda) => "win-cuda-cu12.4-x64",
+        ("windows", "x86_64", Backend::Vulkan) => "win-vulkan-x64",
+        ("windows", "x86_64", Backend::Sycl) => "win-sycl-x64",
+        ("windows", "x86_64", Backend::Cpu) => "win-avx2-x64",
+        _ => return None,
+    };
+
+    Some(format!("llama-{BUILD}-bin-{platform}.zip"))
+}
+
+/// The directory of the managed build unpacked from the given release
+/// archive; every backend gets its own.
+async fn managed_directory(asset: &str) -> Result<PathBuf, Error> {
+    let name = asset.strip_suffix(".zip").unwrap_or(asset);
+
+    Ok(data::data_dir()
+        .await?
+        .join("llama.cpp")
+        .join(BUILD)
+        .join(name))
+}
+
+/// The `llama-server` of the managed build for the given backend, if
+/// installed.
+async fn managed_server(backend: Backend) -> Option<PathBuf> {
+    let directory = managed_directory(&release(backend)?).await.ok()?;
+    let executable = format!("llama-server{}", std::env::consts::EXE_SUFFIX);
+
+    // Releases keep their binaries in different places across platforms
+    for candidate in [
+        directory.join("build").join("bin").join(&executable),
+        directory.join(&executable),
+    ] {
+        if fs::try_exists(&candidate).await.unwrap_or(false) {
+            return Some(candidate);
+        }
+    }
+
+    None
+}
+
+/// The version line of the given `llama-server`, if it runs.
+async fn version(server: &Path) -> Option<String> {
+    let output = process::Command::new(server)
+        .arg("--version")
+        .output()
+        .await
+        .ok()?;
+
+    // Older builds print their version to stderr
+    let output = [output.stdout, output.stderr].concat();
+    let output = String::from_utf8_lossy(&output);
+
+    Some(
+        output
+            .lines()
+            .find(|line| line.starts_with("version"))
+            .or_else(|| output.lines().find(|line| !line.trim().is_empty()))
+            .unwrap_or("unknown version")
+            .trim()
+            .to_owned(),
+    )
+}
+
+async fn run(program: &Path, args: &[&str]) -> Option<String> {
+    let output = process::Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_follow_the_docker_tag() {
+        let executor = Executor::default();
+
+        assert_eq!(
+            executor.image(Backend::Cuda),
+            "ghcr.io/ggerganov/llama.cpp:server-cuda-b4600"
+        );
+        assert_eq!(
+            executor.image(Backend::Metal),
+            "ghcr.io/ggerganov/llama.cpp:server-b4600"
+        );
+
+        let executor = Executor {
+            docker_tag: "b5000".to_owned(),
+            ..Executor::default()
+        };
+
+        assert_eq!(
+            executor.image(Backend::Rocm),
+            "ghcr.io/hecrj/icebreaker:server-rocm-b5000"
+        );
+    }
+}
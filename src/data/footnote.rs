@@ -0,0 +1,186 @@
+//! Footnotes in Markdown, like `[^1]` and `[^1]: A note`.
+use std::ops::Range;
+
+/// The text of a footnote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition {
+    pub label: String,
+    pub text: String,
+}
+
+impl Definition {
+    /// The first web address in the footnote, if any.
+    pub fn url(&self) -> Option<&str> {
+        self.text
+            .split(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '(' | ')'))
+            .find(|word| word.starts_with("https://") || word.starts_with("http://"))
+            .map(|url| url.trim_end_matches(['.', ',', ';']))
+    }
+}
+
+/// Splits the footnote definitions out of some Markdown.
+///
+/// Indented lines after a definition continue it.
+pub fn extract(markdown: &str) -> (String, Vec<Definition>) {
+    let mut body = String::with_capacity(markdown.len());
+    let mut definitions: Vec<Definition> = Vec::new();
+    let mut continues = false;
+
+    for line in markdown.split_inclusive('\n') {
+        if let Some((label, text)) = definition(line) {
+            definitions.push(Definition {
+                label: label.to_owned(),
+                text: text.trim().to_owned(),
+            });
+
+            continues = true;
+        } else if continues && line.starts_with([' ', '\t']) && !line.trim().is_empty() {
+            if let Some(definition) = definitions.last_mut() {
+                definition.text.push(' ');
+                definition.text.push_str(line.trim());
+            }
+        } else {
+            continues = false;
+            body.push_str(line);
+        }
+    }
+
+    (body, definitions)
+}
+
+/// Finds the footnote references in some Markdown, with their byte ranges.
+pub fn references(markdown: &str) -> Vec<(Range<usize>, &str)> {
+    let mut references = Vec::new();
+    let mut offset = 0;
+
+    while let Some(start) = markdown[offset..].find("[^").map(|start| offset + start) {
+        let label_start = start + 2;
+
+        match label(&markdown[label_start..]) {
+            Some(label) => {
+                let end = label_start + label.len() + 1;
+
+                if !markdown[end..].starts_with(':') {
+                    references.push((start..end, label));
+                }
+
+                offset = end;
+            }
+            None => {
+                offset = label_start;
+            }
+        }
+    }
+
+    references
+}
+
+/// Returns the start of a footnote reference left unfinished at the end of
+/// some streamed Markdown, if any.
+pub fn unfinished(markdown: &str) -> Option<usize> {
+    let start = markdown.rfind("[^")?;
+    let rest = &markdown[start + 2..];
+
+    rest.chars()
+        .all(|c| !c.is_whitespace() && c != ']' && c != '[')
+        .then_some(start)
+}
+
+/// Prefixes every footnote label, so the footnotes of different messages do
+/// not collide once joined in the same document.
+pub fn namespace(markdown: &str, prefix: &str) -> String {
+    let (body, definitions) = extract(markdown);
+    let mut namespaced = String::with_capacity(markdown.len());
+    let mut offset = 0;
+
+    for (range, label) in references(&body) {
+        namespaced.push_str(&body[offset..range.start]);
+        namespaced.push_str(&format!("[^{prefix}-{label}]"));
+        offset = range.end;
+    }
+
+    namespaced.push_str(&body[offset..]);
+
+    if !definitions.is_empty() {
+        namespaced = namespaced.trim_end().to_owned();
+        namespaced.push('\n');
+
+        for definition in definitions {
+            namespaced.push_str(&format!(
+                "\n[^{prefix}-{label}]: {text}",
+                label = definition.label,
+                text = definition.text
+            ));
+        }
+
+        namespaced.push('\n');
+    }
+
+    namespaced
+}
+
+fn definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("[^")?;
+    let label = label(rest)?;
+
+    rest[label.len() + 1..]
+        .strip_prefix(':')
+        .map(|text| (label, text))
+}
+
+fn label(text: &str) -> Option<&str> {
+    let end = text.find(']')?;
+    let label = &text[..end];
+
+    (!label.is_empty() && !label.contains(|c: char| c.is_whitespace() || c == '[')).then_some(label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn definitions_are_extracted() {
+        let (body, definitions) = extract(
+            "Rust is fast[^1] and safe[^safe].\n\n\
+            [^1]: See https://www.rust-lang.org.\n\
+            [^safe]: Memory safety\n    without a garbage collector.\n",
+        );
+
+        assert_eq!(body, "Rust is fast[^1] and safe[^safe].\n\n");
+        assert_eq!(definitions.len(), 2);
+        assert_eq!(definitions[0].url(), Some("https://www.rust-lang.org"));
+        assert_eq!(
+            definitions[1].text,
+            "Memory safety without a garbage collector."
+        );
+    }
+
+    #[test]
+    fn references_are_found() {
+        let text = "A[^1], B[^note], not [^ this], nor [^].";
+
+        let labels: Vec<_> = references(text)
+            .into_iter()
+            .map(|(range, label)| (&text[range], label))
+            .collect();
+
+        assert_eq!(labels, [("[^1]", "1"), ("[^note]", "note")]);
+    }
+
+    #[test]
+    fn unfinished_references_are_detected() {
+        assert_eq!(unfinished("Streaming[^1"), Some(9));
+        assert_eq!(unfinished("Streaming[^"), Some(9));
+        assert_eq!(unfinished("Done[^1]"), None);
+        assert_eq!(unfinished("Done[^1] and more"), None);
+    }
+
+    #[test]
+    fn labels_are_namespaced() {
+        assert_eq!(
+            namespace("Fast[^1].\n\n[^1]: Benchmarks.\n", "m2"),
+            "Fast[^m2-1].\n\n[^m2-1]: Benchmarks.\n"
+        );
+    }
+}
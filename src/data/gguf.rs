@@ -0,0 +1,315 @@
+//! The metadata in the header of GGUF files.
+use crate::data::Error;
+
+use tokio::task;
+
+use std::collections::BTreeMap;
+use std::io::{self, BufReader, Read};
+use std::path::PathBuf;
+
+/// The key-value metadata of a GGUF file, along with the amount of
+/// parameters of its tensors.
+///
+/// Arrays, like the vocabulary of the tokenizer, are skipped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    values: BTreeMap<String, Value>,
+    parameters: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Integer(i128),
+    Float(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl Metadata {
+    /// Reads the metadata in the header of the GGUF file at the given path.
+    pub async fn read(path: PathBuf) -> Result<Self, Error> {
+        task::spawn_blocking(move || {
+            let file = std::fs::File::open(path)?;
+
+            Self::parse(BufReader::new(file))
+        })
+        .await?
+    }
+
+    fn parse(mut reader: impl Read) -> Result<Self, Error> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != b"GGUF" {
+            return Err(Error::InvalidModelFile("it is not a GGUF file"));
+        }
+
+        if read_u32(&mut reader)? < 2 {
+            return Err(Error::InvalidModelFile("its GGUF version is not supported"));
+        }
+
+        let tensors = read_u64(&mut reader)?;
+        let entries = read_u64(&mut reader)?;
+
+        let mut values = BTreeMap::new();
+
+        for _ in 0..entries {
+            let key = read_string(&mut reader)?;
+            let kind = read_u32(&mut reader)?;
+
+            if let Some(value) = read_value(&mut reader, kind)? {
+                let _ = values.insert(key, value);
+            }
+        }
+
+        // The tensor infos follow the metadata; their shapes add up to the
+        // amount of parameters
+        let mut parameters: u64 = 0;
+
+        for _ in 0..tensors {
+            let _name = read_string(&mut reader)?;
+            let dimensions = read_u32(&mut reader)?;
+            let mut elements: u64 = 1;
+
+            for _ in 0..dimensions {
+                elements = elements.saturating_mul(read_u64(&mut reader)?);
+            }
+
+            let _kind = read_u32(&mut reader)?;
+            let _offset = read_u64(&mut reader)?;
+
+            parameters = parameters.saturating_add(elements);
+        }
+
+        Ok(Self { values, parameters })
+    }
+
+    /// The architecture of the model, like `llama` or `qwen2`.
+    pub fn architecture(&self) -> Option<&str> {
+        self.string("general.architecture")
+    }
+
+    /// The amount of parameters of the model, if it has any tensors.
+    pub fn parameters(&self) -> Option<u64> {
+        Some(self.parameters).filter(|parameters| *parameters > 0)
+    }
+
+    /// The quantization of most of the tensors of the model.
+    pub fn quantization(&self) -> Option<&'static str> {
+        // The `llama_ftype` values of llama.cpp
+        Some(match self.integer("general.file_type")? {
+            0 => "F32",
+            1 => "F16",
+            2 => "Q4_0",
+            3 => "Q4_1",
+            7 => "Q8_0",
+            8 => "Q5_0",
+            9 => "Q5_1",
+            10 => "Q2_K",
+            11 => "Q3_K_S",
+            12 => "Q3_K_M",
+            13 => "Q3_K_L",
+            14 => "Q4_K_S",
+            15 => "Q4_K_M",
+            16 => "Q5_K_S",
+            17 => "Q5_K_M",
+            18 => "Q6_K",
+            19 => "IQ2_XXS",
+            20 => "IQ2_XS",
+            21 => "Q2_K_S",
+            22 => "IQ3_XS",
+            23 => "IQ3_XXS",
+            24 => "IQ1_S",
+            25 => "IQ4_NL",
+            26 => "IQ3_S",
+            27 => "IQ3_M",
+            28 => "IQ2_S",
+            29 => "IQ2_M",
+            30 => "IQ4_XS",
+            31 => "IQ1_M",
+            32 => "BF16",
+            _ => return None,
+        })
+    }
+
+    /// The context size, in tokens, the model was trained with.
+    pub fn context_length(&self) -> Option<u64> {
+        let architecture = self.architecture()?;
+
+        u64::try_from(self.integer(&format!("{architecture}.context_length"))?).ok()
+    }
+
+    /// The known details of the model, like `qwen2 · 0.5B parameters ·
+    /// Q4_K_M · 32768 tokens of context`.
+    pub fn summary(&self) -> String {
+        let parameters = self.parameters().map(|parameters| {
+            if parameters >= 1_000_000_000 {
+                format!("{:.1}B parameters", parameters as f64 / 1e9)
+            } else {
+                format!("{}M parameters", parameters / 1_000_000)
+            }
+        });
+
+        let context_length = self
+            .context_length()
+            .map(|context_length| format!("{context_length} tokens of context"));
+
+        [
+            self.architecture().map(str::to_owned),
+            parameters,
+            self.quantization().map(str::to_owned),
+            context_length,
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" · ")
+    }
+
+    /// The string value of the given key, if any.
+    pub fn string(&self, key: &str) -> Option<&str> {
+        match self.values.get(key)? {
+            Value::String(value) => Some(value),
+            Value::Integer(_) | Value::Float(_) | Value::Bool(_) => None,
+        }
+    }
+
+    fn integer(&self, key: &str) -> Option<i128> {
+        match self.values.get(key)? {
+            Value::Integer(value) => Some(*value),
+            Value::Float(_) | Value::Bool(_) | Value::String(_) => None,
+        }
+    }
+}
+
+fn read_value(reader: &mut impl Read, kind: u32) -> Result<Option<Value>, Error> {
+    Ok(Some(match kind {
+        0 => Value::Integer(read::<1>(reader)?[0].into()),
+        1 => Value::Integer(i8::from_le_bytes(read(reader)?).into()),
+        2 => Value::Integer(u16::from_le_bytes(read(reader)?).into()),
+        3 => Value::Integer(i16::from_le_bytes(read(reader)?).into()),
+        4 => Value::Integer(read_u32(reader)?.into()),
+        5 => Value::Integer(i32::from_le_bytes(read(reader)?).into()),
+        6 => Value::Float(f32::from_le_bytes(read(reader)?).into()),
+        7 => Value::Bool(read::<1>(reader)?[0] != 0),
+        8 => Value::String(read_string(reader)?),
+        9 => {
+            let kind = read_u32(reader)?;
+            let length = read_u64(reader)?;
+
+            for _ in 0..length {
+                let _ = read_value(reader, kind)?;
+            }
+
+            return Ok(None);
+        }
+        10 => Value::Integer(read_u64(reader)?.into()),
+        11 => Value::Integer(i64::from_le_bytes(read(reader)?).into()),
+        12 => Value::Float(f64::from_le_bytes(read(reader)?)),
+        _ => return Err(Error::InvalidModelFile("its metadata is corrupted")),
+    }))
+}
+
+fn read<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], io::Error> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, io::Error> {
+    Ok(u32::from_le_bytes(read(reader)?))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, io::Error> {
+    Ok(u64::from_le_bytes(read(reader)?))
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, Error> {
+    // Keys and names are short; anything longer is skipped
+    const MAX_LENGTH: u64 = 1 << 20;
+
+    let length = read_u64(reader)?;
+
+    if length > MAX_LENGTH {
+        let _ = io::copy(&mut reader.take(length), &mut io::sink())?;
+
+        return Ok(String::new());
+    }
+
+    let mut bytes = vec![0; length as usize];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_is_parsed() {
+        fn string(bytes: &mut Vec<u8>, value: &str) {
+            bytes.extend((value.len() as u64).to_le_bytes());
+            bytes.extend(value.as_bytes());
+        }
+
+        let mut bytes = b"GGUF".to_vec();
+        bytes.extend(3u32.to_le_bytes());
+        bytes.extend(2u64.to_le_bytes());
+        bytes.extend(5u64.to_le_bytes());
+
+        string(&mut bytes, "general.architecture");
+        bytes.extend(8u32.to_le_bytes());
+        string(&mut bytes, "qwen2");
+
+        string(&mut bytes, "general.file_type");
+        bytes.extend(4u32.to_le_bytes());
+        bytes.extend(15u32.to_le_bytes());
+
+        string(&mut bytes, "general.name");
+        bytes.extend(8u32.to_le_bytes());
+        string(&mut bytes, "Qwen2.5 0.5B Instruct");
+
+        string(&mut bytes, "tokenizer.ggml.tokens");
+        bytes.extend(9u32.to_le_bytes());
+        bytes.extend(8u32.to_le_bytes());
+        bytes.extend(2u64.to_le_bytes());
+        string(&mut bytes, "<s>");
+        string(&mut bytes, "</s>");
+
+        string(&mut bytes, "qwen2.context_length");
+        bytes.extend(4u32.to_le_bytes());
+        bytes.extend(32768u32.to_le_bytes());
+
+        for (name, shape) in [
+            ("token_embd.weight", [896, 151936]),
+            ("output_norm.weight", [896, 1]),
+        ] {
+            string(&mut bytes, name);
+            bytes.extend(2u32.to_le_bytes());
+            bytes.extend(shape.into_iter().flat_map(u64::to_le_bytes));
+            bytes.extend(12u32.to_le_bytes());
+            bytes.extend(0u64.to_le_bytes());
+        }
+
+        let metadata = Metadata::parse(bytes.as_slice()).expect("metadata should parse");
+
+        assert_eq!(
+            metadata.string("general.name"),
+            Some("Qwen2.5 0.5B Instruct")
+        );
+        assert_eq!(metadata.string("qwen2.context_length"), None);
+        assert_eq!(metadata.string("tokenizer.ggml.tokens"), None);
+        assert_eq!(metadata.architecture(), Some("qwen2"));
+        assert_eq!(metadata.quantization(), Some("Q4_K_M"));
+        assert_eq!(metadata.context_length(), Some(32768));
+        assert_eq!(metadata.parameters(), Some(896 * 151936 + 896));
+        assert_eq!(
+            metadata.summary(),
+            "qwen2 · 136M parameters · Q4_K_M · 32768 tokens of context"
+        );
+
+        assert!(Metadata::parse(b"GGML".as_slice()).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+/// Instructions always appended to the system prompt, kept apart from it
+/// so they hold no matter how the prompt is changed.
+///
+/// Guardrails are inherited: the ones of a chat are layered on top of the
+/// global ones in the settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Guardrails {
+    /// Topics the assistant must refuse to discuss, separated by commas.
+    pub refused_topics: String,
+    /// Constraints on the tone of the replies.
+    pub tone: String,
+}
+
+impl Guardrails {
+    pub fn is_empty(&self) -> bool {
+        self.refused_topics().next().is_none() && self.tone.trim().is_empty()
+    }
+
+    /// Layers these guardrails on top of the given ones.
+    ///
+    /// Refused topics add up, while a tone replaces the inherited one.
+    pub fn inherit(&self, parent: &Self) -> Self {
+        let mut topics: Vec<&str> = parent.refused_topics().collect();
+
+        for topic in self.refused_topics() {
+            if !topics
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(topic))
+            {
+                topics.push(topic);
+            }
+        }
+
+        Self {
+            refused_topics: topics.join(", "),
+            tone: if self.tone.trim().is_empty() {
+                parent.tone.trim().to_owned()
+            } else {
+                self.tone.trim().to_owned()
+            },
+        }
+    }
+
+    /// The instructions given to the model, if any.
+    pub fn instructions(&self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut instructions = String::from("Always follow these rules:\n");
+
+        for topic in self.refused_topics() {
+            instructions.push_str(&format!(
+                "- Politely refuse to discuss anything about {topic}.\n"
+            ));
+        }
+
+        let tone = self.tone.trim();
+
+        if !tone.is_empty() {
+            instructions.push_str(&format!("- Keep the following tone: {tone}\n"));
+        }
+
+        Some(instructions)
+    }
+
+    fn refused_topics(&self) -> impl Iterator<Item = &str> {
+        self.refused_topics
+            .split(',')
+            .map(str::trim)
+            .filter(|topic| !topic.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guardrails_are_inherited() {
+        let global = Guardrails {
+            refused_topics: "politics, medical advice".to_owned(),
+            tone: "Friendly".to_owned(),
+        };
+
+        let chat = Guardrails {
+            refused_topics: " Politics,, gambling ".to_owned(),
+            tone: String::new(),
+        };
+
+        let effective = chat.inherit(&global);
+
+        assert_eq!(
+            effective.refused_topics,
+            "politics, medical advice, gambling"
+        );
+        assert_eq!(effective.tone, "Friendly");
+
+        let formal = Guardrails {
+            tone: "Formal".to_owned(),
+            ..Guardrails::default()
+        };
+
+        assert_eq!(formal.inherit(&global).tone, "Formal");
+        assert!(Guardrails::default().instructions().is_none());
+        assert_eq!(
+            effective.instructions().as_deref(),
+            Some(
+                "Always follow these rules:\n\
+                - Politely refuse to discuss anything about politics.\n\
+                - Politely refuse to discuss anything about medical advice.\n\
+                - Politely refuse to discuss anything about gambling.\n\
+                - Keep the following tone: Friendly\n"
+            )
+        );
+    }
+}
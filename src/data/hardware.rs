@@ -0,0 +1,270 @@
+//! The memory of the system, and which models fit in it.
+use crate::data::assistant::File;
+use crate::data::convert;
+
+use tokio::fs;
+use tokio::process;
+
+use std::fmt;
+
+/// The memory available to run models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Memory {
+    /// The total RAM of the system, in bytes.
+    pub ram: u64,
+    /// The total memory of the graphics card, in bytes, if known.
+    pub vram: Option<u64>,
+}
+
+impl Memory {
+    /// Where a model that needs the given amount of memory can run.
+    pub fn fit(self, required: u64) -> Fit {
+        // Part of the memory is always busy with the system and other apps
+        if self.vram.is_some_and(|vram| required <= vram / 10 * 9) {
+            Fit::Vram
+        } else if required <= self.ram / 4 * 3 {
+            Fit::Ram
+        } else {
+            Fit::TooLarge
+        }
+    }
+
+    /// Whether the given downloaded files can all run at once.
+    pub fn fits_together(self, files: &[&File]) -> bool {
+        let required: Option<u64> = files
+            .iter()
+            .map(|file| {
+                let size = std::fs::metadata(file.path()).ok()?.len();
+
+                estimate(Some(size), None, None)
+            })
+            .sum();
+
+        required.is_some_and(|required| self.fit(required) != Fit::TooLarge)
+    }
+}
+
+/// Where a model fits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Fit {
+    /// Entirely in the graphics card, the fastest.
+    Vram,
+    Ram,
+    TooLarge,
+}
+
+impl fmt::Display for Fit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Fit::Vram => "fits in VRAM",
+            Fit::Ram => "fits in RAM",
+            Fit::TooLarge => "too large",
+        })
+    }
+}
+
+/// The memory in use by the app and its assistant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Usage {
+    /// The resident memory of the app itself.
+    pub app: Option<u64>,
+    /// The resident memory of the `llama-server` process.
+    pub server: Option<u64>,
+    /// The VRAM used by the `llama-server` process or, if it cannot be told
+    /// apart, by the whole graphics card.
+    pub video: Option<u64>,
+}
+
+impl Usage {
+    /// Measures the memory in use, given the id of the `llama-server`
+    /// process, if known.
+    pub async fn measure(server: Option<u32>) -> Self {
+        let app = resident_memory("self").await;
+        let server_memory = match server {
+            Some(pid) => resident_memory(&pid.to_string()).await,
+            None => None,
+        };
+
+        Self {
+            app,
+            server: server_memory,
+            video: video_memory_used(server).await,
+        }
+    }
+
+    /// The RAM in use by both the app and its assistant, if known.
+    pub fn ram(self) -> Option<u64> {
+        match (self.app, self.server) {
+            (None, None) => None,
+            (app, server) => Some(app.unwrap_or_default() + server.unwrap_or_default()),
+        }
+    }
+}
+
+/// Estimates the memory needed to run a model with the default context,
+/// given the size of its file or, if unknown, its amount of parameters and
+/// quantization.
+pub fn estimate(
+    size: Option<u64>,
+    parameters: Option<u64>,
+    quantization: Option<&str>,
+) -> Option<u64> {
+    // The context and the compute buffers of llama.cpp
+    const OVERHEAD: u64 = 500_000_000;
+
+    let weights = match size {
+        Some(size) => size,
+        None => {
+            let bits_per_weight = convert::bits_per_weight(quantization?)?;
+
+            (parameters? as f64 * bits_per_weight / 8.0) as u64
+        }
+    };
+
+    Some(weights + weights / 10 + OVERHEAD)
+}
+
+/// Detects the total memory of the graphics card, using the tools of the
+/// NVIDIA and AMD drivers.
+pub async fn video_memory() -> Option<u64> {
+    const MEBIBYTE: u64 = 1024 * 1024;
+
+    if let Some(output) = run(
+        "nvidia-smi",
+        &["--query-gpu=memory.total", "--format=csv,noheader,nounits"],
+    )
+    .await
+    {
+        let mebibytes: u64 = output.lines().next()?.trim().parse().ok()?;
+
+        return Some(mebibytes * MEBIBYTE);
+    }
+
+    let output = run("rocm-smi", &["--showmeminfo", "vram", "--csv"]).await?;
+
+    rocm_column(&output, "Total Memory")
+}
+
+/// Reads the resident memory of the given process from `/proc`; only
+/// available on Linux.
+async fn resident_memory(process: &str) -> Option<u64> {
+    const KIBIBYTE: u64 = 1024;
+
+    let status = fs::read_to_string(format!("/proc/{process}/status"))
+        .await
+        .ok()?;
+
+    let kibibytes: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(kibibytes * KIBIBYTE)
+}
+
+/// The VRAM used by the given process, with `nvidia-smi`, or by the whole
+/// card otherwise.
+async fn video_memory_used(process: Option<u32>) -> Option<u64> {
+    const MEBIBYTE: u64 = 1024 * 1024;
+
+    if let Some(pid) = process {
+        if let Some(output) = run(
+            "nvidia-smi",
+            &[
+                "--query-compute-apps=pid,used_memory",
+                "--format=csv,noheader,nounits",
+            ],
+        )
+        .await
+        {
+            return output.lines().find_map(|line| {
+                let (candidate, used) = line.split_once(',')?;
+
+                (candidate.trim().parse() == Ok(pid))
+                    .then(|| used.trim().parse::<u64>().ok())
+                    .flatten()
+                    .map(|mebibytes| mebibytes * MEBIBYTE)
+            });
+        }
+    }
+
+    if let Some(output) = run(
+        "nvidia-smi",
+        &["--query-gpu=memory.used", "--format=csv,noheader,nounits"],
+    )
+    .await
+    {
+        let mebibytes: u64 = output.lines().next()?.trim().parse().ok()?;
+
+        return Some(mebibytes * MEBIBYTE);
+    }
+
+    let output = run("rocm-smi", &["--showmeminfo", "vram", "--csv"]).await?;
+
+    rocm_column(&output, "Used Memory")
+}
+
+async fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = process::Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Reads a column of the first card in the CSV output of `rocm-smi`.
+fn rocm_column(output: &str, name: &str) -> Option<u64> {
+    let mut lines = output.lines().filter(|line| line.contains(','));
+
+    let header = lines.next()?;
+    let column = header.split(',').position(|column| column.contains(name))?;
+
+    lines.next()?.split(',').nth(column)?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1_000_000_000;
+
+    #[test]
+    fn memory_is_estimated_from_parameters() {
+        assert_eq!(estimate(Some(4 * GB), None, None), Some(4_900_000_000));
+        assert_eq!(
+            estimate(None, Some(8 * GB), Some("Q8_0")),
+            Some(9_850_000_000)
+        );
+        assert_eq!(estimate(None, Some(8 * GB), None), None);
+    }
+
+    #[test]
+    fn models_fit_where_there_is_room() {
+        let memory = Memory {
+            ram: 16 * GB,
+            vram: Some(8 * GB),
+        };
+
+        assert_eq!(memory.fit(5 * GB), Fit::Vram);
+        assert_eq!(memory.fit(10 * GB), Fit::Ram);
+        assert_eq!(memory.fit(13 * GB), Fit::TooLarge);
+    }
+
+    #[test]
+    fn rocm_output_is_parsed() {
+        let output = "\
+            ============================ ROCm System Management Interface ============================\n\
+            device,VRAM Total Memory (B),VRAM Total Used Memory (B)\n\
+            card0,17163091968,1040384000\n";
+
+        assert_eq!(rocm_column(output, "Total Memory"), Some(17_163_091_968));
+        assert_eq!(rocm_column(output, "Used Memory"), Some(1_040_384_000));
+    }
+}
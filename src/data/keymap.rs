@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+
+use iced::keyboard::{self, key};
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    NewChat,
+    SearchChats,
+    Find,
+    OpenSettings,
+    Submit,
+    CopyLastReply,
+    Quit,
+}
+
+impl Command {
+    pub const ALL: &'static [Self] = &[
+        Self::NewChat,
+        Self::SearchChats,
+        Self::Find,
+        Self::OpenSettings,
+        Self::Submit,
+        Self::CopyLastReply,
+        Self::Quit,
+    ];
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Command::NewChat => "New chat",
+            Command::SearchChats => "Search chats",
+            Command::Find => "Find in chat",
+            Command::OpenSettings => "Open settings",
+            Command::Submit => "Send message",
+            Command::CopyLastReply => "Copy last reply",
+            Command::Quit => "Quit",
+        })
+    }
+}
+
+/// The shortcut of every command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keymap {
+    pub new_chat: Shortcut,
+    pub search_chats: Shortcut,
+    pub find: Shortcut,
+    pub open_settings: Shortcut,
+    pub submit: Shortcut,
+    pub copy_last_reply: Shortcut,
+    pub quit: Shortcut,
+}
+
+impl Keymap {
+    pub fn shortcut(&self, command: Command) -> &Shortcut {
+        match command {
+            Command::NewChat => &self.new_chat,
+            Command::SearchChats => &self.search_chats,
+            Command::Find => &self.find,
+            Command::OpenSettings => &self.open_settings,
+            Command::Submit => &self.submit,
+            Command::CopyLastReply => &self.copy_last_reply,
+            Command::Quit => &self.quit,
+        }
+    }
+
+    pub fn set(&mut self, command: Command, shortcut: Shortcut) {
+        *match command {
+            Command::NewChat => &mut self.new_chat,
+            Command::SearchChats => &mut self.search_chats,
+            Command::Find => &mut self.find,
+            Command::OpenSettings => &mut self.open_settings,
+            Command::Submit => &mut self.submit,
+            Command::CopyLastReply => &mut self.copy_last_reply,
+            Command::Quit => &mut self.quit,
+        } = shortcut;
+    }
+
+    /// Finds the command bound to the given key press, if any.
+    pub fn command(&self, key: &keyboard::Key, modifiers: keyboard::Modifiers) -> Option<Command> {
+        Command::ALL
+            .iter()
+            .copied()
+            .find(|command| self.shortcut(*command).matches(key, modifiers))
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let shortcut = |shortcut: &str| shortcut.parse().expect("valid default shortcut");
+
+        Self {
+            new_chat: shortcut("Ctrl+N"),
+            search_chats: shortcut("Ctrl+Shift+F"),
+            find: shortcut("Ctrl+F"),
+            open_settings: shortcut("Ctrl+,"),
+            submit: shortcut("Ctrl+Enter"),
+            copy_last_reply: shortcut("Ctrl+Shift+C"),
+            quit: shortcut("Ctrl+Q"),
+        }
+    }
+}
+
+/// A key combined with at least one of Ctrl (Cmd on macOS) or Alt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Shortcut {
+    key: Key,
+    command: bool,
+    shift: bool,
+    alt: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Character(char),
+    Named(key::Named),
+}
+
+impl Shortcut {
+    pub fn matches(&self, key: &keyboard::Key, modifiers: keyboard::Modifiers) -> bool {
+        if modifiers.command() != self.command
+            || modifiers.shift() != self.shift
+            || modifiers.alt() != self.alt
+        {
+            return false;
+        }
+
+        match (&self.key, key.as_ref()) {
+            (Key::Character(expected), keyboard::Key::Character(actual)) => {
+                actual.to_lowercase().chars().eq([*expected])
+            }
+            (Key::Named(expected), keyboard::Key::Named(actual)) => *expected == actual,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Shortcut {
+    type Err = &'static str;
+
+    fn from_str(shortcut: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = shortcut.split('+').map(str::trim).collect();
+        let key = parts
+            .pop()
+            .filter(|key| !key.is_empty())
+            .ok_or("missing key")?;
+
+        let mut command = false;
+        let mut shift = false;
+        let mut alt = false;
+
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "cmd" | "command" => command = true,
+                "shift" => shift = true,
+                "alt" | "option" => alt = true,
+                _ => return Err("unknown modifier"),
+            }
+        }
+
+        if !command && !alt {
+            return Err("shortcuts need Ctrl or Alt");
+        }
+
+        let key = match key.to_lowercase().as_str() {
+            "enter" => Key::Named(key::Named::Enter),
+            "tab" => Key::Named(key::Named::Tab),
+            "space" => Key::Named(key::Named::Space),
+            "backspace" => Key::Named(key::Named::Backspace),
+            "delete" => Key::Named(key::Named::Delete),
+            key => {
+                let mut characters = key.chars();
+
+                match (characters.next(), characters.next()) {
+                    (Some(character), None) => Key::Character(character),
+                    _ => return Err("unknown key"),
+                }
+            }
+        };
+
+        Ok(Self {
+            key,
+            command,
+            shift,
+            alt,
+        })
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.command {
+            f.write_str("Ctrl+")?;
+        }
+
+        if self.shift {
+            f.write_str("Shift+")?;
+        }
+
+        if self.alt {
+            f.write_str("Alt+")?;
+        }
+
+        match &self.key {
+            Key::Character(character) => write!(f, "{}", character.to_uppercase()),
+            Key::Named(named) => write!(f, "{named:?}"),
+        }
+    }
+}
+
+impl TryFrom<String> for Shortcut {
+    type Error = &'static str;
+
+    fn try_from(shortcut: String) -> Result<Self, Self::Error> {
+        shortcut.parse()
+    }
+}
+
+impl From<Shortcut> for String {
+    fn from(shortcut: Shortcut) -> Self {
+        shortcut.to_string()
+    }
+}
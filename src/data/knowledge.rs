@@ -0,0 +1,403 @@
+use crate::data::{self, Error};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::fs;
+use tokio::task;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The local folders used to ground replies, and the server that embeds them.
+///
+/// The embedding server is a `llama-server` running an embedding model with
+/// `--embeddings`, like:
+///
+/// ```sh
+/// llama-server -m nomic-embed-text-v1.5.Q8_0.gguf --embeddings --port 8081
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Knowledge {
+    pub folders: Vec<PathBuf>,
+    pub embedding_server: String,
+}
+
+impl Default for Knowledge {
+    fn default() -> Self {
+        Self {
+            folders: Vec::new(),
+            embedding_server: "http://localhost:8081".to_owned(),
+        }
+    }
+}
+
+/// The embedded chunks of every document in the knowledge folders.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    documents: Vec<Document>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Document {
+    path: PathBuf,
+    modified: u64,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Chunk {
+    text: String,
+    embedding: Vec<f32>,
+}
+
+/// The size of an indexed [`Index`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub documents: usize,
+    pub chunks: usize,
+}
+
+/// A chunk of a document relevant to a query.
+#[derive(Debug, Clone)]
+pub struct Passage {
+    pub source: PathBuf,
+    pub text: String,
+}
+
+impl Index {
+    async fn path() -> Result<PathBuf, io::Error> {
+        Ok(data::data_dir().await?.join("knowledge.json"))
+    }
+
+    pub async fn fetch() -> Result<Self, Error> {
+        let Ok(bytes) = fs::read(Self::path().await?).await else {
+            return Ok(Self::default());
+        };
+
+        Ok(task::spawn_blocking(move || serde_json::from_slice(&bytes)).await??)
+    }
+
+    /// Indexes the documents in the knowledge folders.
+    ///
+    /// Documents that did not change since the last build are not embedded
+    /// again.
+    pub async fn build(knowledge: Knowledge) -> Result<Summary, Error> {
+        let previous = Self::fetch().await?;
+        let mut documents = Vec::new();
+
+        for path in files(&knowledge.folders).await {
+            let modified = fs::metadata(&path)
+                .await?
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+
+            if let Some(document) = previous
+                .documents
+                .iter()
+                .find(|document| document.path == path && document.modified == modified)
+            {
+                documents.push(document.clone());
+                continue;
+            }
+
+            let Ok(text) = fs::read_to_string(&path).await else {
+                continue;
+            };
+
+            let texts = chunk(&text);
+            let mut chunks = Vec::with_capacity(texts.len());
+
+            for batch in texts.chunks(16) {
+                let embeddings = embed(&knowledge.embedding_server, batch).await?;
+
+                chunks.extend(
+                    batch
+                        .iter()
+                        .cloned()
+                        .zip(embeddings)
+                        .map(|(text, embedding)| Chunk { text, embedding }),
+                );
+            }
+
+            documents.push(Document {
+                path,
+                modified,
+                chunks,
+            });
+        }
+
+        let index = Self { documents };
+        let summary = index.summary();
+
+        let bytes = task::spawn_blocking(move || serde_json::to_vec(&index)).await??;
+        fs::write(Self::path().await?, bytes).await?;
+
+        Ok(summary)
+    }
+
+    pub fn summary(&self) -> Summary {
+        Summary {
+            documents: self.documents.len(),
+            chunks: self
+                .documents
+                .iter()
+                .map(|document| document.chunks.len())
+                .sum(),
+        }
+    }
+
+    /// Finds the passages most similar to the given embedding.
+    fn search(&self, embedding: &[f32], limit: usize) -> Vec<Passage> {
+        let mut scored: Vec<_> = self
+            .documents
+            .iter()
+            .flat_map(|document| {
+                document.chunks.iter().map(move |chunk| {
+                    (
+                        similarity(&chunk.embedding, embedding),
+                        &document.path,
+                        chunk,
+                    )
+                })
+            })
+            .collect();
+
+        scored.sort_by(|(a, ..), (b, ..)| b.total_cmp(a));
+
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, source, chunk)| Passage {
+                source: source.clone(),
+                text: chunk.text.clone(),
+            })
+            .collect()
+    }
+}
+
+/// The amount of passages retrieved for every message.
+const PASSAGES: usize = 4;
+
+/// Retrieves the passages of the indexed documents most relevant to a query.
+pub async fn retrieve(knowledge: &Knowledge, query: &str) -> Result<Vec<Passage>, Error> {
+    let index = Index::fetch().await?;
+
+    if index.documents.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let embedding = embed(&knowledge.embedding_server, &[query.to_owned()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+
+    Ok(index.search(&embedding, PASSAGES))
+}
+
+/// Describes the given passages for the system prompt, numbered so the model
+/// can cite them.
+pub fn context(passages: &[Passage]) -> String {
+    let mut context = "Answer using the following excerpts of the user's documents \
+        when they are relevant, and cite them by number, like [1]."
+        .to_owned();
+
+    for (index, passage) in passages.iter().enumerate() {
+        context.push_str(&format!(
+            "\n\n[{number}] {source}\n{text}",
+            number = index + 1,
+            source = passage.source.display(),
+            text = passage.text
+        ));
+    }
+
+    context
+}
+
+/// Embeds the given inputs with the embedding server, in order.
+pub async fn embed(server: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, Error> {
+    #[derive(Deserialize)]
+    struct Response {
+        data: Vec<Embedding>,
+    }
+
+    #[derive(Deserialize)]
+    struct Embedding {
+        index: usize,
+        embedding: Vec<f32>,
+    }
+
+    let mut response: Response = reqwest::Client::new()
+        .post(format!(
+            "{server}/v1/embeddings",
+            server = server.trim().trim_end_matches('/')
+        ))
+        .json(&json!({ "input": inputs }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    response.data.sort_by_key(|embedding| embedding.index);
+
+    Ok(response
+        .data
+        .into_iter()
+        .map(|embedding| embedding.embedding)
+        .collect())
+}
+
+/// Lists the Markdown and text files in the given folders and their
+/// subfolders, skipping hidden ones.
+async fn files(folders: &[PathBuf]) -> Vec<PathBuf> {
+    let mut pending = folders.to_vec();
+    let mut files = Vec::new();
+
+    while let Some(folder) = pending.pop() {
+        let Ok(mut entries) = fs::read_dir(&folder).await else {
+            continue;
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+
+            if path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            {
+                continue;
+            }
+
+            match entry.file_type().await {
+                Ok(file_type) if file_type.is_dir() => pending.push(path),
+                Ok(_) if is_document(&path) => files.push(path),
+                _ => {}
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+fn is_document(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            matches!(
+                extension.to_lowercase().as_str(),
+                "md" | "markdown" | "txt" | "text"
+            )
+        })
+}
+
+/// Splits text into chunks of whole paragraphs, or whole words if
+/// a paragraph is too long.
+fn chunk(text: &str) -> Vec<String> {
+    const SIZE: usize = 1_000;
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for paragraph in text
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+    {
+        if !current.is_empty() && current.len() + paragraph.len() > SIZE {
+            chunks.push(std::mem::take(&mut current));
+        }
+
+        if paragraph.len() > SIZE {
+            for word in paragraph.split_whitespace() {
+                if !current.is_empty() && current.len() + word.len() >= SIZE {
+                    chunks.push(std::mem::take(&mut current));
+                }
+
+                if !current.is_empty() {
+                    current.push(' ');
+                }
+
+                current.push_str(word);
+            }
+
+            continue;
+        }
+
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+
+        current.push_str(paragraph);
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+/// The cosine similarity of two embeddings.
+pub fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm = |vector: &[f32]| vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    dot / (norm(a) * norm(b)).max(f32::EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_chunked_by_paragraphs() {
+        let long = "word ".repeat(300);
+        let text = format!("First.\n\nSecond.\n\n{long}\n\nLast.");
+
+        let chunks = chunk(&text);
+
+        assert_eq!(chunks[0], "First.\n\nSecond.");
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 1_000));
+        assert!(chunks
+            .last()
+            .is_some_and(|chunk| chunk.ends_with("word\n\nLast.")));
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.matches("word").count())
+                .sum::<usize>(),
+            300
+        );
+    }
+
+    #[test]
+    fn the_most_similar_passages_are_found_first() {
+        let document = |path: &str, embedding: Vec<f32>| Document {
+            path: PathBuf::from(path),
+            modified: 0,
+            chunks: vec![Chunk {
+                text: path.to_owned(),
+                embedding,
+            }],
+        };
+
+        let index = Index {
+            documents: vec![
+                document("cats.md", vec![1.0, 0.0]),
+                document("dogs.md", vec![0.0, 1.0]),
+                document("pets.md", vec![0.7, 0.7]),
+            ],
+        };
+
+        let passages = index.search(&[0.1, 1.0], 2);
+
+        assert_eq!(passages.len(), 2);
+        assert_eq!(passages[0].text, "dogs.md");
+        assert_eq!(passages[1].text, "pets.md");
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// The conventions used to format dates and numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    #[default]
+    EnglishUs,
+    EnglishUk,
+    German,
+    Spanish,
+    French,
+}
+
+impl Locale {
+    pub const ALL: &'static [Self] = &[
+        Self::EnglishUs,
+        Self::EnglishUk,
+        Self::German,
+        Self::Spanish,
+        Self::French,
+    ];
+
+    /// Formats the given date with the month spelled out.
+    pub fn date(self, date: impl Datelike) -> String {
+        let day = date.day();
+        let month = self.month(date.month0() as usize);
+        let year = date.year();
+
+        match self {
+            Locale::EnglishUs => format!("{month} {day}, {year}"),
+            Locale::EnglishUk | Locale::French => format!("{day} {month} {year}"),
+            Locale::German => format!("{day}. {month} {year}"),
+            Locale::Spanish => format!("{day} de {month} de {year}"),
+        }
+    }
+
+    /// Formats the given date with numbers only.
+    pub fn short_date(self, date: impl Datelike) -> String {
+        let (day, month, year) = (date.day(), date.month(), date.year() % 100);
+
+        match self {
+            Locale::EnglishUs => format!("{month}/{day}/{year:02}"),
+            Locale::EnglishUk | Locale::Spanish | Locale::French => {
+                format!("{day:02}/{month:02}/{year:02}")
+            }
+            Locale::German => format!("{day:02}.{month:02}.{year:02}"),
+        }
+    }
+
+    /// Formats the given integer with digit grouping.
+    pub fn number(self, number: u64) -> String {
+        let digits = number.to_string();
+        let mut grouped = String::new();
+
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push_str(self.group_separator());
+            }
+
+            grouped.push(digit);
+        }
+
+        grouped
+    }
+
+    /// Formats the given integer in thousands or millions, when large.
+    pub fn compact(self, number: u64) -> String {
+        match number {
+            1_000_000.. => format!("{}M", self.decimal(number as f64 / 1_000_000.0, 2)),
+            1_000.. => format!("{}k", self.decimal(number as f64 / 1_000.0, 2)),
+            _ => number.to_string(),
+        }
+    }
+
+    /// Formats the given number with the given amount of decimals.
+    pub fn decimal(self, number: f64, decimals: usize) -> String {
+        let formatted = format!("{number:.decimals$}");
+
+        let (integer, fraction) = match formatted.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (formatted.as_str(), None),
+        };
+
+        let (sign, integer) = match integer.strip_prefix('-') {
+            Some(integer) => ("-", integer),
+            None => ("", integer),
+        };
+
+        let integer = self.number(integer.parse().unwrap_or_default());
+
+        match fraction {
+            Some(fraction) => format!(
+                "{sign}{integer}{separator}{fraction}",
+                separator = self.decimal_separator()
+            ),
+            None => format!("{sign}{integer}"),
+        }
+    }
+
+    /// Formats the given amount of bytes in the closest decimal unit.
+    pub fn bytes(self, bytes: u64) -> String {
+        const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+
+        let mut size = bytes as f64;
+        let mut unit = 0;
+
+        while size >= 1000.0 && unit < UNITS.len() - 1 {
+            size /= 1000.0;
+            unit += 1;
+        }
+
+        let decimals = if unit == 0 { 0 } else { 1 };
+
+        format!("{} {}", self.decimal(size, decimals), UNITS[unit])
+    }
+
+    fn month(self, index: usize) -> &'static str {
+        const ENGLISH: [&str; 12] = [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ];
+
+        const GERMAN: [&str; 12] = [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ];
+
+        const SPANISH: [&str; 12] = [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ];
+
+        const FRENCH: [&str; 12] = [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ];
+
+        let months = match self {
+            Locale::EnglishUs | Locale::EnglishUk => &ENGLISH,
+            Locale::German => &GERMAN,
+            Locale::Spanish => &SPANISH,
+            Locale::French => &FRENCH,
+        };
+
+        months[index % 12]
+    }
+
+    fn group_separator(self) -> &'static str {
+        match self {
+            Locale::EnglishUs | Locale::EnglishUk => ",",
+            Locale::German | Locale::Spanish => ".",
+            Locale::French => "\u{202f}",
+        }
+    }
+
+    fn decimal_separator(self) -> char {
+        match self {
+            Locale::EnglishUs | Locale::EnglishUk => '.',
+            Locale::German | Locale::Spanish | Locale::French => ',',
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Locale::EnglishUs => "English (United States)",
+            Locale::EnglishUk => "English (United Kingdom)",
+            Locale::German => "Deutsch",
+            Locale::Spanish => "Español",
+            Locale::French => "Français",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_follow_the_locale() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 5).expect("valid date");
+
+        assert_eq!(Locale::EnglishUs.date(date), "March 5, 2024");
+        assert_eq!(Locale::German.date(date), "5. März 2024");
+        assert_eq!(Locale::EnglishUk.short_date(date), "05/03/24");
+        assert_eq!(Locale::EnglishUs.short_date(date), "3/5/24");
+
+        assert_eq!(Locale::EnglishUs.number(1_234_567), "1,234,567");
+        assert_eq!(Locale::German.number(1_234_567), "1.234.567");
+        assert_eq!(Locale::EnglishUs.number(999), "999");
+        assert_eq!(Locale::French.compact(1_520_000), "1,52M");
+
+        assert_eq!(Locale::Spanish.decimal(-1234.5, 2), "-1.234,50");
+        assert_eq!(Locale::EnglishUk.bytes(4_370_000_000), "4.4 GB");
+        assert_eq!(Locale::French.bytes(512), "512 B");
+    }
+}
@@ -0,0 +1,567 @@
+//! A client of the Model Context Protocol, which lets models call the tools
+//! of other programs: local ones over stdio, or remote ones over HTTP.
+use crate::data::Error;
+
+use futures::future;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt};
+use tokio::process;
+
+use std::fmt;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The version of the protocol spoken by the app.
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// How long a server may take to answer a request.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An MCP server, as configured in the settings.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Server {
+    /// The name of the server, which prefixes the names of its tools.
+    pub name: String,
+    pub transport: Transport,
+    /// The command that runs a stdio server, or the URL of an HTTP one.
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    /// A program run by the app, talking through its standard streams.
+    #[default]
+    Stdio,
+    /// A streamable HTTP endpoint.
+    Http,
+}
+
+impl Transport {
+    pub const ALL: &'static [Self] = &[Self::Stdio, Self::Http];
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Transport::Stdio => "Command",
+            Transport::Http => "HTTP",
+        })
+    }
+}
+
+/// A tool offered by an MCP server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tool {
+    /// The name of the tool for models, prefixed by the name of its server
+    /// so tools of different servers never clash.
+    pub name: String,
+    /// The name of the tool in its server.
+    pub original: String,
+    pub server: String,
+    pub description: String,
+    /// The JSON schema of the arguments of the tool.
+    pub schema: Value,
+}
+
+/// The servers connected, with their tools.
+static CONNECTIONS: Mutex<Vec<Connected>> = Mutex::new(Vec::new());
+
+struct Connected {
+    server: Server,
+    connection: Arc<futures::lock::Mutex<Connection>>,
+    tools: Vec<Tool>,
+}
+
+/// Connects to the given server and lists its tools, which models may call
+/// from then on.
+///
+/// A live connection to the very same server is reused.
+pub async fn connect(server: Server) -> Result<Vec<Tool>, Error> {
+    if let Some(tools) = CONNECTIONS.lock().ok().and_then(|connections| {
+        connections
+            .iter()
+            .find(|connected| connected.server == server)
+            .map(|connected| connected.tools.clone())
+    }) {
+        return Ok(tools);
+    }
+
+    let mut connection = Connection::open(&server).await?;
+    let tools = connection.list_tools(&server.name).await?;
+
+    if let Ok(mut connections) = CONNECTIONS.lock() {
+        connections.retain(|connected| connected.server.name != server.name);
+        connections.push(Connected {
+            server,
+            connection: Arc::new(futures::lock::Mutex::new(connection)),
+            tools: tools.clone(),
+        });
+    }
+
+    Ok(tools)
+}
+
+/// Connects to all the given servers, logging the ones that fail.
+pub async fn connect_all(servers: Vec<Server>) {
+    for result in future::join_all(servers.into_iter().map(connect)).await {
+        if let Err(error) = result {
+            let _ = dbg!(error);
+        }
+    }
+}
+
+/// Drops the connection to the given server, if any, stopping its program.
+pub fn disconnect(server: &Server) {
+    if let Ok(mut connections) = CONNECTIONS.lock() {
+        connections.retain(|connected| connected.server.name != server.name);
+    }
+}
+
+/// Drops the connections to the servers that are not in the given list;
+/// for instance, after they are edited or removed.
+pub fn retain(servers: &[Server]) {
+    if let Ok(mut connections) = CONNECTIONS.lock() {
+        connections.retain(|connected| servers.contains(&connected.server));
+    }
+}
+
+/// The tools of all the connected servers.
+pub fn tools() -> Vec<Tool> {
+    CONNECTIONS
+        .lock()
+        .map(|connections| {
+            connections
+                .iter()
+                .flat_map(|connected| connected.tools.iter().cloned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Calls the given tool with the given JSON arguments, returning the text
+/// of its result.
+pub async fn call(tool: &Tool, arguments: &str) -> Result<String, String> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Response {
+        #[serde(default)]
+        content: Vec<Content>,
+        #[serde(default)]
+        is_error: bool,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    enum Content {
+        Text {
+            text: String,
+        },
+        #[serde(other)]
+        Other,
+    }
+
+    let arguments: Value = if arguments.trim().is_empty() {
+        json!({})
+    } else {
+        serde_json::from_str(arguments).map_err(|error| error.to_string())?
+    };
+
+    let connection = CONNECTIONS
+        .lock()
+        .ok()
+        .and_then(|connections| {
+            connections
+                .iter()
+                .find(|connected| connected.server.name == tool.server)
+                .map(|connected| connected.connection.clone())
+        })
+        .ok_or_else(|| format!("{} is not connected", tool.server))?;
+
+    let result = connection
+        .lock()
+        .await
+        .request(
+            "tools/call",
+            json!({
+                "name": tool.original,
+                "arguments": arguments,
+            }),
+        )
+        .await;
+
+    let result = match result {
+        Ok(result) => result,
+        // The server is gone; its tools are dropped until it connects again
+        Err(error @ (Error::IOFailed(_) | Error::RequestFailed(_))) => {
+            if let Ok(mut connections) = CONNECTIONS.lock() {
+                connections.retain(|connected| !Arc::ptr_eq(&connected.connection, &connection));
+            }
+
+            return Err(error.to_string());
+        }
+        Err(error) => return Err(error.to_string()),
+    };
+
+    let response: Response = serde_json::from_value(result).map_err(|error| error.to_string())?;
+
+    let text = response
+        .content
+        .into_iter()
+        .map(|content| match content {
+            Content::Text { text } => text,
+            Content::Other => "[unsupported content]".to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if response.is_error {
+        return Err(text);
+    }
+
+    Ok(text)
+}
+
+/// A connection to an MCP server, speaking JSON-RPC.
+struct Connection {
+    channel: Channel,
+    next_id: u64,
+}
+
+enum Channel {
+    Stdio(Box<Pipes>),
+    Http {
+        client: reqwest::Client,
+        url: String,
+        /// The session assigned by the server, if any.
+        session: Option<String>,
+    },
+}
+
+/// The standard streams of a server run by the app.
+struct Pipes {
+    _process: process::Child,
+    input: process::ChildStdin,
+    output: io::Lines<io::BufReader<process::ChildStdout>>,
+}
+
+impl Connection {
+    async fn open(server: &Server) -> Result<Self, Error> {
+        let channel = match server.transport {
+            Transport::Stdio => {
+                let mut args = server.target.split_whitespace();
+
+                let Some(program) = args.next() else {
+                    return Err(Error::McpFailed("no command is configured".to_owned()));
+                };
+
+                let mut process = process::Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .kill_on_drop(true)
+                    .spawn()?;
+
+                Channel::Stdio(Box::new(Pipes {
+                    input: process.stdin.take().expect("piped stdin"),
+                    output: io::BufReader::new(process.stdout.take().expect("piped stdout"))
+                        .lines(),
+                    _process: process,
+                }))
+            }
+            Transport::Http => {
+                let url = server.target.trim();
+
+                if url.is_empty() {
+                    return Err(Error::McpFailed("no URL is configured".to_owned()));
+                }
+
+                Channel::Http {
+                    client: reqwest::Client::builder().timeout(TIMEOUT).build()?,
+                    url: url.to_owned(),
+                    session: None,
+                }
+            }
+        };
+
+        let mut connection = Self {
+            channel,
+            next_id: 0,
+        };
+
+        let _ = connection
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            )
+            .await?;
+
+        let _ = connection
+            .exchange(
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/initialized",
+                }),
+                None,
+            )
+            .await?;
+
+        Ok(connection)
+    }
+
+    async fn list_tools(&mut self, server: &str) -> Result<Vec<Tool>, Error> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Page {
+            tools: Vec<Definition>,
+            next_cursor: Option<String>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Definition {
+            name: String,
+            description: Option<String>,
+            input_schema: Option<Value>,
+        }
+
+        let mut tools = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let parameters = match cursor.take() {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+
+            let page: Page = serde_json::from_value(self.request("tools/list", parameters).await?)?;
+
+            tools.extend(page.tools.into_iter().map(|definition| {
+                Tool {
+                    name: qualified(server, &definition.name),
+                    original: definition.name,
+                    server: server.to_owned(),
+                    description: definition.description.unwrap_or_default(),
+                    schema: definition
+                        .input_schema
+                        .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+                }
+            }));
+
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(tools)
+    }
+
+    async fn request(&mut self, method: &str, parameters: Value) -> Result<Value, Error> {
+        self.next_id += 1;
+
+        let id = self.next_id;
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": parameters,
+        });
+
+        let mut reply = tokio::time::timeout(TIMEOUT, self.exchange(message, Some(id)))
+            .await
+            .map_err(|_| Error::McpFailed(format!("{method} timed out")))??
+            .ok_or_else(|| Error::McpFailed(format!("{method} got no reply")))?;
+
+        if let Some(error) = reply.get("error") {
+            let message = error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+
+            return Err(Error::McpFailed(message.to_owned()));
+        }
+
+        Ok(reply["result"].take())
+    }
+
+    /// Sends the given message and waits for the reply with the given id,
+    /// if any.
+    async fn exchange(&mut self, message: Value, id: Option<u64>) -> Result<Option<Value>, Error> {
+        match &mut self.channel {
+            Channel::Stdio(pipes) => {
+                let Pipes { input, output, .. } = pipes.as_mut();
+
+                input.write_all(format!("{message}\n").as_bytes()).await?;
+                input.flush().await?;
+
+                let Some(id) = id else {
+                    return Ok(None);
+                };
+
+                while let Some(line) = output.next_line().await? {
+                    // Some servers log to their output too
+                    let Ok(reply) = serde_json::from_str::<Value>(&line) else {
+                        continue;
+                    };
+
+                    if reply.get("method").is_some() {
+                        if let Some(answer) = answer(&reply) {
+                            input.write_all(format!("{answer}\n").as_bytes()).await?;
+                            input.flush().await?;
+                        }
+
+                        continue;
+                    }
+
+                    if reply.get("id") == Some(&json!(id)) {
+                        return Ok(Some(reply));
+                    }
+                }
+
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the server exited").into())
+            }
+            Channel::Http {
+                client,
+                url,
+                session,
+            } => {
+                let mut request = client
+                    .post(url.as_str())
+                    .header("Accept", "application/json, text/event-stream")
+                    .json(&message);
+
+                if let Some(session) = session.as_deref() {
+                    request = request.header("Mcp-Session-Id", session);
+                }
+
+                let response = request.send().await?.error_for_status()?;
+
+                if let Some(assigned) = response
+                    .headers()
+                    .get("Mcp-Session-Id")
+                    .and_then(|assigned| assigned.to_str().ok())
+                {
+                    *session = Some(assigned.to_owned());
+                }
+
+                let Some(id) = id else {
+                    return Ok(None);
+                };
+
+                let is_stream = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|content_type| content_type.to_str().ok())
+                    .is_some_and(|content_type| content_type.starts_with("text/event-stream"));
+
+                let body = response.text().await?;
+
+                if !is_stream {
+                    return Ok(Some(serde_json::from_str(&body)?));
+                }
+
+                Ok(events(&body)
+                    .into_iter()
+                    .find(|reply| reply.get("id") == Some(&json!(id))))
+            }
+        }
+    }
+}
+
+/// The answer to a request of the server, if it expects one.
+///
+/// Only pings are supported; the app offers no capabilities to servers.
+fn answer(request: &Value) -> Option<Value> {
+    let id = request.get("id")?;
+
+    Some(match request.get("method").and_then(Value::as_str) {
+        Some("ping") => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {},
+        }),
+        _ => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": -32601,
+                "message": "method not found",
+            },
+        }),
+    })
+}
+
+/// The JSON messages of a stream of server-sent events.
+fn events(body: &str) -> Vec<Value> {
+    body.replace("\r\n", "\n")
+        .split("\n\n")
+        .filter_map(|event| {
+            let data = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(str::trim_start)
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            serde_json::from_str(&data).ok()
+        })
+        .collect()
+}
+
+/// The name of the given tool for models, prefixed by the name of its
+/// server; with only the characters function names allow.
+fn qualified(server: &str, tool: &str) -> String {
+    let sanitize = |name: &str| -> String {
+        name.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+
+    format!("{}__{}", sanitize(server), sanitize(tool))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_are_read_from_event_streams() {
+        let body = "event: message\r\n\
+            data: {\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\"}\r\n\r\n\
+            event: message\r\n\
+            data: {\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{\"tools\":[]}}\r\n\r\n";
+
+        let replies = events(body);
+
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[1]["id"], json!(2));
+        assert_eq!(replies[1]["result"], json!({ "tools": [] }));
+    }
+
+    #[test]
+    fn tools_are_prefixed_by_their_server() {
+        assert_eq!(qualified("files", "read_file"), "files__read_file");
+        assert_eq!(
+            qualified("My Server", "get.weather"),
+            "My_Server__get_weather"
+        );
+    }
+}
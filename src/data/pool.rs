@@ -0,0 +1,176 @@
+use crate::data::assistant::{Assistant, File};
+
+use std::sync::{Arc, Mutex};
+
+/// The assistants kept running after their chats are closed, so opening a
+/// chat with any of them again is instant.
+///
+/// A [`Pool`] is a shared handle; clones refer to the same assistants.
+#[derive(Debug, Clone, Default)]
+pub struct Pool {
+    assistants: Arc<Mutex<Assistants>>,
+}
+
+#[derive(Debug, Default)]
+struct Assistants {
+    /// Recently used assistants, most recent first.
+    warm: Vec<Assistant>,
+    /// Assistants booted ahead of time, kept until a chat takes them.
+    preloaded: Vec<Assistant>,
+    /// Ports of the assistants still booting in the background.
+    reserved: Vec<u64>,
+}
+
+impl Pool {
+    /// The ports used by pooled assistants, after [`Assistant::HOST_PORT`].
+    const PORTS: std::ops::Range<u64> = 8090..8100;
+
+    /// Takes the running assistant of the given file out of the pool.
+    pub fn take(&self, file: &File) -> Option<Assistant> {
+        let mut assistants = self.assistants.lock().expect("lock pool");
+        let Assistants {
+            warm, preloaded, ..
+        } = &mut *assistants;
+
+        for list in [preloaded, warm] {
+            if let Some(index) = list.iter().position(|assistant| assistant.file() == file) {
+                return Some(list.remove(index));
+            }
+        }
+
+        None
+    }
+
+    /// Returns a handle to the running assistant of the given file, leaving
+    /// it in the pool.
+    pub fn get(&self, file: &File) -> Option<Assistant> {
+        let assistants = self.assistants.lock().expect("lock pool");
+
+        assistants
+            .warm
+            .iter()
+            .chain(&assistants.preloaded)
+            .find(|assistant| assistant.file() == file)
+            .cloned()
+    }
+
+    /// Returns true if an assistant of the given file is running in the pool.
+    pub fn contains(&self, file: &File) -> bool {
+        let assistants = self.assistants.lock().expect("lock pool");
+
+        assistants
+            .warm
+            .iter()
+            .chain(&assistants.preloaded)
+            .any(|assistant| assistant.file() == file)
+    }
+
+    /// The files of all the assistants running in the pool.
+    pub fn files(&self) -> Vec<File> {
+        let assistants = self.assistants.lock().expect("lock pool");
+
+        assistants
+            .warm
+            .iter()
+            .chain(&assistants.preloaded)
+            .map(|assistant| assistant.file().clone())
+            .collect()
+    }
+
+    /// Keeps the given assistant running, shutting down the least recently
+    /// used ones beyond the given capacity.
+    ///
+    /// Preloaded assistants do not count towards the capacity.
+    pub fn keep(&self, assistant: Assistant, capacity: usize) {
+        let mut assistants = self.assistants.lock().expect("lock pool");
+
+        assistants
+            .warm
+            .retain(|pooled| pooled.file() != assistant.file());
+        assistants.warm.insert(0, assistant);
+        assistants.warm.truncate(capacity);
+    }
+
+    /// Keeps the given preloaded assistant running until a chat takes it,
+    /// freeing the port reserved for it.
+    pub fn preload(&self, assistant: Assistant) {
+        let mut assistants = self.assistants.lock().expect("lock pool");
+
+        assistants.reserved.retain(|port| *port != assistant.port());
+        assistants
+            .preloaded
+            .retain(|pooled| pooled.file() != assistant.file());
+        assistants.preloaded.push(assistant);
+    }
+
+    /// A port that no pooled assistant is listening on.
+    pub fn port(&self) -> u64 {
+        self.port_besides(&[])
+    }
+
+    /// A port that neither pooled assistants nor the given ports use.
+    pub fn port_besides(&self, ports: &[u64]) -> u64 {
+        let assistants = self.assistants.lock().expect("lock pool");
+
+        let used: Vec<_> = assistants
+            .warm
+            .iter()
+            .chain(&assistants.preloaded)
+            .map(Assistant::port)
+            .chain(assistants.reserved.iter().copied())
+            .chain(ports.iter().copied())
+            .collect();
+
+        free_port(&used)
+    }
+
+    /// Sets aside a port for an assistant booting in the background.
+    pub fn reserve(&self, port: u64) {
+        self.assistants
+            .lock()
+            .expect("lock pool")
+            .reserved
+            .push(port);
+    }
+
+    /// Gives back a port set aside with [`Pool::reserve`].
+    pub fn unreserve(&self, port: u64) {
+        self.assistants
+            .lock()
+            .expect("lock pool")
+            .reserved
+            .retain(|reserved| *reserved != port);
+    }
+}
+
+fn free_port(used: &[u64]) -> u64 {
+    std::iter::once(Assistant::HOST_PORT)
+        .chain(Pool::PORTS)
+        .find(|port| !used.contains(port))
+        .unwrap_or(Assistant::HOST_PORT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pooled_ports_are_skipped() {
+        assert_eq!(free_port(&[]), Assistant::HOST_PORT);
+        assert_eq!(free_port(&[Assistant::HOST_PORT]), 8090);
+        assert_eq!(free_port(&[8090, Assistant::HOST_PORT]), 8091);
+        assert_eq!(free_port(&[8091]), Assistant::HOST_PORT);
+    }
+
+    #[test]
+    fn reserved_ports_are_skipped() {
+        let pool = Pool::default();
+
+        pool.reserve(Assistant::HOST_PORT);
+        assert_eq!(pool.port(), 8090);
+        assert_eq!(pool.port_besides(&[8090]), 8091);
+
+        pool.unreserve(Assistant::HOST_PORT);
+        assert_eq!(pool.port(), Assistant::HOST_PORT);
+    }
+}
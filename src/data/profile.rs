@@ -0,0 +1,74 @@
+use crate::data::assistant::{Backend, File, Inference};
+use crate::data::{self, Error};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use std::io;
+use std::path::PathBuf;
+
+/// The inference options that last booted a model successfully on a backend.
+///
+/// Profiles fill in the options left unset in the settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub file: File,
+    pub backend: Backend,
+    pub inference: Inference,
+}
+
+impl Profile {
+    async fn path() -> Result<PathBuf, io::Error> {
+        Ok(data::data_dir().await?.join("profiles.json"))
+    }
+
+    pub async fn list() -> Result<Vec<Self>, Error> {
+        let Ok(bytes) = fs::read(Self::path().await?).await else {
+            return Ok(Vec::new());
+        };
+
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub async fn find(file: &File, backend: Backend) -> Option<Inference> {
+        Self::list()
+            .await
+            .ok()?
+            .into_iter()
+            .find(|profile| &profile.file == file && profile.backend == backend)
+            .map(|profile| profile.inference)
+    }
+
+    pub async fn record(file: File, backend: Backend, inference: Inference) -> Result<(), Error> {
+        if inference == Inference::default() {
+            return Ok(());
+        }
+
+        let mut profiles = Self::list().await?;
+        profiles.retain(|profile| profile.file != file || profile.backend != backend);
+
+        profiles.insert(
+            0,
+            Self {
+                file,
+                backend,
+                inference,
+            },
+        );
+
+        Self::save(profiles).await
+    }
+
+    pub async fn reset(self) -> Result<(), Error> {
+        let mut profiles = Self::list().await?;
+        profiles.retain(|profile| profile != &self);
+
+        Self::save(profiles).await
+    }
+
+    async fn save(profiles: Vec<Self>) -> Result<(), Error> {
+        fs::write(Self::path().await?, serde_json::to_vec_pretty(&profiles)?).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use crate::data::Error;
+
+use serde::{Deserialize, Serialize};
+use tokio::process;
+
+use std::time::Duration;
+
+/// A llama-server running on another machine, used instead of launching
+/// one locally.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Remote {
+    pub enabled: bool,
+    /// The machine running llama-server; the SSH destination when tunneling.
+    pub host: String,
+    pub port: u64,
+    /// Whether to forward the port through SSH, for servers that only
+    /// listen on the remote machine.
+    pub tunnel: bool,
+}
+
+/// An open connection to a [`Remote`].
+#[derive(Debug)]
+pub struct Connection {
+    pub host: String,
+    pub port: u64,
+    tunnel: Option<process::Child>,
+}
+
+impl Remote {
+    /// The local port used to check a tunneled connection.
+    const CHECK_PORT: u64 = 8083;
+
+    /// How many times the health of the server is checked before giving up.
+    const HEALTH_CHECKS: usize = 10;
+
+    pub fn address(&self) -> String {
+        format!("{host}:{port}", host = self.host, port = self.port)
+    }
+
+    /// Connects to the server; a tunnel forwards it to the given local port.
+    pub async fn connect(&self, local_port: u64) -> Result<Connection, Error> {
+        if self.host.trim().is_empty() {
+            return Err(Error::ExecutorFailed("no remote host is set"));
+        }
+
+        let mut connection = if self.tunnel {
+            let tunnel = process::Command::new("ssh")
+                .args([
+                    "-N",
+                    "-o",
+                    "BatchMode=yes",
+                    "-o",
+                    "ExitOnForwardFailure=yes",
+                    "-L",
+                    &format!("{local_port}:localhost:{port}", port = self.port),
+                    self.host.trim(),
+                ])
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .kill_on_drop(true)
+                .spawn()?;
+
+            Connection {
+                host: "localhost".to_owned(),
+                port: local_port,
+                tunnel: Some(tunnel),
+            }
+        } else {
+            Connection {
+                host: self.host.trim().to_owned(),
+                port: self.port,
+                tunnel: None,
+            }
+        };
+
+        for _ in 0..Self::HEALTH_CHECKS {
+            if connection.is_healthy().await {
+                return Ok(connection);
+            }
+
+            if let Some(tunnel) = &mut connection.tunnel {
+                if tunnel.try_wait()?.is_some() {
+                    return Err(Error::ExecutorFailed("the SSH tunnel could not be opened"));
+                }
+            }
+
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+
+        Err(Error::ExecutorFailed(
+            "the remote llama-server is unreachable",
+        ))
+    }
+
+    /// Connects to the server and returns the model it is running.
+    pub async fn check(self) -> Result<String, Error> {
+        let connection = self.connect(Self::CHECK_PORT).await?;
+
+        Ok(connection
+            .model()
+            .await
+            .unwrap_or_else(|| "an unknown model".to_owned()))
+    }
+}
+
+impl Default for Remote {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: 8080,
+            tunnel: false,
+        }
+    }
+}
+
+impl Connection {
+    async fn is_healthy(&self) -> bool {
+        reqwest::get(self.url("/health"))
+            .await
+            .is_ok_and(|response| response.status().is_success())
+    }
+
+    /// The name of the model file loaded by the server.
+    pub async fn model(&self) -> Option<String> {
+        let props: serde_json::Value = reqwest::get(self.url("/props"))
+            .await
+            .ok()?
+            .json()
+            .await
+            .ok()?;
+
+        let path = props["model_path"]
+            .as_str()
+            .or_else(|| props["default_generation_settings"]["model"].as_str())?;
+
+        Some(path.rsplit(['/', '\\']).next().unwrap_or(path).to_owned())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "http://{host}:{port}{path}",
+            host = self.host,
+            port = self.port
+        )
+    }
+}
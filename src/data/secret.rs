@@ -0,0 +1,50 @@
+//! Secrets kept in the keyring of the system, instead of the settings.
+use crate::data::Error;
+
+use tokio::task;
+
+/// The service the secrets of the app are stored under.
+const SERVICE: &str = "icebreaker";
+
+/// A secret of the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Secret {
+    HuggingFaceToken,
+}
+
+impl Secret {
+    fn account(self) -> &'static str {
+        match self {
+            Secret::HuggingFaceToken => "hugging_face_token",
+        }
+    }
+
+    /// Reads the secret from the keyring, if stored.
+    pub async fn read(self) -> Result<Option<String>, Error> {
+        task::spawn_blocking(move || {
+            match keyring::Entry::new(SERVICE, self.account())?.get_password() {
+                Ok(secret) => Ok(Some(secret)),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(error) => Err(error.into()),
+            }
+        })
+        .await?
+    }
+
+    /// Stores the secret in the keyring; an empty one is deleted instead.
+    pub async fn write(self, secret: String) -> Result<(), Error> {
+        task::spawn_blocking(move || {
+            let entry = keyring::Entry::new(SERVICE, self.account())?;
+
+            if !secret.is_empty() {
+                return Ok(entry.set_password(&secret)?);
+            }
+
+            match entry.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(error) => Err(error.into()),
+            }
+        })
+        .await?
+    }
+}
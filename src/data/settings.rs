@@ -0,0 +1,373 @@
+use crate::data::appearance::Appearance;
+use crate::data::assistant::{Backend, BootOptions, File, Inference, Launch};
+use crate::data::executor::Executor;
+use crate::data::guardrails::Guardrails;
+use crate::data::keymap::Keymap;
+use crate::data::knowledge::Knowledge;
+use crate::data::locale::Locale;
+use crate::data::mcp;
+use crate::data::remote::Remote;
+use crate::data::secret::Secret;
+use crate::data::template::Variable;
+use crate::data::theme::{self, Mode, Theme};
+use crate::data::web;
+use crate::data::window::Window;
+use crate::data::{self, Error};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::task;
+
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub system_prompt: String,
+    pub guardrails: Guardrails,
+    pub variables: Vec<Variable>,
+    pub memory: Vec<String>,
+    /// Whether the model suggests chat titles; otherwise, chats are titled
+    /// after the first words of their first message.
+    pub suggest_titles: bool,
+    pub inference: Inference,
+    /// Extra flags passed as is to every `llama-server`.
+    pub extra_flags: String,
+    /// The files that boot with their own options.
+    pub boot_options: Vec<BootOptions>,
+    pub executor: Executor,
+    pub remote: Remote,
+    /// The amount of models kept running after switching to another one.
+    pub warm_models: u32,
+    /// Whether hovering a chat of another model boots it in the background,
+    /// if it fits in memory next to the running ones.
+    pub preload_on_hover: bool,
+    /// Whether the messages of a chat are summarized for the model it is
+    /// switched to, instead of replaying them verbatim.
+    pub summarize_on_switch: bool,
+    /// Whether closing the window hides it in the tray, or minimizes it,
+    /// instead; keeping the running models loaded until the app is quit.
+    pub run_in_background: bool,
+    /// A smaller model that writes titles for the replies of reasoning
+    /// models, keeping the main model free for the actual replies.
+    pub summarizer: Option<File>,
+    pub keymap: Keymap,
+    /// The command that opens replies in an external editor; the default
+    /// app for the file is used if empty.
+    pub editor: String,
+    pub record_usage: bool,
+    pub search: web::Search,
+    /// The MCP servers whose tools models may call.
+    pub mcp: Vec<mcp::Server>,
+    /// Whether an access token, used to browse and download gated models,
+    /// is stored in the keyring.
+    pub hugging_face_token_set: bool,
+    /// The plain-text token of older versions, until it is moved to the
+    /// keyring.
+    #[serde(rename = "hugging_face_token", skip_serializing_if = "Option::is_none")]
+    pub legacy_hugging_face_token: Option<String>,
+    pub knowledge: Knowledge,
+    pub locale: Locale,
+    /// The geometry of the window when the app was last closed.
+    pub window: Window,
+    pub appearance: Appearance,
+    pub theme: Theme,
+    /// The themes used by [`Theme::System`] in each mode.
+    pub light_theme: Theme,
+    pub dark_theme: Theme,
+    /// The themes made or imported by the user.
+    pub themes: Vec<theme::Custom>,
+}
+
+impl Settings {
+    pub const DEFAULT_SYSTEM_PROMPT: &'static str = "You are a helpful assistant.";
+
+    async fn path() -> Result<PathBuf, io::Error> {
+        Ok(data::data_dir().await?.join("settings.toml"))
+    }
+
+    pub async fn fetch() -> Result<Self, Error> {
+        let Ok(contents) = fs::read_to_string(Self::path().await?).await else {
+            return Ok(Self::default());
+        };
+
+        Ok(task::spawn_blocking(move || toml::from_str(&contents)).await??)
+    }
+
+    /// Reads the settings before the app starts, since its window opens
+    /// before any task can run.
+    pub fn fetch_blocking() -> Self {
+        std::fs::read_to_string(data::data_path().join("settings.toml"))
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// How the given file is launched, after applying its [`BootOptions`].
+    pub fn launch(&self, file: &File, backend: Backend) -> Launch {
+        let launch = Launch {
+            backend,
+            inference: self.inference,
+            extra_flags: self.extra_flags.clone(),
+            executor: self.executor.clone(),
+        };
+
+        let Some(options) = self
+            .boot_options
+            .iter()
+            .find(|options| options.file == *file)
+        else {
+            return launch;
+        };
+
+        Launch {
+            backend: options.backend.unwrap_or(backend),
+            inference: Inference {
+                gpu_layers: options.gpu_layers.or(self.inference.gpu_layers),
+                ..self.inference
+            },
+            // The flags of the file come last, so they win
+            extra_flags: format!("{} {}", self.extra_flags, options.extra_flags),
+            ..launch
+        }
+    }
+
+    /// The theme of the app in the given mode of the system.
+    pub fn theme(&self, mode: Mode) -> iced::Theme {
+        let theme = match (&self.theme, mode) {
+            (Theme::System, Mode::Light) => &self.light_theme,
+            (Theme::System, Mode::Dark) => &self.dark_theme,
+            (theme, _) => theme,
+        };
+
+        theme.resolve(&self.themes)
+    }
+
+    /// A copy of the settings without secrets or personal details, fit to
+    /// be shared in a bug report.
+    pub fn redacted(&self) -> Self {
+        let redact = |value: &str| {
+            if value.is_empty() {
+                String::new()
+            } else {
+                "<redacted>".to_owned()
+            }
+        };
+
+        Self {
+            variables: self
+                .variables
+                .iter()
+                .map(|variable| Variable {
+                    name: variable.name.clone(),
+                    value: redact(&variable.value),
+                })
+                .collect(),
+            memory: self.memory.iter().map(|memory| redact(memory)).collect(),
+            search: web::Search {
+                brave_api_key: redact(&self.search.brave_api_key),
+                tavily_api_key: redact(&self.search.tavily_api_key),
+                ..self.search.clone()
+            },
+            // Commands and URLs may carry keys
+            mcp: self
+                .mcp
+                .iter()
+                .map(|server| mcp::Server {
+                    target: redact(&server.target),
+                    ..server.clone()
+                })
+                .collect(),
+            legacy_hugging_face_token: self.legacy_hugging_face_token.as_deref().map(redact),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of the settings without API keys or tokens, fit to be kept
+    /// in a backup.
+    pub fn without_secrets(&self) -> Self {
+        Self {
+            search: web::Search {
+                brave_api_key: String::new(),
+                tavily_api_key: String::new(),
+                ..self.search.clone()
+            },
+            hugging_face_token_set: false,
+            legacy_hugging_face_token: None,
+            ..self.clone()
+        }
+    }
+
+    /// Takes the API keys and tokens of the given settings, which
+    /// [`without_secrets`](Self::without_secrets) leaves out.
+    pub fn with_secrets_of(self, settings: &Self) -> Self {
+        Self {
+            search: web::Search {
+                brave_api_key: settings.search.brave_api_key.clone(),
+                tavily_api_key: settings.search.tavily_api_key.clone(),
+                ..self.search
+            },
+            hugging_face_token_set: settings.hugging_face_token_set,
+            legacy_hugging_face_token: settings.legacy_hugging_face_token.clone(),
+            ..self
+        }
+    }
+
+    /// Moves the plain-text token of older versions to the keyring, saving
+    /// the settings without it.
+    ///
+    /// The token is kept in the settings if the keyring is unavailable.
+    pub async fn migrate_token(mut self) -> Self {
+        let Some(token) = self.legacy_hugging_face_token.take() else {
+            return self;
+        };
+
+        let token = token.trim().to_owned();
+
+        if let Err(error) = Secret::HuggingFaceToken.write(token.clone()).await {
+            let _ = dbg!(error);
+
+            self.legacy_hugging_face_token = Some(token);
+            return self;
+        }
+
+        self.hugging_face_token_set = !token.is_empty();
+
+        if let Err(error) = self.clone().save().await {
+            let _ = dbg!(error);
+        }
+
+        self
+    }
+
+    pub async fn save(self) -> Result<(), Error> {
+        let contents = task::spawn_blocking(move || toml::to_string_pretty(&self)).await??;
+
+        fs::write(Self::path().await?, contents).await?;
+
+        Ok(())
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            system_prompt: Self::DEFAULT_SYSTEM_PROMPT.to_owned(),
+            guardrails: Guardrails::default(),
+            variables: Vec::new(),
+            memory: Vec::new(),
+            suggest_titles: true,
+            inference: Inference::default(),
+            extra_flags: String::new(),
+            boot_options: Vec::new(),
+            executor: Executor::default(),
+            remote: Remote::default(),
+            warm_models: 0,
+            preload_on_hover: false,
+            summarize_on_switch: false,
+            run_in_background: false,
+            summarizer: None,
+            keymap: Keymap::default(),
+            editor: String::new(),
+            record_usage: true,
+            search: web::Search::default(),
+            mcp: Vec::new(),
+            hugging_face_token_set: false,
+            legacy_hugging_face_token: None,
+            knowledge: Knowledge::default(),
+            locale: Locale::default(),
+            appearance: Appearance::default(),
+            window: Window::default(),
+            theme: Theme::default(),
+            light_theme: Theme::Builtin(iced::Theme::Light.to_string()),
+            dark_theme: Theme::default(),
+            themes: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::keymap;
+    use crate::data::snapshot;
+
+    #[test]
+    fn legacy_settings_are_completed_with_defaults() {
+        let settings: Settings = toml::from_str(&snapshot::read("settings/legacy.toml"))
+            .expect("settings should decode");
+
+        assert_eq!(settings.system_prompt, "You are a pirate. Answer like one.");
+        assert_eq!(settings.inference, Inference::default());
+        assert_eq!(settings.keymap, Keymap::default());
+
+        // Tokens are moved to the keyring once loaded
+        assert_eq!(
+            settings.legacy_hugging_face_token.as_deref(),
+            Some("hf_example")
+        );
+        assert!(!settings.hugging_face_token_set);
+        assert!(!toml::to_string(&settings.without_secrets())
+            .expect("settings should encode")
+            .contains("hf_example"));
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut settings = Settings {
+            variables: vec![Variable {
+                name: "name".to_owned(),
+                value: "Ferris".to_owned(),
+            }],
+            memory: vec!["Prefers short answers.".to_owned()],
+            inference: Inference {
+                gpu_layers: Some(20),
+                context_size: Some(8192),
+                ..Inference::default()
+            },
+            extra_flags: "--flash-attn".to_owned(),
+            boot_options: vec![BootOptions {
+                backend: Some(Backend::Cpu),
+                extra_flags: "--no-mmap".to_owned(),
+                ..BootOptions::new(
+                    serde_json::from_str(
+                        r#"{
+                            "model": "bartowski/Example-8B-GGUF",
+                            "name": "Example-8B-Q4_K_M.gguf"
+                        }"#,
+                    )
+                    .expect("file should parse"),
+                )
+            }],
+            search: web::Search {
+                provider: web::Provider::Searxng,
+                searxng_url: "http://localhost:8080".to_owned(),
+                ..web::Search::default()
+            },
+            ..Settings::default()
+        };
+
+        settings.keymap.set(
+            keymap::Command::NewChat,
+            "Alt+Shift+N".parse().expect("shortcut should parse"),
+        );
+
+        let encoded = toml::to_string_pretty(&settings).expect("settings should encode");
+        snapshot::assert("settings/current.toml", &encoded);
+
+        let decoded: Settings = toml::from_str(&encoded).expect("settings should decode");
+        assert_eq!(decoded, settings);
+    }
+
+    #[test]
+    fn system_theme_follows_the_mode() {
+        let settings = Settings {
+            theme: Theme::System,
+            ..Settings::default()
+        };
+
+        assert_eq!(settings.theme(Mode::Light), iced::Theme::Light);
+        assert_eq!(settings.theme(Mode::Dark), iced::Theme::TokyoNight);
+    }
+}
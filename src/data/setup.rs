@@ -0,0 +1,116 @@
+//! The setup of a chat, shareable as a template file.
+use crate::data::assistant;
+use crate::data::guardrails::Guardrails;
+use crate::data::Error;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::task;
+
+use std::path::PathBuf;
+
+/// How a chat is set up before its first message: the persona of the
+/// assistant, its sampling strategy, and some messages to start with.
+///
+/// Setups are shared as TOML files with the [`Setup::EXTENSION`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Setup {
+    /// The version of the file format.
+    pub format: u32,
+    pub name: String,
+    /// The system prompt of the chat; the default one is used if unset.
+    pub persona: Option<String>,
+    #[serde(default)]
+    pub guardrails: Guardrails,
+    #[serde(default)]
+    pub sampling: assistant::Sampling,
+    #[serde(default)]
+    pub memory: Vec<String>,
+    /// Messages suggested to start the chat with.
+    #[serde(default)]
+    pub starters: Vec<String>,
+}
+
+impl Setup {
+    pub const EXTENSION: &'static str = "icebreaker-template";
+
+    const FORMAT: u32 = 1;
+
+    pub fn new(
+        name: String,
+        persona: Option<String>,
+        guardrails: Guardrails,
+        sampling: assistant::Sampling,
+        memory: Vec<String>,
+        starters: Vec<String>,
+    ) -> Self {
+        Self {
+            format: Self::FORMAT,
+            name,
+            persona,
+            guardrails,
+            sampling,
+            memory,
+            starters,
+        }
+    }
+
+    /// Reads the setup in the template file at the given path.
+    pub async fn read(path: PathBuf) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).await?;
+
+        task::spawn_blocking(move || Self::parse(&contents)).await?
+    }
+
+    /// Writes the setup into a template file at the given path.
+    pub async fn write(self, path: PathBuf) -> Result<(), Error> {
+        let contents = task::spawn_blocking(move || toml::to_string_pretty(&self)).await??;
+
+        fs::write(path, contents).await?;
+
+        Ok(())
+    }
+
+    fn parse(contents: &str) -> Result<Self, Error> {
+        let setup: Self = toml::from_str(contents)
+            .map_err(|_| Error::ImportFailed("the file is not a valid template"))?;
+
+        if setup.format > Self::FORMAT {
+            return Err(Error::ImportFailed(
+                "the template was made by a newer version of Icebreaker",
+            ));
+        }
+
+        Ok(setup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setups_are_parsed() {
+        let setup = Setup::parse(
+            r#"
+            format = 1
+            name = "Code reviewer"
+            persona = "You review Rust code."
+            starters = ["Review this function:"]
+
+            [guardrails]
+            tone = "Blunt"
+            "#,
+        )
+        .expect("template should parse");
+
+        assert_eq!(setup.name, "Code reviewer");
+        assert_eq!(setup.persona.as_deref(), Some("You review Rust code."));
+        assert_eq!(setup.guardrails.tone, "Blunt");
+        assert_eq!(setup.sampling, assistant::Sampling::default());
+        assert_eq!(setup.starters, ["Review this function:"]);
+
+        assert!(Setup::parse("format = 2\nname = \"Future\"").is_err());
+        assert!(Setup::parse("name = \"No format\"").is_err());
+    }
+}
@@ -0,0 +1,36 @@
+//! Helpers for the snapshot tests of the data formats.
+//!
+//! Snapshots live in the `fixtures` directory at the root of the crate. Run
+//! the tests with `UPDATE_SNAPSHOTS=1` to rewrite them after an intentional
+//! format change.
+use std::path::PathBuf;
+
+/// Returns the path of the given fixture.
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(name)
+}
+
+/// Reads the given fixture.
+pub fn read(name: &str) -> String {
+    std::fs::read_to_string(path(name))
+        .unwrap_or_else(|error| panic!("fixture {name} should be readable: {error}"))
+}
+
+/// Asserts that the given output matches the stored snapshot.
+pub fn assert(name: &str, actual: &str) {
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(path(name), format!("{}\n", actual.trim_end()))
+            .expect("snapshot should be writable");
+        return;
+    }
+
+    let expected = read(name);
+
+    assert_eq!(
+        expected.trim_end(),
+        actual.trim_end(),
+        "snapshot {name} does not match; run with UPDATE_SNAPSHOTS=1 to update it"
+    );
+}
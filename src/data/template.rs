@@ -0,0 +1,65 @@
+use crate::data::Error;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Variable {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Context<'a> {
+    pub model: &'a str,
+    pub variables: &'a [Variable],
+}
+
+impl Context<'_> {
+    fn resolve(&self, name: &str) -> Option<String> {
+        if let Some(variable) = self
+            .variables
+            .iter()
+            .find(|variable| variable.name.trim() == name)
+        {
+            return Some(variable.value.clone());
+        }
+
+        match name {
+            "date" => Some(chrono::Local::now().format("%Y-%m-%d").to_string()),
+            "time" => Some(chrono::Local::now().format("%H:%M").to_string()),
+            "model" => Some(self.model.to_owned()),
+            "os" => Some(std::env::consts::OS.to_owned()),
+            _ => None,
+        }
+    }
+}
+
+/// Substitutes every `{{variable}}` in the template.
+///
+/// Unknown variables are reported as errors instead of being
+/// sent to the model verbatim.
+pub fn render(template: &str, context: &Context<'_>) -> Result<String, Error> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+
+        output.push_str(&rest[..start]);
+
+        let name = rest[start + 2..start + end].trim();
+
+        let Some(value) = context.resolve(name) else {
+            return Err(Error::UnknownVariable(name.to_owned()));
+        };
+
+        output.push_str(&value);
+        rest = &rest[start + end + 2..];
+    }
+
+    output.push_str(rest);
+
+    Ok(output)
+}
@@ -0,0 +1,299 @@
+//! The colors of the app: one of the themes of iced or a custom palette.
+use crate::data::Error;
+
+use iced::theme::Palette;
+use iced::Color;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::process;
+
+use std::fmt;
+use std::path::PathBuf;
+
+/// The theme of the app.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    /// One of the themes of iced, by name.
+    Builtin(String),
+    /// One of the custom themes in the settings, by name.
+    Custom(String),
+    /// The light or dark theme in the settings, following the mode of the
+    /// system.
+    System,
+}
+
+impl Theme {
+    /// The theme of iced for this choice, given the custom themes.
+    ///
+    /// Unknown themes fall back to the default one, and so does
+    /// [`Theme::System`], which needs the [`Mode`] of the system to be
+    /// resolved.
+    pub fn resolve(&self, customs: &[Custom]) -> iced::Theme {
+        match self {
+            Theme::Builtin(name) => iced::Theme::ALL
+                .iter()
+                .find(|theme| theme.to_string() == *name)
+                .cloned()
+                .unwrap_or(iced::Theme::TokyoNight),
+            Theme::Custom(name) => customs
+                .iter()
+                .find(|custom| custom.name == *name)
+                .map(Custom::theme)
+                .unwrap_or(iced::Theme::TokyoNight),
+            Theme::System => iced::Theme::TokyoNight,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::Builtin(iced::Theme::TokyoNight.to_string())
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Theme::Builtin(name) => f.write_str(name),
+            Theme::Custom(name) => write!(f, "{name} (custom)"),
+            Theme::System => f.write_str("System"),
+        }
+    }
+}
+
+/// Whether the system prefers light or dark colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    Light,
+    #[default]
+    Dark,
+}
+
+impl Mode {
+    /// Detects the mode of the system with the tools of the desktop, if
+    /// possible.
+    pub async fn detect() -> Option<Self> {
+        if cfg!(target_os = "macos") {
+            // The key only exists in dark mode
+            let output = process::Command::new("defaults")
+                .args(["read", "-g", "AppleInterfaceStyle"])
+                .output()
+                .await
+                .ok()?;
+
+            return Some(Self::dark_if(
+                String::from_utf8_lossy(&output.stdout).contains("Dark"),
+            ));
+        }
+
+        if cfg!(target_os = "windows") {
+            let output = run(
+                "reg",
+                &[
+                    "query",
+                    r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+                    "/v",
+                    "AppsUseLightTheme",
+                ],
+            )
+            .await?;
+
+            return Some(Self::dark_if(output.contains("0x0")));
+        }
+
+        if let Some(scheme) = run(
+            "gsettings",
+            &["get", "org.gnome.desktop.interface", "color-scheme"],
+        )
+        .await
+        {
+            if !scheme.contains("default") {
+                return Some(Self::dark_if(scheme.contains("dark")));
+            }
+        }
+
+        let theme = run(
+            "gsettings",
+            &["get", "org.gnome.desktop.interface", "gtk-theme"],
+        )
+        .await?;
+
+        Some(Self::dark_if(theme.to_lowercase().contains("dark")))
+    }
+
+    fn dark_if(is_dark: bool) -> Self {
+        if is_dark {
+            Self::Dark
+        } else {
+            Self::Light
+        }
+    }
+}
+
+/// A palette made by the user, with its colors in hex notation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Custom {
+    pub name: String,
+    pub background: String,
+    pub text: String,
+    pub primary: String,
+    pub success: String,
+    pub danger: String,
+}
+
+/// A color of a [`Custom`] theme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Background,
+    Text,
+    Primary,
+    Success,
+    Danger,
+}
+
+impl Role {
+    pub const ALL: &'static [Self] = &[
+        Self::Background,
+        Self::Text,
+        Self::Primary,
+        Self::Success,
+        Self::Danger,
+    ];
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Background => "Background",
+            Role::Text => "Text",
+            Role::Primary => "Primary",
+            Role::Success => "Success",
+            Role::Danger => "Danger",
+        })
+    }
+}
+
+impl Custom {
+    /// Creates a custom theme with the colors of the given one.
+    pub fn new(name: String, theme: &iced::Theme) -> Self {
+        let palette = theme.palette();
+
+        Self {
+            name,
+            background: hex(palette.background),
+            text: hex(palette.text),
+            primary: hex(palette.primary),
+            success: hex(palette.success),
+            danger: hex(palette.danger),
+        }
+    }
+
+    /// Reads a custom theme from a TOML file, like the ones in the settings.
+    pub async fn import(path: PathBuf) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).await?;
+
+        let custom: Self =
+            toml::from_str(&contents).map_err(|_| Error::ImportFailed("the theme is not valid"))?;
+
+        if Role::ALL
+            .iter()
+            .any(|role| Color::parse(custom.color(*role)).is_none())
+        {
+            return Err(Error::ImportFailed("the theme has invalid colors"));
+        }
+
+        Ok(custom)
+    }
+
+    pub fn color(&self, role: Role) -> &str {
+        match role {
+            Role::Background => &self.background,
+            Role::Text => &self.text,
+            Role::Primary => &self.primary,
+            Role::Success => &self.success,
+            Role::Danger => &self.danger,
+        }
+    }
+
+    pub fn color_mut(&mut self, role: Role) -> &mut String {
+        match role {
+            Role::Background => &mut self.background,
+            Role::Text => &mut self.text,
+            Role::Primary => &mut self.primary,
+            Role::Success => &mut self.success,
+            Role::Danger => &mut self.danger,
+        }
+    }
+
+    /// The theme of iced with these colors.
+    ///
+    /// Invalid colors are taken from the default theme, so a theme can be
+    /// edited color by color.
+    pub fn theme(&self) -> iced::Theme {
+        let fallback = iced::Theme::TokyoNight.palette();
+        let color = |role, fallback| Color::parse(self.color(role)).unwrap_or(fallback);
+
+        iced::Theme::custom(
+            self.name.clone(),
+            Palette {
+                background: color(Role::Background, fallback.background),
+                text: color(Role::Text, fallback.text),
+                primary: color(Role::Primary, fallback.primary),
+                success: color(Role::Success, fallback.success),
+                danger: color(Role::Danger, fallback.danger),
+            },
+        )
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = process::Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    String::from_utf8(output.stdout).ok()
+}
+
+fn hex(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_themes_keep_their_colors() {
+        let custom = Custom::new("Night".to_owned(), &iced::Theme::TokyoNight);
+        let palette = iced::Theme::TokyoNight.palette();
+
+        assert_eq!(custom.color(Role::Background), hex(palette.background));
+        assert_eq!(custom.theme().palette().primary, palette.primary);
+
+        let edited = Custom {
+            primary: "#ff0000".to_owned(),
+            danger: "not a color".to_owned(),
+            ..custom.clone()
+        };
+
+        assert_eq!(
+            edited.theme().palette().primary,
+            Color::from_rgb8(255, 0, 0)
+        );
+        assert_eq!(edited.theme().palette().danger, palette.danger);
+
+        let theme = Theme::Custom("Night".to_owned());
+
+        assert_eq!(theme.resolve(&[custom]).to_string(), "Night");
+        assert_eq!(
+            Theme::Builtin("Dracula".to_owned()).resolve(&[]),
+            iced::Theme::Dracula
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use crate::data::assistant::ToolCall;
+use crate::data::mcp;
+use crate::data::web;
+
+use serde::Deserialize;
+use serde_json::json;
+
+/// A tool that models with native function calling may use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tool {
+    WebSearch,
+    Scrape,
+    Calculator,
+    /// A tool of a connected MCP server.
+    Mcp(mcp::Tool),
+}
+
+impl Tool {
+    /// The tools built into the app.
+    pub const ALL: &'static [Self] = &[Self::WebSearch, Self::Scrape, Self::Calculator];
+
+    /// The maximum length of the result of a tool, in characters.
+    const LIMIT: usize = 8_000;
+
+    /// The built-in tools, followed by the ones of the connected MCP
+    /// servers.
+    pub fn available() -> Vec<Self> {
+        Self::ALL
+            .iter()
+            .cloned()
+            .chain(mcp::tools().into_iter().map(Self::Mcp))
+            .collect()
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Tool::WebSearch => "web_search",
+            Tool::Scrape => "scrape",
+            Tool::Calculator => "calculator",
+            Tool::Mcp(tool) => &tool.name,
+        }
+    }
+
+    pub fn find(name: &str) -> Option<Self> {
+        Self::available()
+            .into_iter()
+            .find(|tool| tool.name() == name)
+    }
+
+    /// The OpenAI-compatible definition of the tool.
+    pub fn definition(&self) -> serde_json::Value {
+        let (description, parameters) = match self {
+            Tool::WebSearch => (
+                "Searches the web and returns the top results with their URLs.",
+                json!({
+                    "query": {
+                        "type": "string",
+                        "description": "The search terms",
+                    }
+                }),
+            ),
+            Tool::Scrape => (
+                "Fetches a web page and returns its text content.",
+                json!({
+                    "url": {
+                        "type": "string",
+                        "description": "The URL of the page",
+                    }
+                }),
+            ),
+            Tool::Calculator => (
+                "Evaluates an arithmetic expression with + - * / % ^ and parentheses.",
+                json!({
+                    "expression": {
+                        "type": "string",
+                        "description": "The expression to evaluate, like (2 + 3) * 4",
+                    }
+                }),
+            ),
+            Tool::Mcp(tool) => {
+                return json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.schema,
+                    }
+                });
+            }
+        };
+
+        let required: Vec<String> = parameters
+            .as_object()
+            .map(|properties| properties.keys().cloned().collect())
+            .unwrap_or_default();
+
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name(),
+                "description": description,
+                "parameters": {
+                    "type": "object",
+                    "properties": parameters,
+                    "required": required,
+                }
+            }
+        })
+    }
+}
+
+/// Runs the given tool call and returns its result for the model.
+///
+/// Failures are returned as text, so the model can recover from them. Web
+/// content and the results of MCP tools are guarded, unless disabled in the
+/// search settings.
+#[tracing::instrument(skip_all, fields(tool = call.name))]
+pub async fn call(call: &ToolCall, search: &web::Search) -> String {
+    #[derive(Deserialize)]
+    struct Query {
+        query: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Page {
+        url: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Expression {
+        expression: String,
+    }
+
+    let Some(tool) = Tool::find(&call.name) else {
+        return format!("Error: unknown tool {}", call.name);
+    };
+
+    let result = match &tool {
+        Tool::WebSearch => match serde_json::from_str::<Query>(&call.arguments) {
+            Ok(Query { query }) => web::search(search, &query).await,
+            Err(error) => Err(error.to_string()),
+        },
+        Tool::Scrape => match serde_json::from_str::<Page>(&call.arguments) {
+            Ok(Page { url }) => web::scrape(&url).await,
+            Err(error) => Err(error.to_string()),
+        },
+        Tool::Calculator => match serde_json::from_str::<Expression>(&call.arguments) {
+            Ok(Expression { expression }) => calculate(&expression).map(|value| value.to_string()),
+            Err(error) => Err(error.to_string()),
+        },
+        Tool::Mcp(tool) => mcp::call(tool, &call.arguments).await,
+    };
+
+    let output: String = match result {
+        Ok(output) => output.chars().take(Tool::LIMIT).collect(),
+        Err(error) => return format!("Error: {error}"),
+    };
+
+    if !search.guard || tool == Tool::Calculator {
+        return output;
+    }
+
+    let (guarded, removed) = web::guard(tool.name(), &output);
+
+    if removed > 0 {
+        tracing::warn!(removed, "removed possible instructions from web content");
+    }
+
+    guarded
+}
+
+/// Evaluates an arithmetic expression.
+fn calculate(expression: &str) -> Result<f64, String> {
+    struct Parser<'a> {
+        tokens: std::iter::Peekable<std::str::CharIndices<'a>>,
+        expression: &'a str,
+    }
+
+    impl Parser<'_> {
+        /// How deeply operators and parentheses can nest, so expressions
+        /// cannot overflow the stack.
+        const MAX_DEPTH: usize = 64;
+
+        fn skip_whitespace(&mut self) {
+            while self.tokens.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        }
+
+        fn peek(&mut self) -> Option<char> {
+            self.skip_whitespace();
+            self.tokens.peek().map(|(_, c)| *c)
+        }
+
+        fn sum(&mut self, depth: usize) -> Result<f64, String> {
+            let mut value = self.product(depth)?;
+
+            while let Some(operator @ ('+' | '-')) = self.peek() {
+                let _ = self.tokens.next();
+                let rhs = self.product(depth)?;
+
+                value = if operator == '+' {
+                    value + rhs
+                } else {
+                    value - rhs
+                };
+            }
+
+            Ok(value)
+        }
+
+        fn product(&mut self, depth: usize) -> Result<f64, String> {
+            let mut value = self.unary(depth)?;
+
+            while let Some(operator @ ('*' | '/' | '%')) = self.peek() {
+                let _ = self.tokens.next();
+                let rhs = self.unary(depth)?;
+
+                value = match operator {
+                    '*' => value * rhs,
+                    '/' => value / rhs,
+                    _ => value % rhs,
+                };
+            }
+
+            Ok(value)
+        }
+
+        fn unary(&mut self, depth: usize) -> Result<f64, String> {
+            if depth > Self::MAX_DEPTH {
+                return Err("expression is nested too deeply".to_owned());
+            }
+
+            match self.peek() {
+                Some('-') => {
+                    let _ = self.tokens.next();
+
+                    Ok(-self.unary(depth + 1)?)
+                }
+                Some('+') => {
+                    let _ = self.tokens.next();
+
+                    self.unary(depth + 1)
+                }
+                _ => self.power(depth),
+            }
+        }
+
+        fn power(&mut self, depth: usize) -> Result<f64, String> {
+            let base = self.atom(depth)?;
+
+            if self.peek() == Some('^') {
+                let _ = self.tokens.next();
+
+                return Ok(base.powf(self.unary(depth + 1)?));
+            }
+
+            Ok(base)
+        }
+
+        fn atom(&mut self, depth: usize) -> Result<f64, String> {
+            match self.peek() {
+                Some('(') => {
+                    let _ = self.tokens.next();
+                    let value = self.sum(depth + 1)?;
+
+                    match self.peek() {
+                        Some(')') => {
+                            let _ = self.tokens.next();
+
+                            Ok(value)
+                        }
+                        _ => Err("missing closing parenthesis".to_owned()),
+                    }
+                }
+                Some(c) if c.is_ascii_digit() || c == '.' => {
+                    let (start, _) = *self.tokens.peek().expect("peeked digit");
+                    let mut end = start;
+
+                    while let Some((index, c)) = self
+                        .tokens
+                        .next_if(|(_, c)| c.is_ascii_digit() || *c == '.')
+                    {
+                        end = index + c.len_utf8();
+                    }
+
+                    self.expression[start..end]
+                        .parse()
+                        .map_err(|_| format!("invalid number {}", &self.expression[start..end]))
+                }
+                Some(c) => Err(format!("unexpected {c}")),
+                None => Err("unexpected end of expression".to_owned()),
+            }
+        }
+    }
+
+    let mut parser = Parser {
+        tokens: expression.char_indices().peekable(),
+        expression,
+    };
+
+    let value = parser.sum(0)?;
+
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected {c}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calculator_follows_precedence() {
+        assert_eq!(calculate("2 + 3 * 4"), Ok(14.0));
+        assert_eq!(calculate("(2 + 3) * 4"), Ok(20.0));
+        assert_eq!(calculate("2 ^ 3 ^ 2"), Ok(512.0));
+        assert_eq!(calculate("-2 ^ 2"), Ok(-4.0));
+        assert_eq!(calculate("2 ^ -1"), Ok(0.5));
+        assert_eq!(calculate("10 % 4 / 2"), Ok(1.0));
+        assert_eq!(calculate("1.5 * 2"), Ok(3.0));
+    }
+
+    #[test]
+    fn calculator_rejects_deep_nesting() {
+        assert_eq!(
+            calculate(&format!("{}1{}", "(".repeat(64), ")".repeat(64))),
+            Ok(1.0)
+        );
+        assert!(calculate(&format!("{}1", "(".repeat(10_000))).is_err());
+        assert!(calculate(&format!("{}1", "-".repeat(10_000))).is_err());
+        assert!(calculate(&"2^".repeat(10_000)).is_err());
+    }
+
+    #[test]
+    fn calculator_rejects_invalid_expressions() {
+        assert!(calculate("2 +").is_err());
+        assert!(calculate("(2 + 3").is_err());
+        assert!(calculate("2 3").is_err());
+        assert!(calculate("1..2").is_err());
+    }
+}
@@ -0,0 +1,17 @@
+//! Spans of the work done by assistants, for subscribers of [`tracing`].
+//!
+//! Spans close when their work finishes or is dropped, so their timings
+//! measure the whole operation.
+use futures::Stream;
+use tracing::Span;
+
+/// Enters the given span every time the stream is polled.
+pub fn stream<T>(span: Span, stream: impl Stream<Item = T>) -> impl Stream<Item = T> {
+    let mut stream = Box::pin(stream);
+
+    futures::stream::poll_fn(move |context| {
+        let _entered = span.enter();
+
+        stream.as_mut().poll_next(context)
+    })
+}
@@ -0,0 +1,87 @@
+use crate::data::{self, Error};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// A feature whose usage is counted.
+///
+/// Counts are stored next to the settings and never leave the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Feature {
+    ChatSearch,
+    Find,
+    Copy,
+    Regenerate,
+    Branch,
+    Export,
+    RestoreSnapshot,
+    RunCode,
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Feature::ChatSearch => "Chat search",
+            Feature::Find => "Find in chat",
+            Feature::Copy => "Copy",
+            Feature::Regenerate => "Regenerate",
+            Feature::Branch => "Branch",
+            Feature::Export => "Export",
+            Feature::RestoreSnapshot => "Restore history",
+            Feature::RunCode => "Run code",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub since: Option<chrono::DateTime<chrono::Local>>,
+    pub counts: BTreeMap<Feature, u64>,
+}
+
+impl Stats {
+    async fn path() -> Result<PathBuf, io::Error> {
+        Ok(data::data_dir().await?.join("usage.json"))
+    }
+
+    pub async fn fetch() -> Result<Self, Error> {
+        let Ok(bytes) = fs::read(Self::path().await?).await else {
+            return Ok(Self::default());
+        };
+
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub async fn reset() -> Result<(), Error> {
+        match fs::remove_file(Self::path().await?).await {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Counts one use of the given feature.
+///
+/// Failing to write the counts never fails the feature itself.
+pub async fn record(feature: Feature) {
+    let result: Result<(), Error> = async {
+        let mut stats = Stats::fetch().await.unwrap_or_default();
+
+        let _ = stats.since.get_or_insert_with(chrono::Local::now);
+        *stats.counts.entry(feature).or_default() += 1;
+
+        fs::write(Stats::path().await?, serde_json::to_vec_pretty(&stats)?).await?;
+
+        Ok(())
+    }
+    .await;
+
+    if let Err(error) = result {
+        let _ = dbg!(error);
+    }
+}
@@ -0,0 +1,153 @@
+//! The geometry of the main window, restored between sessions.
+use iced::window;
+use iced::{Point, Size};
+use serde::{Deserialize, Serialize};
+
+/// The size, position, and state of the window when the app was closed.
+///
+/// Sizes and positions are in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Window {
+    pub size: Option<[f32; 2]>,
+    pub position: Option<[f32; 2]>,
+    pub maximized: bool,
+}
+
+impl Window {
+    const MIN_SIZE: Size = Size::new(400.0, 300.0);
+
+    /// No desktop spans further than this, even across many monitors.
+    const MAX_EXTENT: f32 = 16_384.0;
+
+    /// The least of the window that must stay inside the desktop to be
+    /// grabbed and moved back.
+    const VISIBLE: f32 = 100.0;
+
+    /// The settings of a window with this geometry; it still needs to be
+    /// maximized once open, if it was.
+    ///
+    /// Sizes are kept within reasonable bounds, and positions that would
+    /// leave the window out of reach are dropped, since the monitor they
+    /// belonged to may be gone.
+    pub fn settings(self) -> window::Settings {
+        let defaults = window::Settings::default();
+
+        let size = self
+            .size
+            .map(|[width, height]| Size::new(width, height))
+            .filter(|size| size.width.is_finite() && size.height.is_finite())
+            .map(|size| {
+                Size::new(
+                    size.width.clamp(Self::MIN_SIZE.width, Self::MAX_EXTENT),
+                    size.height.clamp(Self::MIN_SIZE.height, Self::MAX_EXTENT),
+                )
+            })
+            .unwrap_or(defaults.size);
+
+        let position = self
+            .position
+            .map(|[x, y]| Point::new(x, y))
+            .filter(|position| Self::is_reachable(*position, size))
+            .map_or(window::Position::Default, window::Position::Specific);
+
+        window::Settings {
+            size,
+            position,
+            // The window is saved before closing
+            exit_on_close_request: false,
+            ..defaults
+        }
+    }
+
+    /// Whether a window at the given position can still be grabbed, as
+    /// far as can be told without knowing the monitors.
+    ///
+    /// Monitors can sit on any side of the primary one, so only positions
+    /// beyond any plausible desktop are discarded.
+    fn is_reachable(position: Point, size: Size) -> bool {
+        position.x.is_finite()
+            && position.y.is_finite()
+            && position.x + size.width >= -Self::MAX_EXTENT + Self::VISIBLE
+            && position.x <= Self::MAX_EXTENT - Self::VISIBLE
+            && position.y >= -Self::MAX_EXTENT
+            && position.y <= Self::MAX_EXTENT - Self::VISIBLE
+    }
+
+    /// The geometry of a window closed with the given state.
+    ///
+    /// The size and position of a maximized window fill the monitor, so the
+    /// previous ones are kept to restore it once unmaximized.
+    pub fn closed(self, size: Size, position: Option<Point>, maximized: bool) -> Self {
+        if maximized {
+            return Self { maximized, ..self };
+        }
+
+        Self {
+            size: Some([size.width, size.height]),
+            position: position.map(|position| [position.x, position.y]),
+            maximized,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geometry_is_restored_within_reach() {
+        let window = Window {
+            size: Some([1280.0, 800.0]),
+            position: Some([-1920.0, 40.0]),
+            maximized: false,
+        };
+
+        let settings = window.settings();
+
+        assert_eq!(settings.size, Size::new(1280.0, 800.0));
+        assert_eq!(
+            settings.position,
+            window::Position::Specific(Point::new(-1920.0, 40.0))
+        );
+
+        let lost = Window {
+            size: Some([100.0, f32::INFINITY]),
+            position: Some([50_000.0, 40.0]),
+            maximized: false,
+        };
+
+        let settings = lost.settings();
+
+        assert_eq!(settings.size, window::Settings::default().size);
+        assert_eq!(settings.position, window::Position::Default);
+
+        let tiny = Window {
+            size: Some([100.0, 100.0]),
+            ..Window::default()
+        };
+
+        assert_eq!(tiny.settings().size, Window::MIN_SIZE);
+    }
+
+    #[test]
+    fn maximized_windows_keep_their_previous_geometry() {
+        let window = Window {
+            size: Some([1280.0, 800.0]),
+            position: Some([100.0, 100.0]),
+            maximized: false,
+        };
+
+        let maximized = window.closed(Size::new(2560.0, 1440.0), Some(Point::ORIGIN), true);
+
+        assert_eq!(maximized.size, window.size);
+        assert_eq!(maximized.position, window.position);
+        assert!(maximized.maximized);
+
+        let moved = maximized.closed(Size::new(800.0, 600.0), Some(Point::new(10.0, 20.0)), false);
+
+        assert_eq!(moved.size, Some([800.0, 600.0]));
+        assert_eq!(moved.position, Some([10.0, 20.0]));
+        assert!(!moved.maximized);
+    }
+}
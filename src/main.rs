@@ -3,84 +3,244 @@
 mod data;
 mod icon;
 mod screen;
+mod tray;
 mod widget;
 
 use crate::data::assistant;
-use crate::data::{Chat, Error};
+use crate::data::diagnostics;
+use crate::data::hardware::{self, Memory};
+use crate::data::keymap;
+use crate::data::mcp;
+use crate::data::pool::Pool;
+use crate::data::theme;
+use crate::data::{Chat, Error, Settings};
 use crate::screen::boot;
 use crate::screen::conversation;
+use crate::screen::downloads;
+use crate::screen::embeddings;
+use crate::screen::gallery;
+use crate::screen::preloads;
 use crate::screen::search;
+use crate::screen::settings;
 use crate::screen::Screen;
 
+use iced::keyboard;
 use iced::system;
+use iced::window;
 use iced::{Element, Subscription, Task, Theme};
 
 pub fn main() -> iced::Result {
+    use tracing_subscriber::filter::{LevelFilter, Targets};
+    use tracing_subscriber::fmt::{self, format::FmtSpan};
+    use tracing_subscriber::prelude::*;
+
+    // The app keeps its recent logs for diagnostics bundles
+    let logs = fmt::layer()
+        .with_ansi(false)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(diagnostics::Sink)
+        .with_filter(
+            Targets::new()
+                .with_target(env!("CARGO_CRATE_NAME"), LevelFilter::DEBUG)
+                .with_default(LevelFilter::WARN),
+        );
+
+    #[cfg(feature = "trace")]
+    let logs = logs.and_then(
+        fmt::layer()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(std::io::stderr)
+            .with_filter(LevelFilter::INFO),
+    );
+
+    tracing_subscriber::registry().with(logs).init();
+
+    let window = Settings::fetch_blocking().window;
+
     iced::application(Icebreaker::title, Icebreaker::update, Icebreaker::view)
+        .window(window.settings())
         .font(icon::FONT)
         .subscription(Icebreaker::subscription)
         .theme(Icebreaker::theme)
+        .scale_factor(Icebreaker::scale_factor)
         .run_with(Icebreaker::new)
 }
 
 struct Icebreaker {
     screen: Screen,
     system: Option<system::Information>,
+    memory: Option<Memory>,
+    settings: Settings,
+    settings_screen: Option<screen::Settings>,
+    embeddings_screen: Option<screen::Embeddings>,
+    downloads: screen::Downloads,
+    preloads: screen::Preloads,
+    pool: Pool,
+    /// The mode of the system, for the system theme.
+    mode: theme::Mode,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     Loaded {
+        settings: Box<Settings>,
         last_chat: Result<Chat, Error>,
         system: Box<system::Information>,
+        video_memory: Option<u64>,
     },
     Escape,
     Search(search::Message),
     Boot(boot::Message),
     Conversation(conversation::Message),
+    Settings(settings::Message),
+    SettingsSaved(Result<(), Error>),
+    Downloads(downloads::Message),
+    Preloads(preloads::Message),
+    Embeddings(embeddings::Message),
+    Gallery(gallery::Message),
+    KeyPressed(keyboard::Key, keyboard::Modifiers),
+    DetectMode,
+    ModeDetected(Option<theme::Mode>),
+    CloseRequested(window::Id),
+    Tray(tray::Event),
+    Quit(window::Id),
+    Closing(window::Id, data::window::Window),
 }
 
 impl Icebreaker {
     pub fn new() -> (Self, Task<Message>) {
+        if std::env::args().any(|arg| arg == screen::Gallery::FLAG) {
+            return (
+                Self {
+                    screen: Screen::Gallery(screen::Gallery::new()),
+                    system: None,
+                    memory: None,
+                    settings: Settings::default(),
+                    settings_screen: None,
+                    embeddings_screen: None,
+                    downloads: screen::Downloads::new(),
+                    preloads: screen::Preloads::new(),
+                    pool: Pool::default(),
+                    mode: theme::Mode::default(),
+                },
+                Task::none(),
+            );
+        }
+
         (
             Self {
                 screen: Screen::Loading,
                 system: None,
+                memory: None,
+                settings: Settings::default(),
+                settings_screen: None,
+                embeddings_screen: None,
+                downloads: screen::Downloads::new(),
+                preloads: screen::Preloads::new(),
+                pool: Pool::default(),
+                mode: theme::Mode::default(),
             },
-            Task::future(Chat::fetch_last_opened()).then(|last_chat| {
-                system::fetch_information()
-                    .map(Box::new)
-                    .map(move |system| Message::Loaded {
-                        last_chat: last_chat.clone(),
-                        system,
-                    })
-            }),
+            Task::batch([
+                Task::future(async {
+                    let settings = Settings::fetch()
+                        .await
+                        .unwrap_or_default()
+                        .migrate_token()
+                        .await;
+
+                    if let Some(token) = &settings.legacy_hugging_face_token {
+                        assistant::authenticate(token);
+                    } else if settings.hugging_face_token_set {
+                        if let Err(error) = assistant::log_in().await {
+                            let _ = dbg!(error);
+                        }
+                    }
+
+                    (
+                        settings,
+                        Chat::fetch_last_opened().await,
+                        hardware::video_memory().await,
+                    )
+                })
+                .then(|(settings, last_chat, video_memory)| {
+                    system::fetch_information()
+                        .map(Box::new)
+                        .map(move |system| Message::Loaded {
+                            settings: Box::new(settings.clone()),
+                            last_chat: last_chat.clone(),
+                            system,
+                            video_memory,
+                        })
+                }),
+                Task::perform(theme::Mode::detect(), Message::ModeDetected),
+                tray::show().discard(),
+            ]),
         )
     }
 
     fn title(&self) -> String {
+        if let Some(settings) = &self.settings_screen {
+            return settings.title();
+        }
+
+        if let Some(embeddings) = &self.embeddings_screen {
+            return embeddings.title();
+        }
+
         match &self.screen {
             Screen::Loading => "Icebreaker".to_owned(),
             Screen::Search(search) => search.title(),
             Screen::Boot(boot) => boot.title(),
             Screen::Conversation(conversation) => conversation.title(),
+            Screen::Gallery(gallery) => gallery.title(),
         }
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
-            Message::Loaded { last_chat, system } => {
+            Message::Loaded {
+                settings,
+                last_chat,
+                system,
+                video_memory,
+            } => {
                 let backend = assistant::Backend::detect(&system.graphics_adapter);
+                self.memory = Some(Memory {
+                    ram: system.memory_total,
+                    // The GPU of Apple Silicon can use most of the unified memory
+                    vram: video_memory.or((backend == assistant::Backend::Metal)
+                        .then_some(system.memory_total / 3 * 2)),
+                });
                 self.system = Some(*system);
+                self.settings = *settings;
+                widget::icon::set_appearance(self.settings.appearance);
+
+                let servers = Task::future(mcp::connect_all(self.settings.mcp.clone())).discard();
+
+                let maximize = if self.settings.window.maximized {
+                    window::get_oldest().and_then(|id| window::maximize(id, true))
+                } else {
+                    Task::none()
+                };
 
                 if let Ok(last_chat) = last_chat {
-                    let (conversation, task) = screen::Conversation::open(last_chat, backend);
+                    let (conversation, task) = screen::Conversation::open(
+                        last_chat,
+                        backend,
+                        self.settings.clone(),
+                        self.pool.clone(),
+                    );
 
-                    self.screen = Screen::Conversation(conversation);
+                    self.screen = Screen::Conversation(Box::new(conversation));
 
-                    task.map(Message::Conversation)
+                    Task::batch([
+                        task.map(Message::Conversation),
+                        self.preload_summarizer(),
+                        maximize,
+                        servers,
+                    ])
                 } else {
-                    self.search()
+                    Task::batch([self.search(), self.preload_summarizer(), maximize, servers])
                 }
             }
             Message::Search(message) => {
@@ -90,13 +250,50 @@ impl Icebreaker {
                     match action {
                         search::Action::None => Task::none(),
                         search::Action::Run(task) => task.map(Message::Search),
+                        search::Action::OpenSettings => self.open_settings(),
+                        search::Action::SaveBootOptions(options) => {
+                            self.settings
+                                .boot_options
+                                .retain(|saved| saved.file != options.file);
+
+                            if !options.is_empty() {
+                                self.settings.boot_options.push(options);
+                            }
+
+                            Task::perform(self.settings.clone().save(), Message::SettingsSaved)
+                        }
+                        search::Action::Download(file) => {
+                            self.downloads.push(file).map(Message::Downloads)
+                        }
+                        search::Action::Downloads(message) => {
+                            self.downloads.update(message).map(Message::Downloads)
+                        }
                         search::Action::Boot(model) => {
-                            let (boot, task) = screen::Boot::new(model, self.system.as_ref());
+                            let (boot, task) =
+                                screen::Boot::new(model, self.system.as_ref(), self.memory);
 
                             self.screen = Screen::Boot(boot);
 
                             task.map(Message::Boot)
                         }
+                        search::Action::BootFile(file) => {
+                            let backend = self
+                                .system
+                                .as_ref()
+                                .map(|system| assistant::Backend::detect(&system.graphics_adapter))
+                                .unwrap_or(assistant::Backend::Cpu);
+
+                            let (conversation, task) = screen::Conversation::new(
+                                file,
+                                backend,
+                                self.settings.clone(),
+                                self.pool.clone(),
+                            );
+
+                            self.screen = Screen::Conversation(Box::new(conversation));
+
+                            task.map(Message::Conversation)
+                        }
                     }
                 } else {
                     Task::none()
@@ -108,13 +305,39 @@ impl Icebreaker {
 
                     match action {
                         boot::Action::None => Task::none(),
-                        boot::Action::Boot { file, backend } => {
-                            let (conversation, task) = screen::Conversation::new(file, backend);
+                        boot::Action::Run(task) => task.map(Message::Boot),
+                        boot::Action::Boot {
+                            file,
+                            backend,
+                            raw_completion,
+                        } => {
+                            let (mut conversation, task) = screen::Conversation::new(
+                                file,
+                                backend,
+                                self.settings.clone(),
+                                self.pool.clone(),
+                            );
 
-                            self.screen = Screen::Conversation(conversation);
+                            conversation.use_raw_completion(raw_completion);
+
+                            self.screen = Screen::Conversation(Box::new(conversation));
 
                             task.map(Message::Conversation)
                         }
+                        boot::Action::Download(file) => Task::batch([
+                            self.downloads.push(file).map(Message::Downloads),
+                            self.search(),
+                        ]),
+                        boot::Action::Convert(file) => Task::batch([
+                            self.downloads.convert(file).map(Message::Downloads),
+                            self.search(),
+                        ]),
+                        boot::Action::Quantize { source, target } => Task::batch([
+                            self.downloads
+                                .quantize(source, target)
+                                .map(Message::Downloads),
+                            self.search(),
+                        ]),
                         boot::Action::Abort => self.search(),
                     }
                 } else {
@@ -129,34 +352,266 @@ impl Icebreaker {
                         conversation::Action::None => Task::none(),
                         conversation::Action::Run(task) => task.map(Message::Conversation),
                         conversation::Action::Back => self.search(),
+                        conversation::Action::OpenSettings => self.open_settings(),
+                        conversation::Action::Preload { file, backend } => {
+                            let launch = self.settings.launch(&file, backend);
+
+                            self.preloads
+                                .push(file, launch, &self.pool, conversation.port())
+                                .map(Message::Preloads)
+                        }
+                        conversation::Action::PreloadOnHover { file, backend } => {
+                            let running = self.pool.files();
+
+                            // Only models that fit next to the running ones
+                            let fits = self.memory.is_some_and(|memory| {
+                                memory.fits_together(
+                                    &[&file, conversation.file()]
+                                        .into_iter()
+                                        .chain(&running)
+                                        .collect::<Vec<_>>(),
+                                )
+                            });
+
+                            if !fits || &file == conversation.file() {
+                                return Task::none();
+                            }
+
+                            let launch = self.settings.launch(&file, backend);
+
+                            self.preloads
+                                .hover(file, launch, &self.pool, conversation.port())
+                                .map(Message::Preloads)
+                        }
+                        conversation::Action::CancelHoverPreload(file) => self
+                            .preloads
+                            .unhover(&file, &self.pool, conversation.port())
+                            .map(Message::Preloads),
+                        conversation::Action::Preloads(message) => {
+                            self.update(Message::Preloads(message))
+                        }
                     }
                 } else {
                     Task::none()
                 }
             }
-            Message::Escape => {
-                if matches!(self.screen, Screen::Search(_)) {
+            Message::Settings(message) => {
+                if let Some(settings) = &mut self.settings_screen {
+                    match settings.update(message) {
+                        settings::Action::None => Task::none(),
+                        settings::Action::Change(settings) => {
+                            let settings = *settings;
+                            self.settings = settings.clone();
+                            widget::icon::set_appearance(settings.appearance);
+
+                            if let Screen::Conversation(conversation) = &mut self.screen {
+                                conversation.update_settings(settings.clone());
+                            }
+
+                            let detect_mode = if settings.theme == theme::Theme::System {
+                                Task::perform(theme::Mode::detect(), Message::ModeDetected)
+                            } else {
+                                Task::none()
+                            };
+
+                            Task::batch([
+                                Task::perform(settings.save(), Message::SettingsSaved),
+                                self.preload_summarizer(),
+                                detect_mode,
+                            ])
+                        }
+                        settings::Action::Run(task) => task.map(Message::Settings),
+                        settings::Action::OpenEmbeddings => {
+                            self.settings_screen = None;
+                            self.embeddings_screen =
+                                Some(screen::Embeddings::new(&self.settings.knowledge));
+
+                            Task::none()
+                        }
+                        settings::Action::Close => {
+                            self.settings_screen = None;
+
+                            Task::none()
+                        }
+                    }
+                } else {
                     Task::none()
+                }
+            }
+            Message::Downloads(message) => self.downloads.update(message).map(Message::Downloads),
+            Message::Preloads(message) => {
+                let port = match &self.screen {
+                    Screen::Conversation(conversation) => conversation.port(),
+                    _ => None,
+                };
+
+                self.preloads
+                    .update(message, &self.pool, port)
+                    .map(Message::Preloads)
+            }
+            Message::Embeddings(message) => {
+                if let Some(embeddings) = &mut self.embeddings_screen {
+                    match embeddings.update(message) {
+                        embeddings::Action::None => Task::none(),
+                        embeddings::Action::Run(task) => task.map(Message::Embeddings),
+                        embeddings::Action::Close => {
+                            self.embeddings_screen = None;
+
+                            Task::none()
+                        }
+                    }
+                } else {
+                    Task::none()
+                }
+            }
+            Message::Gallery(message) => {
+                if let Screen::Gallery(gallery) = &mut self.screen {
+                    gallery.update(message).map(Message::Gallery)
                 } else {
-                    self.search()
+                    Task::none()
                 }
             }
+            Message::SettingsSaved(Ok(())) => Task::none(),
+            Message::SettingsSaved(Err(error)) => {
+                dbg!(error);
+
+                Task::none()
+            }
+            Message::CloseRequested(id) => {
+                // The models keep running until quit, from the tray if any
+                if self.settings.run_in_background {
+                    return if tray::SUPPORTED {
+                        window::change_mode(id, window::Mode::Hidden)
+                    } else {
+                        window::minimize(id, true)
+                    };
+                }
+
+                self.update(Message::Quit(id))
+            }
+            Message::Tray(tray::Event::Toggle) => window::get_oldest().and_then(|id| {
+                window::get_mode(id).then(move |mode| match mode {
+                    window::Mode::Hidden => reveal(id),
+                    _ => window::change_mode(id, window::Mode::Hidden),
+                })
+            }),
+            Message::Tray(tray::Event::NewChat) => {
+                let new_chat = match &self.screen {
+                    Screen::Conversation(_) => self.update(Message::Conversation(
+                        conversation::Message::Shortcut(keymap::Command::NewChat),
+                    )),
+                    _ => Task::none(),
+                };
+
+                Task::batch([window::get_oldest().and_then(reveal), new_chat])
+            }
+            Message::Tray(tray::Event::Quit) => {
+                window::get_oldest().and_then(|id| Task::done(Message::Quit(id)))
+            }
+            Message::Quit(id) => {
+                // The settings are not loaded yet
+                if matches!(self.screen, Screen::Loading | Screen::Gallery(_)) {
+                    return window::close(id);
+                }
+
+                let last = self.settings.window;
+
+                window::get_maximized(id).then(move |maximized| {
+                    window::get_size(id).then(move |size| {
+                        window::get_position(id).map(move |position| {
+                            Message::Closing(id, last.closed(size, position, maximized))
+                        })
+                    })
+                })
+            }
+            Message::Closing(id, geometry) => {
+                self.settings.window = geometry;
+
+                Task::future(self.settings.clone().save()).then(move |result| {
+                    if let Err(error) = result {
+                        let _ = dbg!(error);
+                    }
+
+                    window::close(id)
+                })
+            }
+            Message::DetectMode => Task::perform(theme::Mode::detect(), Message::ModeDetected),
+            Message::ModeDetected(mode) => {
+                if let Some(mode) = mode {
+                    self.mode = mode;
+                }
+
+                Task::none()
+            }
+            Message::KeyPressed(key, modifiers) => {
+                let Some(command) = self.settings.keymap.command(&key, modifiers) else {
+                    return Task::none();
+                };
+
+                match command {
+                    keymap::Command::OpenSettings => self.open_settings(),
+                    keymap::Command::Quit => {
+                        window::get_oldest().and_then(|id| Task::done(Message::Quit(id)))
+                    }
+                    _ if self.settings_screen.is_some() || self.embeddings_screen.is_some() => {
+                        Task::none()
+                    }
+                    command => match &self.screen {
+                        Screen::Conversation(_) => self.update(Message::Conversation(
+                            conversation::Message::Shortcut(command),
+                        )),
+                        _ => Task::none(),
+                    },
+                }
+            }
+            Message::Escape if self.settings_screen.is_some() => {
+                self.settings_screen = None;
+
+                Task::none()
+            }
+            Message::Escape if self.embeddings_screen.is_some() => {
+                self.embeddings_screen = None;
+
+                Task::none()
+            }
+            Message::Escape => match &self.screen {
+                Screen::Search(_) | Screen::Gallery(_) => Task::none(),
+                Screen::Conversation(conversation) if conversation.is_finding() => {
+                    self.update(Message::Conversation(conversation::Message::CloseFind))
+                }
+                _ => self.search(),
+            },
         }
     }
 
     fn view(&self) -> Element<Message> {
+        if let Some(settings) = &self.settings_screen {
+            return settings.view().map(Message::Settings);
+        }
+
+        if let Some(embeddings) = &self.embeddings_screen {
+            return embeddings.view().map(Message::Embeddings);
+        }
+
         match &self.screen {
             Screen::Loading => screen::loading(),
-            Screen::Search(search) => search.view().map(Message::Search),
+            Screen::Search(search) => search
+                .view(
+                    &self.downloads,
+                    self.settings.locale,
+                    self.settings.appearance,
+                )
+                .map(Message::Search),
             Screen::Boot(boot) => boot.view(self.theme()).map(Message::Boot),
-            Screen::Conversation(conversation) => {
-                conversation.view(&self.theme()).map(Message::Conversation)
-            }
+            Screen::Conversation(conversation) => conversation
+                .view(&self.theme(), &self.preloads)
+                .map(Message::Conversation),
+            Screen::Gallery(gallery) => gallery.view(&self.theme()).map(Message::Gallery),
         }
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        use iced::keyboard;
+        use iced::event::{self, Event};
 
         let screen = match &self.screen {
             Screen::Loading => Subscription::none(),
@@ -165,6 +620,7 @@ impl Icebreaker {
             Screen::Conversation(conversation) => {
                 conversation.subscription().map(Message::Conversation)
             }
+            Screen::Gallery(gallery) => gallery.subscription().map(Message::Gallery),
         };
 
         let hotkeys = keyboard::on_key_press(|key, _modifiers| match key {
@@ -172,18 +628,106 @@ impl Icebreaker {
             _ => None,
         });
 
-        Subscription::batch([screen, hotkeys])
+        let shortcuts = event::listen_with(|event, status, _window| match (event, status) {
+            (
+                Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }),
+                event::Status::Ignored,
+            ) if modifiers.command() || modifiers.alt() => {
+                Some(Message::KeyPressed(key, modifiers))
+            }
+            _ => None,
+        });
+
+        // The mode of the system is polled, since iced does not report its changes
+        let mode = if self.settings.theme == theme::Theme::System {
+            iced::time::every(iced::time::Duration::from_secs(5)).map(|_| Message::DetectMode)
+        } else {
+            Subscription::none()
+        };
+
+        Subscription::batch([
+            screen,
+            hotkeys,
+            shortcuts,
+            mode,
+            window::close_requests().map(Message::CloseRequested),
+            tray::events().map(Message::Tray),
+        ])
     }
 
     fn theme(&self) -> Theme {
-        Theme::TokyoNight
+        self.settings.theme(self.mode)
+    }
+
+    fn scale_factor(&self) -> f64 {
+        self.settings.appearance.scale.factor()
+    }
+
+    fn open_settings(&mut self) -> Task<Message> {
+        let assistant = match &self.screen {
+            Screen::Conversation(conversation) => conversation.assistant().cloned(),
+            _ => None,
+        };
+
+        let backend = self
+            .system
+            .as_ref()
+            .map(|system| assistant::Backend::detect(&system.graphics_adapter))
+            .unwrap_or(assistant::Backend::Cpu);
+
+        let (settings, task) = screen::Settings::new(self.settings.clone(), assistant, backend);
+
+        self.settings_screen = Some(settings);
+
+        task.map(Message::Settings)
     }
 
     fn search(&mut self) -> Task<Message> {
-        let (search, task) = screen::Search::new();
+        if let Screen::Conversation(conversation) = &self.screen {
+            conversation.release();
+        }
+
+        let (search, task) = screen::Search::new(self.memory, self.settings.boot_options.clone());
 
         self.screen = Screen::Search(search);
 
         task.map(Message::Search)
     }
+
+    /// Boots the summarizer in the background, if any, so chats with
+    /// reasoning models can pair with it.
+    fn preload_summarizer(&mut self) -> Task<Message> {
+        let (Some(summarizer), Some(system)) = (&self.settings.summarizer, &self.system) else {
+            return Task::none();
+        };
+
+        if !summarizer.is_downloaded() {
+            return Task::none();
+        }
+
+        let port = match &self.screen {
+            Screen::Conversation(conversation) if conversation.file() == summarizer => {
+                return Task::none();
+            }
+            Screen::Conversation(conversation) => conversation.port(),
+            _ => None,
+        };
+
+        let launch = self.settings.launch(
+            summarizer,
+            assistant::Backend::detect(&system.graphics_adapter),
+        );
+
+        self.preloads
+            .push(summarizer.clone(), launch, &self.pool, port)
+            .map(Message::Preloads)
+    }
+}
+
+/// Shows the window, if hidden, and focuses it.
+fn reveal<T: 'static>(id: window::Id) -> Task<T> {
+    Task::batch([
+        window::change_mode(id, window::Mode::Windowed),
+        window::gain_focus(id),
+    ])
 }
@@ -1,10 +1,20 @@
 pub mod boot;
 pub mod conversation;
+pub mod downloads;
+pub mod embeddings;
+pub mod gallery;
+pub mod preloads;
 pub mod search;
+pub mod settings;
 
 pub use boot::Boot;
 pub use conversation::Conversation;
+pub use downloads::Downloads;
+pub use embeddings::Embeddings;
+pub use gallery::Gallery;
+pub use preloads::Preloads;
 pub use search::Search;
+pub use settings::Settings;
 
 use iced::widget::horizontal_space;
 use iced::Element;
@@ -13,7 +23,8 @@ pub enum Screen {
     Loading,
     Search(Search),
     Boot(Boot),
-    Conversation(Conversation),
+    Conversation(Box<Conversation>),
+    Gallery(Gallery),
 }
 
 pub fn loading<'a, Message: 'a>() -> Element<'a, Message> {
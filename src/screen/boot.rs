@@ -1,4 +1,9 @@
-use crate::data::assistant::{Backend, File, Model};
+use crate::data::assistant::{Backend, File, Kind, Model};
+use crate::data::convert;
+use crate::data::gguf;
+use crate::data::hardware::{self, Fit, Memory};
+use crate::data::Error;
+use crate::screen::search;
 use crate::widget::tip;
 
 use iced::system;
@@ -8,57 +13,150 @@ use iced::widget::{
 };
 use iced::{Center, Element, Fill, Font, Task, Theme};
 
+use std::fmt;
+
 pub struct Boot {
     model: Model,
+    variants: Vec<Variant>,
     file: Option<File>,
+    details: Option<String>,
     readme: Vec<markdown::Item>,
-    use_gpu: bool,
-    supported_backend: Backend,
+    /// The backend chosen to boot with; the detected one by default.
+    backend: Backend,
+    raw_completion: bool,
+    can_convert: bool,
+    can_requantize: bool,
+    requantizations: Vec<Requantization>,
+}
+
+/// A file of the model, with where it fits in the memory of the system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    file: File,
+    required: Option<u64>,
+    fit: Option<Fit>,
+}
+
+/// A smaller file that can be quantized from the selected one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requantization {
+    file: File,
+    estimated_size: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     ReadmeFetched(Vec<markdown::Item>),
     FileSelected(File),
+    DetailsRead(File, Result<String, Error>),
+    Requantize(Requantization),
     Boot,
+    Download,
+    Convert,
     Abort,
-    UseGPUToggled(bool),
+    BackendSelected(Backend),
+    RawCompletionToggled(bool),
     LinkClicked(markdown::Url),
 }
 
 pub enum Action {
     None,
-    Boot { file: File, backend: Backend },
+    Run(Task<Message>),
+    Boot {
+        file: File,
+        backend: Backend,
+        raw_completion: bool,
+    },
+    Download(File),
+    Convert(File),
+    Quantize {
+        source: File,
+        target: File,
+    },
     Abort,
 }
 
 impl Boot {
-    pub fn new(model: Model, system: Option<&system::Information>) -> (Self, Task<Message>) {
+    pub fn new(
+        mut model: Model,
+        system: Option<&system::Information>,
+        memory: Option<Memory>,
+    ) -> (Self, Task<Message>) {
         let supported_backend = system
             .map(|system| Backend::detect(&system.graphics_adapter))
             .unwrap_or(Backend::Cpu);
 
+        if model.convertible {
+            let converted = model.converted();
+
+            if converted.is_downloaded() && !model.files.contains(&converted) {
+                model.files.push(converted);
+            }
+        }
+
+        // Files quantized locally are not listed by Hugging Face
+        let requantized: Vec<File> = model
+            .files
+            .iter()
+            .filter(|file| file.is_downloaded())
+            .flat_map(|file| {
+                file.smaller_quantizations()
+                    .into_iter()
+                    .filter_map(|quantization| file.requantized(quantization))
+            })
+            .filter(File::is_downloaded)
+            .collect();
+
+        for file in requantized {
+            if !model.files.contains(&file) {
+                model.files.push(file);
+            }
+        }
+
+        let variants: Vec<_> = model
+            .files
+            .iter()
+            .map(|file| Variant::new(file, model.parameters, memory))
+            .collect();
+
+        let file = if model.files.len() == 1 {
+            model.files.first().cloned()
+        } else {
+            Variant::best(&variants, supported_backend.uses_gpu())
+        };
+
+        let can_requantize = convert::can_requantize();
+        let read_details = file.clone().map(details).unwrap_or_else(Task::none);
+
         (
             Self {
                 model: model.clone(),
-                file: if model.files.len() == 1 {
-                    model.files.first().cloned()
-                } else {
-                    None
-                },
+                variants,
+                requantizations: file
+                    .as_ref()
+                    .filter(|_| can_requantize)
+                    .map(requantizations)
+                    .unwrap_or_default(),
+                file,
+                details: None,
                 readme: Vec::new(),
-                use_gpu: supported_backend.uses_gpu(),
-                supported_backend,
+                backend: supported_backend,
+                raw_completion: model.kind == Kind::Base,
+                can_convert: model.convertible && convert::Tooling::detect().is_some(),
+                can_requantize,
             },
-            Task::future(model.fetch_readme())
-                .and_then(|readme| {
-                    Task::future(async move {
-                        tokio::task::spawn_blocking(move || markdown::parse(&readme).collect())
-                            .await
-                            .unwrap_or_default()
+            Task::batch([
+                Task::future(model.fetch_readme())
+                    .and_then(|readme| {
+                        Task::future(async move {
+                            tokio::task::spawn_blocking(move || markdown::parse(&readme).collect())
+                                .await
+                                .unwrap_or_default()
+                        })
                     })
-                })
-                .map(Message::ReadmeFetched),
+                    .map(Message::ReadmeFetched),
+                read_details,
+            ]),
         )
     }
 
@@ -74,7 +172,25 @@ impl Boot {
                 Action::None
             }
             Message::FileSelected(file) => {
-                self.file = Some(file);
+                self.requantizations = if self.can_requantize {
+                    requantizations(&file)
+                } else {
+                    Vec::new()
+                };
+                self.details = None;
+                self.file = Some(file.clone());
+
+                Action::Run(details(file))
+            }
+            Message::DetailsRead(file, Ok(details)) => {
+                if self.file.as_ref() == Some(&file) {
+                    self.details = Some(details);
+                }
+
+                Action::None
+            }
+            Message::DetailsRead(_file, Err(error)) => {
+                let _ = dbg!(error);
 
                 Action::None
             }
@@ -82,19 +198,33 @@ impl Boot {
                 if let Some(file) = self.file.clone() {
                     Action::Boot {
                         file,
-                        backend: if self.use_gpu {
-                            self.supported_backend
-                        } else {
-                            Backend::Cpu
-                        },
+                        backend: self.backend,
+                        raw_completion: self.raw_completion,
                     }
                 } else {
                     Action::None
                 }
             }
+            Message::Download => match self.file.clone() {
+                Some(file) => Action::Download(file),
+                None => Action::None,
+            },
+            Message::Convert => Action::Convert(self.model.converted()),
+            Message::Requantize(requantization) => match self.file.clone() {
+                Some(source) => Action::Quantize {
+                    source,
+                    target: requantization.file,
+                },
+                None => Action::None,
+            },
             Message::Abort => Action::Abort,
-            Message::UseGPUToggled(use_gpu) => {
-                self.use_gpu = use_gpu;
+            Message::BackendSelected(backend) => {
+                self.backend = backend;
+
+                Action::None
+            }
+            Message::RawCompletionToggled(raw_completion) => {
+                self.raw_completion = raw_completion;
 
                 Action::None
             }
@@ -107,22 +237,36 @@ impl Boot {
     }
 
     pub fn view(&self, theme: Theme) -> Element<Message> {
-        let title = text(self.model.name()).size(20).font(Font::MONOSPACE);
+        let title = row![
+            text(self.model.name()).size(20).font(Font::MONOSPACE),
+            search::kind(self.model.kind),
+        ]
+        .spacing(10)
+        .align_y(Center);
 
-        let boot = {
-            let use_cuda = {
-                let toggle = toggler(self.use_gpu).label("Use GPU").on_toggle_maybe(
-                    self.supported_backend
-                        .uses_gpu()
-                        .then_some(Message::UseGPUToggled),
-                );
-
-                tip(
-                    toggle,
-                    "Only supported on AMD and NVIDIA cards!",
-                    tip::Position::Left,
+        let warning = (self.model.kind == Kind::Base).then(|| {
+            column![
+                text(
+                    "This looks like a base model. It was not tuned to chat, so it may \
+                    ramble or answer for you too."
                 )
-            };
+                .size(14)
+                .style(text::danger),
+                toggler(self.raw_completion)
+                    .label("Continue a plain transcript instead of chatting")
+                    .on_toggle(Message::RawCompletionToggled)
+                    .size(16)
+                    .text_size(14),
+            ]
+            .spacing(5)
+        });
+
+        let boot = {
+            let backend = tip(
+                pick_list(Backend::ALL, Some(self.backend), Message::BackendSelected).text_size(14),
+                "The GPU backend of llama.cpp; CPU runs anywhere",
+                tip::Position::Left,
+            );
 
             let boot = action("Boot")
                 .style(button::success)
@@ -132,21 +276,92 @@ impl Boot {
                 .style(button::danger)
                 .on_press(Message::Abort);
 
-            let file = pick_list(
-                self.model.files.as_slice(),
-                self.file.as_ref(),
-                Message::FileSelected,
-            )
+            let download = tip(
+                action("Download")
+                    .style(button::secondary)
+                    .on_press_maybe(self.file.is_some().then_some(Message::Download)),
+                "Download in the background",
+                tip::Position::Top,
+            );
+
+            let selected = self
+                .file
+                .as_ref()
+                .and_then(|file| self.variants.iter().find(|variant| variant.file == *file));
+
+            let file = pick_list(self.variants.as_slice(), selected, |variant| {
+                Message::FileSelected(variant.file)
+            })
             .width(Fill)
             .placeholder("Select a file to boot...");
 
-            column![
-                file,
-                row![abort, horizontal_space(), use_cuda, boot]
-                    .spacing(10)
-                    .align_y(Center)
-            ]
-            .spacing(10)
+            let too_large = selected
+                .filter(|variant| variant.fit == Some(Fit::TooLarge))
+                .map(|_| {
+                    text("This file is likely too large for the memory of your computer.")
+                        .size(14)
+                        .style(text::danger)
+                });
+
+            let conversion = self.model.files.is_empty().then(|| {
+                let hint = if !self.model.convertible {
+                    "This repository has neither GGUF files nor safetensors weights to convert."
+                } else if self.can_convert {
+                    "Its original weights can be converted into a GGUF file locally."
+                } else {
+                    "Install llama.cpp, with convert_hf_to_gguf.py and llama-quantize in \
+                    your PATH, to convert its original weights into a GGUF file."
+                };
+
+                row![
+                    text(hint).size(14).width(Fill),
+                    tip(
+                        action("Convert")
+                            .style(button::secondary)
+                            .on_press_maybe(self.can_convert.then_some(Message::Convert)),
+                        "Download, convert, and quantize in the background",
+                        tip::Position::Top,
+                    ),
+                ]
+                .spacing(10)
+                .align_y(Center)
+            });
+
+            let requantize = (!self.requantizations.is_empty()).then(|| {
+                row![
+                    text("Too large? Quantize it into a smaller file locally.")
+                        .size(14)
+                        .width(Fill),
+                    pick_list(
+                        self.requantizations.as_slice(),
+                        None::<Requantization>,
+                        Message::Requantize
+                    )
+                    .placeholder("Requantize...")
+                    .text_size(14),
+                ]
+                .spacing(10)
+                .align_y(Center)
+            });
+
+            column![]
+                .push_maybe(warning)
+                .push_maybe(conversion)
+                .push(file)
+                .push_maybe(too_large)
+                .push_maybe(self.details.as_ref().map(|details| {
+                    text(details)
+                        .size(12)
+                        .font(Font::MONOSPACE)
+                        .style(text::secondary)
+                }))
+                .push_maybe(requantize)
+                .push(
+                    row![abort, horizontal_space(), backend, download, boot]
+                        .spacing(10)
+                        .align_y(Center),
+                )
+                .spacing(10)
         };
 
         let readme: Element<_> = if self.readme.is_empty() {
@@ -181,6 +396,114 @@ impl Boot {
     }
 }
 
+/// Reads the details of the model from the header of the given file, if it
+/// is downloaded.
+fn details(file: File) -> Task<Message> {
+    if !file.is_downloaded() {
+        return Task::none();
+    }
+
+    Task::perform(gguf::Metadata::read(file.path()), move |metadata| {
+        Message::DetailsRead(file.clone(), metadata.map(|metadata| metadata.summary()))
+    })
+}
+
+/// The smaller files that can be quantized from the given one, if it is
+/// downloaded.
+fn requantizations(file: &File) -> Vec<Requantization> {
+    let Ok(metadata) = std::fs::metadata(file.path()) else {
+        return Vec::new();
+    };
+
+    let Some(quantization) = file.quantization() else {
+        return Vec::new();
+    };
+
+    file.smaller_quantizations()
+        .into_iter()
+        .filter_map(|target| {
+            Some(Requantization {
+                file: file.requantized(target)?,
+                estimated_size: convert::estimate(metadata.len(), quantization, target),
+            })
+        })
+        .filter(|requantization| !requantization.file.is_downloaded())
+        .collect()
+}
+
+impl Variant {
+    fn new(file: &File, parameters: Option<u64>, memory: Option<Memory>) -> Self {
+        let size = std::fs::metadata(file.path())
+            .ok()
+            .map(|metadata| metadata.len());
+
+        let required = hardware::estimate(size, parameters, file.quantization());
+
+        Self {
+            file: file.clone(),
+            required,
+            fit: memory
+                .zip(required)
+                .map(|(memory, required)| memory.fit(required)),
+        }
+    }
+
+    /// The largest file that fits in the graphics card, if used, or else in
+    /// the RAM.
+    ///
+    /// Unquantized files are never preferred; they are as good as `Q8_0`.
+    fn best(variants: &[Self], use_gpu: bool) -> Option<File> {
+        let quantized = |variant: &&Self| {
+            variant
+                .file
+                .quantization()
+                .is_some_and(|quantization| !matches!(quantization, "F16" | "BF16" | "F32"))
+        };
+
+        let largest = |fit: Fit| {
+            variants
+                .iter()
+                .filter(quantized)
+                .filter(|variant| variant.fit.is_some_and(|candidate| candidate <= fit))
+                .max_by_key(|variant| variant.required)
+        };
+
+        use_gpu
+            .then(|| largest(Fit::Vram))
+            .flatten()
+            .or_else(|| largest(Fit::Ram))
+            .map(|variant| variant.file.clone())
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.required, self.fit) {
+            (Some(required), Some(fit)) => write!(
+                f,
+                "{file} ({fit}, ~{size:.1} GB)",
+                file = self.file,
+                size = required as f64 / 1e9
+            ),
+            (Some(required), None) => {
+                write!(f, "{} (~{:.1} GB)", self.file, required as f64 / 1e9)
+            }
+            (None, _) => self.file.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for Requantization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quantization = self.file.quantization().unwrap_or_default();
+
+        match self.estimated_size {
+            Some(size) => write!(f, "{quantization} (~{:.1} GB)", size as f64 / 1e9),
+            None => f.write_str(quantization),
+        }
+    }
+}
+
 fn action(text: &str) -> button::Button<Message> {
     button(container(text).center_x(Fill)).width(100)
 }
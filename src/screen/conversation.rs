@@ -1,7 +1,22 @@
+mod comparison;
+mod infill;
+mod sidebar;
+
+use comparison::Comparison;
+use infill::Infill;
+
+use crate::data::appearance::Appearance;
 use crate::data::assistant::{self, Assistant, Backend, BootEvent, File};
-use crate::data::chat::{self, Chat, Entry, Id};
-use crate::data::Error;
-use crate::icon;
+use crate::data::chat::{self, Chat, Entry, Hit, Id, Language, Metadata, Snapshot};
+use crate::data::guardrails::Guardrails;
+use crate::data::locale::Locale;
+use crate::data::pool::Pool;
+use crate::data::setup::Setup;
+use crate::data::usage::{self, Feature};
+use crate::data::{self, diagnostics, editor, exec, hardware, keymap, template, Error, Settings};
+use crate::screen::preloads::{self, Preloads};
+use crate::widget::icon;
+use crate::widget::markdown::Interaction;
 use crate::widget::tip;
 
 use iced::border;
@@ -11,14 +26,19 @@ use iced::task::{self, Task};
 use iced::time::{self, Duration, Instant};
 use iced::widget::{
     self, bottom, bottom_center, button, center, center_x, center_y, column, container,
-    horizontal_space, hover, markdown, progress_bar, right, right_center, row, scrollable, stack,
-    text, text_editor, tooltip, value, vertical_rule, vertical_space, Text,
+    horizontal_space, hover, markdown, pick_list, progress_bar, right, right_center, row,
+    scrollable, stack, text, text_editor, text_input, tooltip, value, vertical_rule, Space, Text,
 };
 use iced::{Center, Element, Fill, Font, Rectangle, Shrink, Subscription, Theme};
 
+use std::ops::Range;
+use std::path::PathBuf;
+
 pub struct Conversation {
     backend: Backend,
+    pool: Pool,
     chats: Vec<Entry>,
+    downloads: Vec<String>,
     state: State,
     id: Option<Id>,
     title: Option<String>,
@@ -27,11 +47,108 @@ pub struct Conversation {
     input_height: f32,
     error: Option<Error>,
     sidebar_open: bool,
+    find: Option<Find>,
+    filter: Filter,
+    settings: Settings,
+    system_prompt: Option<String>,
+    guardrails: Guardrails,
+    persona: Option<text_editor::Content>,
+    inspector: Option<Inspector>,
+    carry_over: Option<CarryOver>,
+    autosaved_at: Instant,
+    sampling: assistant::Sampling,
+    sampling_open: bool,
+    knowledge: bool,
+    raw_completion: bool,
+    memory: Vec<String>,
+    starters: Vec<String>,
+    chat_search: sidebar::Search,
+    revealing: Option<(String, usize)>,
+    /// The file of the chat hovered in the sidebar, if any.
+    hovered: Option<File>,
+    available_space: Option<u64>,
+    /// The memory used by the app and the assistant, measured periodically.
+    resources: Option<hardware::Usage>,
+    /// The output of the server, while the logs panel is open.
+    logs: Option<Vec<String>>,
+    /// The health checks failed in a row by the local server.
+    failed_checks: usize,
+    /// Whether the local server stopped answering its health checks.
+    crashed: bool,
+    /// The times the server was restarted after crashing.
+    restarts: usize,
+    unsaved: bool,
+    truncated: usize,
+    stage: Option<chat::Stage>,
+    tags: Option<String>,
+    smaller: Vec<File>,
+    snapshots: Option<Vec<Snapshot>>,
+    run: Option<Run>,
+    editing: Option<Editing>,
+    comparison: Option<Comparison>,
+    infill: Option<Infill>,
+}
+
+/// What the next message would send to the assistant.
+enum Inspector {
+    Unavailable,
+    Inspecting,
+    Ready(chat::Preview),
+    Failed(Error),
+}
+
+/// The earlier messages of a chat summarized for the model it was
+/// switched to.
+///
+/// Carry-overs are not saved; reopening the chat replays it in full.
+struct CarryOver {
+    /// The amount of messages covered by the recap.
+    covered: usize,
+    model: String,
+    /// The recap, once written.
+    recap: Option<String>,
+}
+
+impl CarryOver {
+    fn view(&self) -> Element<'_, Message> {
+        let note = if self.recap.is_some() {
+            text!(
+                "The messages above were summarized for {model}",
+                model = self.model
+            )
+        } else {
+            text!(
+                "Summarizing the messages above for {model}...",
+                model = self.model
+            )
+        };
+
+        center_x(note.size(12).font(Font::MONOSPACE).style(text::secondary)).into()
+    }
+}
+
+/// A code block waiting for confirmation before it is run.
+struct Run {
+    index: usize,
+    language: exec::Language,
+    code: String,
+    sandbox: Option<exec::Sandbox>,
+}
+
+/// A reply, or one of its code blocks, opened in an external editor.
+struct Editing {
+    index: usize,
+    /// The code block being edited, if not the whole reply.
+    code: Option<String>,
+    draft: Option<editor::Draft>,
+    /// The content saved in the editor, waiting to be applied.
+    edits: Option<String>,
 }
 
 enum State {
     Booting {
         file: File,
+        port: u64,
         logs: Vec<String>,
         stage: String,
         progress: u64,
@@ -42,20 +159,85 @@ enum State {
         assistant: Assistant,
         sending: Option<task::Handle>,
     },
+    Missing {
+        file: File,
+        alternatives: Vec<File>,
+    },
+}
+
+impl State {
+    fn boot(
+        file: File,
+        backend: Backend,
+        settings: &Settings,
+        pool: &Pool,
+    ) -> (Self, Task<Message>) {
+        if let Some(assistant) = pool.take(&file) {
+            return (
+                Self::Running {
+                    assistant: assistant.with_raw_completion(false),
+                    sending: None,
+                },
+                Task::none(),
+            );
+        }
+
+        let port = pool.port();
+        let launch = settings.launch(&file, backend);
+
+        let boot = if settings.remote.enabled {
+            Task::run(
+                Assistant::connect(
+                    file.clone(),
+                    settings.remote.clone(),
+                    launch.inference,
+                    port,
+                ),
+                Message::Booting,
+            )
+        } else {
+            Task::run(
+                Assistant::boot(file.clone(), launch, port),
+                Message::Booting,
+            )
+        };
+
+        let (boot, handle) = boot.abortable();
+
+        (
+            Self::Booting {
+                file,
+                port,
+                logs: Vec::new(),
+                stage: "Booting...".to_owned(),
+                progress: 0,
+                tick: 0,
+                _task: handle.abort_on_drop(),
+            },
+            boot,
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     ChatsListed(Result<Vec<Entry>, Error>),
+    DownloadsListed(Result<Vec<String>, Error>),
     Booting(Result<BootEvent, Error>),
     Tick(Instant),
     InputChanged(text_editor::Action),
     InputMeasured(Option<Rectangle>),
+    ChatScrolled(scrollable::Viewport),
+    ItemMeasured(usize, Option<Rectangle>),
     Submit,
     Chatting(Result<chat::Event, Error>),
     Copy(String),
     Regenerate(usize),
+    Branch(usize),
+    Branched(Result<Chat, Error>),
     ToggleReasoning(usize),
+    ShowReply(usize, Option<usize>),
+    KeepReply(usize, usize),
     Created(Result<Chat, Error>),
     Saved(Result<Chat, Error>),
     Open(chat::Id),
@@ -66,30 +248,166 @@ pub enum Message {
     Search,
     ToggleSidebar,
     LinkClicked(markdown::Url),
+    OpenFind,
+    FindChanged(String),
+    FindNext,
+    FindPrevious,
+    CloseFind,
+    FilterChanged(Filter),
+    TagsChanged(String),
+    SubmitTags,
+    Tagged(Result<(), Error>),
+    TogglePersona,
+    PersonaEdited(text_editor::Action),
+    ResetPersona,
+    RefusedTopicsChanged(String),
+    ToneChanged(String),
+    ToggleInspector,
+    Inspect,
+    Inspected(Result<chat::Preview, Error>),
+    Recapped(Result<String, Error>),
+    Forget(usize),
+    Stop,
+    SearchChats(String),
+    ChatsSearched(String, Result<Vec<Hit>, Error>),
+    OpenHit(Hit),
+    Export(chat::Format),
+    ExportTo(chat::Format, Option<PathBuf>),
+    Exported(Result<(), Error>),
+    ExportSetup,
+    ExportSetupTo(Option<PathBuf>),
+    SetupExported(Result<(), Error>),
+    ImportSetup,
+    ImportSetupFrom(Option<PathBuf>),
+    SetupImported(Result<Setup, Error>),
+    UseStarter(String),
+    Download,
+    SwitchModel(File),
+    Preload(File),
+    Preloads(preloads::Message),
+    ChatHovered(File),
+    ChatUnhovered(File),
+    HoverSettled(File),
+    SmallerListed(Result<Vec<File>, Error>),
+    CheckDiskSpace,
+    DiskSpaceChecked(Result<u64, Error>),
+    MeasureResources,
+    ResourcesMeasured(hardware::Usage),
+    CheckHealth,
+    HealthChecked(bool),
+    Restart,
+    ToggleLogs,
+    RefreshLogs,
+    CopyLogs,
+    SaveLogs,
+    SaveLogsTo(Option<PathBuf>),
+    LogsSaved(Result<(), Error>),
+    ToggleSampling,
+    ToggleKnowledge,
+    ToggleComparison,
+    CompareWith(File),
+    ComparisonBooting(Result<BootEvent, Error>),
+    Compared(usize, Result<chat::Event, Error>),
+    ToggleInfill,
+    InfillPrefixEdited(text_editor::Action),
+    InfillSuffixEdited(text_editor::Action),
+    FillMiddle,
+    MiddleFilled(Result<String, Error>),
+    InfillOver,
+    StopInfill,
+    ToggleSnapshots,
+    SnapshotsListed(Result<Vec<Snapshot>, Error>),
+    RestoreSnapshot(Snapshot),
+    SamplingChanged(assistant::Sampling),
+    MaxTokensChanged(String),
+    ResetSampling,
+    OpenSettings,
+    Shortcut(keymap::Command),
+    RunCode(usize, exec::Language, String),
+    SandboxDetected(exec::Sandbox),
+    ConfirmRun,
+    CancelRun,
+    CodeRan(usize, usize, Result<exec::Output, Error>),
+    ToggleExecution(usize, usize),
+    OpenInEditor(usize),
+    OpenCodeInEditor(usize, Option<String>, String),
+    EditorOpened(Result<editor::Draft, Error>),
+    CheckEdits,
+    EditsChecked(Result<(editor::Draft, Option<String>), Error>),
+    ApplyEdits,
+    StopEditing,
 }
 
 pub enum Action {
     None,
     Run(Task<Message>),
     Back,
+    OpenSettings,
+    Preload { file: File, backend: Backend },
+    PreloadOnHover { file: File, backend: Backend },
+    CancelHoverPreload(File),
+    Preloads(preloads::Message),
 }
 
 impl Conversation {
-    pub fn new(file: File, backend: Backend) -> (Self, Task<Message>) {
-        let (boot, handle) =
-            Task::run(Assistant::boot(file.clone(), backend), Message::Booting).abortable();
+    pub fn new(
+        file: File,
+        backend: Backend,
+        settings: Settings,
+        pool: Pool,
+    ) -> (Self, Task<Message>) {
+        let (state, boot) = State::boot(file, backend, &settings, &pool);
+
+        Self::with_state(state, boot, backend, settings, pool)
+    }
+
+    pub fn open(
+        chat: Chat,
+        backend: Backend,
+        settings: Settings,
+        pool: Pool,
+    ) -> (Self, Task<Message>) {
+        let (conversation, task) = if chat.file.is_downloaded() || settings.remote.enabled {
+            Self::new(chat.file, backend, settings, pool)
+        } else {
+            Self::with_state(
+                State::Missing {
+                    file: chat.file,
+                    alternatives: Vec::new(),
+                },
+                Task::none(),
+                backend,
+                settings,
+                pool,
+            )
+        };
 
+        (
+            Self {
+                id: Some(chat.id),
+                title: chat.title,
+                history: History::restore(chat.history),
+                system_prompt: chat.system_prompt,
+                sampling: chat.sampling,
+                memory: chat.memory,
+                ..conversation
+            },
+            task,
+        )
+    }
+
+    fn with_state(
+        state: State,
+        boot: Task<Message>,
+        backend: Backend,
+        settings: Settings,
+        pool: Pool,
+    ) -> (Self, Task<Message>) {
         (
             Self {
                 backend,
-                state: State::Booting {
-                    file,
-                    logs: Vec::new(),
-                    stage: "Booting...".to_owned(),
-                    progress: 0,
-                    tick: 0,
-                    _task: handle.abort_on_drop(),
-                },
+                pool,
+                state,
                 id: None,
                 title: None,
                 history: History::new(),
@@ -97,11 +415,47 @@ impl Conversation {
                 input_height: 50.0,
                 error: None,
                 chats: Vec::new(),
+                downloads: Vec::new(),
                 sidebar_open: true,
+                find: None,
+                filter: Filter::All,
+                settings,
+                system_prompt: None,
+                guardrails: Guardrails::default(),
+                persona: None,
+                inspector: None,
+                carry_over: None,
+                autosaved_at: Instant::now(),
+                sampling: assistant::Sampling::default(),
+                memory: Vec::new(),
+                starters: Vec::new(),
+                chat_search: sidebar::Search::default(),
+                revealing: None,
+                hovered: None,
+                sampling_open: false,
+                knowledge: false,
+                raw_completion: false,
+                available_space: None,
+                resources: None,
+                logs: None,
+                failed_checks: 0,
+                crashed: false,
+                restarts: 0,
+                unsaved: false,
+                truncated: 0,
+                stage: None,
+                tags: None,
+                smaller: Vec::new(),
+                snapshots: None,
+                run: None,
+                editing: None,
+                comparison: None,
+                infill: None,
             },
             Task::batch([
                 boot,
                 Task::perform(Chat::list(), Message::ChatsListed),
+                Task::perform(File::list_downloaded(), Message::DownloadsListed),
                 widget::focus_next(),
                 measure_input(),
                 snap_chat_to_end(),
@@ -109,20 +463,6 @@ impl Conversation {
         )
     }
 
-    pub fn open(chat: Chat, backend: Backend) -> (Self, Task<Message>) {
-        let (conversation, task) = Self::new(chat.file, backend);
-
-        (
-            Self {
-                id: Some(chat.id),
-                title: chat.title,
-                history: History::restore(chat.history),
-                ..conversation
-            },
-            task,
-        )
-    }
-
     pub fn title(&self) -> String {
         format!(
             "{name} - Icebreaker",
@@ -135,9 +475,22 @@ impl Conversation {
             Message::ChatsListed(Ok(chats)) => {
                 self.chats = chats;
 
+                if let State::Missing { file, alternatives } = &mut self.state {
+                    *alternatives = available_files(&self.chats, &self.downloads, file);
+                }
+
+                Action::None
+            }
+            Message::DownloadsListed(Ok(downloads)) => {
+                self.downloads = downloads;
+
+                if let State::Missing { file, alternatives } = &mut self.state {
+                    *alternatives = available_files(&self.chats, &self.downloads, file);
+                }
+
                 Action::None
             }
-            Message::ChatsListed(Err(error)) => {
+            Message::ChatsListed(Err(error)) | Message::DownloadsListed(Err(error)) => {
                 self.error = Some(dbg!(error));
 
                 Action::None
@@ -166,18 +519,197 @@ impl Conversation {
                 }
                 BootEvent::Finished(assistant) => {
                     self.state = State::Running {
-                        assistant,
+                        assistant: assistant.with_raw_completion(self.raw_completion),
                         sending: None,
                     };
+                    self.failed_checks = 0;
+                    self.crashed = false;
 
-                    Action::None
+                    Action::Run(self.write_recap())
                 }
             },
             Message::Booting(Err(error)) => {
+                diagnostics::record(&error);
+
+                let is_out_of_memory = matches!(error, Error::OutOfMemory);
                 self.error = Some(error);
 
+                match &self.state {
+                    State::Booting { file, .. } if is_out_of_memory => {
+                        let file = file.clone();
+
+                        Action::Run(Task::perform(
+                            async move { file.smaller().await },
+                            Message::SmallerListed,
+                        ))
+                    }
+                    _ => Action::None,
+                }
+            }
+            Message::SmallerListed(Ok(smaller)) => {
+                self.smaller = smaller;
+
+                Action::None
+            }
+            Message::SmallerListed(Err(error)) => {
+                let _ = dbg!(error);
+
+                Action::None
+            }
+            Message::Export(format) => {
+                let file_name = format!(
+                    "{title}.{extension}",
+                    title = self.title.as_deref().unwrap_or("chat"),
+                    extension = format.extension()
+                );
+
+                Action::Run(Task::perform(
+                    rfd::AsyncFileDialog::new()
+                        .set_file_name(file_name)
+                        .add_filter(format.to_string(), &[format.extension()])
+                        .save_file(),
+                    move |file| Message::ExportTo(format, file.map(|file| file.path().to_owned())),
+                ))
+            }
+            Message::ExportTo(format, Some(path)) => {
+                let Some(id) = self.id else {
+                    return Action::None;
+                };
+
+                let chat = Chat {
+                    id,
+                    file: self.file().clone(),
+                    title: self.title.clone(),
+                    history: self.history.messages().collect(),
+                    system_prompt: self.system_prompt.clone(),
+                    guardrails: self.guardrails.clone(),
+                    sampling: self.sampling,
+                    memory: self.memory.clone(),
+                };
+
+                Action::Run(Task::batch([
+                    Task::perform(chat.export_to(format, path), Message::Exported),
+                    self.track(Feature::Export),
+                ]))
+            }
+            Message::ExportTo(_, None) | Message::Exported(Ok(())) => Action::None,
+            Message::Exported(Err(error)) => {
+                self.error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::ExportSetup => {
+                let file_name = format!(
+                    "{title}.{extension}",
+                    title = self.title.as_deref().unwrap_or("chat"),
+                    extension = Setup::EXTENSION
+                );
+
+                Action::Run(Task::perform(
+                    rfd::AsyncFileDialog::new()
+                        .set_file_name(file_name)
+                        .add_filter("Icebreaker template", &[Setup::EXTENSION])
+                        .save_file(),
+                    |file| Message::ExportSetupTo(file.map(|file| file.path().to_owned())),
+                ))
+            }
+            Message::ExportSetupTo(Some(path)) => {
+                // Chats set up by hand start with their first message
+                let starters = if self.starters.is_empty() {
+                    self.history
+                        .messages()
+                        .find_map(|message| match message {
+                            assistant::Message::User(content) => Some(content),
+                            assistant::Message::Assistant { .. } => None,
+                        })
+                        .into_iter()
+                        .collect()
+                } else {
+                    self.starters.clone()
+                };
+
+                let setup = Setup::new(
+                    self.title.clone().unwrap_or_else(|| "Untitled".to_owned()),
+                    self.system_prompt.clone(),
+                    self.guardrails.clone(),
+                    self.sampling,
+                    self.memory.clone(),
+                    starters,
+                );
+
+                Action::Run(Task::perform(setup.write(path), Message::SetupExported))
+            }
+            Message::ExportSetupTo(None) | Message::SetupExported(Ok(())) => Action::None,
+            Message::SetupExported(Err(error)) => {
+                self.error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::ImportSetup => Action::Run(Task::perform(
+                rfd::AsyncFileDialog::new()
+                    .add_filter("Icebreaker template", &[Setup::EXTENSION])
+                    .pick_file(),
+                |file| Message::ImportSetupFrom(file.map(|file| file.path().to_owned())),
+            )),
+            Message::ImportSetupFrom(Some(path)) => {
+                Action::Run(Task::perform(Setup::read(path), Message::SetupImported))
+            }
+            Message::ImportSetupFrom(None) => Action::None,
+            Message::SetupImported(Ok(setup)) => {
+                let action = self.update(Message::New);
+
+                self.system_prompt = setup.persona;
+                self.guardrails = setup.guardrails;
+                self.sampling = setup.sampling;
+                self.memory = setup.memory;
+                self.starters = setup.starters;
+
+                action
+            }
+            Message::SetupImported(Err(error)) => {
+                self.error = Some(dbg!(error));
+
                 Action::None
             }
+            Message::UseStarter(starter) => {
+                self.input = text_editor::Content::with_text(&starter);
+
+                Action::Run(Task::batch([widget::focus_next(), measure_input()]))
+            }
+            Message::Download => {
+                let State::Missing { file, .. } = &self.state else {
+                    return Action::None;
+                };
+
+                let (state, boot) =
+                    State::boot(file.clone(), self.backend, &self.settings, &self.pool);
+                self.state = state;
+                self.error = None;
+
+                Action::Run(boot)
+            }
+            Message::SwitchModel(file) => {
+                self.release();
+
+                self.carry_over = (self.settings.summarize_on_switch && !self.history.is_empty())
+                    .then(|| CarryOver {
+                        covered: self.history.len(),
+                        model: file.name.clone(),
+                        recap: None,
+                    });
+
+                let (state, boot) = State::boot(file, self.backend, &self.settings, &self.pool);
+                self.state = state;
+                self.error = None;
+                self.smaller = Vec::new();
+
+                let boot = Task::batch([boot, self.write_recap()]);
+
+                match self.save() {
+                    Action::Run(save) => Action::Run(Task::batch([boot, save])),
+                    _ => Action::Run(boot),
+                }
+            }
             Message::Tick(_now) => {
                 if let State::Booting { tick, .. } = &mut self.state {
                     *tick += 1;
@@ -198,18 +730,75 @@ impl Conversation {
 
                 Action::None
             }
+            Message::ChatScrolled(viewport) => Action::Run(self.history.scroll(viewport)),
+            Message::ItemMeasured(index, bounds) => {
+                self.history.measure(index, bounds);
+
+                Action::None
+            }
             Message::Submit => {
+                if let Some(fact) = self.input.text().trim().strip_prefix("/remember ") {
+                    let fact = fact.trim();
+
+                    if !fact.is_empty() && !self.memory.iter().any(|known| known == fact) {
+                        self.memory.push(fact.to_owned());
+                    }
+
+                    self.input = text_editor::Content::new();
+
+                    return self.save();
+                }
+
                 if let State::Running { assistant, sending } = &mut self.state {
                     if let Some(message) = chat::Content::parse(&self.input.text()) {
+                        let system_prompt = match compose_system_prompt(
+                            self.system_prompt
+                                .as_deref()
+                                .unwrap_or(&self.settings.system_prompt),
+                            &self.guardrails,
+                            assistant,
+                            &self.settings,
+                            &self.memory,
+                            recap(self.carry_over.as_ref()),
+                        ) {
+                            Ok(system_prompt) => system_prompt,
+                            Err(error) => {
+                                self.error = Some(dbg!(error));
+
+                                return Action::None;
+                            }
+                        };
+
+                        self.error = None;
+
+                        let content = message.as_str().to_owned();
+                        let summarizer = summarizer(assistant, &self.settings, &self.pool);
+
                         let (send, handle) = Task::run(
-                            chat::send(assistant, self.history.messages().collect(), message),
+                            chat::send(
+                                &assistant.clone().with_summarizer(summarizer),
+                                chat::Request {
+                                    system_prompt: &system_prompt,
+                                    sampling: self.sampling,
+                                    search: &self.settings.search,
+                                    knowledge: self.knowledge.then_some(&self.settings.knowledge),
+                                    suggest_titles: self.settings.suggest_titles,
+                                },
+                                prompt(&self.history, self.carry_over.as_ref()),
+                                message,
+                            ),
                             Message::Chatting,
                         )
                         .abortable();
 
                         *sending = Some(handle.abort_on_drop());
 
-                        Action::Run(send)
+                        let mut history = prompt(&self.history, self.carry_over.as_ref());
+                        history.push(assistant::Message::User(content));
+
+                        let compare = self.compare(self.history.len() + 1, history);
+
+                        Action::Run(Task::batch([send, compare]))
                     } else {
                         Action::None
                     }
@@ -233,8 +822,15 @@ impl Conversation {
                     self.history.push(Item::Assistant {
                         reasoning: None,
                         content: String::new(),
-                        content_markdown: markdown::Content::new(),
+                        content_markdown: crate::widget::markdown::Content::new(),
+                        metadata: Metadata::default(),
+                        usage: None,
+                        executions: Vec::new(),
+                        alternatives: Vec::new(),
+                        shown: None,
+                        interrupted: false,
                     });
+                    self.autosaved_at = Instant::now();
 
                     Action::Run(snap_chat_to_end())
                 }
@@ -247,6 +843,7 @@ impl Conversation {
                         reasoning,
                         content,
                         content_markdown,
+                        ..
                     }) = self.history.last_mut()
                     {
                         *reasoning = new_reasoning.map(Reasoning::from);
@@ -257,36 +854,77 @@ impl Conversation {
                         }
                     }
 
+                    if self.autosaved_at.elapsed() >= AUTOSAVE_INTERVAL {
+                        self.autosaved_at = Instant::now();
+
+                        return self.autosave();
+                    }
+
+                    Action::None
+                }
+                chat::Event::UsageReported(new_usage) => {
+                    if let Some(Item::Assistant { usage, .. }) = self.history.last_mut() {
+                        *usage = Some(new_usage);
+                    }
+
+                    Action::None
+                }
+                chat::Event::AlternativeChanged { index, alternative } => {
+                    if let Some(Item::Assistant { alternatives, .. }) = self.history.last_mut() {
+                        if alternatives.len() <= index {
+                            alternatives.resize_with(index + 1, Alternative::default);
+                        }
+
+                        alternatives[index] = Alternative::from(alternative);
+                    }
+
+                    Action::None
+                }
+                chat::Event::AlternativeFailed { index, error } => {
+                    if let Some(Item::Assistant { alternatives, .. }) = self.history.last_mut() {
+                        if alternatives.len() <= index {
+                            alternatives.resize_with(index + 1, Alternative::default);
+                        }
+
+                        alternatives[index].error = Some(error);
+                    }
+
+                    Action::None
+                }
+                chat::Event::ContextTruncated(truncated) => {
+                    self.truncated = truncated;
+
+                    Action::None
+                }
+                chat::Event::StageChanged(stage) => {
+                    self.stage = Some(stage);
+
                     Action::None
                 }
                 chat::Event::ExchangeOver => {
+                    self.stage = None;
+
                     if let State::Running {
                         sending, assistant, ..
                     } = &mut self.state
                     {
                         *sending = None;
 
-                        let messages = self.history.messages().collect();
-
-                        if let Some(id) = &self.id {
-                            Action::Run(Task::perform(
-                                Chat::save(
-                                    id.clone(),
-                                    assistant.file().clone(),
-                                    self.title.clone(),
-                                    messages,
-                                ),
-                                Message::Saved,
-                            ))
+                        let file = assistant.file().clone();
+
+                        if let Some(Item::Assistant {
+                            content, metadata, ..
+                        }) = self.history.last_mut()
+                        {
+                            *metadata = Metadata::compute(content);
+                        }
+
+                        let draft = self.draft(file, self.history.messages().collect());
+
+                        if let Some(id) = self.id {
+                            Action::Run(Task::perform(Chat::save(id, draft), Message::Saved))
                         } else {
-                            Action::Run(Task::perform(
-                                Chat::create(
-                                    assistant.file().clone(),
-                                    self.title.clone(),
-                                    messages,
-                                ),
-                                Message::Created,
-                            ))
+                            Action::Run(Task::perform(Chat::create(draft), Message::Created))
                         }
                     } else {
                         Action::None
@@ -294,8 +932,24 @@ impl Conversation {
                 }
             },
             Message::Chatting(Ok(_outdated_event)) => Action::None,
+            Message::Stop => {
+                if let Some(comparison) = &mut self.comparison {
+                    comparison.stop();
+                }
+
+                if self.can_send() {
+                    return Action::None;
+                }
+
+                // Finishing the exchange drops the sending handle, which
+                // aborts the stream and closes the underlying request
+                self.update(Message::Chatting(Ok(chat::Event::ExchangeOver)))
+            }
             Message::Chatting(Err(error)) => {
+                diagnostics::record(&error);
+
                 self.error = Some(dbg!(error));
+                self.stage = None;
 
                 if let State::Running { sending, .. } = &mut self.state {
                     *sending = None;
@@ -303,80 +957,586 @@ impl Conversation {
 
                 Action::None
             }
-            Message::Copy(content) => Action::Run(clipboard::write(content)),
-            Message::Regenerate(index) => {
-                if let State::Running { assistant, sending } = &mut self.state {
-                    self.history.truncate(index);
+            Message::Copy(content) => Action::Run(Task::batch([
+                clipboard::write(content),
+                self.track(Feature::Copy),
+            ])),
+            Message::RunCode(index, language, code) => {
+                self.run = Some(Run {
+                    index,
+                    language,
+                    code,
+                    sandbox: None,
+                });
 
-                    let (send, handle) = Task::run(
-                        chat::complete(assistant, self.history.messages().collect()),
-                        Message::Chatting,
-                    )
-                    .abortable();
+                Action::Run(Task::perform(
+                    exec::Sandbox::detect(),
+                    Message::SandboxDetected,
+                ))
+            }
+            Message::SandboxDetected(sandbox) => {
+                if let Some(run) = &mut self.run {
+                    run.sandbox = Some(sandbox);
+                }
 
-                    *sending = Some(handle.abort_on_drop());
+                Action::None
+            }
+            Message::ConfirmRun => {
+                let track = self.track(Feature::RunCode);
 
-                    Action::Run(send)
-                } else {
-                    Action::None
+                let Some(Run {
+                    index,
+                    language,
+                    code,
+                    sandbox: Some(sandbox),
+                }) = self.run.take()
+                else {
+                    return Action::None;
+                };
+
+                let Some(Item::Assistant { executions, .. }) = self.history.get_mut(index) else {
+                    return Action::None;
+                };
+
+                let execution = executions.len();
+
+                executions.push(Execution {
+                    language,
+                    output: None,
+                    show: true,
+                });
+
+                Action::Run(Task::batch([
+                    Task::perform(exec::run(language, code, sandbox), move |result| {
+                        Message::CodeRan(index, execution, result)
+                    }),
+                    track,
+                ]))
+            }
+            Message::CancelRun => {
+                self.run = None;
+
+                Action::None
+            }
+            Message::CodeRan(index, execution, result) => {
+                if let Some(Item::Assistant { executions, .. }) = self.history.get_mut(index) {
+                    if let Some(execution) = executions.get_mut(execution) {
+                        execution.output = Some(result.map_err(|error| dbg!(error)));
+                    }
                 }
+
+                Action::None
             }
-            Message::ToggleReasoning(index) => {
-                if let Some(Item::Assistant {
-                    reasoning: Some(reasoning),
-                    ..
-                }) = self.history.get_mut(index)
-                {
-                    reasoning.show = !reasoning.show;
+            Message::ToggleExecution(index, execution) => {
+                if let Some(Item::Assistant { executions, .. }) = self.history.get_mut(index) {
+                    if let Some(execution) = executions.get_mut(execution) {
+                        execution.show = !execution.show;
+                    }
                 }
 
                 Action::None
             }
-            Message::Created(Ok(chat)) | Message::Saved(Ok(chat)) => {
-                self.id = Some(chat.id);
+            Message::OpenInEditor(index) => {
+                let Some(Item::Assistant { content, .. }) = self.history.get(index) else {
+                    return Action::None;
+                };
 
-                Action::Run(Task::perform(Chat::list(), Message::ChatsListed))
+                let content = content.clone();
+
+                Action::Run(self.open_in_editor(index, None, None, content))
             }
-            Message::Created(Err(error)) | Message::Saved(Err(error)) => {
+            Message::OpenCodeInEditor(index, language, code) => {
+                Action::Run(self.open_in_editor(index, language, Some(code.clone()), code))
+            }
+            Message::EditorOpened(Ok(draft)) => match &mut self.editing {
+                Some(editing) => {
+                    editing.draft = Some(draft);
+
+                    Action::None
+                }
+                None => Action::Run(Task::future(draft.discard()).discard()),
+            },
+            Message::EditorOpened(Err(error)) => {
+                self.editing = None;
                 self.error = Some(dbg!(error));
 
                 Action::None
             }
-            Message::Open(chat) => {
-                Action::Run(Task::perform(Chat::fetch(chat), Message::ChatFetched))
+            Message::CheckEdits => {
+                let Some(draft) = self
+                    .editing
+                    .as_ref()
+                    .and_then(|editing| editing.draft.clone())
+                else {
+                    return Action::None;
+                };
+
+                Action::Run(Task::perform(draft.read(), Message::EditsChecked))
             }
-            Message::ChatFetched(Ok(chat)) | Message::LastChatFetched(Ok(chat)) => {
-                match &mut self.state {
-                    State::Booting { file, .. } if file == &chat.file => {
-                        self.id = Some(chat.id);
-                        self.title = chat.title;
-                        self.history = History::restore(chat.history);
-                        self.input = text_editor::Content::new();
+            Message::EditsChecked(Ok((draft, edits))) => {
+                if let Some(editing) = self.editing.as_mut().filter(|editing| {
+                    editing
+                        .draft
+                        .as_ref()
+                        .is_some_and(|current| current.path() == draft.path())
+                }) {
+                    editing.draft = Some(draft);
 
-                        Action::Run(Task::batch([
-                            widget::focus_next(),
-                            snap_chat_to_end(),
-                            measure_input(),
-                        ]))
+                    if edits.is_some() {
+                        editing.edits = edits;
                     }
-                    State::Running { assistant, sending } if assistant.file() == &chat.file => {
-                        self.id = Some(chat.id);
-                        self.title = chat.title;
-                        self.history = History::restore(chat.history);
-                        self.input = text_editor::Content::new();
+                }
+
+                Action::None
+            }
+            Message::EditsChecked(Err(error)) => {
+                self.editing = None;
+                self.error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::ApplyEdits => {
+                let Some(editing) = &mut self.editing else {
+                    return Action::None;
+                };
+
+                let is_writing = matches!(
+                    self.state,
+                    State::Running {
+                        sending: Some(_),
+                        ..
+                    }
+                ) && editing.index + 1 == self.history.len();
+
+                if is_writing {
+                    return Action::None;
+                }
+
+                let Some(edits) = editing.edits.take() else {
+                    return Action::None;
+                };
+
+                let Some(item) = self.history.get_mut(editing.index) else {
+                    return Action::None;
+                };
+
+                match &mut editing.code {
+                    Some(code) => {
+                        // Editors usually end files with a newline
+                        let edits = if code.ends_with('\n') {
+                            format!("{}\n", edits.trim_end_matches('\n'))
+                        } else {
+                            edits.trim_end_matches('\n').to_owned()
+                        };
+
+                        if !item.replace_code(code, &edits) {
+                            return Action::None;
+                        }
+
+                        *code = edits;
+                    }
+                    None => item.replace_content(edits.trim().to_owned()),
+                }
+
+                self.save()
+            }
+            Message::StopEditing => Action::Run(self.stop_editing()),
+            Message::Regenerate(index) => {
+                let track = self.track(Feature::Regenerate);
+
+                let stop_editing = if self
+                    .editing
+                    .as_ref()
+                    .is_some_and(|editing| editing.index >= index)
+                {
+                    self.stop_editing()
+                } else {
+                    Task::none()
+                };
+
+                if let State::Running { assistant, sending } = &mut self.state {
+                    let system_prompt = match compose_system_prompt(
+                        self.system_prompt
+                            .as_deref()
+                            .unwrap_or(&self.settings.system_prompt),
+                        &self.guardrails,
+                        assistant,
+                        &self.settings,
+                        &self.memory,
+                        recap(self.carry_over.as_ref()),
+                    ) {
+                        Ok(system_prompt) => system_prompt,
+                        Err(error) => {
+                            self.error = Some(dbg!(error));
+
+                            return Action::None;
+                        }
+                    };
+
+                    self.error = None;
+                    self.history.truncate(index);
+
+                    if self
+                        .carry_over
+                        .as_ref()
+                        .is_some_and(|carry_over| index < carry_over.covered)
+                    {
+                        self.carry_over = None;
+                    }
+
+                    let summarizer = summarizer(assistant, &self.settings, &self.pool);
+
+                    let (send, handle) = Task::run(
+                        chat::complete(
+                            &assistant.clone().with_summarizer(summarizer),
+                            chat::Request {
+                                system_prompt: &system_prompt,
+                                sampling: self.sampling,
+                                search: &self.settings.search,
+                                knowledge: self.knowledge.then_some(&self.settings.knowledge),
+                                suggest_titles: self.settings.suggest_titles,
+                            },
+                            prompt(&self.history, self.carry_over.as_ref()),
+                        ),
+                        Message::Chatting,
+                    )
+                    .abortable();
+
+                    *sending = Some(handle.abort_on_drop());
+
+                    if let Some(comparison) = &mut self.comparison {
+                        comparison.truncate(index);
+                    }
+
+                    let compare =
+                        self.compare(index, prompt(&self.history, self.carry_over.as_ref()));
+
+                    Action::Run(Task::batch([send, compare, track, stop_editing]))
+                } else {
+                    Action::None
+                }
+            }
+            Message::Branch(index) => {
+                let Some(id) = self.id else {
+                    return Action::None;
+                };
+
+                Action::Run(Task::batch([
+                    Task::perform(Chat::fork(id, index), Message::Branched),
+                    self.track(Feature::Branch),
+                ]))
+            }
+            Message::Branched(Ok(chat)) => {
+                let list = Task::perform(Chat::list(), Message::ChatsListed);
+
+                match self.update(Message::ChatFetched(Ok(chat))) {
+                    Action::Run(task) => Action::Run(Task::batch([task, list])),
+                    _ => Action::Run(list),
+                }
+            }
+            Message::Branched(Err(error)) => {
+                self.error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::ToggleReasoning(index) => {
+                if let Some(item) = self.history.get_mut(index) {
+                    item.toggle_reasoning();
+                }
+
+                Action::None
+            }
+            Message::ShowReply(index, alternative) => {
+                if let Some(Item::Assistant { shown, .. }) = self.history.get_mut(index) {
+                    *shown = alternative;
+                }
+
+                Action::None
+            }
+            Message::KeepReply(index, alternative) => {
+                let is_writing = matches!(
+                    self.state,
+                    State::Running {
+                        sending: Some(_),
+                        ..
+                    }
+                ) && index + 1 == self.history.len();
+
+                if is_writing {
+                    return Action::None;
+                }
+
+                let Some(item) = self.history.get_mut(index) else {
+                    return Action::None;
+                };
+
+                item.keep(alternative);
+
+                self.save()
+            }
+            Message::Created(Ok(chat)) | Message::Saved(Ok(chat)) => {
+                self.id = Some(chat.id);
+                self.unsaved = false;
+
+                Action::Run(Task::perform(Chat::list(), Message::ChatsListed))
+            }
+            Message::Created(Err(error)) | Message::Saved(Err(error)) => {
+                self.error = Some(dbg!(error));
+                self.unsaved = true;
+
+                Action::Run(Task::perform(
+                    data::available_space(),
+                    Message::DiskSpaceChecked,
+                ))
+            }
+            Message::CheckDiskSpace => Action::Run(Task::perform(
+                data::available_space(),
+                Message::DiskSpaceChecked,
+            )),
+            Message::DiskSpaceChecked(Ok(available_space)) => {
+                self.available_space = Some(available_space);
+
+                // Retry pending saves once space is available again
+                if self.unsaved && !self.is_low_on_space() && self.can_send() {
+                    self.save()
+                } else {
+                    Action::None
+                }
+            }
+            Message::DiskSpaceChecked(Err(error)) => {
+                let _ = dbg!(error);
+
+                Action::None
+            }
+            Message::MeasureResources => {
+                let State::Running { assistant, .. } = &self.state else {
+                    return Action::None;
+                };
+
+                Action::Run(Task::perform(
+                    hardware::Usage::measure(assistant.process_id()),
+                    Message::ResourcesMeasured,
+                ))
+            }
+            Message::ResourcesMeasured(resources) => {
+                self.resources = Some(resources);
+
+                Action::None
+            }
+            Message::CheckHealth => {
+                let State::Running { assistant, .. } = &self.state else {
+                    return Action::None;
+                };
+
+                Action::Run(Task::perform(
+                    assistant.clone().check_health(),
+                    Message::HealthChecked,
+                ))
+            }
+            Message::HealthChecked(true) => {
+                self.failed_checks = 0;
+
+                Action::None
+            }
+            Message::HealthChecked(false) => {
+                let State::Running { sending, .. } = &self.state else {
+                    return Action::None;
+                };
+
+                self.failed_checks += 1;
+
+                // A busy server may miss a check, and restarting it would
+                // kill the reply being generated
+                if self.failed_checks < MAX_FAILED_CHECKS || sending.is_some() {
+                    return Action::None;
+                }
+
+                self.crashed = true;
+
+                // Give up after a few crashes, in case the model itself is broken
+                if self.restarts < MAX_RESTARTS {
+                    self.restart()
+                } else {
+                    Action::None
+                }
+            }
+            Message::Restart => self.restart(),
+            Message::ToggleLogs => {
+                self.logs = if self.logs.is_some() {
+                    None
+                } else {
+                    Some(self.server_logs())
+                };
+
+                Action::None
+            }
+            Message::RefreshLogs => {
+                if self.logs.is_some() {
+                    self.logs = Some(self.server_logs());
+                }
+
+                Action::None
+            }
+            Message::CopyLogs => {
+                let Some(logs) = &self.logs else {
+                    return Action::None;
+                };
+
+                Action::Run(clipboard::write(logs.join("\n")))
+            }
+            Message::SaveLogs => Action::Run(Task::perform(
+                rfd::AsyncFileDialog::new()
+                    .set_file_name("llama-server.log")
+                    .add_filter("Log", &["log", "txt"])
+                    .save_file(),
+                |file| Message::SaveLogsTo(file.map(|file| file.path().to_owned())),
+            )),
+            Message::SaveLogsTo(Some(path)) => {
+                let Some(logs) = &self.logs else {
+                    return Action::None;
+                };
+
+                let mut contents = logs.join("\n");
+                contents.push('\n');
+
+                Action::Run(Task::perform(
+                    async move { tokio::fs::write(path, contents).await.map_err(Error::from) },
+                    Message::LogsSaved,
+                ))
+            }
+            Message::SaveLogsTo(None) | Message::LogsSaved(Ok(())) => Action::None,
+            Message::LogsSaved(Err(error)) => {
+                self.error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::Open(chat) => {
+                Action::Run(Task::perform(Chat::fetch(chat), Message::ChatFetched))
+            }
+            Message::SearchChats(query) => {
+                self.chat_search.set_query(query.clone());
+
+                if self.chat_search.is_active() {
+                    Action::Run(Task::perform(Chat::search(query.clone()), move |hits| {
+                        Message::ChatsSearched(query.clone(), hits)
+                    }))
+                } else {
+                    Action::None
+                }
+            }
+            Message::ChatsSearched(query, Ok(hits)) => {
+                self.chat_search.set_hits(&query, hits);
+
+                Action::None
+            }
+            Message::ChatsSearched(_query, Err(error)) => {
+                self.error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::OpenHit(hit) => {
+                let query = self.chat_search.query().trim().to_owned();
+                let track = self.track(Feature::ChatSearch);
+
+                if Some(hit.id) == self.id {
+                    return match hit.message {
+                        Some(message) => {
+                            Action::Run(Task::batch([self.reveal(query, message), track]))
+                        }
+                        None => Action::Run(track),
+                    };
+                }
+
+                self.revealing = hit.message.map(|message| (query, message));
+
+                Action::Run(Task::batch([
+                    Task::perform(Chat::fetch(hit.id), Message::ChatFetched),
+                    track,
+                ]))
+            }
+            Message::ChatFetched(Ok(chat)) | Message::LastChatFetched(Ok(chat)) => {
+                let revealing = self.revealing.take();
+                let stop_editing = self.stop_editing();
+
+                let action = match &mut self.state {
+                    State::Booting { file, .. } if file == &chat.file => {
+                        self.id = Some(chat.id);
+                        self.title = chat.title;
+                        self.history = History::restore(chat.history);
+                        self.clear_comparison();
+                        self.system_prompt = chat.system_prompt;
+                        self.guardrails = chat.guardrails;
+                        self.sampling = chat.sampling;
+                        self.memory = chat.memory;
+                        self.input = text_editor::Content::new();
+                        self.find = None;
+                        self.tags = None;
+                        self.snapshots = None;
+                        self.persona = None;
+                        self.carry_over = None;
+                        self.starters = Vec::new();
+
+                        Action::Run(Task::batch([
+                            widget::focus_next(),
+                            snap_chat_to_end(),
+                            measure_input(),
+                        ]))
+                    }
+                    State::Running { assistant, sending } if assistant.file() == &chat.file => {
+                        self.id = Some(chat.id);
+                        self.title = chat.title;
+                        self.history = History::restore(chat.history);
+                        self.system_prompt = chat.system_prompt;
+                        self.guardrails = chat.guardrails;
+                        self.sampling = chat.sampling;
+                        self.memory = chat.memory;
+                        self.input = text_editor::Content::new();
                         self.error = None;
+                        self.find = None;
+                        self.tags = None;
+                        self.snapshots = None;
+                        self.persona = None;
+                        self.carry_over = None;
+                        self.starters = Vec::new();
 
                         *sending = None;
+                        self.clear_comparison();
 
                         Action::Run(Task::batch([widget::focus_next(), snap_chat_to_end()]))
                     }
                     _ => {
-                        let (conversation, task) = Self::open(chat, self.backend);
+                        self.release();
+
+                        let (conversation, task) = Self::open(
+                            chat,
+                            self.backend,
+                            self.settings.clone(),
+                            self.pool.clone(),
+                        );
+
+                        let chat_search = std::mem::take(&mut self.chat_search);
 
                         *self = conversation;
+                        self.chat_search = chat_search;
 
                         Action::Run(task)
                     }
+                };
+
+                let action = match action {
+                    Action::Run(task) => Action::Run(Task::batch([task, stop_editing])),
+                    _ => Action::Run(stop_editing),
+                };
+
+                let Some((query, message)) = revealing else {
+                    return action;
+                };
+
+                let reveal = self.reveal(query, message);
+
+                match action {
+                    Action::Run(task) => Action::Run(task.chain(reveal)),
+                    _ => Action::Run(reveal),
                 }
             }
             Message::ChatFetched(Err(error)) => {
@@ -388,14 +1548,25 @@ impl Conversation {
                 self.id = None;
                 self.title = None;
                 self.history = History::new();
+                self.clear_comparison();
+                self.system_prompt = None;
+                self.guardrails = Guardrails::default();
+                self.sampling = assistant::Sampling::default();
+                self.memory = Vec::new();
                 self.input = text_editor::Content::new();
                 self.error = None;
+                self.find = None;
+                self.tags = None;
+                self.snapshots = None;
+                self.persona = None;
+                self.carry_over = None;
+                self.starters = Vec::new();
 
                 if let State::Running { sending, .. } = &mut self.state {
                     *sending = None;
                 }
 
-                Action::Run(widget::focus_next())
+                Action::Run(Task::batch([widget::focus_next(), self.stop_editing()]))
             }
             Message::Delete => {
                 if let Some(id) = self.id.clone() {
@@ -410,6 +1581,32 @@ impl Conversation {
                 }
             }
             Message::Search => Action::Back,
+            Message::Shortcut(command) => match command {
+                keymap::Command::NewChat => self.update(Message::New),
+                keymap::Command::SearchChats => {
+                    self.sidebar_open = true;
+
+                    Action::Run(
+                        text_input::focus(SEARCH_CHATS).chain(text_input::select_all(SEARCH_CHATS)),
+                    )
+                }
+                keymap::Command::Find => self.update(Message::OpenFind),
+                keymap::Command::CopyLastReply => {
+                    let last_reply = self.history.items().rev().find_map(|item| match item {
+                        Item::Assistant { content, .. } => Some(content.clone()),
+                        Item::User { .. } => None,
+                    });
+
+                    match last_reply {
+                        Some(reply) => Action::Run(clipboard::write(reply)),
+                        None => Action::None,
+                    }
+                }
+                keymap::Command::OpenSettings => Action::OpenSettings,
+                keymap::Command::Submit => self.update(Message::Submit),
+                // Quitting is handled by the app
+                keymap::Command::Quit => Action::None,
+            },
             Message::ToggleSidebar => {
                 self.sidebar_open = !self.sidebar_open;
 
@@ -420,62 +1617,692 @@ impl Conversation {
 
                 Action::None
             }
-        }
-    }
+            Message::OpenFind => {
+                if self.find.is_none() {
+                    self.find = Some(Find::new());
+                }
 
-    pub fn view(&self, theme: &Theme) -> Element<Message> {
-        let header: Element<_> = {
-            let title: Element<_> = match &self.title {
-                Some(title) => column![
-                    text(title).font(Font::MONOSPACE).size(20),
-                    text(self.model_name())
-                        .font(Font::MONOSPACE)
-                        .size(14)
-                        .style(text::secondary)
-                ]
-                .spacing(5)
-                .align_x(Center)
-                .into(),
-                None => text(self.model_name())
-                    .font(Font::MONOSPACE)
-                    .size(20)
-                    .align_x(Center)
-                    .into(),
-            };
+                Action::Run(Task::batch([
+                    text_input::focus(FIND).chain(text_input::select_all(FIND)),
+                    self.track(Feature::Find),
+                ]))
+            }
+            Message::FindChanged(query) => {
+                if let Some(find) = &mut self.find {
+                    find.search(query, &self.history);
 
-            let toggle_sidebar = tip(
-                button(if self.sidebar_open {
-                    icon::collapse()
+                    Action::Run(find.scroll_to_current(&self.history))
                 } else {
-                    icon::expand()
-                })
-                .padding(0)
-                .on_press(Message::ToggleSidebar)
-                .style(button::text),
-                if self.sidebar_open {
-                    "Close sidebar"
+                    Action::None
+                }
+            }
+            Message::FindNext => {
+                if let Some(find) = &mut self.find {
+                    find.next();
+
+                    Action::Run(find.scroll_to_current(&self.history))
                 } else {
-                    "Open sidebar"
-                },
-                tip::Position::Right,
-            );
+                    Action::None
+                }
+            }
+            Message::FindPrevious => {
+                if let Some(find) = &mut self.find {
+                    find.previous();
 
-            let delete: Element<_> = if self.id.is_some() {
-                tip(
-                    button(icon::trash().style(text::danger))
-                        .padding(0)
-                        .on_press(Message::Delete)
-                        .style(button::text),
-                    "Delete Chat",
-                    tip::Position::Left,
+                    Action::Run(find.scroll_to_current(&self.history))
+                } else {
+                    Action::None
+                }
+            }
+            Message::CloseFind => {
+                self.find = None;
+
+                Action::Run(widget::focus_next())
+            }
+            Message::FilterChanged(filter) => {
+                self.filter = filter;
+
+                Action::None
+            }
+            Message::TagsChanged(tags) => {
+                self.tags = Some(tags);
+
+                Action::None
+            }
+            Message::SubmitTags => {
+                use itertools::Itertools;
+
+                let (Some(id), Some(tags)) = (self.id, self.tags.take()) else {
+                    return Action::None;
+                };
+
+                let tags: Vec<String> = tags
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .unique()
+                    .map(str::to_owned)
+                    .collect();
+
+                if let Some(entry) = self.chats.iter_mut().find(|entry| entry.id == id) {
+                    entry.tags = tags.clone();
+                }
+
+                Action::Run(Task::perform(Chat::tag(id, tags), Message::Tagged))
+            }
+            Message::Tagged(Ok(())) => {
+                Action::Run(Task::perform(Chat::list(), Message::ChatsListed))
+            }
+            Message::Tagged(Err(error)) => {
+                self.error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::TogglePersona => {
+                if self.persona.take().is_some() {
+                    return self.save();
+                }
+
+                self.persona = Some(text_editor::Content::with_text(self.system_prompt()));
+
+                Action::None
+            }
+            Message::PersonaEdited(action) => {
+                if let Some(persona) = &mut self.persona {
+                    let is_edit = action.is_edit();
+
+                    persona.perform(action);
+
+                    if is_edit {
+                        let system_prompt = persona.text().trim().to_owned();
+
+                        self.system_prompt =
+                            (system_prompt != self.settings.system_prompt).then_some(system_prompt);
+                    }
+                }
+
+                Action::None
+            }
+            Message::ResetPersona => {
+                self.system_prompt = None;
+
+                if let Some(persona) = &mut self.persona {
+                    *persona = text_editor::Content::with_text(&self.settings.system_prompt);
+                }
+
+                Action::None
+            }
+            Message::RefusedTopicsChanged(topics) => {
+                self.guardrails.refused_topics = topics;
+
+                Action::None
+            }
+            Message::ToneChanged(tone) => {
+                self.guardrails.tone = tone;
+
+                Action::None
+            }
+            Message::ToggleInspector => {
+                if self.inspector.is_some() {
+                    self.inspector = None;
+
+                    Action::None
+                } else {
+                    self.inspect()
+                }
+            }
+            Message::Inspect => self.inspect(),
+            Message::Recapped(Ok(new_recap)) => {
+                if let Some(carry_over) = &mut self.carry_over {
+                    carry_over.recap = Some(new_recap);
+                }
+
+                Action::None
+            }
+            Message::Recapped(Err(error)) => {
+                self.carry_over = None;
+                self.error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::Inspected(result) => {
+                if self.inspector.is_some() {
+                    self.inspector = Some(match result {
+                        Ok(preview) => Inspector::Ready(preview),
+                        Err(error) => Inspector::Failed(error),
+                    });
+                }
+
+                Action::None
+            }
+            Message::Forget(index) => {
+                if index < self.memory.len() {
+                    let _ = self.memory.remove(index);
+                }
+
+                self.save()
+            }
+            Message::ToggleSnapshots => {
+                if self.snapshots.take().is_some() {
+                    return Action::None;
+                }
+
+                let Some(id) = self.id else {
+                    return Action::None;
+                };
+
+                Action::Run(Task::perform(Chat::snapshots(id), Message::SnapshotsListed))
+            }
+            Message::SnapshotsListed(Ok(snapshots)) => {
+                self.snapshots = Some(snapshots);
+
+                Action::None
+            }
+            Message::SnapshotsListed(Err(error)) => {
+                self.error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::RestoreSnapshot(snapshot) => {
+                self.snapshots = None;
+
+                Action::Run(Task::batch([
+                    Task::perform(Chat::restore(snapshot), Message::ChatFetched),
+                    self.track(Feature::RestoreSnapshot),
+                ]))
+            }
+            Message::ToggleSampling => {
+                self.sampling_open = !self.sampling_open;
+
+                if self.sampling_open {
+                    Action::None
+                } else {
+                    self.save()
+                }
+            }
+            Message::ToggleKnowledge => {
+                self.knowledge = !self.knowledge;
+
+                Action::None
+            }
+            Message::ToggleComparison => {
+                self.comparison = if self.comparison.is_some() {
+                    None
+                } else {
+                    Some(Comparison::new(available_files(
+                        &self.chats,
+                        &self.downloads,
+                        self.file(),
+                    )))
+                };
+
+                Action::None
+            }
+            Message::CompareWith(file) => {
+                let Some(comparison) = &mut self.comparison else {
+                    return Action::None;
+                };
+
+                let launch = self.settings.launch(&file, self.backend);
+
+                Action::Run(comparison.boot(file, launch))
+            }
+            Message::ComparisonBooting(event) => {
+                if let Some(comparison) = &mut self.comparison {
+                    comparison.booting(event);
+                }
+
+                Action::None
+            }
+            Message::Compared(index, Ok(event)) => {
+                if let Some(comparison) = &mut self.comparison {
+                    comparison.update(index, event);
+                }
+
+                Action::None
+            }
+            Message::ToggleInfill => {
+                self.infill = if self.infill.is_some() {
+                    None
+                } else {
+                    Some(Infill::new())
+                };
+
+                Action::None
+            }
+            Message::InfillPrefixEdited(action) => {
+                if let Some(infill) = &mut self.infill {
+                    infill.edit_prefix(action);
+                }
+
+                Action::None
+            }
+            Message::InfillSuffixEdited(action) => {
+                if let Some(infill) = &mut self.infill {
+                    infill.edit_suffix(action);
+                }
+
+                Action::None
+            }
+            Message::FillMiddle => {
+                let (Some(infill), State::Running { assistant, .. }) =
+                    (&mut self.infill, &self.state)
+                else {
+                    return Action::None;
+                };
+
+                self.error = None;
+
+                Action::Run(infill.fill(assistant, self.sampling))
+            }
+            Message::MiddleFilled(Ok(middle)) => {
+                if let Some(infill) = &mut self.infill {
+                    infill.push(&middle);
+                }
+
+                Action::None
+            }
+            Message::MiddleFilled(Err(error)) => {
+                self.error = Some(dbg!(error));
+
+                if let Some(infill) = &mut self.infill {
+                    infill.stop();
+                }
+
+                Action::None
+            }
+            Message::InfillOver | Message::StopInfill => {
+                if let Some(infill) = &mut self.infill {
+                    infill.stop();
+                }
+
+                Action::None
+            }
+            Message::Compared(_, Err(error)) => {
+                self.error = Some(dbg!(error));
+
+                if let Some(comparison) = &mut self.comparison {
+                    comparison.stop();
+                }
+
+                Action::None
+            }
+            Message::SamplingChanged(sampling) => {
+                self.sampling = sampling;
+
+                Action::None
+            }
+            Message::MaxTokensChanged(max_tokens) => {
+                if max_tokens.trim().is_empty() {
+                    self.sampling.max_tokens = None;
+                } else if let Ok(max_tokens) = max_tokens.trim().parse() {
+                    self.sampling.max_tokens = Some(max_tokens);
+                }
+
+                Action::None
+            }
+            Message::ResetSampling => {
+                self.sampling = assistant::Sampling::default();
+
+                Action::None
+            }
+            Message::OpenSettings => Action::OpenSettings,
+            Message::Preload(file) => Action::Preload {
+                file,
+                backend: self.backend,
+            },
+            Message::Preloads(message) => Action::Preloads(message),
+            Message::ChatHovered(file) => {
+                // Passing over a chat should not boot its model
+                const DWELL: Duration = Duration::from_millis(600);
+
+                self.hovered = Some(file.clone());
+
+                Action::Run(Task::perform(tokio::time::sleep(DWELL), move |_| {
+                    Message::HoverSettled(file.clone())
+                }))
+            }
+            Message::HoverSettled(file) => {
+                if self.hovered.as_ref() != Some(&file) {
+                    return Action::None;
+                }
+
+                Action::PreloadOnHover {
+                    file,
+                    backend: self.backend,
+                }
+            }
+            Message::ChatUnhovered(file) => {
+                if self.hovered.as_ref() == Some(&file) {
+                    self.hovered = None;
+                }
+
+                Action::CancelHoverPreload(file)
+            }
+        }
+    }
+
+    pub fn view<'a>(&'a self, theme: &Theme, preloads: &'a Preloads) -> Element<'a, Message> {
+        let header: Element<_> = {
+            let title: Element<_> = match &self.title {
+                Some(title) => column![
+                    text(title).font(Font::MONOSPACE).size(20),
+                    text(self.model_name())
+                        .font(Font::MONOSPACE)
+                        .size(14)
+                        .style(text::secondary)
+                ]
+                .spacing(5)
+                .align_x(Center)
+                .into(),
+                None => text(self.model_name())
+                    .font(Font::MONOSPACE)
+                    .size(20)
+                    .align_x(Center)
+                    .into(),
+            };
+
+            let toggle_sidebar = tip(
+                button(if self.sidebar_open {
+                    icon::collapse()
+                } else {
+                    icon::expand()
+                })
+                .padding(0)
+                .on_press(Message::ToggleSidebar)
+                .style(button::text),
+                if self.sidebar_open {
+                    "Close sidebar"
+                } else {
+                    "Open sidebar"
+                },
+                tip::Position::Right,
+            );
+
+            let persona = tip(
+                button(text("Persona").size(14))
+                    .padding(0)
+                    .on_press(Message::TogglePersona)
+                    .style(if self.persona.is_some() {
+                        button::primary
+                    } else {
+                        button::text
+                    }),
+                "Edit system prompt",
+                tip::Position::Left,
+            );
+
+            let sampling = tip(
+                button(text("Sampling").size(14))
+                    .padding(0)
+                    .on_press(Message::ToggleSampling)
+                    .style(if self.sampling_open {
+                        button::primary
+                    } else {
+                        button::text
+                    }),
+                "Adjust sampling parameters",
+                tip::Position::Left,
+            );
+
+            let inspect = tip(
+                button(text("Inspect").size(14))
+                    .padding(0)
+                    .on_press(Message::ToggleInspector)
+                    .style(if self.inspector.is_some() {
+                        button::primary
+                    } else {
+                        button::text
+                    }),
+                "Inspect what the next message sends",
+                tip::Position::Left,
+            );
+
+            let knowledge = (!self.settings.knowledge.folders.is_empty()).then(|| {
+                tip(
+                    button(text("Knowledge").size(14))
+                        .padding(0)
+                        .on_press(Message::ToggleKnowledge)
+                        .style(if self.knowledge {
+                            button::primary
+                        } else {
+                            button::text
+                        }),
+                    "Ground replies in your documents",
+                    tip::Position::Left,
                 )
-            } else {
-                horizontal_space().into()
+            });
+
+            let infill = tip(
+                button(text("Fill in").size(14))
+                    .padding(0)
+                    .on_press(Message::ToggleInfill)
+                    .style(if self.infill.is_some() {
+                        button::primary
+                    } else {
+                        button::text
+                    }),
+                "Complete code between a prefix and a suffix",
+                tip::Position::Left,
+            );
+
+            let compare = tip(
+                button(text("Compare").size(14))
+                    .padding(0)
+                    .on_press(Message::ToggleComparison)
+                    .style(if self.comparison.is_some() {
+                        button::primary
+                    } else {
+                        button::text
+                    }),
+                "Answer with a second model side by side",
+                tip::Position::Left,
+            );
+
+            let logs = tip(
+                button(text("Logs").size(14))
+                    .padding(0)
+                    .on_press(Message::ToggleLogs)
+                    .style(if self.logs.is_some() {
+                        button::primary
+                    } else {
+                        button::text
+                    }),
+                "Show the output of llama-server",
+                tip::Position::Left,
+            );
+
+            let snapshots = self.id.is_some().then(|| {
+                tip(
+                    button(text("History").size(14))
+                        .padding(0)
+                        .on_press(Message::ToggleSnapshots)
+                        .style(if self.snapshots.is_some() {
+                            button::primary
+                        } else {
+                            button::text
+                        }),
+                    "Restore an earlier version",
+                    tip::Position::Left,
+                )
+            });
+
+            let locale = self.settings.locale;
+
+            let usage = self.history.usage().map(|usage| {
+                tip(
+                    text!(
+                        "{tokens} tokens · {speed} tok/s",
+                        tokens = locale.number(usage.completion_tokens),
+                        speed = locale.decimal(usage.tokens_per_second().into(), 1)
+                    )
+                    .font(Font::MONOSPACE)
+                    .size(10)
+                    .style(text::secondary),
+                    format!(
+                        "Generated in this chat; the last prompt had {} tokens",
+                        locale.number(usage.prompt_tokens)
+                    ),
+                    tip::Position::Bottom,
+                )
+            });
+
+            let context = match &self.state {
+                State::Running { assistant, .. } => assistant.context_size(),
+                _ => None,
+            }
+            .zip(self.history.last_usage())
+            .map(|(context_size, usage)| {
+                let used = usage.prompt_tokens + usage.completion_tokens;
+                let percent = 100 * used / context_size.max(1);
+
+                let (used, context_size) = (locale.number(used), locale.number(context_size));
+
+                let tooltip = if self.truncated > 0 {
+                    format!(
+                        "{used} of {context_size} tokens in context; \
+                        the {truncated} oldest messages were left out",
+                        truncated = self.truncated
+                    )
+                } else {
+                    format!("{used} of {context_size} tokens in context")
+                };
+
+                tip(
+                    text!("{percent}% context")
+                        .font(Font::MONOSPACE)
+                        .size(10)
+                        .style(if self.truncated > 0 || percent >= 90 {
+                            text::danger
+                        } else {
+                            text::secondary
+                        }),
+                    tooltip,
+                    tip::Position::Bottom,
+                )
+            });
+
+            let resources = self
+                .resources
+                .filter(|resources| resources.ram().is_some() || resources.video.is_some())
+                .map(|resources| {
+                    let ram = resources
+                        .ram()
+                        .map(|ram| format!("{} RAM", locale.bytes(ram)));
+
+                    let vram = resources
+                        .video
+                        .map(|vram| format!("{} VRAM", locale.bytes(vram)));
+
+                    let tooltip = [
+                        resources
+                            .app
+                            .map(|app| format!("Icebreaker: {} of RAM", locale.bytes(app))),
+                        resources
+                            .server
+                            .map(|server| format!("Assistant: {} of RAM", locale.bytes(server))),
+                        resources
+                            .video
+                            .map(|vram| format!("Graphics card: {} in use", locale.bytes(vram))),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                    tip(
+                        text(
+                            [ram, vram]
+                                .into_iter()
+                                .flatten()
+                                .collect::<Vec<_>>()
+                                .join(" · "),
+                        )
+                        .font(Font::MONOSPACE)
+                        .size(10)
+                        .style(text::secondary),
+                        tooltip,
+                        tip::Position::Bottom,
+                    )
+                });
+
+            let tags = self.id.map(|id| {
+                let tags = self.tags.clone().unwrap_or_else(|| {
+                    self.chats
+                        .iter()
+                        .find(|entry| entry.id == id)
+                        .map(|entry| entry.tags.join(", "))
+                        .unwrap_or_default()
+                });
+
+                tip(
+                    text_input("Add tags...", &tags)
+                        .on_input(Message::TagsChanged)
+                        .on_submit(Message::SubmitTags)
+                        .size(12)
+                        .padding([0, 5])
+                        .width(150),
+                    "Comma-separated tags; press Enter to save",
+                    tip::Position::Bottom,
+                )
+            });
+
+            let switch = {
+                let files = available_files(&self.chats, &self.downloads, self.file());
+
+                (!files.is_empty() && !matches!(self.state, State::Missing { .. })).then(|| {
+                    pick_list(files, None::<File>, Message::SwitchModel)
+                        .placeholder("Model")
+                        .text_size(14)
+                        .padding([0, 5])
+                        .style(|theme, status| pick_list::Style {
+                            border: border::rounded(2),
+                            ..pick_list::default(theme, status)
+                        })
+                })
             };
 
+            let export = self.id.is_some().then(|| {
+                pick_list(chat::Format::ALL, None::<chat::Format>, Message::Export)
+                    .placeholder("Export")
+                    .text_size(14)
+                    .padding([0, 5])
+                    .style(|theme, status| pick_list::Style {
+                        border: border::rounded(2),
+                        ..pick_list::default(theme, status)
+                    })
+            });
+
+            let delete = self.id.is_some().then(|| {
+                tip(
+                    button(icon::trash().style(text::danger))
+                        .padding(0)
+                        .on_press(Message::Delete)
+                        .style(button::text),
+                    "Delete Chat",
+                    tip::Position::Left,
+                )
+            });
+
             let bar = stack![
-                center_x(title).padding([0, 40]),
-                row![toggle_sidebar, horizontal_space(), delete],
+                center_x(title).padding([0, 180]),
+                row![toggle_sidebar, horizontal_space()]
+                    .push_maybe(usage)
+                    .push_maybe(context)
+                    .push_maybe(resources)
+                    .push_maybe(tags)
+                    .push_maybe(snapshots)
+                    .push_maybe(knowledge)
+                    .push(compare)
+                    .push(infill)
+                    .push(sampling)
+                    .push(inspect)
+                    .push(logs)
+                    .push(persona)
+                    .push_maybe(switch)
+                    .push_maybe(export)
+                    .push_maybe(delete)
+                    .spacing(10),
             ]
             .into();
 
@@ -550,11 +2377,387 @@ impl Conversation {
 
                     stack![bar, right_center(progress)].into()
                 }
-                State::Running { .. } => bar,
+                State::Running { .. } | State::Missing { .. } => bar,
             }
         };
 
+        let persona = self.persona.as_ref().map(|persona| {
+            let editor = text_editor(persona)
+                .placeholder("Describe how the assistant should behave...")
+                .on_action(Message::PersonaEdited)
+                .padding(10)
+                .height(150);
+
+            let reset = button(text("Reset to default").size(12))
+                .on_press_maybe(
+                    self.system_prompt
+                        .is_some()
+                        .then_some(Message::ResetPersona),
+                )
+                .padding([2, 7])
+                .style(button::text);
+
+            let use_template = tip(
+                button(text("Use template").size(12))
+                    .on_press(Message::ImportSetup)
+                    .padding([2, 7])
+                    .style(button::text),
+                "Start a new chat from a template file; you can drop one here too",
+                tip::Position::Bottom,
+            );
+
+            let save_template = tip(
+                button(text("Save as template").size(12))
+                    .on_press(Message::ExportSetup)
+                    .padding([2, 7])
+                    .style(button::text),
+                "Share the persona, sampling, and memory of this chat",
+                tip::Position::Bottom,
+            );
+
+            let hint = text(if self.system_prompt.is_some() {
+                "This chat uses a custom system prompt."
+            } else {
+                "This chat uses the default system prompt from Settings."
+            })
+            .size(12)
+            .style(text::secondary);
+
+            let guardrails = column![
+                row![
+                    text("Refuse to discuss").size(12).width(Fill),
+                    text_input(
+                        "Topics, separated by commas",
+                        &self.guardrails.refused_topics
+                    )
+                    .on_input(Message::RefusedTopicsChanged)
+                    .size(12)
+                    .padding(5)
+                    .width(300),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                row![
+                    text("Tone").size(12).width(Fill),
+                    text_input("Concise and formal", &self.guardrails.tone)
+                        .on_input(Message::ToneChanged)
+                        .size(12)
+                        .padding(5)
+                        .width(300),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            ]
+            .spacing(5);
+
+            let memory = (!self.memory.is_empty()).then(|| {
+                column![
+                    text("Memory").size(12).font(Font::MONOSPACE),
+                    column(self.memory.iter().enumerate().map(|(index, fact)| {
+                        row![
+                            text(fact).size(12).width(Fill),
+                            button(text("Forget").size(12))
+                                .on_press(Message::Forget(index))
+                                .padding([0, 5])
+                                .style(button::text),
+                        ]
+                        .align_y(Center)
+                        .into()
+                    })),
+                ]
+                .spacing(5)
+            });
+
+            center_x(
+                container(
+                    column![
+                        editor,
+                        row![hint, horizontal_space(), use_template, save_template, reset]
+                            .align_y(Center),
+                        text("Guardrails").size(12).font(Font::MONOSPACE),
+                        guardrails,
+                    ]
+                    .push_maybe(memory)
+                    .spacing(5),
+                )
+                .max_width(600),
+            )
+            .padding([0, 40])
+        });
+
+        let sampling = self
+            .sampling_open
+            .then(|| center_x(sampling(self.sampling)).padding([0, 40]));
+
+        let inspector = self
+            .inspector
+            .as_ref()
+            .map(|inspector| center_x(self::inspector(inspector, self.sampling)).padding([0, 40]));
+
+        let snapshots = self
+            .snapshots
+            .as_deref()
+            .map(|snapshots| center_x(self::snapshots(snapshots)).padding([0, 40]));
+
+        let logs = self
+            .logs
+            .as_deref()
+            .map(|logs| center_x(self::logs(logs)).padding([0, 40]));
+
+        let find = self
+            .find
+            .as_ref()
+            .map(|find| center_x(find.view()).padding([0, 40]));
+
+        let disk_warning = (self.is_low_on_space() || self.unsaved).then(|| {
+            let warning = match self.available_space {
+                Some(available_space) if self.is_low_on_space() => format!(
+                    "Low disk space ({} left). Your chat is kept in memory \
+                    and will be saved once space is freed.",
+                    self.settings.locale.bytes(available_space)
+                ),
+                _ => "Your chat could not be saved. Retrying...".to_owned(),
+            };
+
+            center_x(
+                container(text(warning).size(12).style(text::danger))
+                    .padding([5, 10])
+                    .style(container::rounded_box),
+            )
+            .padding([0, 40])
+        });
+
+        let crash = self.crashed.then(|| {
+            let content: Element<_> = if matches!(self.state, State::Booting { .. }) {
+                text("The assistant stopped responding. Restarting it; your chat is kept.")
+                    .size(12)
+                    .into()
+            } else {
+                row![
+                    text("The assistant stopped responding and could not be restarted.")
+                        .size(12)
+                        .style(text::danger)
+                        .width(Fill),
+                    button(text("Restart").size(12))
+                        .on_press(Message::Restart)
+                        .padding([2, 7])
+                        .style(button::primary),
+                ]
+                .spacing(10)
+                .align_y(Center)
+                .into()
+            };
+
+            center_x(
+                container(content)
+                    .padding([5, 10])
+                    .max_width(600)
+                    .style(container::rounded_box),
+            )
+            .padding([0, 40])
+        });
+
+        let missing = if let State::Missing { file, alternatives } = &self.state {
+            Some(
+                center_x(
+                    container(
+                        row![
+                            text!("{file} is not downloaded. This chat is read-only.")
+                                .size(12)
+                                .width(Fill),
+                            button(text("Download").size(12))
+                                .on_press(Message::Download)
+                                .padding([2, 7])
+                                .style(button::primary),
+                            pick_list(alternatives.as_slice(), None::<File>, Message::SwitchModel)
+                                .placeholder("Switch model...")
+                                .text_size(12)
+                                .padding([2, 7]),
+                        ]
+                        .spacing(10)
+                        .align_y(Center),
+                    )
+                    .padding([5, 10])
+                    .max_width(600)
+                    .style(container::rounded_box),
+                )
+                .padding([0, 40]),
+            )
+        } else {
+            None
+        };
+
+        let out_of_memory = match (&self.state, &self.error) {
+            (State::Booting { file, .. }, Some(Error::OutOfMemory)) => {
+                let suggestion: Element<_> = if let Some(smaller) = self.smaller.first() {
+                    row![
+                        tip(
+                            button(text("Use smaller file").size(12))
+                                .on_press_with(|| Message::SwitchModel(smaller.clone()))
+                                .padding([2, 7])
+                                .style(button::primary),
+                            smaller.name.as_str(),
+                            tip::Position::Bottom,
+                        ),
+                        pick_list(self.smaller.as_slice(), None::<File>, Message::SwitchModel)
+                            .placeholder("Other files...")
+                            .text_size(12)
+                            .padding([2, 7]),
+                    ]
+                    .spacing(10)
+                    .into()
+                } else {
+                    text("No smaller quantization is available.")
+                        .size(12)
+                        .style(text::secondary)
+                        .into()
+                };
+
+                Some(
+                    center_x(
+                        container(
+                            row![
+                                text!("{file} does not fit in memory.").size(12).width(Fill),
+                                suggestion,
+                            ]
+                            .spacing(10)
+                            .align_y(Center),
+                        )
+                        .padding([5, 10])
+                        .max_width(600)
+                        .style(container::rounded_box),
+                    )
+                    .padding([0, 40]),
+                )
+            }
+            _ => None,
+        };
+
+        let run = self.run.as_ref().map(|run| {
+            let warning = match run.sandbox {
+                None => text("Looking for a sandbox...").style(text::secondary),
+                Some(exec::Sandbox::Docker) => {
+                    text!(
+                        "It will run in {} without network access.",
+                        exec::Sandbox::Docker
+                    )
+                }
+                Some(exec::Sandbox::Process) => text!(
+                    "Docker is not available. It will run in {} with your permissions.",
+                    exec::Sandbox::Process
+                )
+                .style(text::danger),
+            };
+
+            center_x(
+                container(
+                    column![
+                        text!("Run this {} code?", run.language).size(14),
+                        scrollable(text(&run.code).font(Font::MONOSPACE).size(12)).height(Shrink),
+                        row![
+                            warning.size(12).width(Fill),
+                            button(text("Cancel").size(12))
+                                .on_press(Message::CancelRun)
+                                .padding([2, 7])
+                                .style(button::secondary),
+                            button(text("Run").size(12))
+                                .on_press_maybe(run.sandbox.map(|_| Message::ConfirmRun))
+                                .padding([2, 7])
+                                .style(button::primary),
+                        ]
+                        .spacing(10)
+                        .align_y(Center),
+                    ]
+                    .spacing(10),
+                )
+                .padding([5, 10])
+                .max_width(600)
+                .max_height(300)
+                .style(container::rounded_box),
+            )
+            .padding([0, 40])
+        });
+
+        let editing = self.editing.as_ref().map(|editing| {
+            let subject = if editing.code.is_some() {
+                "A code block"
+            } else {
+                "A reply"
+            };
+
+            let status = if editing.draft.is_none() {
+                text!("{subject} is opening in your editor...")
+            } else if editing.edits.is_some() {
+                text!("{subject} was edited in your editor.")
+            } else {
+                text!("{subject} is open in your editor; save it to pull the edits.")
+            };
+
+            center_x(
+                container(
+                    row![
+                        status.size(12).width(Fill),
+                        button(text("Stop").size(12))
+                            .on_press(Message::StopEditing)
+                            .padding([2, 7])
+                            .style(button::secondary),
+                        button(text("Apply edits").size(12))
+                            .on_press_maybe(editing.edits.is_some().then_some(Message::ApplyEdits))
+                            .padding([2, 7])
+                            .style(button::primary),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                )
+                .padding([5, 10])
+                .max_width(600)
+                .style(container::rounded_box),
+            )
+            .padding([0, 40])
+        });
+
+        let comparison = self
+            .comparison
+            .as_ref()
+            .map(|comparison| center_x(comparison.view()).padding([0, 40]));
+
+        let infill = self.infill.as_ref().map(|infill| {
+            center_x(infill.view(matches!(self.state, State::Running { .. }))).padding([0, 40])
+        });
+
+        let header: Element<_> = column![header]
+            .push_maybe(missing)
+            .push_maybe(comparison)
+            .push_maybe(infill)
+            .push_maybe(out_of_memory)
+            .push_maybe(run)
+            .push_maybe(editing)
+            .push_maybe(disk_warning)
+            .push_maybe(crash)
+            .push_maybe(persona)
+            .push_maybe(sampling)
+            .push_maybe(inspector)
+            .push_maybe(logs)
+            .push_maybe(snapshots)
+            .push_maybe(find)
+            .spacing(10)
+            .into();
+
         let messages: Element<_> = if self.history.is_empty() {
+            let starters = (!self.starters.is_empty()
+                && !matches!(self.state, State::Missing { .. }))
+            .then(|| {
+                column(self.starters.iter().map(|starter| {
+                    button(text(starter).size(14))
+                        .on_press_with(|| Message::UseStarter(starter.clone()))
+                        .padding([5, 10])
+                        .style(button::secondary)
+                        .into()
+                }))
+                .spacing(5)
+                .align_x(Center)
+            });
+
             center(
                 match &self.state {
                     State::Running { .. } => column![
@@ -565,48 +2768,145 @@ impl Conversation {
                         text("Your assistant is launching..."),
                         text("You can begin typing while you wait! ↓").style(text::success),
                     ],
+                    State::Missing { .. } => column![text("This chat is empty.")],
                 }
+                .push_maybe(starters)
                 .spacing(10)
                 .align_x(Center),
             )
             .into()
         } else {
+            let (visible, before, after) = self.history.window();
+
+            let items =
+                self.history
+                    .items()
+                    .enumerate()
+                    .skip(visible.start)
+                    .take(visible.len())
+                    .map(|(i, item)| {
+                        let item = item.view(
+                            i,
+                            theme,
+                            self.find.as_ref().and_then(|find| find.highlight(i)),
+                            self.find.as_ref().and_then(|find| find.query(i)),
+                            self.settings.locale,
+                            self.settings.appearance,
+                        );
+
+                        let item = match self.comparison.as_ref().and_then(|comparison| {
+                            comparison.reply(i, theme, self.settings.appearance)
+                        }) {
+                            Some(reply) => {
+                                row![container(item).width(Fill), container(reply).width(Fill)]
+                                    .spacing(20)
+                                    .into()
+                            }
+                            None => item,
+                        };
+
+                        let item = match self
+                            .carry_over
+                            .as_ref()
+                            .filter(|carry_over| carry_over.covered == i + 1)
+                        {
+                            Some(carry_over) => column![item, carry_over.view()].spacing(10).into(),
+                            None => item,
+                        };
+
+                        container(item).id(item_id(i)).into()
+                    });
+
+            let space = |height: f32| (height > 0.0).then(|| Space::with_height(height).into());
+
             scrollable(
                 center_x(
-                    column(
-                        self.history
-                            .items()
-                            .enumerate()
-                            .map(|(i, item)| item.view(i, theme)),
-                    )
-                    .padding(20)
-                    .max_width(600),
+                    column(space(before).into_iter().chain(items).chain(space(after)))
+                        .padding(20)
+                        .max_width(if self.comparison.is_some() { 1200 } else { 600 }),
                 )
                 .padding(padding::bottom(self.input_height)),
             )
+            .on_scroll(Message::ChatScrolled)
             .id(CHAT)
             .spacing(10)
             .height(Fill)
             .into()
         };
 
+        let error = matches!(self.state, State::Running { .. })
+            .then_some(self.error.as_ref())
+            .flatten()
+            .map(|error| {
+                value(error)
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .style(text::danger)
+            });
+
+        let stop = matches!(
+            self.state,
+            State::Running {
+                sending: Some(_),
+                ..
+            }
+        )
+        .then(|| {
+            right(
+                button(text("Stop").size(12))
+                    .on_press(Message::Stop)
+                    .padding([2, 7])
+                    .style(button::danger),
+            )
+        });
+
+        let stage = self.stage.filter(|_| !self.can_send()).map(|stage| {
+            container(
+                text!("{stage}...")
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .style(text::secondary),
+            )
+            .padding([2, 7])
+            .style(container::rounded_box)
+        });
+
         let input = container(
-            text_editor(&self.input)
-                .placeholder("Type your message here...")
-                .on_action(Message::InputChanged)
-                .padding(10)
-                .min_height(51)
-                .max_height(16.0 * 1.3 * 20.0) // approx. 20 lines with 1.3 line height
-                .key_binding(|key_press| {
-                    let modifiers = key_press.modifiers;
-
-                    match text_editor::Binding::from_key_press(key_press) {
-                        Some(text_editor::Binding::Enter) if !modifiers.shift() => {
-                            Some(text_editor::Binding::Custom(Message::Submit))
-                        }
-                        binding => binding,
-                    }
-                }),
+            column![]
+                .push_maybe(error)
+                .push_maybe(stop)
+                .push(
+                    text_editor(&self.input)
+                        .placeholder("Type your message here...")
+                        .on_action(Message::InputChanged)
+                        .font(self.settings.appearance.chat_font.font())
+                        .size(self.settings.appearance.text_size.offset(0))
+                        .padding(10)
+                        .min_height(51)
+                        // approx. 20 lines with 1.3 line height
+                        .max_height(self.settings.appearance.text_size.offset(0) * 1.3 * 20.0)
+                        .key_binding(|key_press| {
+                            let modifiers = key_press.modifiers;
+
+                            if self
+                                .settings
+                                .keymap
+                                .submit
+                                .matches(&key_press.key, modifiers)
+                            {
+                                return Some(text_editor::Binding::Custom(Message::Submit));
+                            }
+
+                            match text_editor::Binding::from_key_press(key_press) {
+                                Some(text_editor::Binding::Enter) if !modifiers.shift() => {
+                                    Some(text_editor::Binding::Custom(Message::Submit))
+                                }
+                                binding => binding,
+                            }
+                        }),
+                )
+                .push_maybe(stage)
+                .spacing(5),
         )
         .width(Shrink)
         .max_width(600);
@@ -617,67 +2917,7 @@ impl Conversation {
         ];
 
         if self.sidebar_open {
-            let sidebar = {
-                let new = button(text("New Chat").width(Fill).align_x(Center))
-                    .on_press(Message::New)
-                    .style(button::success);
-
-                let search = button(text("Search Models").width(Fill).align_x(Center))
-                    .on_press(Message::Search)
-                    .style(button::secondary);
-
-                if self.chats.is_empty() {
-                    column![vertical_space(), new, search]
-                } else {
-                    let chats = column(self.chats.iter().map(|chat| {
-                        let card: Element<_> = match &chat.title {
-                            Some(title) => {
-                                let title = text(title).font(Font::MONOSPACE);
-                                let subtitle =
-                                    text(chat.file.model.name()).font(Font::MONOSPACE).size(10);
-
-                                column![title, subtitle].spacing(3).into()
-                            }
-                            None => text(chat.file.model.name()).font(Font::MONOSPACE).into(),
-                        };
-
-                        let is_active = Some(&chat.id) == self.id.as_ref();
-
-                        if is_active {
-                            container(card)
-                                .style(|theme: &Theme| {
-                                    let pair = theme.extended_palette().secondary.weak;
-
-                                    container::Style {
-                                        background: Some(pair.color.into()),
-                                        text_color: Some(pair.text),
-                                        border: border::rounded(2),
-                                        ..container::Style::default()
-                                    }
-                                })
-                                .padding(5)
-                                .width(Fill)
-                                .into()
-                        } else {
-                            button(card)
-                                .on_press_with(move || Message::Open(chat.id.clone()))
-                                .padding(5)
-                                .width(Fill)
-                                .style(|theme: &Theme, status: button::Status| match status {
-                                    button::Status::Active => button::text(theme, status),
-                                    _ => button::secondary(theme, status),
-                                })
-                                .into()
-                        }
-                    }))
-                    .clip(true)
-                    .spacing(10);
-
-                    column![scrollable(chats).height(Fill).spacing(10), new, search]
-                }
-                .width(250)
-                .spacing(10)
-            };
+            let sidebar = self.sidebar(preloads);
 
             row![sidebar, chat].spacing(10).padding(10).into()
         } else {
@@ -686,36 +2926,804 @@ impl Conversation {
     }
 
     pub fn subscription(&self) -> Subscription<Message> {
-        match &self.state {
+        let state = match &self.state {
             State::Booting { .. } => time::every(Duration::from_millis(100)).map(Message::Tick),
-            State::Running { .. } => Subscription::none(),
-        }
+            State::Running { .. } => {
+                time::every(Duration::from_secs(if self.unsaved { 5 } else { 30 }))
+                    .map(|_| Message::CheckDiskSpace)
+            }
+            State::Missing { .. } => Subscription::none(),
+        };
+
+        let editing = if self
+            .editing
+            .as_ref()
+            .is_some_and(|editing| editing.draft.is_some())
+        {
+            time::every(Duration::from_secs(1)).map(|_| Message::CheckEdits)
+        } else {
+            Subscription::none()
+        };
+
+        let drops = iced::event::listen_with(|event, _status, _window| match event {
+            iced::Event::Window(iced::window::Event::FileDropped(path))
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == Setup::EXTENSION) =>
+            {
+                Some(Message::ImportSetupFrom(Some(path)))
+            }
+            _ => None,
+        });
+
+        let logs = if self.logs.is_some() {
+            time::every(Duration::from_secs(1)).map(|_| Message::RefreshLogs)
+        } else {
+            Subscription::none()
+        };
+
+        let monitor = match &self.state {
+            State::Running { assistant, .. } if assistant.is_local() => Subscription::batch([
+                time::every(Duration::from_secs(3)).map(|_| Message::MeasureResources),
+                time::every(HEALTH_CHECK_INTERVAL).map(|_| Message::CheckHealth),
+            ]),
+            _ => Subscription::none(),
+        };
+
+        Subscription::batch([state, editing, drops, logs, monitor])
     }
 
     pub fn model_name(&self) -> &str {
         match &self.state {
-            State::Booting { file, .. } => file.model.name(),
+            State::Booting { file, .. } | State::Missing { file, .. } => file.model.name(),
             State::Running { assistant, .. } => assistant.name(),
         }
     }
 
+    fn is_low_on_space(&self) -> bool {
+        self.available_space
+            .is_some_and(|available_space| available_space < LOW_DISK_SPACE)
+    }
+
     pub fn can_send(&self) -> bool {
         matches!(self.state, State::Running { sending: None, .. })
     }
+
+    pub fn is_finding(&self) -> bool {
+        self.find.is_some()
+    }
+
+    /// Makes the assistant continue a plain transcript once booted, for
+    /// base models that cannot chat.
+    pub fn use_raw_completion(&mut self, raw_completion: bool) {
+        self.raw_completion = raw_completion;
+
+        if let State::Running { assistant, .. } = &mut self.state {
+            *assistant = assistant.clone().with_raw_completion(raw_completion);
+        }
+    }
+
+    /// The assistant of this conversation, once running.
+    pub fn assistant(&self) -> Option<&Assistant> {
+        match &self.state {
+            State::Running { assistant, .. } => Some(assistant),
+            State::Booting { .. } | State::Missing { .. } => None,
+        }
+    }
+
+    pub fn file(&self) -> &File {
+        match &self.state {
+            State::Booting { file, .. } | State::Missing { file, .. } => file,
+            State::Running { assistant, .. } => assistant.file(),
+        }
+    }
+
+    /// The port of the assistant of this conversation, if any.
+    pub fn port(&self) -> Option<u64> {
+        match &self.state {
+            State::Booting { port, .. } => Some(*port),
+            State::Running { assistant, .. } => Some(assistant.port()),
+            State::Missing { .. } => None,
+        }
+    }
+
+    /// Keeps the running assistant warm in the pool, so reopening a chat
+    /// with it is instant.
+    /// The output of the server so far, including its boot.
+    fn server_logs(&self) -> Vec<String> {
+        match &self.state {
+            State::Booting { logs, .. } => logs.clone(),
+            State::Running { assistant, .. } => assistant.logs().lines(),
+            State::Missing { .. } => Vec::new(),
+        }
+    }
+
+    /// Boots the assistant again, keeping the conversation.
+    fn restart(&mut self) -> Action {
+        let file = self.file().clone();
+
+        self.restarts += 1;
+
+        let (state, boot) = State::boot(file, self.backend, &self.settings, &self.pool);
+        self.state = state;
+        self.error = None;
+
+        Action::Run(boot)
+    }
+
+    pub fn release(&self) {
+        if let State::Running { assistant, .. } = &self.state {
+            self.pool
+                .keep(assistant.clone(), self.settings.warm_models as usize);
+        }
+    }
+
+    pub fn update_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+    }
+
+    fn system_prompt(&self) -> &str {
+        self.system_prompt
+            .as_deref()
+            .unwrap_or(&self.settings.system_prompt)
+    }
+
+    /// Sends the given history to the compared assistant, if any, to show its
+    /// reply next to the item at the given index.
+    fn compare(&mut self, index: usize, history: Vec<assistant::Message>) -> Task<Message> {
+        let Some(assistant) = self.comparison.as_ref().and_then(Comparison::assistant) else {
+            return Task::none();
+        };
+
+        let system_prompt = match compose_system_prompt(
+            self.system_prompt(),
+            &self.guardrails,
+            assistant,
+            &self.settings,
+            &self.memory,
+            recap(self.carry_over.as_ref()),
+        ) {
+            Ok(system_prompt) => system_prompt,
+            Err(error) => {
+                self.error = Some(dbg!(error));
+
+                return Task::none();
+            }
+        };
+
+        let complete = chat::complete(
+            assistant,
+            chat::Request {
+                system_prompt: &system_prompt,
+                sampling: assistant::Sampling {
+                    replies: 1,
+                    ..self.sampling
+                },
+                search: &self.settings.search,
+                knowledge: self.knowledge.then_some(&self.settings.knowledge),
+                // Titles of compared replies are never used
+                suggest_titles: false,
+            },
+            history,
+        );
+
+        match &mut self.comparison {
+            Some(comparison) => comparison.complete(index, complete),
+            None => Task::none(),
+        }
+    }
+
+    fn clear_comparison(&mut self) {
+        if let Some(comparison) = &mut self.comparison {
+            comparison.clear();
+        }
+    }
+
+    /// Asks the running assistant to summarize the messages of a pending
+    /// carry-over.
+    fn write_recap(&self) -> Task<Message> {
+        let (Some(carry_over), State::Running { assistant, .. }) = (&self.carry_over, &self.state)
+        else {
+            return Task::none();
+        };
+
+        if carry_over.recap.is_some() {
+            return Task::none();
+        }
+
+        Task::perform(
+            chat::recap(
+                assistant.clone(),
+                self.history.messages().take(carry_over.covered).collect(),
+            ),
+            Message::Recapped,
+        )
+    }
+
+    /// Opens the given content of the reply at the given index in the
+    /// external editor, replacing any other one being edited.
+    fn open_in_editor(
+        &mut self,
+        index: usize,
+        language: Option<String>,
+        code: Option<String>,
+        content: String,
+    ) -> Task<Message> {
+        let stop = self.stop_editing();
+
+        self.editing = Some(Editing {
+            index,
+            code,
+            draft: None,
+            edits: None,
+        });
+
+        Task::batch([
+            stop,
+            Task::perform(
+                editor::Draft::open(self.settings.editor.clone(), language, content),
+                Message::EditorOpened,
+            ),
+        ])
+    }
+
+    fn stop_editing(&mut self) -> Task<Message> {
+        match self.editing.take().and_then(|editing| editing.draft) {
+            Some(draft) => Task::future(draft.discard()).discard(),
+            None => Task::none(),
+        }
+    }
+
+    fn inspect(&mut self) -> Action {
+        let State::Running { assistant, .. } = &self.state else {
+            self.inspector = Some(Inspector::Unavailable);
+
+            return Action::None;
+        };
+
+        let system_prompt = match compose_system_prompt(
+            self.system_prompt(),
+            &self.guardrails,
+            assistant,
+            &self.settings,
+            &self.memory,
+            recap(self.carry_over.as_ref()),
+        ) {
+            Ok(system_prompt) => system_prompt,
+            Err(error) => {
+                self.inspector = Some(Inspector::Failed(error));
+
+                return Action::None;
+            }
+        };
+
+        let mut messages = prompt(&self.history, self.carry_over.as_ref());
+        let input = self.input.text();
+
+        if !input.trim().is_empty() {
+            messages.push(assistant::Message::User(input.trim().to_owned()));
+        }
+
+        self.inspector = Some(Inspector::Inspecting);
+
+        Action::Run(Task::perform(
+            chat::preview(
+                assistant.clone(),
+                system_prompt,
+                self.sampling,
+                messages,
+                self.knowledge.then(|| self.settings.knowledge.clone()),
+                self.settings.search.guard,
+            ),
+            Message::Inspected,
+        ))
+    }
+
+    fn track(&self, feature: Feature) -> Task<Message> {
+        if self.settings.record_usage {
+            Task::future(usage::record(feature)).discard()
+        } else {
+            Task::none()
+        }
+    }
+
+    fn reveal(&mut self, query: String, item: usize) -> Task<Message> {
+        let mut find = Find::new();
+        find.search(query, &self.history);
+
+        if let Some(current) = find.matches.iter().position(|m| m.item == item) {
+            find.current = current;
+        }
+
+        let scroll = find.scroll_to_current(&self.history);
+        self.find = Some(find);
+
+        scroll
+    }
+
+    /// Saves the chat with the reply being streamed marked as interrupted,
+    /// so it is kept if the app closes before the reply finishes.
+    ///
+    /// Chats are only autosaved once they exist.
+    fn autosave(&self) -> Action {
+        let Some(id) = self.id else {
+            return Action::None;
+        };
+
+        let mut messages: Vec<_> = self.history.messages().collect();
+
+        if let Some(assistant::Message::Assistant { interrupted, .. }) = messages.last_mut() {
+            *interrupted = true;
+        }
+
+        Action::Run(Task::perform(
+            Chat::save(id, self.draft(self.file().clone(), messages)),
+            Message::Saved,
+        ))
+    }
+
+    fn save(&self) -> Action {
+        let Some(id) = self.id else {
+            if !self.unsaved || self.history.is_empty() {
+                return Action::None;
+            }
+
+            return Action::Run(Task::perform(
+                Chat::create(self.draft(self.file().clone(), self.history.messages().collect())),
+                Message::Created,
+            ));
+        };
+
+        Action::Run(Task::perform(
+            Chat::save(
+                id,
+                self.draft(self.file().clone(), self.history.messages().collect()),
+            ),
+            Message::Saved,
+        ))
+    }
+
+    fn draft(&self, file: assistant::File, history: Vec<assistant::Message>) -> chat::Draft {
+        chat::Draft {
+            file,
+            title: self.title.clone(),
+            history,
+            system_prompt: self.system_prompt.clone(),
+            guardrails: self.guardrails.clone(),
+            sampling: self.sampling,
+            memory: self.memory.clone(),
+        }
+    }
+}
+
+/// Renders the given system prompt, followed by the facts to remember and
+/// the guardrails of the chat inherited from the settings.
+fn compose_system_prompt(
+    system_prompt: &str,
+    guardrails: &Guardrails,
+    assistant: &Assistant,
+    settings: &Settings,
+    memory: &[String],
+    recap: Option<&str>,
+) -> Result<String, Error> {
+    let mut system_prompt = template::render(
+        system_prompt,
+        &template::Context {
+            model: assistant.name(),
+            variables: &settings.variables,
+        },
+    )?;
+
+    let mut facts = settings
+        .memory
+        .iter()
+        .chain(memory)
+        .map(|fact| fact.trim())
+        .filter(|fact| !fact.is_empty())
+        .peekable();
+
+    if facts.peek().is_some() {
+        system_prompt.push_str("\n\nKeep in mind the following facts about the user:\n");
+
+        for fact in facts {
+            system_prompt.push_str(&format!("- {fact}\n"));
+        }
+    }
+
+    if let Some(instructions) = guardrails.inherit(&settings.guardrails).instructions() {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(&instructions);
+    }
+
+    if let Some(recap) = recap {
+        system_prompt.push_str(
+            "\n\nThe conversation started with another assistant. \
+            This is a summary of its earlier messages:\n",
+        );
+        system_prompt.push_str(recap);
+    }
+
+    Ok(system_prompt)
+}
+
+/// The recap of the given carry-over, once written.
+fn recap(carry_over: Option<&CarryOver>) -> Option<&str> {
+    carry_over?.recap.as_deref()
+}
+
+/// The messages of the history sent to the assistant, leaving out the ones
+/// covered by a written recap.
+fn prompt(history: &History, carry_over: Option<&CarryOver>) -> Vec<assistant::Message> {
+    let covered = carry_over
+        .filter(|carry_over| carry_over.recap.is_some())
+        .map_or(0, |carry_over| carry_over.covered);
+
+    history.messages().skip(covered).collect()
+}
+
+/// The running summarizer to pair the given assistant with, if any.
+fn summarizer(assistant: &Assistant, settings: &Settings, pool: &Pool) -> Option<Assistant> {
+    settings
+        .summarizer
+        .as_ref()
+        .filter(|summarizer| *summarizer != assistant.file())
+        .and_then(|summarizer| pool.get(summarizer))
+}
+
+/// The downloaded files other than the given one, identified by the model
+/// of the chats that used them when possible.
+fn available_files(chats: &[Entry], downloads: &[String], missing: &File) -> Vec<File> {
+    let mut files: Vec<File> = Vec::new();
+
+    for name in downloads {
+        if *name == missing.name || files.iter().any(|file| file.name == *name) {
+            continue;
+        }
+
+        let file = chats
+            .iter()
+            .find(|entry| entry.file.name == *name)
+            .map(|entry| entry.file.clone())
+            .unwrap_or_else(|| File::local(name.clone()));
+
+        files.push(file);
+    }
+
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    files
+}
+
+fn sampling<'a>(sampling: assistant::Sampling) -> Element<'a, Message> {
+    use iced::widget::slider;
+
+    fn parameter<'a>(
+        label: &'a str,
+        value: String,
+        input: impl Into<Element<'a, Message>>,
+    ) -> Element<'a, Message> {
+        row![
+            text(label).size(14).width(150),
+            input.into(),
+            text(value).size(12).font(Font::MONOSPACE).width(50),
+        ]
+        .spacing(10)
+        .align_y(Center)
+        .into()
+    }
+
+    let temperature = parameter(
+        "Temperature",
+        format!("{:.2}", sampling.temperature),
+        slider(0.0..=2.0, sampling.temperature, move |temperature| {
+            Message::SamplingChanged(assistant::Sampling {
+                temperature,
+                ..sampling
+            })
+        })
+        .step(0.05),
+    );
+
+    let top_p = parameter(
+        "Top P",
+        format!("{:.2}", sampling.top_p),
+        slider(0.0..=1.0, sampling.top_p, move |top_p| {
+            Message::SamplingChanged(assistant::Sampling { top_p, ..sampling })
+        })
+        .step(0.01),
+    );
+
+    let top_k = parameter(
+        "Top K",
+        sampling.top_k.to_string(),
+        slider(0..=200, sampling.top_k, move |top_k| {
+            Message::SamplingChanged(assistant::Sampling { top_k, ..sampling })
+        }),
+    );
+
+    let repeat_penalty = parameter(
+        "Repeat penalty",
+        format!("{:.2}", sampling.repeat_penalty),
+        slider(1.0..=2.0, sampling.repeat_penalty, move |repeat_penalty| {
+            Message::SamplingChanged(assistant::Sampling {
+                repeat_penalty,
+                ..sampling
+            })
+        })
+        .step(0.01),
+    );
+
+    let max_tokens = parameter(
+        "Max tokens",
+        String::new(),
+        text_input(
+            "Unlimited",
+            &sampling
+                .max_tokens
+                .map(|max_tokens| max_tokens.to_string())
+                .unwrap_or_default(),
+        )
+        .on_input(Message::MaxTokensChanged)
+        .padding(5)
+        .size(14),
+    );
+
+    let replies = parameter(
+        "Replies",
+        sampling.replies.to_string(),
+        slider(1..=4, sampling.replies, move |replies| {
+            Message::SamplingChanged(assistant::Sampling {
+                replies,
+                ..sampling
+            })
+        }),
+    );
+
+    let reset = button(text("Reset to defaults").size(12))
+        .on_press_maybe(
+            (sampling != assistant::Sampling::default()).then_some(Message::ResetSampling),
+        )
+        .padding([2, 7])
+        .style(button::text);
+
+    container(
+        column![
+            temperature,
+            top_p,
+            top_k,
+            repeat_penalty,
+            max_tokens,
+            replies,
+            right(reset)
+        ]
+        .spacing(10),
+    )
+    .padding(10)
+    .max_width(600)
+    .style(container::rounded_box)
+    .into()
+}
+
+/// The output of the server, newest at the bottom.
+fn logs(logs: &[String]) -> Element<'_, Message> {
+    let action = |label, message| {
+        button(text(label).size(12))
+            .on_press_maybe((!logs.is_empty()).then_some(message))
+            .padding([2, 7])
+            .style(button::text)
+    };
+
+    let header = row![
+        text("Server logs").size(12).font(Font::MONOSPACE),
+        horizontal_space(),
+        action("Copy all", Message::CopyLogs),
+        action("Save", Message::SaveLogs),
+    ]
+    .align_y(Center);
+
+    let content: Element<'_, Message> = if logs.is_empty() {
+        text("Nothing logged yet. Remote servers keep their logs to themselves.")
+            .size(12)
+            .into()
+    } else {
+        container(
+            scrollable(
+                text(logs.join("\n"))
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .width(Fill),
+            )
+            .anchor_bottom(),
+        )
+        .max_height(300)
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    };
+
+    container(column![header, content].spacing(10))
+        .padding(10)
+        .max_width(600)
+        .style(container::bordered_box)
+        .into()
+}
+
+fn inspector(inspector: &Inspector, sampling: assistant::Sampling) -> Element<'_, Message> {
+    let refresh = button(text("Refresh").size(12))
+        .on_press(Message::Inspect)
+        .padding([2, 7])
+        .style(button::text);
+
+    let header = row![
+        text("Next message").size(12).font(Font::MONOSPACE),
+        horizontal_space(),
+        refresh,
+    ]
+    .align_y(Center);
+
+    let content: Element<'_, Message> = match inspector {
+        Inspector::Unavailable => {
+            text("The next message can be inspected once the model is running.")
+                .size(12)
+                .into()
+        }
+        Inspector::Inspecting => text("Preparing...").size(12).into(),
+        Inspector::Failed(error) => text(error.to_string()).size(12).style(text::danger).into(),
+        Inspector::Ready(preview) => {
+            let system_prompt = container(scrollable(
+                text(&preview.system_prompt)
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .width(Fill),
+            ))
+            .max_height(200)
+            .padding(10)
+            .style(container::rounded_box);
+
+            let sources = (!preview.sources.is_empty()).then(|| {
+                column(
+                    preview
+                        .sources
+                        .iter()
+                        .map(|source| text!("Excerpts from {}", source.display()).size(12).into()),
+                )
+            });
+
+            let history = if preview.dropped > 0 {
+                text!(
+                    "Sends {} messages; the {} oldest are dropped to fit the context.",
+                    preview.sent,
+                    preview.dropped
+                )
+            } else {
+                text!("Sends {} messages.", preview.sent)
+            };
+
+            let tools = if preview.tools.is_empty() {
+                "No tools".to_owned()
+            } else {
+                format!("Tools: {}", preview.tools.join(", "))
+            };
+
+            let max_tokens = sampling
+                .max_tokens
+                .map(|max_tokens| max_tokens.to_string())
+                .unwrap_or_else(|| "unlimited".to_owned());
+
+            column![system_prompt]
+                .push_maybe(sources)
+                .push(history.size(12))
+                .push(text(tools).size(12))
+                .push(
+                text!(
+                    "Temperature {:.2} · Top P {:.2} · Top K {} · Repeat penalty {:.2} · Max tokens {}",
+                    sampling.temperature,
+                    sampling.top_p,
+                    sampling.top_k,
+                    sampling.repeat_penalty,
+                    max_tokens,
+                )
+                .size(12)
+                .font(Font::MONOSPACE),
+                )
+                .spacing(5)
+                .into()
+        }
+    };
+
+    container(column![header, content].spacing(5))
+        .max_width(600)
+        .into()
+}
+
+fn snapshots(snapshots: &[Snapshot]) -> Element<'_, Message> {
+    let content: Element<_> = if snapshots.is_empty() {
+        text("No earlier versions yet.")
+            .size(12)
+            .style(text::secondary)
+            .into()
+    } else {
+        scrollable(
+            column(snapshots.iter().map(|snapshot| {
+                let preview = snapshot
+                    .last_message
+                    .as_deref()
+                    .map(|message| message.lines().next().unwrap_or_default())
+                    .unwrap_or("Empty chat");
+
+                row![
+                    column![
+                        text!(
+                            "{saved_at} · {messages} message{plural}",
+                            saved_at = snapshot.saved_at.format("%Y-%m-%d %H:%M:%S"),
+                            messages = snapshot.messages,
+                            plural = if snapshot.messages == 1 { "" } else { "s" },
+                        )
+                        .font(Font::MONOSPACE)
+                        .size(10)
+                        .style(text::secondary),
+                        text(preview).size(12),
+                    ]
+                    .spacing(2)
+                    .width(Fill),
+                    button(text("Restore").size(12))
+                        .on_press_with(|| Message::RestoreSnapshot(snapshot.clone()))
+                        .padding([2, 7])
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center)
+                .into()
+            }))
+            .spacing(10),
+        )
+        .height(Shrink)
+        .spacing(10)
+        .into()
+    };
+
+    container(content)
+        .padding(10)
+        .max_width(600)
+        .max_height(300)
+        .style(container::rounded_box)
+        .into()
 }
 
 pub struct History {
     items: Vec<Item>,
+    window: Window,
+}
+
+/// The part of a long [`History`] laid out around the viewport.
+///
+/// Items away from it are replaced by empty space of their last measured
+/// height, or of an estimate until they are shown for the first time.
+#[derive(Debug, Default)]
+struct Window {
+    viewport: Option<scrollable::Viewport>,
+    heights: Vec<f32>,
 }
 
 impl History {
+    /// Histories longer than this are only laid out around the viewport.
+    const VIRTUALIZED_AFTER: usize = 100;
+
     pub fn new() -> Self {
-        Self { items: Vec::new() }
+        Self {
+            items: Vec::new(),
+            window: Window::default(),
+        }
     }
 
     pub fn restore(messages: impl IntoIterator<Item = assistant::Message>) -> Self {
+        let items: Vec<_> = messages.into_iter().map(Item::from).collect();
+
         Self {
-            items: messages.into_iter().map(Item::from).collect(),
+            window: Window {
+                viewport: None,
+                heights: items.iter().map(Item::estimated_height).collect(),
+            },
+            items,
         }
     }
 
@@ -723,24 +3731,182 @@ impl History {
         self.items.is_empty()
     }
 
-    pub fn items(&self) -> impl Iterator<Item = &Item> {
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn items(&self) -> impl DoubleEndedIterator<Item = &Item> {
         self.items.iter()
     }
 
+    pub fn get(&self, index: usize) -> Option<&Item> {
+        self.items.get(index)
+    }
+
     pub fn get_mut(&mut self, index: usize) -> Option<&mut Item> {
         self.items.get_mut(index)
     }
 
     pub fn push(&mut self, item: impl Into<Item>) {
-        self.items.push(item.into());
+        let item = item.into();
+
+        self.window.heights.push(item.estimated_height());
+        self.items.push(item);
     }
 
     pub fn last_mut(&mut self) -> Option<&mut Item> {
         self.items.last_mut()
     }
 
+    /// Sums the usage of every reply.
+    ///
+    /// Prompt tokens are taken from the last reply, since every
+    /// prompt already includes the whole history.
+    pub fn usage(&self) -> Option<assistant::Usage> {
+        self.items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Assistant { usage, .. } => *usage,
+                Item::User { .. } => None,
+            })
+            .reduce(|total, usage| assistant::Usage {
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: total.completion_tokens + usage.completion_tokens,
+                generation_time: total.generation_time + usage.generation_time,
+            })
+    }
+
+    pub fn last_usage(&self) -> Option<assistant::Usage> {
+        self.items.iter().rev().find_map(|item| match item {
+            Item::Assistant { usage, .. } => *usage,
+            Item::User { .. } => None,
+        })
+    }
+
     pub fn truncate(&mut self, amount: usize) {
         self.items.truncate(amount);
+        self.window.heights.truncate(amount);
+    }
+
+    /// Updates the viewport of the history, measuring the items around it
+    /// if it moved.
+    pub fn scroll(&mut self, viewport: scrollable::Viewport) -> Task<Message> {
+        let moved = self.window.viewport.is_none_or(|previous| {
+            previous.absolute_offset() != viewport.absolute_offset()
+                || previous.bounds() != viewport.bounds()
+        });
+
+        self.window.viewport = Some(viewport);
+
+        if !moved {
+            return Task::none();
+        }
+
+        Task::batch(self.around().map(|index| {
+            container::visible_bounds(item_id(index))
+                .map(move |bounds| Message::ItemMeasured(index, bounds))
+        }))
+    }
+
+    /// Remembers the height of the item at the given index, unless it is
+    /// cut off by the viewport.
+    pub fn measure(&mut self, index: usize, bounds: Option<Rectangle>) {
+        let (Some(bounds), Some(viewport)) = (bounds, self.window.viewport) else {
+            return;
+        };
+
+        let visible = viewport.bounds();
+
+        if bounds.y <= visible.y || bounds.y + bounds.height >= visible.y + visible.height {
+            return;
+        }
+
+        if let Some(height) = self.window.heights.get_mut(index) {
+            *height = bounds.height;
+        }
+    }
+
+    /// The items to lay out, with the space taken by the ones before and
+    /// after them.
+    ///
+    /// Items are laid out one viewport above and below the current one.
+    pub fn window(&self) -> (Range<usize>, f32, f32) {
+        if self.items.len() <= Self::VIRTUALIZED_AFTER {
+            return (0..self.items.len(), 0.0, 0.0);
+        }
+
+        let visible = self.around();
+        let heights = &self.window.heights;
+
+        (
+            visible.clone(),
+            heights[..visible.start].iter().sum(),
+            heights[visible.end..].iter().sum(),
+        )
+    }
+
+    /// The items within one viewport above and below the current one.
+    fn around(&self) -> Range<usize> {
+        const DEFAULT_HEIGHT: f32 = 1000.0;
+
+        let heights = &self.window.heights;
+        let total: f32 = heights.iter().sum();
+
+        // Chats open at the end, before the viewport is known
+        let (offset, height) = match self.window.viewport {
+            Some(viewport) => (viewport.absolute_offset().y, viewport.bounds().height),
+            None => ((total - DEFAULT_HEIGHT).max(0.0), DEFAULT_HEIGHT),
+        };
+
+        let (from, to) = (offset - height, offset + height * 2.0);
+
+        let mut top = 0.0;
+        let mut start = heights.len();
+        let mut end = heights.len();
+
+        for (index, height) in heights.iter().enumerate() {
+            if top > to {
+                end = index;
+                break;
+            }
+
+            if start == heights.len() && top + height >= from {
+                start = index;
+            }
+
+            top += height;
+        }
+
+        start.min(end)..end
+    }
+
+    /// Scrolls to the given position of an item, relative to its content,
+    /// centering it in the viewport.
+    ///
+    /// The heights of the items are measured whenever they are in view, and
+    /// estimated until then.
+    pub fn scroll_to(&self, index: usize, position: f32) -> Task<Message> {
+        // The padding of the history
+        const PADDING: f32 = 20.0;
+
+        let heights = &self.window.heights;
+
+        let offset = PADDING
+            + heights[..index.min(heights.len())].iter().sum::<f32>()
+            + heights.get(index).copied().unwrap_or_default() * position.clamp(0.0, 1.0);
+
+        let height = self
+            .window
+            .viewport
+            .map_or(0.0, |viewport| viewport.bounds().height);
+
+        scrollable::scroll_to(
+            CHAT,
+            scrollable::AbsoluteOffset {
+                x: 0.0,
+                y: (offset - height / 2.0).max(0.0),
+            },
+        )
     }
 
     pub fn messages<'a>(&'a self) -> impl Iterator<Item = assistant::Message> + 'a {
@@ -753,33 +3919,101 @@ pub enum Item {
     User {
         content: String,
         markdown: Vec<markdown::Item>,
+        metadata: Metadata,
     },
     Assistant {
         reasoning: Option<Reasoning>,
         content: String,
-        content_markdown: markdown::Content,
+        content_markdown: crate::widget::markdown::Content,
+        metadata: Metadata,
+        usage: Option<assistant::Usage>,
+        executions: Vec<Execution>,
+        alternatives: Vec<Alternative>,
+        shown: Option<usize>,
+        interrupted: bool,
     },
 }
 
 impl Item {
-    pub fn view<'a>(&'a self, index: usize, theme: &Theme) -> Element<'a, Message> {
+    pub fn view<'a>(
+        &'a self,
+        index: usize,
+        theme: &Theme,
+        highlight: Option<Highlight>,
+        query: Option<&str>,
+        locale: Locale,
+        appearance: Appearance,
+    ) -> Element<'a, Message> {
         use iced::border;
 
         let copy = action(icon::clipboard(), "Copy", || Message::Copy(self.to_text()));
+        let branch = action(text("Branch").size(12), "Branch from here", move || {
+            Message::Branch(index)
+        });
 
         match self {
             Self::Assistant {
                 reasoning,
                 content,
                 content_markdown,
-                ..
+                metadata,
+                usage,
+                executions,
+                alternatives,
+                shown,
+                interrupted,
             } => {
-                let message = markdown(
-                    content_markdown.items(),
-                    markdown::Settings::default(),
-                    markdown::Style::from_palette(theme.palette()),
-                )
-                .map(Message::LinkClicked);
+                let shown_alternative = shown.and_then(|shown| alternatives.get(shown));
+
+                let content_markdown =
+                    shown_alternative.map_or(content_markdown, |alternative| &alternative.markdown);
+
+                let message =
+                    crate::widget::markdown::view(content_markdown, theme, appearance, query).map(
+                        move |interaction| match interaction {
+                            Interaction::LinkClicked(url) => Message::LinkClicked(url),
+                            Interaction::Copy(code) => Message::Copy(code),
+                            Interaction::Run(language, code) => {
+                                Message::RunCode(index, language, code)
+                            }
+                            Interaction::OpenInEditor(language, code) => {
+                                Message::OpenCodeInEditor(index, language, code)
+                            }
+                        },
+                    );
+
+                let message: Element<_> = if let Some(alternative) = shown {
+                    let footer: Element<_> = if let Some(error) =
+                        shown_alternative.and_then(|alternative| alternative.error.as_ref())
+                    {
+                        text!("This reply failed: {error}")
+                            .size(12)
+                            .style(text::danger)
+                            .into()
+                    } else {
+                        button(text("Keep this reply").size(12))
+                            .on_press(Message::KeepReply(index, *alternative))
+                            .padding([2, 7])
+                            .style(button::secondary)
+                            .into()
+                    };
+
+                    column![message, footer].spacing(10).into()
+                } else if executions.is_empty() {
+                    message
+                } else {
+                    column![message]
+                        .extend(
+                            executions
+                                .iter()
+                                .enumerate()
+                                .map(|(execution, output)| output.view(index, execution)),
+                        )
+                        .spacing(10)
+                        .into()
+                };
+
+                let reasoning = reasoning.as_ref().filter(|_| shown_alternative.is_none());
 
                 let message: Element<_> = if let Some(reasoning) = reasoning {
                     let toggle = button(
@@ -831,45 +4065,193 @@ impl Item {
                     message.into()
                 };
 
+                let message: Element<_> = if *interrupted {
+                    column![
+                        message,
+                        text("This reply was interrupted before it finished.")
+                            .size(12)
+                            .style(text::secondary)
+                    ]
+                    .spacing(10)
+                    .into()
+                } else {
+                    message
+                };
+
+                let message: Element<_> = if alternatives.is_empty() {
+                    message
+                } else {
+                    column![replies(index, alternatives.len(), *shown), message]
+                        .spacing(10)
+                        .into()
+                };
+
                 let regenerate = action(icon::refresh(), "Regenerate", move || {
                     Message::Regenerate(index)
                 });
 
-                let actions = row![copy, regenerate].spacing(10);
+                let edit = action(text("Edit").size(12), "Open in editor", move || {
+                    Message::OpenInEditor(index)
+                });
+
+                let actions = row![copy, regenerate, edit, branch, stats(metadata)]
+                    .push_maybe(usage.as_ref().map(|usage| self::usage(usage, locale)))
+                    .spacing(10)
+                    .align_y(Center);
 
-                hover(container(message).padding([30, 0]), bottom(actions))
+                let message = container(message)
+                    .padding(10)
+                    .style(move |theme| highlighted(theme, highlight));
+
+                hover(container(message).padding([20, 0]), bottom(actions))
             }
             Self::User {
-                markdown: content, ..
+                markdown: content,
+                metadata,
+                ..
             } => {
                 let message = container(
                     container(
-                        markdown(
+                        crate::widget::markdown::items(
                             content,
-                            markdown::Settings::default(),
+                            markdown::Settings::with_text_size(appearance.text_size.offset(0)),
                             markdown::Style::from_palette(theme.palette()),
+                            appearance.chat_font.font(),
+                            query,
                         )
                         .map(Message::LinkClicked),
                     )
-                    .style(|theme: &Theme| {
+                    .style(move |theme: &Theme| {
                         let palette = theme.extended_palette();
 
                         container::Style {
                             background: Some(palette.background.weak.color.into()),
                             text_color: Some(palette.background.weak.text),
                             border: border::rounded(10),
-                            ..container::Style::default()
+                            ..highlighted(theme, highlight)
                         }
                     })
                     .padding(10),
                 )
                 .padding(padding::all(20).left(30).right(0));
 
-                right(hover(message, center_y(copy))).into()
+                let stats = container(stats(metadata))
+                    .align_right(Fill)
+                    .align_bottom(Fill)
+                    .padding([0, 10]);
+
+                let actions = row![copy, branch].spacing(10).align_y(Center);
+
+                right(hover(message, stack![center_y(actions), stats])).into()
             }
         }
     }
 
+    pub fn content(&self) -> &str {
+        match self {
+            Self::User { content, .. } | Self::Assistant { content, .. } => content,
+        }
+    }
+
+    /// A rough height for the item, until it is laid out.
+    fn estimated_height(&self) -> f32 {
+        const LINE_HEIGHT: f32 = 24.0;
+        const LINE_LENGTH: f32 = 70.0;
+        const DECORATIONS: f32 = 50.0;
+
+        let lines: f32 = self
+            .content()
+            .lines()
+            .map(|line| (line.len() as f32 / LINE_LENGTH).ceil().max(1.0))
+            .sum();
+
+        DECORATIONS + lines * LINE_HEIGHT
+    }
+
+    pub fn toggle_reasoning(&mut self) {
+        if let Self::Assistant {
+            reasoning: Some(reasoning),
+            ..
+        } = self
+        {
+            reasoning.show = !reasoning.show;
+        }
+    }
+
+    /// Keeps the given alternative as the reply, turning the current one into
+    /// an alternative.
+    pub fn keep(&mut self, alternative: usize) {
+        let Self::Assistant {
+            reasoning,
+            content,
+            content_markdown,
+            metadata,
+            usage,
+            executions,
+            alternatives,
+            shown,
+            interrupted,
+        } = self
+        else {
+            return;
+        };
+
+        let Some(chosen) = alternatives
+            .get_mut(alternative)
+            .filter(|chosen| chosen.error.is_none())
+        else {
+            return;
+        };
+
+        let kept = Alternative::from(assistant::Alternative {
+            reasoning: reasoning.as_ref().map(Reasoning::to_assistant),
+            content: std::mem::take(content),
+        });
+
+        let chosen = std::mem::replace(chosen, kept);
+
+        *reasoning = chosen.reasoning.map(Reasoning::from);
+        *content = chosen.content;
+        *content_markdown = chosen.markdown;
+        *metadata = Metadata::compute(content);
+        *usage = None;
+        *shown = None;
+        *interrupted = false;
+        executions.clear();
+    }
+
+    /// Replaces the content of the reply.
+    pub fn replace_content(&mut self, new_content: String) {
+        if let Self::Assistant {
+            content,
+            content_markdown,
+            metadata,
+            ..
+        } = self
+        {
+            *content_markdown = crate::widget::markdown::Content::parse(&new_content);
+            *metadata = Metadata::compute(&new_content);
+            *content = new_content;
+        }
+    }
+
+    /// Replaces the given code block of the reply, returning false if the
+    /// reply does not contain it.
+    pub fn replace_code(&mut self, code: &str, new_code: &str) -> bool {
+        let Self::Assistant { content, .. } = self else {
+            return false;
+        };
+
+        if !content.contains(code) {
+            return false;
+        }
+
+        let new_content = content.replacen(code, new_code, 1);
+        self.replace_content(new_content);
+
+        true
+    }
+
     pub fn to_text(&self) -> String {
         match self {
             Self::User { content, .. } => content.clone(),
@@ -891,13 +4273,25 @@ impl Item {
         match self {
             Self::User { content, .. } => assistant::Message::User(content.clone()),
             Self::Assistant {
-                reasoning, content, ..
+                reasoning,
+                content,
+                usage,
+                alternatives,
+                interrupted,
+                ..
             } => assistant::Message::Assistant {
-                reasoning: reasoning.as_ref().map(|reasoning| assistant::Reasoning {
-                    content: reasoning.thoughts.join("\n\n"),
-                    duration: reasoning.duration,
-                }),
+                reasoning: reasoning.as_ref().map(Reasoning::to_assistant),
                 content: content.clone(),
+                usage: *usage,
+                alternatives: alternatives
+                    .iter()
+                    .filter(|alternative| alternative.error.is_none())
+                    .map(|alternative| assistant::Alternative {
+                        reasoning: alternative.reasoning.clone(),
+                        content: alternative.content.clone(),
+                    })
+                    .collect(),
+                interrupted: *interrupted,
             },
         }
     }
@@ -906,24 +4300,169 @@ impl Item {
 impl From<assistant::Message> for Item {
     fn from(message: assistant::Message) -> Self {
         match message {
-            assistant::Message::Assistant { reasoning, content } => {
-                let content_markdown = markdown::Content::parse(&content);
+            assistant::Message::Assistant {
+                reasoning,
+                content,
+                usage,
+                alternatives,
+                interrupted,
+            } => {
+                let content_markdown = crate::widget::markdown::Content::parse(&content);
+                let metadata = Metadata::compute(&content);
 
                 Item::Assistant {
                     reasoning: reasoning.map(Reasoning::from),
                     content,
                     content_markdown,
+                    metadata,
+                    usage,
+                    executions: Vec::new(),
+                    alternatives: alternatives.into_iter().map(Alternative::from).collect(),
+                    shown: None,
+                    interrupted,
                 }
             }
             assistant::Message::User(content) => {
                 let markdown = markdown::parse(&content).collect();
+                let metadata = Metadata::compute(&content);
 
-                Item::User { content, markdown }
+                Item::User {
+                    content,
+                    markdown,
+                    metadata,
+                }
             }
         }
     }
 }
 
+/// Another reply generated for the same message.
+#[derive(Debug, Default)]
+pub struct Alternative {
+    reasoning: Option<assistant::Reasoning>,
+    content: String,
+    markdown: crate::widget::markdown::Content,
+    /// The error that stopped it; failed alternatives are not saved.
+    error: Option<Error>,
+}
+
+impl From<assistant::Alternative> for Alternative {
+    fn from(alternative: assistant::Alternative) -> Self {
+        Self {
+            markdown: crate::widget::markdown::Content::parse(&alternative.content),
+            reasoning: alternative.reasoning,
+            content: alternative.content,
+            error: None,
+        }
+    }
+}
+
+/// The tabs to switch between the replies of a message.
+fn replies<'a>(index: usize, alternatives: usize, shown: Option<usize>) -> Element<'a, Message> {
+    let tab = |label: String, alternative: Option<usize>| {
+        button(text(label).size(12))
+            .on_press(Message::ShowReply(index, alternative))
+            .padding([2, 7])
+            .style(if alternative == shown {
+                button::primary
+            } else {
+                button::text
+            })
+            .into()
+    };
+
+    row(std::iter::once(tab("Kept".to_owned(), None)).chain(
+        (0..alternatives)
+            .map(|alternative| tab(format!("Reply {}", alternative + 2), Some(alternative))),
+    ))
+    .spacing(5)
+    .into()
+}
+
+/// The output of a code block run from a reply.
+#[derive(Debug)]
+pub struct Execution {
+    language: exec::Language,
+    output: Option<Result<exec::Output, Error>>,
+    show: bool,
+}
+
+impl Execution {
+    fn view(&self, index: usize, execution: usize) -> Element<'_, Message> {
+        let status = match &self.output {
+            None => text!("Running {}...", self.language),
+            Some(Ok(output)) if output.timed_out => text!(
+                "{} timed out after {} seconds",
+                self.language,
+                exec::TIMEOUT.as_secs()
+            )
+            .style(text::danger),
+            Some(Ok(output)) => text!(
+                "{} exited with {} in {:.1}s",
+                self.language,
+                output
+                    .status
+                    .map_or_else(|| "a signal".to_owned(), |status| status.to_string()),
+                output.duration.as_secs_f32()
+            )
+            .style(if output.is_success() {
+                text::success
+            } else {
+                text::danger
+            }),
+            Some(Err(_error)) => text!("{} could not run", self.language).style(text::danger),
+        };
+
+        let toggle = button(
+            row![
+                status.font(Font::MONOSPACE).size(12),
+                if self.show {
+                    icon::arrow_down()
+                } else {
+                    icon::arrow_up()
+                }
+                .size(12),
+            ]
+            .spacing(10),
+        )
+        .on_press(Message::ToggleExecution(index, execution))
+        .style(button::secondary);
+
+        let output: Option<Element<_>> = match &self.output {
+            Some(Ok(output)) if self.show => Some(
+                column![]
+                    .push_maybe(
+                        (!output.stdout.is_empty())
+                            .then(|| text(&output.stdout).font(Font::MONOSPACE).size(12)),
+                    )
+                    .push_maybe((!output.stderr.is_empty()).then(|| {
+                        text(&output.stderr)
+                            .font(Font::MONOSPACE)
+                            .size(12)
+                            .style(text::danger)
+                    }))
+                    .spacing(5)
+                    .into(),
+            ),
+            Some(Err(error)) if self.show => Some(
+                value(error)
+                    .font(Font::MONOSPACE)
+                    .size(12)
+                    .style(text::danger)
+                    .into(),
+            ),
+            _ => None,
+        };
+
+        column![toggle]
+            .push_maybe(
+                output.map(|output| row![vertical_rule(1), output].spacing(10).height(Shrink)),
+            )
+            .spacing(10)
+            .into()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Reasoning {
     thoughts: Vec<String>,
@@ -931,6 +4470,15 @@ pub struct Reasoning {
     show: bool,
 }
 
+impl Reasoning {
+    fn to_assistant(&self) -> assistant::Reasoning {
+        assistant::Reasoning {
+            content: self.thoughts.join("\n\n"),
+            duration: self.duration,
+        }
+    }
+}
+
 impl From<assistant::Reasoning> for Reasoning {
     fn from(reasoning: assistant::Reasoning) -> Self {
         Self {
@@ -941,17 +4489,266 @@ impl From<assistant::Reasoning> for Reasoning {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    All,
+    Code,
+    Language(Language),
+    Tag(String),
+}
+
+impl Filter {
+    fn matches(&self, entry: &Entry) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Code => entry.has_code,
+            Filter::Language(language) => entry.languages.contains(language),
+            Filter::Tag(tag) => entry.tags.contains(tag),
+        }
+    }
+}
+
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Filter::All => f.write_str("All chats"),
+            Filter::Code => f.write_str("Chats with code"),
+            Filter::Language(language) => write!(f, "{language} chats"),
+            Filter::Tag(tag) => write!(f, "#{tag}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    Match,
+    Current,
+}
+
+fn highlighted(theme: &Theme, highlight: Option<Highlight>) -> container::Style {
+    let palette = theme.extended_palette();
+
+    let border = match highlight {
+        None => return container::Style::default(),
+        Some(Highlight::Match) => border::color(palette.primary.weak.color).width(1),
+        Some(Highlight::Current) => border::color(palette.primary.strong.color).width(2),
+    };
+
+    container::Style {
+        border: border.rounded(10),
+        ..container::Style::default()
+    }
+}
+
+struct Find {
+    query: String,
+    needle: String,
+    matches: Vec<Match>,
+    current: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Match {
+    item: usize,
+    offset: usize,
+}
+
+impl Find {
+    fn new() -> Self {
+        Self {
+            query: String::new(),
+            needle: String::new(),
+            matches: Vec::new(),
+            current: 0,
+        }
+    }
+
+    fn search(&mut self, query: String, history: &History) {
+        let needle = query.trim().to_lowercase();
+
+        self.query = query;
+        self.current = 0;
+        self.matches = if needle.is_empty() {
+            Vec::new()
+        } else {
+            history
+                .items()
+                .enumerate()
+                .flat_map(|(item, entry)| {
+                    entry
+                        .content()
+                        .to_lowercase()
+                        .match_indices(&needle)
+                        .map(|(offset, _)| Match { item, offset })
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        self.needle = needle;
+    }
+
+    fn next(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + 1) % self.matches.len();
+        }
+    }
+
+    fn previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = self
+                .current
+                .checked_sub(1)
+                .unwrap_or(self.matches.len() - 1);
+        }
+    }
+
+    fn highlight(&self, item: usize) -> Option<Highlight> {
+        if self.matches.get(self.current)?.item == item {
+            Some(Highlight::Current)
+        } else if self.matches.iter().any(|m| m.item == item) {
+            Some(Highlight::Match)
+        } else {
+            None
+        }
+    }
+
+    fn scroll_to_current(&self, history: &History) -> Task<Message> {
+        let Some(current) = self.matches.get(self.current) else {
+            return Task::none();
+        };
+
+        let Some(item) = history.get(current.item) else {
+            return Task::none();
+        };
+
+        let length = item.content().len().max(1);
+
+        history.scroll_to(current.item, current.offset as f32 / length as f32)
+    }
+
+    /// The query to highlight in the given item, if it has any matches.
+    fn query(&self, item: usize) -> Option<&str> {
+        self.matches
+            .iter()
+            .any(|m| m.item == item)
+            .then_some(self.needle.as_str())
+    }
+
+    fn view(&self) -> Element<Message> {
+        let input = text_input("Find in chat...", &self.query)
+            .id(FIND)
+            .on_input(Message::FindChanged)
+            .on_submit(Message::FindNext)
+            .padding(5)
+            .size(14);
+
+        let status = if self.matches.is_empty() {
+            text(if self.query.trim().is_empty() {
+                ""
+            } else {
+                "No matches"
+            })
+        } else {
+            text!("{} of {}", self.current + 1, self.matches.len())
+        }
+        .font(Font::MONOSPACE)
+        .size(12)
+        .style(text::secondary);
+
+        let navigate = |icon: Text<'static>, label, message| {
+            tip(
+                button(icon.size(12))
+                    .on_press_maybe((!self.matches.is_empty()).then_some(message))
+                    .padding([2, 7])
+                    .style(button::text),
+                label,
+                tip::Position::Bottom,
+            )
+        };
+
+        container(
+            row![
+                input,
+                status,
+                navigate(icon::arrow_up(), "Previous match", Message::FindPrevious),
+                navigate(icon::arrow_down(), "Next match", Message::FindNext),
+                button(text("Done").size(12))
+                    .on_press(Message::CloseFind)
+                    .padding([2, 7])
+                    .style(button::text),
+            ]
+            .spacing(10)
+            .align_y(Center),
+        )
+        .max_width(600)
+        .into()
+    }
+}
+
 const INPUT: &str = "input";
 const CHAT: &str = "chat";
+const FIND: &str = "find";
+pub(super) const SEARCH_CHATS: &str = "search_chats";
+
+const LOW_DISK_SPACE: u64 = 200 * 1024 * 1024;
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The health checks a server may fail in a row before it is considered
+/// crashed.
+const MAX_FAILED_CHECKS: usize = 3;
+
+/// The times a crashed server is restarted without asking.
+const MAX_RESTARTS: usize = 2;
 
 fn measure_input() -> Task<Message> {
     container::visible_bounds(INPUT).map(Message::InputMeasured)
 }
 
+fn item_id(index: usize) -> container::Id {
+    container::Id::new(format!("item-{index}"))
+}
+
 fn snap_chat_to_end() -> Task<Message> {
     scrollable::snap_to(CHAT, scrollable::RelativeOffset::END)
 }
 
+fn stats(metadata: &Metadata) -> Element<'_, Message> {
+    tip(
+        text!(
+            "{words} word{plural}",
+            words = metadata.words,
+            plural = if metadata.words != 1 { "s" } else { "" }
+        )
+        .font(Font::MONOSPACE)
+        .size(10)
+        .style(text::secondary),
+        metadata
+            .language
+            .as_ref()
+            .map(Language::name)
+            .unwrap_or("Unknown language"),
+        tip::Position::Bottom,
+    )
+}
+
+fn usage<'a>(usage: &assistant::Usage, locale: Locale) -> Element<'a, Message> {
+    tip(
+        text!(
+            "{tokens} tokens · {speed} tok/s",
+            tokens = locale.number(usage.completion_tokens),
+            speed = locale.decimal(usage.tokens_per_second().into(), 1)
+        )
+        .font(Font::MONOSPACE)
+        .size(10)
+        .style(text::secondary),
+        format!("{} prompt tokens", locale.number(usage.prompt_tokens)),
+        tip::Position::Bottom,
+    )
+}
+
 fn action<'a>(
     icon: Text<'a>,
     label: &'a str,
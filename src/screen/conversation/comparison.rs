@@ -0,0 +1,281 @@
+use crate::data::appearance::Appearance;
+use crate::data::assistant::{self, Assistant, BootEvent, File, Launch};
+use crate::data::chat;
+use crate::data::diagnostics;
+use crate::data::Error;
+use crate::screen::conversation::Message;
+use crate::widget::markdown::{self, Interaction};
+
+use futures::Stream;
+use iced::task::{self, Task};
+use iced::widget::{button, column, container, pick_list, row, text, value};
+use iced::{Center, Element, Fill, Font, Theme};
+
+use std::collections::BTreeMap;
+
+/// A second assistant answering every message next to the main one.
+///
+/// Its replies are only kept while the conversation is open; the saved
+/// history only contains the replies of the main assistant.
+pub struct Comparison {
+    state: State,
+    files: Vec<File>,
+    replies: BTreeMap<usize, Reply>,
+}
+
+enum State {
+    Picking,
+    Booting {
+        file: File,
+        stage: String,
+        progress: u64,
+        _task: task::Handle,
+    },
+    Running {
+        assistant: Assistant,
+        sending: Option<task::Handle>,
+    },
+    Failed {
+        file: File,
+        error: Error,
+    },
+}
+
+/// A reply of the compared assistant.
+struct Reply {
+    content: String,
+    markdown: markdown::Content,
+}
+
+impl Comparison {
+    /// Creates a [`Comparison`] that picks one of the given files.
+    pub fn new(files: Vec<File>) -> Self {
+        Self {
+            state: State::Picking,
+            files,
+            replies: BTreeMap::new(),
+        }
+    }
+
+    /// Boots the given model on a separate port.
+    pub fn boot(&mut self, file: File, launch: Launch) -> Task<Message> {
+        let (boot, handle) = Task::run(
+            Assistant::boot(file.clone(), launch, Assistant::COMPARISON_PORT),
+            Message::ComparisonBooting,
+        )
+        .abortable();
+
+        self.state = State::Booting {
+            file,
+            stage: "Booting...".to_owned(),
+            progress: 0,
+            _task: handle.abort_on_drop(),
+        };
+
+        boot
+    }
+
+    pub fn booting(&mut self, event: Result<BootEvent, Error>) {
+        match (event, &mut self.state) {
+            (
+                Ok(BootEvent::Progressed {
+                    stage: new_stage,
+                    percent,
+                }),
+                State::Booting {
+                    stage, progress, ..
+                },
+            ) => {
+                new_stage.clone_into(stage);
+                *progress = percent;
+            }
+            (Ok(BootEvent::Logged(_)), _) => {}
+            (Ok(BootEvent::Finished(assistant)), _) => {
+                self.state = State::Running {
+                    assistant,
+                    sending: None,
+                };
+            }
+            (Err(error), State::Booting { file, .. }) => {
+                diagnostics::record(&error);
+
+                self.state = State::Failed {
+                    file: file.clone(),
+                    error: dbg!(error),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    pub fn assistant(&self) -> Option<&Assistant> {
+        match &self.state {
+            State::Running { assistant, .. } => Some(assistant),
+            _ => None,
+        }
+    }
+
+    /// Completes the given history with the compared assistant, whose reply
+    /// is shown next to the item at the given index.
+    pub fn complete(
+        &mut self,
+        index: usize,
+        stream: impl Stream<Item = Result<chat::Event, Error>> + Send + 'static,
+    ) -> Task<Message> {
+        let State::Running { sending, .. } = &mut self.state else {
+            return Task::none();
+        };
+
+        let _ = self.replies.remove(&index);
+
+        let (task, handle) =
+            Task::run(stream, move |event| Message::Compared(index, event)).abortable();
+
+        *sending = Some(handle.abort_on_drop());
+
+        task
+    }
+
+    pub fn update(&mut self, index: usize, event: chat::Event) {
+        match event {
+            chat::Event::LastMessageChanged {
+                content, new_token, ..
+            } => {
+                let reply = self.replies.entry(index).or_insert_with(|| Reply {
+                    content: String::new(),
+                    markdown: markdown::Content::new(),
+                });
+
+                reply.content = content;
+
+                if let assistant::Token::Talking(token) = new_token {
+                    reply.markdown.push_str(&token);
+                }
+            }
+            chat::Event::ExchangeOver => {
+                self.stop();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let State::Running { sending, .. } = &mut self.state {
+            *sending = None;
+        }
+    }
+
+    /// Forgets the replies to the messages that are not in the history anymore.
+    pub fn truncate(&mut self, len: usize) {
+        self.stop();
+        self.replies.retain(|index, _| *index < len);
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let status: Element<_> = match &self.state {
+            State::Picking => text(if self.files.is_empty() {
+                "Chat with another downloaded model first to compare with it."
+            } else {
+                "Pick a model to answer next to this one."
+            })
+            .size(12)
+            .width(Fill)
+            .into(),
+            State::Booting {
+                file,
+                stage,
+                progress,
+                ..
+            } => text!("Launching {file}... {stage} {progress}%")
+                .size(12)
+                .font(Font::MONOSPACE)
+                .width(Fill)
+                .into(),
+            State::Running { assistant, .. } => text!("Comparing with {}", assistant.file())
+                .size(12)
+                .width(Fill)
+                .into(),
+            State::Failed { file, error } => column![
+                text!("{file} failed to launch.").size(12),
+                value(error)
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .style(text::danger)
+            ]
+            .spacing(5)
+            .width(Fill)
+            .into(),
+        };
+
+        let picker = matches!(self.state, State::Picking | State::Failed { .. }).then(|| {
+            pick_list(self.files.as_slice(), None::<File>, Message::CompareWith)
+                .placeholder("Compare with...")
+                .text_size(12)
+                .padding([2, 7])
+        });
+
+        let close = button(text("Stop comparing").size(12))
+            .on_press(Message::ToggleComparison)
+            .padding([2, 7])
+            .style(button::text);
+
+        container(
+            row![status]
+                .push_maybe(picker)
+                .push(close)
+                .spacing(10)
+                .align_y(Center),
+        )
+        .padding([5, 10])
+        .max_width(600)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    /// The reply of the compared assistant to the item at the given index.
+    pub fn reply<'a>(
+        &'a self,
+        index: usize,
+        theme: &Theme,
+        appearance: Appearance,
+    ) -> Option<Element<'a, Message>> {
+        let reply = self.replies.get(&index)?;
+
+        let name = match &self.state {
+            State::Running { assistant, .. } => assistant.name(),
+            _ => "",
+        };
+
+        let content = markdown::view(&reply.markdown, theme, appearance, None).map(|interaction| {
+            match interaction {
+                Interaction::LinkClicked(url) => Message::LinkClicked(url),
+                Interaction::Copy(code)
+                | Interaction::Run(_, code)
+                | Interaction::OpenInEditor(_, code) => Message::Copy(code),
+            }
+        });
+
+        let copy = button(text("Copy").size(12))
+            .on_press_with(|| Message::Copy(reply.content.clone()))
+            .padding([2, 7])
+            .style(button::text);
+
+        Some(
+            column![
+                text(name)
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .style(text::secondary),
+                container(content).padding(10).style(container::rounded_box),
+                copy,
+            ]
+            .spacing(5)
+            .padding([20, 0])
+            .into(),
+        )
+    }
+}
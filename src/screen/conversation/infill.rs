@@ -0,0 +1,150 @@
+use crate::data::assistant::{self, Assistant};
+use crate::screen::conversation::Message;
+
+use iced::task::{self, Task};
+use iced::widget::{button, column, container, row, scrollable, text, text_editor};
+use iced::{Center, Element, Fill, Font};
+
+/// Fills in the code between a prefix and a suffix, for code models trained
+/// for fill-in-the-middle.
+pub struct Infill {
+    prefix: text_editor::Content,
+    suffix: text_editor::Content,
+    middle: String,
+    filling: Option<task::Handle>,
+}
+
+impl Infill {
+    pub fn new() -> Self {
+        Self {
+            prefix: text_editor::Content::new(),
+            suffix: text_editor::Content::new(),
+            middle: String::new(),
+            filling: None,
+        }
+    }
+
+    pub fn edit_prefix(&mut self, action: text_editor::Action) {
+        self.prefix.perform(action);
+    }
+
+    pub fn edit_suffix(&mut self, action: text_editor::Action) {
+        self.suffix.perform(action);
+    }
+
+    pub fn fill(&mut self, assistant: &Assistant, sampling: assistant::Sampling) -> Task<Message> {
+        self.middle.clear();
+
+        let (task, handle) = Task::run(
+            assistant.infill(self.prefix.text(), self.suffix.text(), sampling),
+            Message::MiddleFilled,
+        )
+        .chain(Task::done(Message::InfillOver))
+        .abortable();
+
+        self.filling = Some(handle.abort_on_drop());
+
+        task
+    }
+
+    pub fn push(&mut self, middle: &str) {
+        self.middle.push_str(middle);
+    }
+
+    pub fn stop(&mut self) {
+        self.filling = None;
+    }
+
+    /// The prefix, the filled middle, and the suffix together.
+    fn code(&self) -> String {
+        format!(
+            "{prefix}{middle}{suffix}",
+            prefix = self.prefix.text(),
+            middle = self.middle,
+            suffix = self.suffix.text()
+        )
+    }
+
+    pub fn view(&self, can_fill: bool) -> Element<'_, Message> {
+        fn editor<'a>(
+            content: &'a text_editor::Content,
+            placeholder: &'a str,
+            on_action: fn(text_editor::Action) -> Message,
+        ) -> Element<'a, Message> {
+            text_editor(content)
+                .placeholder(placeholder)
+                .on_action(on_action)
+                .font(Font::MONOSPACE)
+                .size(12)
+                .padding(10)
+                .height(120)
+                .into()
+        }
+
+        let middle = container(
+            scrollable(
+                text(if self.middle.is_empty() && self.filling.is_none() {
+                    "The middle will appear here."
+                } else {
+                    &self.middle
+                })
+                .font(Font::MONOSPACE)
+                .size(12),
+            )
+            .width(Fill),
+        )
+        .padding(10)
+        .max_height(200)
+        .style(container::rounded_box);
+
+        let fill = if self.filling.is_some() {
+            button(text("Stop").size(12))
+                .on_press(Message::StopInfill)
+                .style(button::danger)
+        } else {
+            button(text("Fill in the middle").size(12))
+                .on_press_maybe(can_fill.then_some(Message::FillMiddle))
+                .style(button::primary)
+        }
+        .padding([2, 7]);
+
+        let copy = button(text("Copy code").size(12))
+            .on_press_maybe(
+                (!self.middle.is_empty() && self.filling.is_none())
+                    .then(|| Message::Copy(self.code())),
+            )
+            .padding([2, 7])
+            .style(button::text);
+
+        container(
+            column![
+                editor(
+                    &self.prefix,
+                    "Code before the cursor...",
+                    Message::InfillPrefixEdited
+                ),
+                middle,
+                editor(
+                    &self.suffix,
+                    "Code after the cursor...",
+                    Message::InfillSuffixEdited
+                ),
+                row![
+                    text("Needs a code model trained for fill-in-the-middle.")
+                        .size(12)
+                        .style(text::secondary)
+                        .width(Fill),
+                    copy,
+                    fill
+                ]
+                .spacing(10)
+                .align_y(Center),
+            ]
+            .spacing(10),
+        )
+        .padding(10)
+        .max_width(600)
+        .style(container::rounded_box)
+        .into()
+    }
+}
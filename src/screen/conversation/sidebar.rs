@@ -0,0 +1,235 @@
+use crate::data::assistant::File;
+use crate::data::chat::Hit;
+use crate::screen::conversation::{Conversation, Filter, Message, State, SEARCH_CHATS};
+use crate::screen::preloads::Preloads;
+
+use iced::border;
+use iced::font;
+use iced::widget::{
+    button, column, container, mouse_area, pick_list, rich_text, scrollable, span, text,
+    text_input, vertical_space,
+};
+use iced::{Center, Element, Fill, Font, Theme};
+
+#[derive(Debug, Default)]
+pub struct Search {
+    query: String,
+    hits: Vec<Hit>,
+}
+
+impl Search {
+    pub fn is_active(&self) -> bool {
+        !self.query.trim().is_empty()
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        if query.trim().is_empty() {
+            self.hits.clear();
+        }
+
+        self.query = query;
+    }
+
+    pub fn set_hits(&mut self, query: &str, hits: Vec<Hit>) {
+        // Ignore outdated results
+        if self.query == query {
+            self.hits = hits;
+        }
+    }
+}
+
+impl Conversation {
+    pub(super) fn sidebar<'a>(&'a self, preloads: &'a Preloads) -> Element<'a, Message> {
+        let new = button(text("New Chat").width(Fill).align_x(Center))
+            .on_press(Message::New)
+            .style(button::success);
+
+        let search = button(text("Search Models").width(Fill).align_x(Center))
+            .on_press(Message::Search)
+            .style(button::secondary);
+
+        let settings = button(text("Settings").width(Fill).align_x(Center))
+            .on_press(Message::OpenSettings)
+            .style(button::secondary);
+
+        let preloads = (!preloads.is_empty()).then(|| preloads.view().map(Message::Preloads));
+
+        if self.chats.is_empty() {
+            return column![vertical_space()]
+                .push_maybe(preloads)
+                .push(new)
+                .push(search)
+                .push(settings)
+                .width(250)
+                .spacing(10)
+                .into();
+        }
+
+        let search_chats = text_input("Search chats...", self.chat_search.query())
+            .id(SEARCH_CHATS)
+            .on_input(Message::SearchChats)
+            .padding(5)
+            .size(14);
+
+        let list = if self.chat_search.is_active() {
+            self.hits()
+        } else {
+            self.chats()
+        };
+
+        column![search_chats, list]
+            .push_maybe(preloads)
+            .push(new)
+            .push(search)
+            .push(settings)
+            .width(250)
+            .spacing(10)
+            .into()
+    }
+
+    fn chats(&self) -> Element<'_, Message> {
+        let filters = {
+            let mut filters = vec![Filter::All, Filter::Code];
+
+            for language in self.chats.iter().flat_map(|chat| &chat.languages) {
+                let filter = Filter::Language(language.clone());
+
+                if !filters.contains(&filter) {
+                    filters.push(filter);
+                }
+            }
+
+            for tag in self.chats.iter().flat_map(|chat| &chat.tags) {
+                let filter = Filter::Tag(tag.clone());
+
+                if !filters.contains(&filter) {
+                    filters.push(filter);
+                }
+            }
+
+            pick_list(filters, Some(&self.filter), Message::FilterChanged)
+                .text_size(14)
+                .width(Fill)
+        };
+
+        let chats = self.chats.iter().filter(|chat| self.filter.matches(chat));
+
+        let chats = column(chats.map(|chat| {
+            let card: Element<_> = match &chat.title {
+                Some(title) => {
+                    let title = text(title).font(Font::MONOSPACE);
+                    let subtitle = text(chat.file.model.name()).font(Font::MONOSPACE).size(10);
+
+                    column![title, subtitle].spacing(3).into()
+                }
+                None => text(chat.file.model.name()).font(Font::MONOSPACE).into(),
+            };
+
+            let is_active = Some(&chat.id) == self.id.as_ref();
+
+            if is_active {
+                container(card).style(active).padding(5).width(Fill).into()
+            } else {
+                let card = button(card)
+                    .on_press_with(move || Message::Open(chat.id.clone()))
+                    .padding(5)
+                    .width(Fill)
+                    .style(inactive);
+
+                // Right-clicking preloads the model of the chat in the background
+                if self.can_preload(&chat.file) {
+                    let area = mouse_area(card).on_right_press(Message::Preload(chat.file.clone()));
+
+                    if self.settings.preload_on_hover {
+                        area.on_enter(Message::ChatHovered(chat.file.clone()))
+                            .on_exit(Message::ChatUnhovered(chat.file.clone()))
+                            .into()
+                    } else {
+                        area.into()
+                    }
+                } else {
+                    card.into()
+                }
+            }
+        }))
+        .clip(true)
+        .spacing(10);
+
+        column![filters, scrollable(chats).height(Fill).spacing(10)]
+            .spacing(10)
+            .height(Fill)
+            .into()
+    }
+
+    fn can_preload(&self, file: &File) -> bool {
+        let current = match &self.state {
+            State::Booting { file, .. } | State::Missing { file, .. } => file,
+            State::Running { assistant, .. } => assistant.file(),
+        };
+
+        file != current && file.is_downloaded()
+    }
+
+    fn hits(&self) -> Element<'_, Message> {
+        if self.chat_search.hits.is_empty() {
+            return container(text("No chats found.").size(14).style(text::secondary))
+                .center_x(Fill)
+                .height(Fill)
+                .into();
+        }
+
+        let hits = column(self.chat_search.hits.iter().map(|hit| {
+            let title = text(hit.title.as_deref().unwrap_or("Untitled chat"))
+                .font(Font::MONOSPACE)
+                .size(12);
+
+            let (before, rest) = hit.snippet.split_at(hit.highlight.start);
+            let (matched, after) = rest.split_at(hit.highlight.len());
+
+            let snippet = rich_text![
+                span(before),
+                span(matched).font(Font {
+                    weight: font::Weight::Bold,
+                    ..Font::DEFAULT
+                }),
+                span(after),
+            ]
+            .size(12);
+
+            let card = column![title, snippet].spacing(3);
+
+            button(card)
+                .on_press_with(move || Message::OpenHit(hit.clone()))
+                .padding(5)
+                .width(Fill)
+                .style(inactive)
+                .into()
+        }))
+        .clip(true)
+        .spacing(10);
+
+        scrollable(hits).height(Fill).spacing(10).into()
+    }
+}
+
+fn active(theme: &Theme) -> container::Style {
+    let pair = theme.extended_palette().secondary.weak;
+
+    container::Style {
+        background: Some(pair.color.into()),
+        text_color: Some(pair.text),
+        border: border::rounded(2),
+        ..container::Style::default()
+    }
+}
+
+fn inactive(theme: &Theme, status: button::Status) -> button::Style {
+    match status {
+        button::Status::Active => button::text(theme, status),
+        _ => button::secondary(theme, status),
+    }
+}
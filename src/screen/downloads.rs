@@ -0,0 +1,364 @@
+use crate::data::assistant::File;
+use crate::data::convert;
+use crate::data::download::{self, Progress};
+use crate::data::locale::Locale;
+use crate::data::{diagnostics, Error};
+
+use iced::task::{self, Task};
+use iced::widget::{button, column, container, progress_bar, row, scrollable, text};
+use iced::{Center, Element, Fill, Font};
+
+pub struct Downloads {
+    queue: Vec<Download>,
+}
+
+struct Download {
+    file: File,
+    kind: Kind,
+    state: State,
+    progress: Option<Progress>,
+}
+
+enum State {
+    Queued,
+    Running { _task: task::Handle, stage: Stage },
+    Paused,
+    Finished,
+    Failed(Error),
+}
+
+/// How a file is obtained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Download,
+    /// Converted from the original weights of its model.
+    Conversion,
+    /// Quantized from a larger file of the same model.
+    Quantization(File),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Downloading,
+    Verifying,
+    Converting,
+    Quantizing,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Downloading(File, Result<download::Event, Error>),
+    Pause(File),
+    Resume(File),
+    Cancel(File),
+    Canceled(Result<(), Error>),
+    Dismiss(File),
+}
+
+impl Downloads {
+    const MAX_PARALLEL: usize = 2;
+
+    pub fn new() -> Self {
+        Self { queue: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn push(&mut self, file: File) -> Task<Message> {
+        self.enqueue(file, Kind::Download)
+    }
+
+    /// Queues the conversion of the original weights of a model into the
+    /// given file.
+    pub fn convert(&mut self, file: File) -> Task<Message> {
+        self.enqueue(file, Kind::Conversion)
+    }
+
+    /// Queues the quantization of the given source into a smaller file.
+    pub fn quantize(&mut self, source: File, target: File) -> Task<Message> {
+        self.enqueue(target, Kind::Quantization(source))
+    }
+
+    fn enqueue(&mut self, file: File, kind: Kind) -> Task<Message> {
+        if let Some(download) = self.find(&file) {
+            if matches!(download.state, State::Paused | State::Failed(_)) {
+                download.state = State::Queued;
+            }
+        } else {
+            self.queue.push(Download {
+                file,
+                kind,
+                state: State::Queued,
+                progress: None,
+            });
+        }
+
+        self.schedule()
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::Downloading(file, Ok(event)) => {
+                let Some(download) = self.find(&file) else {
+                    return Task::none();
+                };
+
+                match event {
+                    download::Event::Progressed(progress) => {
+                        download.progress = Some(progress);
+
+                        Task::none()
+                    }
+                    download::Event::Verifying
+                    | download::Event::Converting
+                    | download::Event::Quantizing => {
+                        if let State::Running { stage, .. } = &mut download.state {
+                            *stage = match event {
+                                download::Event::Converting => Stage::Converting,
+                                download::Event::Quantizing => Stage::Quantizing,
+                                _ => Stage::Verifying,
+                            };
+                        }
+
+                        // Quantizing reports its own progress
+                        if event == download::Event::Quantizing {
+                            download.progress = None;
+                        }
+
+                        Task::none()
+                    }
+                    download::Event::Finished => {
+                        download.state = State::Finished;
+
+                        self.schedule()
+                    }
+                }
+            }
+            Message::Downloading(file, Err(error)) => {
+                diagnostics::record(&error);
+
+                if let Some(download) = self.find(&file) {
+                    download.state = State::Failed(dbg!(error));
+                }
+
+                self.schedule()
+            }
+            Message::Pause(file) => {
+                if let Some(download) = self.find(&file) {
+                    // Dropping the handle aborts the download
+                    download.state = State::Paused;
+                }
+
+                self.schedule()
+            }
+            Message::Resume(file) => {
+                let kind = self
+                    .find(&file)
+                    .map_or(Kind::Download, |download| download.kind.clone());
+
+                self.enqueue(file, kind)
+            }
+            Message::Cancel(file) => {
+                let kind = self
+                    .find(&file)
+                    .map_or(Kind::Download, |download| download.kind.clone());
+
+                self.queue.retain(|download| download.file != file);
+
+                let cancel = match kind {
+                    Kind::Download => Task::perform(download::cancel(file), Message::Canceled),
+                    Kind::Conversion | Kind::Quantization(_) => {
+                        Task::perform(convert::cancel(file), Message::Canceled)
+                    }
+                };
+
+                Task::batch([cancel, self.schedule()])
+            }
+            Message::Canceled(Ok(())) => Task::none(),
+            Message::Canceled(Err(error)) => {
+                let _ = dbg!(error);
+
+                Task::none()
+            }
+            Message::Dismiss(file) => {
+                self.queue.retain(|download| download.file != file);
+
+                Task::none()
+            }
+        }
+    }
+
+    pub fn view(&self, locale: Locale) -> Element<'_, Message> {
+        let downloads = column(self.queue.iter().map(|download| download.view(locale))).spacing(10);
+
+        container(
+            column![
+                text("Downloads").font(Font::MONOSPACE),
+                scrollable(downloads).spacing(10)
+            ]
+            .spacing(10),
+        )
+        .width(Fill)
+        .max_height(250)
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    fn find(&mut self, file: &File) -> Option<&mut Download> {
+        self.queue
+            .iter_mut()
+            .find(|download| &download.file == file)
+    }
+
+    fn schedule(&mut self) -> Task<Message> {
+        let mut running = self
+            .queue
+            .iter()
+            .filter(|download| matches!(download.state, State::Running { .. }))
+            .count();
+
+        let mut tasks = Vec::new();
+
+        for download in &mut self.queue {
+            if running >= Self::MAX_PARALLEL {
+                break;
+            }
+
+            if !matches!(download.state, State::Queued) {
+                continue;
+            }
+
+            let file = download.file.clone();
+
+            let task = match &download.kind {
+                Kind::Download => Task::run(download::run(file.clone()), move |event| {
+                    Message::Downloading(file.clone(), event)
+                }),
+                Kind::Conversion => Task::run(convert::run(file.clone()), move |event| {
+                    Message::Downloading(file.clone(), event)
+                }),
+                Kind::Quantization(source) => Task::run(
+                    convert::requantize(source.clone(), file.clone()),
+                    move |event| Message::Downloading(file.clone(), event),
+                ),
+            };
+
+            let (task, handle) = task.abortable();
+
+            download.state = State::Running {
+                _task: handle.abort_on_drop(),
+                stage: Stage::Downloading,
+            };
+            running += 1;
+
+            tasks.push(task);
+        }
+
+        Task::batch(tasks)
+    }
+}
+
+impl Default for Downloads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Download {
+    fn view(&self, locale: Locale) -> Element<'_, Message> {
+        let action = |label, message| {
+            button(text(label).size(12))
+                .on_press(message)
+                .padding([2, 7])
+                .style(button::text)
+        };
+
+        let status = match &self.state {
+            State::Queued => text("Queued"),
+            State::Running {
+                stage: Stage::Verifying,
+                ..
+            } => text("Verifying..."),
+            State::Running {
+                stage: Stage::Converting,
+                ..
+            } => text("Converting to GGUF..."),
+            State::Running {
+                stage: Stage::Quantizing,
+                ..
+            } => match self
+                .progress
+                .and_then(|progress| Some((progress, progress.total?)))
+            {
+                Some((progress, total)) => text!(
+                    "Quantizing... {percent:.0}% of ~{total}",
+                    percent = progress.percent(),
+                    total = locale.bytes(total)
+                ),
+                None => text("Quantizing..."),
+            },
+            State::Running { .. } => match &self.progress {
+                Some(progress) => match progress.total {
+                    Some(total) => text!(
+                        "{percent:.0}% of {total} @ {speed}/s",
+                        percent = progress.percent(),
+                        total = locale.bytes(total),
+                        speed = locale.bytes(progress.speed as u64)
+                    ),
+                    None => text!(
+                        "{downloaded} @ {speed}/s",
+                        downloaded = locale.bytes(progress.downloaded),
+                        speed = locale.bytes(progress.speed as u64)
+                    ),
+                },
+                None => text("Starting..."),
+            },
+            State::Paused => text("Paused"),
+            State::Finished => text("Finished"),
+            State::Failed(error) => text!("{error}").style(text::danger),
+        }
+        .font(Font::MONOSPACE)
+        .size(10);
+
+        let actions = match &self.state {
+            State::Queued | State::Running { .. } => row![
+                action("Pause", Message::Pause(self.file.clone())),
+                action("Cancel", Message::Cancel(self.file.clone())),
+            ],
+            State::Paused => row![
+                action("Resume", Message::Resume(self.file.clone())),
+                action("Cancel", Message::Cancel(self.file.clone())),
+            ],
+            State::Failed(_) => row![
+                action("Retry", Message::Resume(self.file.clone())),
+                action("Cancel", Message::Cancel(self.file.clone())),
+            ],
+            State::Finished => row![action("Dismiss", Message::Dismiss(self.file.clone()))],
+        };
+
+        let progress = progress_bar(
+            0.0..=100.0,
+            match &self.state {
+                State::Finished => 100.0,
+                _ => self
+                    .progress
+                    .as_ref()
+                    .map(Progress::percent)
+                    .unwrap_or_default(),
+            },
+        )
+        .girth(5);
+
+        column![
+            row![text(&self.file.name).size(14).width(Fill), status, actions]
+                .spacing(10)
+                .align_y(Center),
+            progress,
+        ]
+        .spacing(5)
+        .into()
+    }
+}
@@ -0,0 +1,251 @@
+use crate::data::knowledge::{self, Knowledge};
+use crate::data::Error;
+
+use iced::task::Task;
+use iced::widget::{
+    button, center_x, column, container, horizontal_space, row, scrollable, text, text_input, value,
+};
+use iced::{Center, Color, Element, Fill, Font, Theme};
+
+/// Compares the embeddings of some texts, to check how well the embedding
+/// model of the knowledge folders tells them apart.
+pub struct Embeddings {
+    server: String,
+    texts: Vec<String>,
+    state: State,
+}
+
+enum State {
+    Idle,
+    Computing,
+    Computed {
+        dimensions: usize,
+        similarities: Vec<Vec<f32>>,
+    },
+    Failed(Error),
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    TextChanged(usize, String),
+    AddText,
+    RemoveText(usize),
+    Compare,
+    Embedded(Result<Vec<Vec<f32>>, Error>),
+    Close,
+}
+
+pub enum Action {
+    None,
+    Run(Task<Message>),
+    Close,
+}
+
+impl Embeddings {
+    pub fn new(knowledge: &Knowledge) -> Self {
+        Self {
+            server: knowledge.embedding_server.clone(),
+            texts: vec![String::new(), String::new()],
+            state: State::Idle,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        "Embeddings - Icebreaker".to_owned()
+    }
+
+    pub fn update(&mut self, message: Message) -> Action {
+        match message {
+            Message::TextChanged(index, new_text) => {
+                if let Some(text) = self.texts.get_mut(index) {
+                    *text = new_text;
+                }
+
+                Action::None
+            }
+            Message::AddText => {
+                self.texts.push(String::new());
+
+                Action::None
+            }
+            Message::RemoveText(index) => {
+                if index < self.texts.len() {
+                    let _ = self.texts.remove(index);
+                    self.state = State::Idle;
+                }
+
+                Action::None
+            }
+            Message::Compare => {
+                let server = self.server.clone();
+                let texts = self.texts.clone();
+
+                self.state = State::Computing;
+
+                Action::Run(Task::perform(
+                    async move { knowledge::embed(&server, &texts).await },
+                    Message::Embedded,
+                ))
+            }
+            Message::Embedded(Ok(embeddings)) => {
+                self.state = State::Computed {
+                    dimensions: embeddings.first().map(Vec::len).unwrap_or_default(),
+                    similarities: embeddings
+                        .iter()
+                        .map(|a| {
+                            embeddings
+                                .iter()
+                                .map(|b| knowledge::similarity(a, b))
+                                .collect()
+                        })
+                        .collect(),
+                };
+
+                Action::None
+            }
+            Message::Embedded(Err(error)) => {
+                self.state = State::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::Close => Action::Close,
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let header = row![
+            text("Embeddings").size(20).font(Font::MONOSPACE),
+            horizontal_space(),
+            button("Close")
+                .on_press(Message::Close)
+                .style(button::secondary),
+        ]
+        .align_y(Center);
+
+        let description = text!(
+            "Texts are embedded by {server}, the embedding server of your \
+            knowledge folders. Similar meanings should score close to 1.",
+            server = self.server
+        )
+        .size(12)
+        .style(text::secondary);
+
+        let texts = column(self.texts.iter().enumerate().map(|(index, content)| {
+            row![
+                text!("#{}", index + 1)
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .width(30),
+                text_input("Type some text...", content)
+                    .on_input(move |text| Message::TextChanged(index, text))
+                    .on_submit(Message::Compare)
+                    .size(14)
+                    .padding(5),
+                button(text("Remove").size(12))
+                    .on_press_maybe((self.texts.len() > 2).then_some(Message::RemoveText(index)))
+                    .padding([2, 7])
+                    .style(button::text),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into()
+        }))
+        .spacing(5);
+
+        let can_compare = !matches!(self.state, State::Computing)
+            && self.texts.iter().all(|text| !text.trim().is_empty());
+
+        let actions = row![
+            button(text("Add text").size(12))
+                .on_press(Message::AddText)
+                .padding([2, 7])
+                .style(button::secondary),
+            horizontal_space(),
+            button(text("Compare").size(12))
+                .on_press_maybe(can_compare.then_some(Message::Compare))
+                .padding([2, 7])
+                .style(button::primary),
+        ];
+
+        let result: Element<_> = match &self.state {
+            State::Idle => horizontal_space().into(),
+            State::Computing => text("Embedding...").size(12).into(),
+            State::Computed {
+                dimensions,
+                similarities,
+            } => column![
+                text!("{dimensions} dimensions")
+                    .size(12)
+                    .style(text::secondary),
+                matrix(similarities),
+            ]
+            .spacing(10)
+            .into(),
+            State::Failed(error) => value(error)
+                .size(12)
+                .font(Font::MONOSPACE)
+                .style(text::danger)
+                .into(),
+        };
+
+        let content = scrollable(
+            container(column![description, texts, actions, result].spacing(20))
+                .width(Fill)
+                .padding(10)
+                .style(container::rounded_box),
+        )
+        .height(Fill)
+        .spacing(10);
+
+        center_x(
+            column![header, content]
+                .spacing(20)
+                .padding(10)
+                .max_width(600),
+        )
+        .into()
+    }
+}
+
+/// The pairwise similarities, tinted by how close they are.
+fn matrix(similarities: &[Vec<f32>]) -> Element<'_, Message> {
+    fn cell<'a>(content: impl text::IntoFragment<'a>) -> Element<'a, Message> {
+        container(text(content).size(12).font(Font::MONOSPACE))
+            .center_x(60)
+            .into()
+    }
+
+    let labels = row![cell("")]
+        .extend((1..=similarities.len()).map(|index| cell(format!("#{index}"))))
+        .spacing(5);
+
+    let rows = similarities.iter().enumerate().map(|(index, scores)| {
+        row![cell(format!("#{}", index + 1))]
+            .extend(scores.iter().map(|similarity| {
+                let similarity = *similarity;
+
+                container(text!("{similarity:.3}").size(12).font(Font::MONOSPACE))
+                    .center_x(60)
+                    .padding([2, 0])
+                    .style(move |theme: &Theme| {
+                        let palette = theme.extended_palette();
+
+                        container::Style {
+                            background: Some(
+                                Color {
+                                    a: similarity.clamp(0.0, 1.0) * 0.6,
+                                    ..palette.primary.base.color
+                                }
+                                .into(),
+                            ),
+                            ..container::Style::default()
+                        }
+                    })
+                    .into()
+            }))
+            .spacing(5)
+            .into()
+    });
+
+    column![labels].extend(rows).spacing(5).into()
+}
@@ -0,0 +1,224 @@
+use crate::data::appearance::Appearance;
+use crate::data::assistant;
+use crate::data::locale::Locale;
+use crate::screen::conversation::{self, Item};
+use crate::widget::markdown;
+
+use iced::clipboard;
+use iced::time::{self, Duration};
+use iced::widget::{button, center_x, column, container, horizontal_space, row, scrollable, text};
+use iced::{Center, Element, Fill, Font, Subscription, Task, Theme};
+
+/// A showcase of the custom widgets with canned data.
+///
+/// It is only reachable with the `--widget-gallery` flag and never boots
+/// a model.
+pub struct Gallery {
+    items: Vec<Item>,
+    stream: markdown::Content,
+    streamed: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Conversation(Box<conversation::Message>),
+    Stream,
+    Restart,
+}
+
+impl Gallery {
+    pub const FLAG: &'static str = "--widget-gallery";
+
+    pub fn new() -> Self {
+        let items = vec![
+            Item::from(assistant::Message::User(
+                "How do I read a file line by line in **Rust**?".to_owned(),
+            )),
+            Item::from(assistant::Message::Assistant {
+                reasoning: Some(assistant::Reasoning {
+                    content: "The user wants to iterate over lines.\n\n\
+                        `BufRead::lines` is the idiomatic way."
+                        .to_owned(),
+                    duration: Duration::from_secs(3),
+                }),
+                content: REPLY.to_owned(),
+                usage: Some(assistant::Usage {
+                    prompt_tokens: 42,
+                    completion_tokens: 128,
+                    generation_time: Duration::from_millis(2_500),
+                }),
+                alternatives: Vec::new(),
+                interrupted: false,
+            }),
+        ];
+
+        Self {
+            items,
+            stream: markdown::Content::new(),
+            streamed: 0,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        "Widget Gallery - Icebreaker".to_owned()
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::Conversation(message) => match *message {
+                conversation::Message::Copy(content) => clipboard::write(content),
+                conversation::Message::LinkClicked(url) => {
+                    let _ = open::that_in_background(url.to_string());
+
+                    Task::none()
+                }
+                conversation::Message::ToggleReasoning(index) => {
+                    if let Some(item) = self.items.get_mut(index) {
+                        item.toggle_reasoning();
+                    }
+
+                    Task::none()
+                }
+                _ => Task::none(),
+            },
+            Message::Stream => {
+                let next = REPLY[self.streamed..]
+                    .char_indices()
+                    .nth(STREAM_CHUNK)
+                    .map_or(REPLY.len(), |(offset, _)| self.streamed + offset);
+
+                self.stream.push_str(&REPLY[self.streamed..next]);
+                self.streamed = next;
+
+                Task::none()
+            }
+            Message::Restart => {
+                self.stream = markdown::Content::new();
+                self.streamed = 0;
+
+                Task::none()
+            }
+        }
+    }
+
+    pub fn view(&self, theme: &Theme) -> Element<Message> {
+        let header = row![
+            text("Widget Gallery").size(20).font(Font::MONOSPACE),
+            horizontal_space(),
+        ]
+        .align_y(Center);
+
+        let conversation = Element::from(column(self.items.iter().enumerate().map(
+            |(index, item)| {
+                item.view(
+                    index,
+                    theme,
+                    None,
+                    None,
+                    Locale::default(),
+                    Appearance::default(),
+                )
+            },
+        )))
+        .map(|message| Message::Conversation(Box::new(message)));
+
+        let streaming = column![
+            row![
+                text!("{} / {} bytes", self.streamed, REPLY.len())
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .style(text::secondary),
+                horizontal_space(),
+                button(text("Restart").size(12))
+                    .on_press(Message::Restart)
+                    .padding([2, 7])
+                    .style(button::secondary),
+            ]
+            .align_y(Center),
+            markdown::view(&self.stream, theme, Appearance::default(), None).map(|interaction| {
+                match interaction {
+                    markdown::Interaction::LinkClicked(url) => {
+                        Message::Conversation(Box::new(conversation::Message::LinkClicked(url)))
+                    }
+                    markdown::Interaction::Copy(code)
+                    | markdown::Interaction::Run(_, code)
+                    | markdown::Interaction::OpenInEditor(_, code) => {
+                        Message::Conversation(Box::new(conversation::Message::Copy(code)))
+                    }
+                }
+            }),
+        ]
+        .spacing(10);
+
+        let sections = scrollable(
+            column![
+                section("Conversation", conversation),
+                section("Streaming reply", streaming),
+            ]
+            .spacing(20)
+            .padding([0, 10]),
+        )
+        .height(Fill)
+        .spacing(10);
+
+        center_x(
+            column![header, sections]
+                .spacing(20)
+                .padding(10)
+                .max_width(800),
+        )
+        .into()
+    }
+
+    pub fn subscription(&self) -> Subscription<Message> {
+        if self.streamed < REPLY.len() {
+            time::every(Duration::from_millis(50)).map(|_| Message::Stream)
+        } else {
+            Subscription::none()
+        }
+    }
+}
+
+/// The amount of characters streamed on every tick.
+const STREAM_CHUNK: usize = 7;
+
+const REPLY: &str = r#"Wrap the file in a [`BufReader`](https://doc.rust-lang.org/std/io/struct.BufReader.html) and iterate over its lines:
+
+```rust
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+fn main() -> io::Result<()> {
+    let file = File::open("notes.txt")?;
+
+    for line in BufReader::new(file).lines() {
+        println!("{}", line?);
+    }
+
+    Ok(())
+}
+```
+
+Each line is an `io::Result<String>`, so:
+
+1. Errors are reported per line
+2. Trailing newlines are stripped
+
+Run it with:
+
+```sh
+cargo run --release
+```
+"#;
+
+fn section<'a>(title: &'a str, content: impl Into<Element<'a, Message>>) -> Element<'a, Message> {
+    column![
+        text(title).font(Font::MONOSPACE),
+        container(content)
+            .width(Fill)
+            .padding(10)
+            .style(container::rounded_box)
+    ]
+    .spacing(10)
+    .into()
+}
@@ -0,0 +1,298 @@
+use crate::data::assistant::{Assistant, BootEvent, File, Launch};
+use crate::data::pool::Pool;
+use crate::data::{diagnostics, Error};
+
+use iced::task::{self, Task};
+use iced::widget::{button, column, container, progress_bar, row, scrollable, text};
+use iced::{Center, Element, Fill, Font};
+
+/// Models booting in the background, one at a time, to be kept in the
+/// [`Pool`] until a chat takes them.
+pub struct Preloads {
+    queue: Vec<Preload>,
+}
+
+struct Preload {
+    file: File,
+    launch: Launch,
+    state: State,
+    /// Whether the preload was started by hovering a chat, and stops if
+    /// the hover ends before it is ready.
+    on_hover: bool,
+}
+
+enum State {
+    Queued,
+    Booting {
+        port: u64,
+        stage: String,
+        progress: u64,
+        _task: task::Handle,
+    },
+    Ready,
+    Failed(Error),
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Booting(File, Result<BootEvent, Error>),
+    Cancel(File),
+    Dismiss(File),
+}
+
+impl Preloads {
+    pub fn new() -> Self {
+        Self { queue: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Queues the given file for preloading, unless it is queued or
+    /// running in the pool already.
+    ///
+    /// The given port is in use by the current assistant.
+    pub fn push(
+        &mut self,
+        file: File,
+        launch: Launch,
+        pool: &Pool,
+        port: Option<u64>,
+    ) -> Task<Message> {
+        self.enqueue(file, launch, false, pool, port)
+    }
+
+    /// Queues the given file for preloading while its chat is hovered; see
+    /// [`Preloads::unhover`].
+    pub fn hover(
+        &mut self,
+        file: File,
+        launch: Launch,
+        pool: &Pool,
+        port: Option<u64>,
+    ) -> Task<Message> {
+        self.enqueue(file, launch, true, pool, port)
+    }
+
+    /// Cancels the preload of the given file if hovering its chat started
+    /// it and it is not ready yet.
+    pub fn unhover(&mut self, file: &File, pool: &Pool, port: Option<u64>) -> Task<Message> {
+        let Some(preload) = self.find(file) else {
+            return Task::none();
+        };
+
+        if !preload.on_hover || !matches!(preload.state, State::Queued | State::Booting { .. }) {
+            return Task::none();
+        }
+
+        self.update(Message::Cancel(file.clone()), pool, port)
+    }
+
+    fn enqueue(
+        &mut self,
+        file: File,
+        launch: Launch,
+        on_hover: bool,
+        pool: &Pool,
+        port: Option<u64>,
+    ) -> Task<Message> {
+        if let Some(preload) = self.find(&file) {
+            if matches!(preload.state, State::Failed(_)) {
+                preload.state = State::Queued;
+            }
+
+            // Asking for it explicitly keeps it going after the hover ends
+            preload.on_hover &= on_hover;
+        } else if !pool.contains(&file) {
+            self.queue.push(Preload {
+                file,
+                launch,
+                state: State::Queued,
+                on_hover,
+            });
+        }
+
+        self.schedule(pool, port)
+    }
+
+    pub fn update(&mut self, message: Message, pool: &Pool, port: Option<u64>) -> Task<Message> {
+        match message {
+            Message::Booting(file, Ok(event)) => {
+                let Some(preload) = self.find(&file) else {
+                    return Task::none();
+                };
+
+                match event {
+                    BootEvent::Progressed {
+                        stage: new_stage,
+                        percent,
+                    } => {
+                        if let State::Booting {
+                            stage, progress, ..
+                        } = &mut preload.state
+                        {
+                            new_stage.clone_into(stage);
+                            *progress = percent;
+                        }
+
+                        Task::none()
+                    }
+                    BootEvent::Logged(_) => Task::none(),
+                    BootEvent::Finished(assistant) => {
+                        pool.preload(assistant);
+                        preload.state = State::Ready;
+
+                        self.schedule(pool, port)
+                    }
+                }
+            }
+            Message::Booting(file, Err(error)) => {
+                diagnostics::record(&error);
+
+                if let Some(preload) = self.find(&file) {
+                    if let State::Booting { port: reserved, .. } = preload.state {
+                        pool.unreserve(reserved);
+                    }
+
+                    preload.state = State::Failed(dbg!(error));
+                }
+
+                self.schedule(pool, port)
+            }
+            Message::Cancel(file) => {
+                if let Some(Preload {
+                    state: State::Booting { port: reserved, .. },
+                    ..
+                }) = self.find(&file)
+                {
+                    pool.unreserve(*reserved);
+                }
+
+                // Dropping the handle aborts the boot
+                self.queue.retain(|preload| preload.file != file);
+
+                self.schedule(pool, port)
+            }
+            Message::Dismiss(file) => {
+                self.queue.retain(|preload| preload.file != file);
+
+                Task::none()
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let preloads = column(self.queue.iter().map(Preload::view)).spacing(10);
+
+        container(
+            column![
+                text("Preloading").size(14).font(Font::MONOSPACE),
+                scrollable(preloads).spacing(10)
+            ]
+            .spacing(10),
+        )
+        .width(Fill)
+        .max_height(200)
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    fn find(&mut self, file: &File) -> Option<&mut Preload> {
+        self.queue.iter_mut().find(|preload| &preload.file == file)
+    }
+
+    /// Boots the next queued file, unless another one is booting already.
+    fn schedule(&mut self, pool: &Pool, port: Option<u64>) -> Task<Message> {
+        if self
+            .queue
+            .iter()
+            .any(|preload| matches!(preload.state, State::Booting { .. }))
+        {
+            return Task::none();
+        }
+
+        let Some(preload) = self
+            .queue
+            .iter_mut()
+            .find(|preload| matches!(preload.state, State::Queued))
+        else {
+            return Task::none();
+        };
+
+        let file = preload.file.clone();
+        let port = pool.port_besides(port.as_slice());
+        pool.reserve(port);
+
+        let (task, handle) = Task::run(
+            Assistant::boot(file.clone(), preload.launch.clone(), port),
+            move |event| Message::Booting(file.clone(), event),
+        )
+        .abortable();
+
+        preload.state = State::Booting {
+            port,
+            stage: "Booting...".to_owned(),
+            progress: 0,
+            _task: handle.abort_on_drop(),
+        };
+
+        task
+    }
+}
+
+impl Default for Preloads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preload {
+    fn view(&self) -> Element<'_, Message> {
+        let action = |label, message| {
+            button(text(label).size(10))
+                .on_press(message)
+                .padding([2, 5])
+                .style(button::text)
+        };
+
+        let status = match &self.state {
+            State::Queued => text("Queued"),
+            State::Booting { stage, .. } => text(stage),
+            State::Ready => text("Ready").style(text::success),
+            State::Failed(error) => text!("{error}").style(text::danger),
+        }
+        .font(Font::MONOSPACE)
+        .size(10);
+
+        let action = match &self.state {
+            State::Queued | State::Booting { .. } => {
+                action("Cancel", Message::Cancel(self.file.clone()))
+            }
+            State::Ready | State::Failed(_) => {
+                action("Dismiss", Message::Dismiss(self.file.clone()))
+            }
+        };
+
+        let progress = progress_bar(
+            0.0..=100.0,
+            match &self.state {
+                State::Booting { progress, .. } => *progress as f32,
+                State::Ready => 100.0,
+                State::Queued | State::Failed(_) => 0.0,
+            },
+        )
+        .girth(5);
+
+        column![
+            row![text(self.file.model.name()).size(12).width(Fill), action]
+                .spacing(5)
+                .align_y(Center),
+            status,
+            progress,
+        ]
+        .spacing(5)
+        .into()
+    }
+}
@@ -1,32 +1,99 @@
-use crate::data::assistant::Model;
+use crate::data::appearance::Appearance;
+use crate::data::assistant::{self, Backend, BootOptions, File, Kind, Model, Query, Sort};
+use crate::data::catalog::{self, Catalog, UseCase};
+use crate::data::hardware::Memory;
+use crate::data::locale::Locale;
 use crate::data::Error;
-use crate::icon;
+use crate::screen::downloads::{self, Downloads};
+use crate::widget::icon;
+use crate::widget::tip;
 
-use iced::time::Duration;
+use iced::time::{self, Duration};
 use iced::widget::{
-    self, button, center, column, container, horizontal_space, hover, iced, row, scrollable, text,
-    text_input, value,
+    self, button, center, column, container, horizontal_space, hover, iced, opaque, pick_list, row,
+    scrollable, stack, text, text_input,
 };
 use iced::window;
 use iced::{Center, Element, Fill, Font, Right, Size, Subscription, Task, Theme};
 
+use std::path::PathBuf;
+
 pub struct Search {
+    tab: Tab,
+    catalog: Catalog,
+    memory: Option<Memory>,
     models: Vec<Model>,
-    search: String,
+    query: Query,
+    /// The licenses and architectures of the models found so far, offered
+    /// as filters.
+    licenses: Vec<String>,
+    architectures: Vec<String>,
     search_temperature: usize,
     is_searching: bool,
     error: Option<Error>,
     window_size: Size,
+    rate_limit: Option<RateLimit>,
+    expanded: Vec<String>,
+    adding: Adding,
+    /// The files that boot with their own options.
+    boot_options: Vec<BootOptions>,
+    /// The boot options being edited, if any.
+    editing: Option<BootOptions>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    /// The models of the catalog, for newcomers.
+    Recommended,
+    All,
+}
+
+/// The state of a GGUF file being added from the disk.
+enum Adding {
+    Idle,
+    Running,
+    Failed(Error),
+}
+
+/// A search waiting to be retried after Hugging Face rate-limited it.
+struct RateLimit {
+    retrying_in: u64,
+    attempts: u32,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    TabSelected(Tab),
+    CatalogFetched(Catalog),
+    CatalogUpdated(Result<Catalog, Error>),
+    Download(File),
+    RunFile(File),
+    EditBootOptions(File),
+    BootBackendSelected(Backend),
+    BootGpuLayersChanged(String),
+    BootFlagsChanged(String),
+    ResetBootOptions,
+    SaveBootOptions,
+    CancelBootOptions,
     ModelsListed(Result<Vec<Model>, Error>),
     SearchChanged(String),
     SearchCooled,
+    SortSelected(Sort),
+    SizeSelected(assistant::Size),
+    QuantizationSelected(&'static str),
+    LicenseSelected(String),
+    ArchitectureSelected(String),
+    ClearFilters,
+    RetryTicked,
     RunModel(Model),
+    ToggleFamily(String),
     LinkPressed(Link),
     WindowResized(Size),
+    OpenSettings,
+    AddFile,
+    FilePicked(Option<PathBuf>),
+    FileAdded(Result<File, Error>),
+    Downloads(downloads::Message),
 }
 
 #[derive(Debug, Clone)]
@@ -41,20 +108,37 @@ pub enum Action {
     None,
     Run(Task<Message>),
     Boot(Model),
+    BootFile(File),
+    Download(File),
+    SaveBootOptions(BootOptions),
+    OpenSettings,
+    Downloads(downloads::Message),
 }
 
 impl Search {
-    pub fn new() -> (Self, Task<Message>) {
+    pub fn new(memory: Option<Memory>, boot_options: Vec<BootOptions>) -> (Self, Task<Message>) {
         (
             Self {
+                tab: Tab::Recommended,
+                catalog: Catalog::bundled(),
+                memory,
                 models: Vec::new(),
-                search: String::new(),
+                query: Query::default(),
+                licenses: Vec::new(),
+                architectures: Vec::new(),
                 search_temperature: 0,
                 is_searching: true,
                 error: None,
                 window_size: Size::ZERO,
+                rate_limit: None,
+                expanded: Vec::new(),
+                adding: Adding::Idle,
+                boot_options,
+                editing: None,
             },
             Task::batch([
+                Task::perform(Catalog::fetch(), Message::CatalogFetched),
+                Task::perform(Catalog::update(), Message::CatalogUpdated),
                 Task::perform(Model::list(), Message::ModelsListed),
                 widget::focus_next(),
                 window::get_latest()
@@ -70,19 +154,154 @@ impl Search {
 
     pub fn update(&mut self, message: Message) -> Action {
         match message {
+            Message::TabSelected(tab) => {
+                self.tab = tab;
+
+                Action::None
+            }
+            Message::CatalogFetched(catalog) | Message::CatalogUpdated(Ok(catalog)) => {
+                if catalog.version > self.catalog.version {
+                    self.catalog = catalog;
+                }
+
+                Action::None
+            }
+            Message::CatalogUpdated(Err(error)) => {
+                let _ = dbg!(error);
+
+                Action::None
+            }
+            Message::Download(file) => Action::Download(file),
+            Message::RunFile(file) => Action::BootFile(file),
+            Message::EditBootOptions(file) => {
+                self.editing = Some(
+                    self.boot_options
+                        .iter()
+                        .find(|options| options.file == file)
+                        .cloned()
+                        .unwrap_or_else(|| BootOptions::new(file)),
+                );
+
+                Action::None
+            }
+            Message::BootBackendSelected(backend) => {
+                if let Some(options) = &mut self.editing {
+                    options.backend = Some(backend);
+                }
+
+                Action::None
+            }
+            Message::BootGpuLayersChanged(gpu_layers) => {
+                let Some(options) = &mut self.editing else {
+                    return Action::None;
+                };
+
+                let gpu_layers = gpu_layers.trim();
+
+                if gpu_layers.is_empty() {
+                    options.gpu_layers = None;
+                } else if let Ok(gpu_layers) = gpu_layers.parse() {
+                    options.gpu_layers = Some(gpu_layers);
+                }
+
+                Action::None
+            }
+            Message::BootFlagsChanged(flags) => {
+                if let Some(options) = &mut self.editing {
+                    options.extra_flags = flags;
+                }
+
+                Action::None
+            }
+            Message::ResetBootOptions => {
+                if let Some(options) = &mut self.editing {
+                    *options = BootOptions::new(options.file.clone());
+                }
+
+                Action::None
+            }
+            Message::SaveBootOptions => {
+                let Some(options) = self
+                    .editing
+                    .take_if(|options| assistant::parse_flags(&options.extra_flags).is_ok())
+                else {
+                    return Action::None;
+                };
+
+                self.boot_options.retain(|saved| saved.file != options.file);
+
+                if !options.is_empty() {
+                    self.boot_options.push(options.clone());
+                }
+
+                Action::SaveBootOptions(options)
+            }
+            Message::CancelBootOptions => {
+                self.editing = None;
+
+                Action::None
+            }
             Message::ModelsListed(Ok(models)) => {
+                for model in &models {
+                    remember(&mut self.licenses, model.license.as_ref());
+                    remember(&mut self.architectures, model.architecture.as_ref());
+                }
+
                 self.models = models;
                 self.is_searching = false;
+                self.error = None;
+                self.rate_limit = None;
+
+                Action::None
+            }
+            Message::ModelsListed(Err(Error::RateLimited(retry_after))) => {
+                let attempts = self
+                    .rate_limit
+                    .as_ref()
+                    .map_or(0, |rate_limit| rate_limit.attempts + 1);
+
+                // Back off exponentially when the server does not say how long to wait
+                let wait = retry_after
+                    .unwrap_or(Duration::from_secs(5 * 2u64.pow(attempts.min(6))))
+                    .max(Duration::from_secs(1));
+
+                self.rate_limit = Some(RateLimit {
+                    retrying_in: wait.as_secs(),
+                    attempts,
+                });
 
                 Action::None
             }
             Message::ModelsListed(Err(error)) => {
                 self.error = Some(dbg!(error));
+                self.is_searching = false;
 
                 Action::None
             }
+            Message::RetryTicked => {
+                // The retry is already underway once the countdown is over
+                let Some(rate_limit) = self
+                    .rate_limit
+                    .as_mut()
+                    .filter(|rate_limit| rate_limit.retrying_in > 0)
+                else {
+                    return Action::None;
+                };
+
+                rate_limit.retrying_in = rate_limit.retrying_in.saturating_sub(1);
+
+                if rate_limit.retrying_in > 0 {
+                    return Action::None;
+                }
+
+                Action::Run(Task::perform(
+                    Model::search(self.query.clone()),
+                    Message::ModelsListed,
+                ))
+            }
             Message::SearchChanged(search) => {
-                self.search = search;
+                self.query.text = search;
+                self.tab = Tab::All;
                 self.search_temperature += 1;
 
                 Action::Run(Task::perform(
@@ -94,17 +313,59 @@ impl Search {
                 self.search_temperature = self.search_temperature.saturating_sub(1);
 
                 if self.search_temperature == 0 {
-                    self.is_searching = true;
-
-                    Action::Run(Task::perform(
-                        Model::search(self.search.clone()),
-                        Message::ModelsListed,
-                    ))
+                    self.search()
                 } else {
                     Action::None
                 }
             }
+            Message::SortSelected(sort) => {
+                self.query.sort = sort;
+
+                self.search()
+            }
+            Message::SizeSelected(size) => {
+                self.query.size = Some(size);
+
+                self.search()
+            }
+            Message::QuantizationSelected(quantization) => {
+                self.query.quantization = Some(quantization);
+
+                self.search()
+            }
+            Message::LicenseSelected(license) => {
+                self.query.license = Some(license);
+
+                self.search()
+            }
+            Message::ArchitectureSelected(architecture) => {
+                self.query.architecture = Some(architecture);
+
+                self.search()
+            }
+            Message::ClearFilters => {
+                self.query = Query {
+                    text: std::mem::take(&mut self.query.text),
+                    sort: self.query.sort,
+                    ..Query::default()
+                };
+
+                self.search()
+            }
             Message::RunModel(model) => Action::Boot(model),
+            Message::ToggleFamily(family) => {
+                if let Some(index) = self
+                    .expanded
+                    .iter()
+                    .position(|expanded| *expanded == family)
+                {
+                    let _ = self.expanded.remove(index);
+                } else {
+                    self.expanded.push(family);
+                }
+
+                Action::None
+            }
             Message::LinkPressed(link) => {
                 let _ = open::that_in_background(match link {
                     Link::Rust => "https://rust-lang.org",
@@ -120,57 +381,221 @@ impl Search {
 
                 Action::None
             }
+            Message::OpenSettings => Action::OpenSettings,
+            Message::AddFile => Action::Run(Task::perform(
+                rfd::AsyncFileDialog::new()
+                    .add_filter("GGUF", &["gguf"])
+                    .pick_file(),
+                |file| Message::FilePicked(file.map(|file| file.path().to_owned())),
+            )),
+            Message::FilePicked(Some(path)) => {
+                self.adding = Adding::Running;
+
+                Action::Run(Task::perform(File::add(path), Message::FileAdded))
+            }
+            Message::FilePicked(None) => Action::None,
+            Message::FileAdded(Ok(file)) => {
+                self.adding = Adding::Idle;
+
+                Action::BootFile(file)
+            }
+            Message::FileAdded(Err(error)) => {
+                self.adding = Adding::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::Downloads(message) => Action::Downloads(message),
         }
     }
 
-    pub fn view(&self) -> Element<Message> {
-        let search = text_input("Search language models...", &self.search)
-            .size(20)
-            .padding(10)
-            .on_input(Message::SearchChanged);
-
-        let models: Element<_> =
-            {
-                let search_terms: Vec<_> = self
-                    .search
-                    .trim()
-                    .split(' ')
-                    .map(str::to_lowercase)
-                    .collect();
-
-                let mut filtered_models = self
-                    .models
-                    .iter()
-                    .filter(|model| {
-                        self.search.is_empty()
-                            || search_terms
-                                .iter()
-                                .all(|term| model.name().to_lowercase().contains(term))
+    fn search(&mut self) -> Action {
+        self.is_searching = true;
+
+        Action::Run(Task::perform(
+            Model::search(self.query.clone()),
+            Message::ModelsListed,
+        ))
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        downloads: &'a Downloads,
+        locale: Locale,
+        appearance: Appearance,
+    ) -> Element<'a, Message> {
+        let search = row![
+            text_input("Search language models...", &self.query.text)
+                .size(20)
+                .padding(10)
+                .on_input(Message::SearchChanged),
+            tip(
+                button(
+                    text(if matches!(self.adding, Adding::Running) {
+                        "Adding..."
+                    } else {
+                        "Add file"
                     })
-                    .peekable();
+                    .size(20)
+                )
+                .padding(10)
+                .on_press_maybe(
+                    (!matches!(self.adding, Adding::Running)).then_some(Message::AddFile)
+                )
+                .style(button::secondary),
+                "Boot a GGUF file from your disk",
+                tip::Position::Bottom,
+            ),
+            button(text("Settings").size(20))
+                .padding(10)
+                .on_press(Message::OpenSettings)
+                .style(button::secondary),
+        ]
+        .spacing(10);
 
-                if filtered_models.peek().is_none() {
-                    center(text(if self.is_searching || self.search_temperature > 0 {
-                        "Searching..."
+        let tabs = {
+            let tab = |label, tab| {
+                button(text(label).size(14))
+                    .on_press(Message::TabSelected(tab))
+                    .padding([5, 10])
+                    .style(if self.tab == tab {
+                        button::primary
                     } else {
-                        "No models found!"
-                    }))
-                    .into()
+                        button::text
+                    })
+            };
+
+            row![
+                tab("Recommended", Tab::Recommended),
+                tab("All models", Tab::All)
+            ]
+            .spacing(5)
+        };
+
+        let filters = (self.tab == Tab::All).then(|| {
+            row![
+                pick_list(Sort::ALL, Some(self.query.sort), Message::SortSelected)
+                    .text_size(14)
+                    .padding([5, 10]),
+                horizontal_space(),
+                pick_list(assistant::Size::ALL, self.query.size, Message::SizeSelected)
+                    .placeholder("Any size")
+                    .text_size(14)
+                    .padding([5, 10]),
+                pick_list(
+                    File::QUANTIZATIONS,
+                    self.query.quantization,
+                    Message::QuantizationSelected
+                )
+                .placeholder("Any quantization")
+                .text_size(14)
+                .padding([5, 10]),
+                pick_list(
+                    self.licenses.as_slice(),
+                    self.query.license.as_ref(),
+                    Message::LicenseSelected
+                )
+                .placeholder("Any license")
+                .text_size(14)
+                .padding([5, 10]),
+                pick_list(
+                    self.architectures.as_slice(),
+                    self.query.architecture.as_ref(),
+                    Message::ArchitectureSelected
+                )
+                .placeholder("Any architecture")
+                .text_size(14)
+                .padding([5, 10]),
+                button(text("Clear").size(14))
+                    .padding([5, 10])
+                    .on_press_maybe(self.query.is_filtered().then_some(Message::ClearFilters))
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center)
+        });
+
+        let models: Element<_> = if self.tab == Tab::Recommended {
+            recommended(
+                &self.catalog,
+                self.memory,
+                &self.boot_options,
+                self.window_size,
+                locale,
+                appearance,
+            )
+        } else {
+            let search_terms: Vec<_> = self
+                .query
+                .text
+                .trim()
+                .split(' ')
+                .map(str::to_lowercase)
+                .collect();
+
+            let filtered_models = self.models.iter().filter(|model| {
+                self.query.text.is_empty()
+                    || search_terms
+                        .iter()
+                        .all(|term| model.name().to_lowercase().contains(term))
+            });
+
+            // Quantized uploads of the same base model are grouped together
+            let mut families: Vec<(String, Vec<&Model>)> = Vec::new();
+
+            for model in filtered_models {
+                let family = model.family();
+
+                match families
+                    .iter_mut()
+                    .find(|(candidate, _)| *candidate == family)
+                {
+                    Some((_, models)) => models.push(model),
+                    None => families.push((family, vec![model])),
+                }
+            }
+
+            if families.is_empty() {
+                let status = if let Some(rate_limit) = &self.rate_limit {
+                    if rate_limit.retrying_in == 0 {
+                        "Retrying...".to_owned()
+                    } else {
+                        format!(
+                            "Hugging Face rate-limited the search, retrying in {}s...",
+                            rate_limit.retrying_in
+                        )
+                    }
+                } else if self.is_searching || self.search_temperature > 0 {
+                    "Searching...".to_owned()
+                } else if let Some(error) = &self.error {
+                    format!("The search failed: {error}")
                 } else {
-                    use itertools::Itertools;
-                    const MIN_CARD_WIDTH: f32 = 450.0;
+                    "No models found!".to_owned()
+                };
+
+                center(text(status)).into()
+            } else {
+                use itertools::Itertools;
+                const MIN_CARD_WIDTH: f32 = 450.0;
 
-                    let n_columns = (self.window_size.width / MIN_CARD_WIDTH).max(1.0) as usize;
+                let n_columns = (self.window_size.width / MIN_CARD_WIDTH).max(1.0) as usize;
 
-                    let cards =
-                        column(filtered_models.chunks(n_columns).into_iter().map(|chunk| {
-                            row(chunk.into_iter().map(model_card)).spacing(10).into()
+                let cards = column(families.iter().chunks(n_columns).into_iter().map(|chunk| {
+                    row(chunk
+                        .into_iter()
+                        .map(|(family, models)| match models.as_slice() {
+                            [model] => model_card(model, locale),
+                            models => {
+                                family_card(family, models, self.expanded.contains(family), locale)
+                            }
                         }))
-                        .spacing(10);
+                    .spacing(10)
+                    .into()
+                }))
+                .spacing(10);
 
-                    scrollable(cards).height(Fill).spacing(10).into()
-                }
-            };
+                scrollable(cards).height(Fill).spacing(10).into()
+            }
+        };
 
         let footer = {
             let text = |content| text(content).font(Font::MONOSPACE).size(12);
@@ -214,58 +639,73 @@ impl Search {
             .align_y(Center)
         };
 
-        container(column![search, models, footer].spacing(10))
-            .padding(10)
-            .into()
-    }
+        let downloads =
+            (!downloads.is_empty()).then(|| downloads.view(locale).map(Message::Downloads));
 
-    pub fn subscription(&self) -> Subscription<Message> {
-        window::resize_events().map(|(_id, size)| Message::WindowResized(size))
-    }
-}
+        let adding_failed = match &self.adding {
+            Adding::Failed(error) => Some(
+                text!("The file could not be added: {error}")
+                    .size(14)
+                    .style(text::danger),
+            ),
+            Adding::Idle | Adding::Running => None,
+        };
 
-fn model_card(model: &Model) -> Element<Message> {
-    use iced::widget::Text;
+        let content = container(
+            column![search, tabs]
+                .push_maybe(filters)
+                .push_maybe(adding_failed)
+                .push(models)
+                .push_maybe(downloads)
+                .push(footer)
+                .spacing(10),
+        )
+        .padding(10);
 
-    fn stat<'a>(
-        icon: Text<'a>,
-        value: Text<'a>,
-        style: fn(&Theme) -> text::Style,
-    ) -> Element<'a, Message> {
-        row![
-            icon.size(10).style(style),
-            value.size(12).font(Font::MONOSPACE).style(style)
-        ]
-        .align_y(Center)
-        .spacing(5)
-        .into()
+        match &self.editing {
+            Some(options) => stack![content, opaque(center(boot_options(options)))].into(),
+            None => content.into(),
+        }
     }
 
-    let title = {
-        const LIMIT: usize = 40;
-
-        let name = model.name();
-
-        if name.len() < LIMIT {
-            text(name)
+    pub fn subscription(&self) -> Subscription<Message> {
+        let retry = if self.rate_limit.is_some() {
+            time::every(Duration::from_secs(1)).map(|_| Message::RetryTicked)
         } else {
-            text!("{}...", &name[0..LIMIT])
-        }
-        .font(Font::MONOSPACE)
-    };
+            Subscription::none()
+        };
+
+        Subscription::batch([
+            window::resize_events().map(|(_id, size)| Message::WindowResized(size)),
+            retry,
+        ])
+    }
+}
 
-    let separator = || text("•").size(12);
+fn model_card(model: &Model, locale: Locale) -> Element<Message> {
+    let title = row![title(model.name()), kind(model.kind)]
+        .push_maybe(model.parameters.map(|parameters| size(parameters, locale)))
+        .spacing(10)
+        .align_y(Center);
 
     let metadata = row![
         stat(icon::user(), text(model.author()), text::default),
         separator(),
-        stat(icon::download(), value(model.downloads), text::primary),
+        stat(
+            icon::download(),
+            text(locale.compact(model.downloads.count())),
+            text::primary
+        ),
         separator(),
-        stat(icon::heart(), value(model.likes), text::danger),
+        stat(
+            icon::heart(),
+            text(locale.number(model.likes.count())),
+            text::danger
+        ),
         separator(),
         stat(
             icon::clock(),
-            value(model.last_modified.format("%-e %B, %y")),
+            text(locale.date(model.last_modified)),
             text::default,
         ),
     ]
@@ -286,3 +726,374 @@ fn model_card(model: &Model) -> Element<Message> {
 
     hover(card, chat)
 }
+
+/// The models of the catalog, grouped by use case, with the file that
+/// best fits the given memory of each.
+fn recommended<'a>(
+    catalog: &'a Catalog,
+    memory: Option<Memory>,
+    boot_options: &[BootOptions],
+    window_size: Size,
+    locale: Locale,
+    appearance: Appearance,
+) -> Element<'a, Message> {
+    use itertools::Itertools;
+    const MIN_CARD_WIDTH: f32 = 450.0;
+
+    let n_columns = (window_size.width / MIN_CARD_WIDTH).max(1.0) as usize;
+
+    let use_cases = column(UseCase::ALL.iter().map(|use_case| {
+        let cards = column(
+            catalog
+                .entries(*use_case)
+                .chunks(n_columns)
+                .into_iter()
+                .map(|chunk| {
+                    row(chunk.map(|entry| {
+                        recommendation(entry, memory, boot_options, locale, appearance)
+                    }))
+                    .spacing(10)
+                    .into()
+                }),
+        )
+        .spacing(10);
+
+        column![text(use_case.to_string()).size(18), cards]
+            .spacing(10)
+            .into()
+    }))
+    .spacing(20);
+
+    scrollable(use_cases).height(Fill).spacing(10).into()
+}
+
+fn recommendation<'a>(
+    entry: &'a catalog::Entry,
+    memory: Option<Memory>,
+    boot_options: &[BootOptions],
+    locale: Locale,
+    appearance: Appearance,
+) -> Element<'a, Message> {
+    let title = row![
+        text(&entry.name).font(Font::MONOSPACE),
+        horizontal_space(),
+        tip(
+            container(text!("{} RAM", entry.tier).size(10).font(Font::MONOSPACE))
+                .padding([1, 5])
+                .style(container::bordered_box),
+            "The memory this model is meant for",
+            tip::Position::Left,
+        ),
+    ]
+    .spacing(10)
+    .align_y(Center);
+
+    // Without knowing the memory, the smallest file is the safest bet
+    let suggestion = match memory {
+        Some(memory) => entry.suggest(memory),
+        None => entry.files.first().map(|choice| entry.file(choice)),
+    };
+
+    let action: Element<_> = match suggestion {
+        Some(file) if file.is_downloaded() => {
+            let is_customized = boot_options.iter().any(|options| options.file == file);
+
+            row![
+                tip(
+                    button(text("Options").size(14))
+                        .on_press(Message::EditBootOptions(file.clone()))
+                        .style(if is_customized {
+                            button::primary
+                        } else {
+                            button::secondary
+                        }),
+                    "Boot options for this file",
+                    tip::Position::Top,
+                ),
+                button(row![icon::chat(), "Run"].spacing(10)).on_press(Message::RunFile(file)),
+            ]
+            .spacing(10)
+            .into()
+        }
+        Some(file) => {
+            let label = match (file.quantization(), entry.size(&file)) {
+                (Some(quantization), Some(size)) => {
+                    format!("{quantization} · {}", locale.bytes(size))
+                }
+                _ => file.name.clone(),
+            };
+
+            row![
+                text(label)
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .style(text::secondary),
+                button(row![icon::download(), "Download"].spacing(10))
+                    .on_press(Message::Download(file)),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into()
+        }
+        None => text("Needs more memory")
+            .size(12)
+            .style(text::danger)
+            .into(),
+    };
+
+    container(
+        column![
+            title,
+            text(&entry.description)
+                .size(appearance.text_size.offset(-2))
+                .font(appearance.chat_font.font())
+                .style(text::secondary),
+            container(action).width(Fill).align_x(Right),
+        ]
+        .spacing(10),
+    )
+    .width(Fill)
+    .padding(10)
+    .style(container::rounded_box)
+    .into()
+}
+
+/// A popover to override how the given file boots.
+fn boot_options(options: &BootOptions) -> Element<'_, Message> {
+    let field = |label, input: Element<'static, Message>| {
+        row![text(label).size(14).width(Fill), input]
+            .spacing(10)
+            .align_y(Center)
+    };
+
+    let backend = pick_list(Backend::ALL, options.backend, Message::BootBackendSelected)
+        .placeholder("Detected")
+        .text_size(14)
+        .width(200);
+
+    let gpu_layers = text_input(
+        "Default",
+        &options
+            .gpu_layers
+            .map(|gpu_layers| gpu_layers.to_string())
+            .unwrap_or_default(),
+    )
+    .on_input(Message::BootGpuLayersChanged)
+    .font(Font::MONOSPACE)
+    .size(14)
+    .padding(5)
+    .width(200);
+
+    let extra_flags = text_input("--flash-attn --no-mmap", &options.extra_flags)
+        .on_input(Message::BootFlagsChanged)
+        .font(Font::MONOSPACE)
+        .size(14)
+        .padding(5)
+        .width(200);
+
+    let invalid_flags = assistant::parse_flags(&options.extra_flags).err();
+
+    container(
+        column![
+            text("Boot options").size(18),
+            text(&options.file.name)
+                .size(12)
+                .font(Font::MONOSPACE)
+                .style(text::secondary),
+            field("Backend", backend.into()),
+            field("Offloaded layers", gpu_layers.into()),
+            field("Extra llama-server flags", extra_flags.into()),
+        ]
+        .push_maybe(
+            invalid_flags
+                .as_ref()
+                .map(|error| text(error.to_string()).size(12).style(text::danger)),
+        )
+        .push(
+            row![
+                button(text("Reset").size(14))
+                    .on_press_maybe((!options.is_empty()).then_some(Message::ResetBootOptions))
+                    .style(button::secondary),
+                horizontal_space(),
+                button(text("Cancel").size(14))
+                    .on_press(Message::CancelBootOptions)
+                    .style(button::secondary),
+                button(text("Save").size(14))
+                    .on_press_maybe(invalid_flags.is_none().then_some(Message::SaveBootOptions)),
+            ]
+            .spacing(10),
+        )
+        .spacing(10),
+    )
+    .width(450)
+    .padding(15)
+    .style(container::bordered_box)
+    .into()
+}
+
+/// A card for the uploads of the same base model, which can be expanded to
+/// compare them.
+fn family_card<'a>(
+    family: &str,
+    models: &[&'a Model],
+    expanded: bool,
+    locale: Locale,
+) -> Element<'a, Message> {
+    let Some(popular) = models.iter().max_by_key(|model| model.downloads) else {
+        return column![].into();
+    };
+
+    let latest = models
+        .iter()
+        .map(|model| model.last_modified)
+        .max()
+        .unwrap_or(popular.last_modified);
+
+    let header = row![title(popular.name()), kind(popular.kind)]
+        .push_maybe(
+            popular
+                .parameters
+                .map(|parameters| size(parameters, locale)),
+        )
+        .push(horizontal_space())
+        .push(
+            button(text(if expanded { "Hide" } else { "Compare" }).size(12))
+                .on_press(Message::ToggleFamily(family.to_owned()))
+                .padding([2, 7])
+                .style(button::secondary),
+        )
+        .spacing(10)
+        .align_y(Center);
+
+    let metadata = row![
+        stat(
+            icon::user(),
+            text!("{} uploads", models.len()),
+            text::default
+        ),
+        separator(),
+        stat(
+            icon::download(),
+            text(locale.compact(popular.downloads.count())),
+            text::primary
+        ),
+        separator(),
+        stat(icon::clock(), text(locale.date(latest)), text::default),
+    ]
+    .spacing(10);
+
+    let uploads = expanded.then(|| {
+        column(models.iter().map(|model| {
+            let quantizations = model.quantizations();
+
+            let quantizations = match quantizations.as_slice() {
+                [] if model.files.is_empty() && model.convertible => "safetensors".to_owned(),
+                [] => format!("{} files", model.files.len()),
+                [quantization] => (*quantization).to_owned(),
+                [smallest, .., largest] => format!("{smallest} to {largest}"),
+            };
+
+            row![
+                column![
+                    text(model.author()).size(14).font(Font::MONOSPACE),
+                    text(quantizations).size(10).style(text::secondary),
+                ]
+                .spacing(2)
+                .width(Fill),
+                stat(
+                    icon::download(),
+                    text(locale.compact(model.downloads.count())),
+                    text::primary
+                ),
+                stat(
+                    icon::clock(),
+                    text(locale.short_date(model.last_modified)),
+                    text::default,
+                ),
+                button(text("Run").size(12))
+                    .on_press(Message::RunModel((*model).clone()))
+                    .padding([2, 7]),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into()
+        }))
+        .spacing(5)
+    });
+
+    container(column![header, metadata].push_maybe(uploads).spacing(10))
+        .width(Fill)
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+}
+
+fn title(name: &str) -> widget::Text<'_> {
+    const LIMIT: usize = 40;
+
+    if name.len() < LIMIT {
+        text(name)
+    } else {
+        text!("{}...", &name[0..LIMIT])
+    }
+    .font(Font::MONOSPACE)
+}
+
+fn stat<'a>(
+    symbol: widget::Text<'a>,
+    value: widget::Text<'a>,
+    style: fn(&Theme) -> text::Style,
+) -> Element<'a, Message> {
+    row![
+        symbol.size(icon::scaled(10.0)).style(style),
+        value.size(12).font(Font::MONOSPACE).style(style)
+    ]
+    .align_y(Center)
+    .spacing(5)
+    .into()
+}
+
+/// A badge with the [`Kind`] of a model.
+pub fn kind<'a, Message: 'a>(kind: Kind) -> Element<'a, Message> {
+    container(
+        text(kind.to_string())
+            .size(10)
+            .font(Font::MONOSPACE)
+            .style(match kind {
+                Kind::Instruct => text::secondary,
+                Kind::Base => text::danger,
+            }),
+    )
+    .padding([1, 5])
+    .style(container::bordered_box)
+    .into()
+}
+
+/// A badge with the amount of parameters of a model, like `8B`.
+fn size<'a>(parameters: u64, locale: Locale) -> Element<'a, Message> {
+    let size = if parameters >= 1_000_000_000 {
+        format!("{}B", locale.decimal(parameters as f64 / 1e9, 1))
+    } else {
+        format!("{}M", parameters / 1_000_000)
+    };
+
+    container(text(size).size(10).font(Font::MONOSPACE))
+        .padding([1, 5])
+        .style(container::bordered_box)
+        .into()
+}
+
+/// Adds the given value to the sorted list, unless it is already there.
+fn remember(values: &mut Vec<String>, value: Option<&String>) {
+    let Some(value) = value else {
+        return;
+    };
+
+    if let Err(index) = values.binary_search(value) {
+        values.insert(index, value.clone());
+    }
+}
+
+fn separator<'a>() -> widget::Text<'a> {
+    text("•").size(12)
+}
@@ -0,0 +1,2962 @@
+use crate::data;
+use crate::data::appearance::{ChatFont, Icons, Scale, TextSize};
+use crate::data::assistant::{self, Assistant, Backend};
+use crate::data::audit;
+use crate::data::backup;
+use crate::data::chat::{self, Chat};
+use crate::data::diagnostics;
+use crate::data::executor::{self, Availability};
+use crate::data::keymap::{self, Keymap};
+use crate::data::knowledge;
+use crate::data::locale::Locale;
+use crate::data::mcp;
+use crate::data::profile::Profile;
+use crate::data::template::Variable;
+use crate::data::theme::{self, Theme};
+use crate::data::usage;
+use crate::data::web;
+use crate::tray;
+use crate::widget::icon;
+
+use iced::system;
+use iced::task;
+use iced::widget::{
+    button, center_x, checkbox, column, container, horizontal_space, pick_list, progress_bar, row,
+    scrollable, text, text_editor, text_input,
+};
+use iced::{Center, Element, Fill, Font, Task};
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub struct Settings {
+    settings: data::Settings,
+    system_prompt: text_editor::Content,
+    relink: Relink,
+    /// The assistant of the open chat, if any, used for maintenance.
+    assistant: Option<Assistant>,
+    /// The backend detected for this system.
+    backend: Backend,
+    /// The saved chats without a title.
+    untitled: Vec<chat::Id>,
+    maintenance: Maintenance,
+    indexing: Indexing,
+    import: Import,
+    backup: Backup,
+    remote: Connection,
+    /// The executors found on the system.
+    executors: Availability,
+    probe: Probe,
+    installing: Installing,
+    report: Report,
+    bundle: Bundle,
+    /// The error of the last theme import, if it failed.
+    theme_error: Option<data::Error>,
+    /// The access token being typed, until it is stored in the keyring.
+    hugging_face_token: String,
+    token_error: Option<data::Error>,
+    requests: Option<Vec<audit::Record>>,
+    profiles: Vec<Profile>,
+    library: Vec<assistant::File>,
+    request_filter: RequestFilter,
+    shortcuts: Vec<String>,
+    usage: usage::Stats,
+    /// The connection to each MCP server of the settings, in order.
+    links: Vec<Link>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestFilter {
+    All,
+    Purpose(audit::Purpose),
+}
+
+impl RequestFilter {
+    fn matches(self, record: &audit::Record) -> bool {
+        match self {
+            RequestFilter::All => true,
+            RequestFilter::Purpose(purpose) => record.purpose == purpose,
+        }
+    }
+}
+
+impl std::fmt::Display for RequestFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestFilter::All => f.write_str("All requests"),
+            RequestFilter::Purpose(purpose) => purpose.fmt(f),
+        }
+    }
+}
+
+enum Backup {
+    Idle,
+    Running,
+    Created,
+    Restored,
+    Failed(data::Error),
+}
+
+enum Import {
+    Idle,
+    Running,
+    Done(usize),
+    Failed(data::Error),
+}
+
+enum Relink {
+    Idle,
+    Running,
+    Done(usize),
+    Failed(data::Error),
+}
+
+/// Titling the saved chats that have none, one at a time.
+enum Maintenance {
+    Idle,
+    Running {
+        queue: Vec<chat::Id>,
+        total: usize,
+        titled: usize,
+        _task: task::Handle,
+    },
+    Done(usize),
+}
+
+enum Connection {
+    Idle,
+    Checking,
+    Connected(String),
+    Failed(data::Error),
+}
+
+/// Connecting to an MCP server.
+enum Link {
+    Idle,
+    Connecting,
+    Connected(Vec<mcp::Tool>),
+    Failed(data::Error),
+}
+
+/// Testing the selected executor.
+enum Probe {
+    Idle,
+    Testing,
+    Passed(String),
+    Failed(data::Error),
+}
+
+/// Installing the managed build of llama.cpp.
+enum Installing {
+    Idle,
+    Running,
+    Failed(data::Error),
+}
+
+enum Report {
+    Idle,
+    Gathering,
+    Copied,
+}
+
+/// Exporting a diagnostics bundle.
+enum Bundle {
+    Idle,
+    Exporting,
+    Exported,
+    Failed(data::Error),
+}
+
+enum Indexing {
+    Idle,
+    Running,
+    Done(knowledge::Summary),
+    Failed(data::Error),
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SystemPromptEdited(text_editor::Action),
+    ResetSystemPrompt,
+    RefusedTopicsChanged(String),
+    ToneChanged(String),
+    SuggestTitlesToggled(bool),
+    AddVariable,
+    VariableNameChanged(usize, String),
+    VariableValueChanged(usize, String),
+    RemoveVariable(usize),
+    AddFact,
+    FactChanged(usize, String),
+    RemoveFact(usize),
+    GpuChanged(String),
+    GpuLayersChanged(String),
+    ContextSizeChanged(String),
+    ThreadsChanged(String),
+    BatchSizeChanged(String),
+    ExtraFlagsChanged(String),
+    ExecutorSelected(executor::Kind),
+    DockerTagChanged(String),
+    ExecutorsDetected(Availability),
+    TestExecutor,
+    ExecutorTested(Result<String, data::Error>),
+    InstallManaged,
+    ManagedInstalled(Result<PathBuf, data::Error>),
+    ToolCallingToggled(bool),
+    SearchProviderSelected(web::Provider),
+    SearchGuardToggled(bool),
+    AddMcpServer,
+    McpNameChanged(usize, String),
+    McpTransportSelected(usize, mcp::Transport),
+    McpTargetChanged(usize, String),
+    RemoveMcpServer(usize),
+    ConnectMcpServer(usize),
+    McpServerConnected(usize, mcp::Server, Result<Vec<mcp::Tool>, data::Error>),
+    LocaleSelected(Locale),
+    IconsSelected(Icons),
+    IconScaleSelected(Scale),
+    ScaleSelected(Scale),
+    ChatFontSelected(ChatFont),
+    TextSizeSelected(TextSize),
+    ThemeSelected(Theme),
+    LightThemeSelected(Theme),
+    DarkThemeSelected(Theme),
+    NewTheme,
+    ThemeRenamed(String),
+    ThemeColorChanged(theme::Role, String),
+    DeleteTheme,
+    ImportTheme,
+    ImportThemeFrom(Option<PathBuf>),
+    ThemeImported(Result<theme::Custom, data::Error>),
+    SearxngUrlChanged(String),
+    BraveApiKeyChanged(String),
+    TavilyApiKeyChanged(String),
+    HuggingFaceTokenChanged(String),
+    StoreHuggingFaceToken,
+    RemoveHuggingFaceToken,
+    HuggingFaceTokenStored(Result<bool, data::Error>),
+    AddFolder,
+    FolderPicked(Option<PathBuf>),
+    RemoveFolder(usize),
+    EmbeddingServerChanged(String),
+    BuildIndex,
+    WarmModelsChanged(String),
+    PreloadOnHoverToggled(bool),
+    SummarizeOnSwitchToggled(bool),
+    RunInBackgroundToggled(bool),
+    EditorChanged(String),
+    SummarizerSelected(assistant::File),
+    RemoveSummarizer,
+    LibraryListed(Result<Vec<chat::Entry>, data::Error>),
+    RemoteToggled(bool),
+    RemoteHostChanged(String),
+    RemotePortChanged(String),
+    RemoteTunnelToggled(bool),
+    CheckRemote,
+    RemoteChecked(Result<String, data::Error>),
+    OpenEmbeddings,
+    IndexBuilt(Result<knowledge::Summary, data::Error>),
+    IndexFetched(Result<knowledge::Index, data::Error>),
+    ProfilesListed(Result<Vec<Profile>, data::Error>),
+    ResetProfile(Profile),
+    ProfileReset(Result<(), data::Error>),
+    ShortcutChanged(keymap::Command, String),
+    RecordUsageToggled(bool),
+    UsageFetched(Result<usage::Stats, data::Error>),
+    ResetUsage,
+    UsageReset(Result<(), data::Error>),
+    ResetShortcuts,
+    Relink,
+    Relinked(Result<usize, data::Error>),
+    TitleChats,
+    ChatTitled(chat::Id, Result<Option<String>, data::Error>),
+    CancelMaintenance,
+    Import,
+    ImportFrom(Option<PathBuf>),
+    Imported(Result<usize, data::Error>),
+    Backup,
+    BackupTo(Option<PathBuf>),
+    BackedUp(Result<(), data::Error>),
+    Restore,
+    RestoreFrom(Option<PathBuf>),
+    Restored(Result<Box<data::Settings>, data::Error>),
+    ShowRequests,
+    RequestsFetched(Result<Vec<audit::Record>, data::Error>),
+    RequestFilterChanged(RequestFilter),
+    ClearRequests,
+    RequestsCleared(Result<(), data::Error>),
+    CopyReport,
+    ReportGathered(String),
+    ExportDiagnostics,
+    ExportDiagnosticsTo(Option<PathBuf>),
+    DiagnosticsExported(Result<(), data::Error>),
+    Close,
+}
+
+pub enum Action {
+    None,
+    Change(Box<data::Settings>),
+    Run(Task<Message>),
+    OpenEmbeddings,
+    Close,
+}
+
+impl Settings {
+    pub fn new(
+        settings: data::Settings,
+        assistant: Option<Assistant>,
+        backend: Backend,
+    ) -> (Self, Task<Message>) {
+        // Live connections are reused, so this only connects new servers
+        let connections = Task::batch(
+            settings
+                .mcp
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(index, server)| connect(index, server)),
+        );
+
+        let settings = Self {
+            links: settings.mcp.iter().map(|_| Link::Connecting).collect(),
+            system_prompt: text_editor::Content::with_text(&settings.system_prompt),
+            shortcuts: shortcuts(&settings.keymap),
+            settings,
+            relink: Relink::Idle,
+            assistant,
+            backend,
+            untitled: Vec::new(),
+            maintenance: Maintenance::Idle,
+            indexing: Indexing::Idle,
+            import: Import::Idle,
+            backup: Backup::Idle,
+            remote: Connection::Idle,
+            executors: Availability::default(),
+            probe: Probe::Idle,
+            installing: Installing::Idle,
+            report: Report::Idle,
+            bundle: Bundle::Idle,
+            theme_error: None,
+            hugging_face_token: String::new(),
+            token_error: None,
+            requests: None,
+            profiles: Vec::new(),
+            library: Vec::new(),
+            request_filter: RequestFilter::All,
+            usage: usage::Stats::default(),
+        };
+
+        (
+            settings,
+            Task::batch([
+                Task::perform(Profile::list(), Message::ProfilesListed),
+                Task::perform(Chat::list(), Message::LibraryListed),
+                Task::perform(usage::Stats::fetch(), Message::UsageFetched),
+                Task::perform(knowledge::Index::fetch(), Message::IndexFetched),
+                Task::perform(Availability::detect(backend), Message::ExecutorsDetected),
+                connections,
+            ]),
+        )
+    }
+
+    pub fn title(&self) -> String {
+        "Settings - Icebreaker".to_owned()
+    }
+
+    pub fn update(&mut self, message: Message) -> Action {
+        match message {
+            Message::SystemPromptEdited(action) => {
+                let is_edit = action.is_edit();
+
+                self.system_prompt.perform(action);
+
+                if is_edit {
+                    self.settings.system_prompt = self.system_prompt.text().trim().to_owned();
+
+                    Action::Change(Box::new(self.settings.clone()))
+                } else {
+                    Action::None
+                }
+            }
+            Message::ResetSystemPrompt => {
+                self.settings.system_prompt = data::Settings::DEFAULT_SYSTEM_PROMPT.to_owned();
+                self.system_prompt = text_editor::Content::with_text(&self.settings.system_prompt);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::RefusedTopicsChanged(topics) => {
+                self.settings.guardrails.refused_topics = topics;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ToneChanged(tone) => {
+                self.settings.guardrails.tone = tone;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::SuggestTitlesToggled(suggest_titles) => {
+                self.settings.suggest_titles = suggest_titles;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::AddVariable => {
+                self.settings.variables.push(Variable::default());
+
+                Action::None
+            }
+            Message::VariableNameChanged(index, name) => {
+                let Some(variable) = self.settings.variables.get_mut(index) else {
+                    return Action::None;
+                };
+
+                variable.name = name
+                    .chars()
+                    .filter(|c| c.is_alphanumeric() || *c == '_')
+                    .collect();
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::VariableValueChanged(index, value) => {
+                let Some(variable) = self.settings.variables.get_mut(index) else {
+                    return Action::None;
+                };
+
+                variable.value = value;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::RemoveVariable(index) => {
+                if index < self.settings.variables.len() {
+                    let _ = self.settings.variables.remove(index);
+                }
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::AddFact => {
+                self.settings.memory.push(String::new());
+
+                Action::None
+            }
+            Message::FactChanged(index, fact) => {
+                let Some(known) = self.settings.memory.get_mut(index) else {
+                    return Action::None;
+                };
+
+                *known = fact;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::RemoveFact(index) => {
+                if index < self.settings.memory.len() {
+                    let _ = self.settings.memory.remove(index);
+                }
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::GpuChanged(gpu) => {
+                let Some(gpu) = parse_optional(&gpu) else {
+                    return Action::None;
+                };
+
+                self.settings.inference.gpu = gpu;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::GpuLayersChanged(gpu_layers) => {
+                let Some(gpu_layers) = parse_optional(&gpu_layers) else {
+                    return Action::None;
+                };
+
+                self.settings.inference.gpu_layers = gpu_layers;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ContextSizeChanged(context_size) => {
+                let Some(context_size) = parse_optional(&context_size) else {
+                    return Action::None;
+                };
+
+                self.settings.inference.context_size = context_size;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ThreadsChanged(threads) => {
+                let Some(threads) = parse_optional(&threads) else {
+                    return Action::None;
+                };
+
+                self.settings.inference.threads = threads;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ExtraFlagsChanged(extra_flags) => {
+                self.settings.extra_flags = extra_flags;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ExecutorSelected(kind) => {
+                self.settings.executor.kind = kind;
+                self.probe = Probe::Idle;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::DockerTagChanged(docker_tag) => {
+                self.settings.executor.docker_tag = docker_tag;
+                self.probe = Probe::Idle;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ExecutorsDetected(executors) => {
+                self.executors = executors;
+
+                Action::None
+            }
+            Message::TestExecutor => {
+                self.probe = Probe::Testing;
+
+                Action::Run(Task::perform(
+                    self.settings.executor.clone().test(self.backend),
+                    Message::ExecutorTested,
+                ))
+            }
+            Message::ExecutorTested(Ok(description)) => {
+                self.probe = Probe::Passed(description);
+
+                Action::None
+            }
+            Message::ExecutorTested(Err(error)) => {
+                self.probe = Probe::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::InstallManaged => {
+                self.installing = Installing::Running;
+
+                Action::Run(Task::perform(
+                    executor::install(self.backend),
+                    Message::ManagedInstalled,
+                ))
+            }
+            Message::ManagedInstalled(Ok(_server)) => {
+                self.installing = Installing::Idle;
+
+                Action::Run(Task::perform(
+                    Availability::detect(self.backend),
+                    Message::ExecutorsDetected,
+                ))
+            }
+            Message::ManagedInstalled(Err(error)) => {
+                self.installing = Installing::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::WarmModelsChanged(warm_models) => {
+                let Some(warm_models) = parse_optional(&warm_models) else {
+                    return Action::None;
+                };
+
+                self.settings.warm_models = warm_models.unwrap_or_default();
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::PreloadOnHoverToggled(preload_on_hover) => {
+                self.settings.preload_on_hover = preload_on_hover;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::SummarizeOnSwitchToggled(summarize_on_switch) => {
+                self.settings.summarize_on_switch = summarize_on_switch;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::RunInBackgroundToggled(run_in_background) => {
+                self.settings.run_in_background = run_in_background;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::SummarizerSelected(summarizer) => {
+                self.settings.summarizer = Some(summarizer);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::RemoveSummarizer => {
+                self.settings.summarizer = None;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::LibraryListed(Ok(chats)) => {
+                self.library.clear();
+
+                self.untitled = chats
+                    .iter()
+                    .filter(|entry| entry.title.is_none())
+                    .map(|entry| entry.id)
+                    .collect();
+
+                for entry in chats {
+                    if !self.library.contains(&entry.file) && entry.file.is_downloaded() {
+                        self.library.push(entry.file);
+                    }
+                }
+
+                Action::None
+            }
+            Message::LibraryListed(Err(error)) => {
+                let _ = dbg!(error);
+
+                Action::None
+            }
+            Message::RemoteToggled(enabled) => {
+                self.settings.remote.enabled = enabled;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::RemoteHostChanged(host) => {
+                self.settings.remote.host = host;
+                self.remote = Connection::Idle;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::RemotePortChanged(port) => {
+                let Ok(port) = port.trim().parse() else {
+                    return Action::None;
+                };
+
+                self.settings.remote.port = port;
+                self.remote = Connection::Idle;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::RemoteTunnelToggled(tunnel) => {
+                self.settings.remote.tunnel = tunnel;
+                self.remote = Connection::Idle;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::CheckRemote => {
+                self.remote = Connection::Checking;
+
+                Action::Run(Task::perform(
+                    self.settings.remote.clone().check(),
+                    Message::RemoteChecked,
+                ))
+            }
+            Message::RemoteChecked(Ok(model)) => {
+                self.remote = Connection::Connected(model);
+
+                Action::None
+            }
+            Message::RemoteChecked(Err(error)) => {
+                self.remote = Connection::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::ToolCallingToggled(tool_calling) => {
+                self.settings.inference.tool_calling = tool_calling.then_some(true);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::SearchProviderSelected(provider) => {
+                self.settings.search.provider = provider;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::SearchGuardToggled(guard) => {
+                self.settings.search.guard = guard;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::AddMcpServer => {
+                self.settings.mcp.push(mcp::Server::default());
+                self.links.push(Link::Idle);
+
+                Action::None
+            }
+            Message::McpNameChanged(index, name) => {
+                self.edit_mcp_server(index, |server| server.name = name)
+            }
+            Message::McpTransportSelected(index, transport) => {
+                self.edit_mcp_server(index, |server| server.transport = transport)
+            }
+            Message::McpTargetChanged(index, target) => {
+                self.edit_mcp_server(index, |server| server.target = target)
+            }
+            Message::RemoveMcpServer(index) => {
+                if index < self.settings.mcp.len() {
+                    let _ = self.settings.mcp.remove(index);
+                    let _ = self.links.remove(index);
+                }
+
+                mcp::retain(&self.settings.mcp);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ConnectMcpServer(index) => {
+                let Some(server) = self.settings.mcp.get(index).cloned() else {
+                    return Action::None;
+                };
+
+                // Connecting again starts over, in case the server died
+                mcp::disconnect(&server);
+                self.links[index] = Link::Connecting;
+
+                Action::Run(connect(index, server))
+            }
+            Message::McpServerConnected(index, server, result) => {
+                // The server may have been edited or removed in the meantime
+                if self.settings.mcp.get(index) != Some(&server) {
+                    return Action::None;
+                }
+
+                self.links[index] = match result {
+                    Ok(tools) => Link::Connected(tools),
+                    Err(error) => Link::Failed(dbg!(error)),
+                };
+
+                Action::None
+            }
+            Message::EditorChanged(editor) => {
+                self.settings.editor = editor;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::IconsSelected(icons) => {
+                self.settings.appearance.icons = icons;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::IconScaleSelected(scale) => {
+                self.settings.appearance.icon_scale = scale;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ScaleSelected(scale) => {
+                self.settings.appearance.scale = scale;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ChatFontSelected(font) => {
+                self.settings.appearance.chat_font = font;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::TextSizeSelected(size) => {
+                self.settings.appearance.text_size = size;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ThemeSelected(theme) => {
+                self.settings.theme = theme;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::LightThemeSelected(theme) => {
+                self.settings.light_theme = theme;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::DarkThemeSelected(theme) => {
+                self.settings.dark_theme = theme;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::NewTheme => {
+                let current = self.settings.theme.resolve(&self.settings.themes);
+                let name = self.theme_name("Custom");
+
+                self.settings
+                    .themes
+                    .push(theme::Custom::new(name.clone(), &current));
+                self.settings.theme = Theme::Custom(name);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ThemeRenamed(name) => {
+                let Some(custom) = self.custom_theme_mut() else {
+                    return Action::None;
+                };
+
+                custom.name = name.clone();
+                self.settings.theme = Theme::Custom(name);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ThemeColorChanged(role, color) => {
+                let Some(custom) = self.custom_theme_mut() else {
+                    return Action::None;
+                };
+
+                *custom.color_mut(role) = color;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::DeleteTheme => {
+                let Theme::Custom(name) = &self.settings.theme else {
+                    return Action::None;
+                };
+
+                let name = name.clone();
+
+                self.settings.themes.retain(|custom| custom.name != name);
+                self.settings.theme = Theme::default();
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ImportTheme => Action::Run(Task::perform(
+                rfd::AsyncFileDialog::new()
+                    .add_filter("TOML", &["toml"])
+                    .pick_file(),
+                |file| Message::ImportThemeFrom(file.map(|file| file.path().to_owned())),
+            )),
+            Message::ImportThemeFrom(Some(path)) => Action::Run(Task::perform(
+                theme::Custom::import(path),
+                Message::ThemeImported,
+            )),
+            Message::ImportThemeFrom(None) => Action::None,
+            Message::ThemeImported(Ok(mut custom)) => {
+                self.theme_error = None;
+
+                // Imported themes never replace the ones already saved
+                if self
+                    .settings
+                    .themes
+                    .iter()
+                    .any(|existing| existing.name == custom.name)
+                {
+                    custom.name = self.theme_name(&custom.name);
+                }
+
+                self.settings.theme = Theme::Custom(custom.name.clone());
+                self.settings.themes.push(custom);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ThemeImported(Err(error)) => {
+                self.theme_error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::LocaleSelected(locale) => {
+                self.settings.locale = locale;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::SearxngUrlChanged(url) => {
+                self.settings.search.searxng_url = url;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::BraveApiKeyChanged(api_key) => {
+                self.settings.search.brave_api_key = api_key;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::TavilyApiKeyChanged(api_key) => {
+                self.settings.search.tavily_api_key = api_key;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::HuggingFaceTokenChanged(token) => {
+                self.hugging_face_token = token;
+
+                Action::None
+            }
+            Message::StoreHuggingFaceToken => {
+                if self.hugging_face_token.trim().is_empty() {
+                    return Action::None;
+                }
+
+                Action::Run(Task::perform(
+                    assistant::store_token(std::mem::take(&mut self.hugging_face_token)),
+                    Message::HuggingFaceTokenStored,
+                ))
+            }
+            Message::RemoveHuggingFaceToken => Action::Run(Task::perform(
+                assistant::store_token(String::new()),
+                Message::HuggingFaceTokenStored,
+            )),
+            Message::HuggingFaceTokenStored(Ok(is_set)) => {
+                self.token_error = None;
+                self.settings.hugging_face_token_set = is_set;
+                self.settings.legacy_hugging_face_token = None;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::HuggingFaceTokenStored(Err(error)) => {
+                self.token_error = Some(dbg!(error));
+
+                Action::None
+            }
+            Message::AddFolder => Action::Run(Task::perform(
+                rfd::AsyncFileDialog::new().pick_folder(),
+                |folder| Message::FolderPicked(folder.map(|folder| folder.path().to_owned())),
+            )),
+            Message::FolderPicked(Some(folder)) => {
+                if self.settings.knowledge.folders.contains(&folder) {
+                    return Action::None;
+                }
+
+                self.settings.knowledge.folders.push(folder);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::FolderPicked(None) => Action::None,
+            Message::RemoveFolder(index) => {
+                if index < self.settings.knowledge.folders.len() {
+                    let _ = self.settings.knowledge.folders.remove(index);
+                }
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::EmbeddingServerChanged(server) => {
+                self.settings.knowledge.embedding_server = server;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::BuildIndex => {
+                self.indexing = Indexing::Running;
+
+                Action::Run(Task::perform(
+                    knowledge::Index::build(self.settings.knowledge.clone()),
+                    Message::IndexBuilt,
+                ))
+            }
+            Message::IndexBuilt(Ok(summary)) => {
+                self.indexing = Indexing::Done(summary);
+
+                Action::None
+            }
+            Message::IndexBuilt(Err(error)) => {
+                self.indexing = Indexing::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::IndexFetched(Ok(index)) => {
+                if matches!(self.indexing, Indexing::Idle) {
+                    self.indexing = Indexing::Done(index.summary());
+                }
+
+                Action::None
+            }
+            Message::IndexFetched(Err(error)) => {
+                let _ = dbg!(error);
+
+                Action::None
+            }
+            Message::BatchSizeChanged(batch_size) => {
+                let Some(batch_size) = parse_optional(&batch_size) else {
+                    return Action::None;
+                };
+
+                self.settings.inference.batch_size = batch_size;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ShortcutChanged(command, shortcut) => {
+                let Some(index) = keymap::Command::ALL
+                    .iter()
+                    .position(|candidate| *candidate == command)
+                else {
+                    return Action::None;
+                };
+
+                let parsed = shortcut.parse();
+                self.shortcuts[index] = shortcut;
+
+                let Ok(shortcut) = parsed else {
+                    return Action::None;
+                };
+
+                self.settings.keymap.set(command, shortcut);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::RecordUsageToggled(record_usage) => {
+                self.settings.record_usage = record_usage;
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::UsageFetched(Ok(usage)) => {
+                self.usage = usage;
+
+                Action::None
+            }
+            Message::ResetUsage => {
+                Action::Run(Task::perform(usage::Stats::reset(), Message::UsageReset))
+            }
+            Message::UsageReset(Ok(())) => {
+                self.usage = usage::Stats::default();
+
+                Action::None
+            }
+            Message::UsageFetched(Err(error)) | Message::UsageReset(Err(error)) => {
+                let _ = dbg!(error);
+
+                Action::None
+            }
+            Message::ResetShortcuts => {
+                self.settings.keymap = Keymap::default();
+                self.shortcuts = shortcuts(&self.settings.keymap);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ProfilesListed(Ok(profiles)) => {
+                self.profiles = profiles;
+
+                Action::None
+            }
+            Message::ProfilesListed(Err(error)) | Message::ProfileReset(Err(error)) => {
+                let _ = dbg!(error);
+
+                Action::None
+            }
+            Message::ResetProfile(profile) => {
+                self.profiles.retain(|candidate| candidate != &profile);
+
+                Action::Run(Task::perform(profile.reset(), Message::ProfileReset))
+            }
+            Message::ProfileReset(Ok(())) => Action::None,
+            Message::TitleChats => {
+                let mut queue = self.untitled.clone();
+                queue.reverse();
+
+                self.title_next(queue.len(), 0, queue)
+            }
+            Message::ChatTitled(id, result) => {
+                let Maintenance::Running {
+                    queue,
+                    total,
+                    titled,
+                    ..
+                } = &mut self.maintenance
+                else {
+                    return Action::None;
+                };
+
+                match result {
+                    Ok(Some(_title)) => {
+                        *titled += 1;
+                        self.untitled.retain(|untitled| *untitled != id);
+                    }
+                    Ok(None) => {}
+                    Err(error) => {
+                        let _ = dbg!(error);
+                    }
+                }
+
+                let (total, titled, queue) = (*total, *titled, std::mem::take(queue));
+
+                self.title_next(total, titled, queue)
+            }
+            Message::CancelMaintenance => {
+                if let Maintenance::Running { titled, .. } = self.maintenance {
+                    self.maintenance = Maintenance::Done(titled);
+                }
+
+                Action::None
+            }
+            Message::Relink => {
+                self.relink = Relink::Running;
+
+                Action::Run(Task::perform(Chat::relink(), Message::Relinked))
+            }
+            Message::Relinked(Ok(relinked)) => {
+                self.relink = Relink::Done(relinked);
+
+                Action::None
+            }
+            Message::Relinked(Err(error)) => {
+                self.relink = Relink::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::Import => Action::Run(Task::perform(
+                rfd::AsyncFileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .pick_file(),
+                |file| Message::ImportFrom(file.map(|file| file.path().to_owned())),
+            )),
+            Message::ImportFrom(Some(path)) => {
+                self.import = Import::Running;
+
+                Action::Run(Task::perform(Chat::import(path), Message::Imported))
+            }
+            Message::ImportFrom(None) => Action::None,
+            Message::Imported(Ok(imported)) => {
+                self.import = Import::Done(imported);
+
+                Action::None
+            }
+            Message::Imported(Err(error)) => {
+                self.import = Import::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::Backup => Action::Run(Task::perform(
+                rfd::AsyncFileDialog::new()
+                    .set_file_name(format!(
+                        "icebreaker-{date}.json",
+                        date = chrono::Local::now().format("%Y-%m-%d")
+                    ))
+                    .add_filter("JSON", &["json"])
+                    .save_file(),
+                |file| Message::BackupTo(file.map(|file| file.path().to_owned())),
+            )),
+            Message::BackupTo(Some(path)) => {
+                self.backup = Backup::Running;
+
+                Action::Run(Task::perform(backup::create(path), Message::BackedUp))
+            }
+            Message::BackupTo(None) | Message::RestoreFrom(None) => Action::None,
+            Message::BackedUp(Ok(())) => {
+                self.backup = Backup::Created;
+
+                Action::None
+            }
+            Message::BackedUp(Err(error)) | Message::Restored(Err(error)) => {
+                self.backup = Backup::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::Restore => Action::Run(Task::perform(
+                rfd::AsyncFileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .pick_file(),
+                |file| Message::RestoreFrom(file.map(|file| file.path().to_owned())),
+            )),
+            Message::RestoreFrom(Some(path)) => {
+                self.backup = Backup::Running;
+
+                Action::Run(Task::perform(backup::restore(path), |result| {
+                    Message::Restored(result.map(Box::new))
+                }))
+            }
+            Message::Restored(Ok(settings)) => {
+                self.backup = Backup::Restored;
+                self.system_prompt = text_editor::Content::with_text(&settings.system_prompt);
+                self.shortcuts = shortcuts(&settings.keymap);
+                self.settings = *settings;
+                self.links = self.settings.mcp.iter().map(|_| Link::Idle).collect();
+
+                mcp::retain(&self.settings.mcp);
+
+                Action::Change(Box::new(self.settings.clone()))
+            }
+            Message::ShowRequests => {
+                Action::Run(Task::perform(audit::fetch(), Message::RequestsFetched))
+            }
+            Message::RequestsFetched(Ok(requests)) => {
+                self.requests = Some(requests);
+
+                Action::None
+            }
+            Message::RequestsFetched(Err(error)) | Message::RequestsCleared(Err(error)) => {
+                let _ = dbg!(error);
+
+                Action::None
+            }
+            Message::RequestFilterChanged(filter) => {
+                self.request_filter = filter;
+
+                Action::None
+            }
+            Message::ClearRequests => {
+                Action::Run(Task::perform(audit::clear(), Message::RequestsCleared))
+            }
+            Message::RequestsCleared(Ok(())) => {
+                self.requests = Some(Vec::new());
+
+                Action::None
+            }
+            Message::CopyReport => {
+                self.report = Report::Gathering;
+                let executor = self.settings.executor.clone();
+
+                Action::Run(system::fetch_information().then(move |information| {
+                    Task::perform(
+                        diagnostics::report(environment(information), executor.clone()),
+                        Message::ReportGathered,
+                    )
+                }))
+            }
+            Message::ReportGathered(report) => {
+                self.report = Report::Copied;
+
+                Action::Run(iced::clipboard::write(report))
+            }
+            Message::ExportDiagnostics => Action::Run(Task::perform(
+                rfd::AsyncFileDialog::new()
+                    .set_file_name(format!(
+                        "icebreaker-diagnostics-{date}.zip",
+                        date = chrono::Local::now().format("%Y-%m-%d")
+                    ))
+                    .add_filter("Zip", &["zip"])
+                    .save_file(),
+                |file| Message::ExportDiagnosticsTo(file.map(|file| file.path().to_owned())),
+            )),
+            Message::ExportDiagnosticsTo(Some(path)) => {
+                self.bundle = Bundle::Exporting;
+
+                let settings = self.settings.clone();
+                let server_logs = self
+                    .assistant
+                    .as_ref()
+                    .map(|assistant| assistant.logs().lines())
+                    .unwrap_or_default();
+
+                Action::Run(system::fetch_information().then(move |information| {
+                    let bundle = diagnostics::bundle(
+                        environment(information),
+                        settings.clone(),
+                        server_logs.clone(),
+                    );
+                    let path = path.clone();
+
+                    Task::perform(
+                        async move {
+                            tokio::fs::write(path, bundle.await?).await?;
+
+                            Ok(())
+                        },
+                        Message::DiagnosticsExported,
+                    )
+                }))
+            }
+            Message::ExportDiagnosticsTo(None) => Action::None,
+            Message::DiagnosticsExported(Ok(())) => {
+                self.bundle = Bundle::Exported;
+
+                Action::None
+            }
+            Message::DiagnosticsExported(Err(error)) => {
+                self.bundle = Bundle::Failed(dbg!(error));
+
+                Action::None
+            }
+            Message::OpenEmbeddings => Action::OpenEmbeddings,
+            Message::Close => Action::Close,
+        }
+    }
+
+    /// The selected custom theme, if any.
+    fn custom_theme_mut(&mut self) -> Option<&mut theme::Custom> {
+        let Theme::Custom(name) = &self.settings.theme else {
+            return None;
+        };
+
+        self.settings
+            .themes
+            .iter_mut()
+            .find(|custom| custom.name == *name)
+    }
+
+    /// Edits the MCP server at the given index, dropping its connection
+    /// until it connects again.
+    fn edit_mcp_server(&mut self, index: usize, edit: impl FnOnce(&mut mcp::Server)) -> Action {
+        let Some(server) = self.settings.mcp.get_mut(index) else {
+            return Action::None;
+        };
+
+        edit(server);
+        self.links[index] = Link::Idle;
+
+        mcp::retain(&self.settings.mcp);
+
+        Action::Change(Box::new(self.settings.clone()))
+    }
+
+    /// A name for a new custom theme, numbered after the given one.
+    fn theme_name(&self, name: &str) -> String {
+        (1..)
+            .map(|n| format!("{name} {n}"))
+            .find(|candidate| {
+                self.settings
+                    .themes
+                    .iter()
+                    .all(|custom| custom.name != *candidate)
+            })
+            .expect("there is always a free name")
+    }
+
+    /// Titles the next chat in the queue with the assistant, pausing
+    /// afterwards so chatting with it stays responsive.
+    fn title_next(&mut self, total: usize, titled: usize, mut queue: Vec<chat::Id>) -> Action {
+        const PAUSE: Duration = Duration::from_secs(2);
+
+        let (Some(assistant), Some(id)) = (self.assistant.clone(), queue.pop()) else {
+            self.maintenance = Maintenance::Done(titled);
+            return Action::None;
+        };
+
+        let (task, handle) = Task::perform(
+            async move {
+                let title = Chat::retitle(assistant, id).await;
+                tokio::time::sleep(PAUSE).await;
+
+                title
+            },
+            move |title| Message::ChatTitled(id, title),
+        )
+        .abortable();
+
+        self.maintenance = Maintenance::Running {
+            queue,
+            total,
+            titled,
+            _task: handle.abort_on_drop(),
+        };
+
+        Action::Run(task)
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let header = row![
+            text("Settings").size(20).font(Font::MONOSPACE),
+            horizontal_space(),
+            button("Close")
+                .on_press(Message::Close)
+                .style(button::secondary),
+        ]
+        .align_y(Center);
+
+        let assistant = section(
+            "Assistant",
+            column![
+                text("Default system prompt").size(14),
+                text_editor(&self.system_prompt)
+                    .placeholder("Describe how the assistant should behave...")
+                    .on_action(Message::SystemPromptEdited)
+                    .padding(10)
+                    .height(150),
+                row![
+                    text("Used by every chat without a custom persona.")
+                        .size(12)
+                        .style(text::secondary),
+                    horizontal_space(),
+                    button(text("Reset to default").size(12))
+                        .on_press_maybe(
+                            (self.settings.system_prompt != data::Settings::DEFAULT_SYSTEM_PROMPT)
+                                .then_some(Message::ResetSystemPrompt)
+                        )
+                        .padding([2, 7])
+                        .style(button::text),
+                ]
+                .align_y(Center),
+                text("Guardrails").size(14),
+                text(
+                    "Always added after the system prompt, even in chats with a custom \
+                    persona. Chats can add their own."
+                )
+                .size(12)
+                .style(text::secondary),
+                row![
+                    text("Refuse to discuss").size(14).width(Fill),
+                    text_input(
+                        "Topics, separated by commas",
+                        &self.settings.guardrails.refused_topics
+                    )
+                    .on_input(Message::RefusedTopicsChanged)
+                    .size(14)
+                    .padding(5)
+                    .width(300),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                row![
+                    text("Tone").size(14).width(Fill),
+                    text_input("Concise and formal", &self.settings.guardrails.tone)
+                        .on_input(Message::ToneChanged)
+                        .size(14)
+                        .padding(5)
+                        .width(300),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                checkbox(
+                    "Let the model suggest chat titles",
+                    self.settings.suggest_titles
+                )
+                .on_toggle(Message::SuggestTitlesToggled)
+                .size(14)
+                .text_size(14),
+                text(
+                    "Titles cost a few tokens after the first and fifth replies. \
+                    When off, chats are titled after the first words of their first message."
+                )
+                .size(12)
+                .style(text::secondary),
+            ]
+            .spacing(10),
+        );
+
+        let variables = section(
+            "Variables",
+            column![
+                text(
+                    "Available in system prompts as {{name}}. \
+                    Built-in: {{date}}, {{time}}, {{model}}, {{os}}."
+                )
+                .size(12)
+                .style(text::secondary),
+                column(
+                    self.settings
+                        .variables
+                        .iter()
+                        .enumerate()
+                        .map(|(index, variable)| {
+                            row![
+                                text_input("name", &variable.name)
+                                    .on_input(move |name| Message::VariableNameChanged(index, name))
+                                    .font(Font::MONOSPACE)
+                                    .size(14)
+                                    .padding(5)
+                                    .width(150),
+                                text_input("value", &variable.value)
+                                    .on_input(move |value| Message::VariableValueChanged(
+                                        index, value
+                                    ))
+                                    .size(14)
+                                    .padding(5),
+                                button(text("Remove").size(12))
+                                    .on_press(Message::RemoveVariable(index))
+                                    .padding([2, 7])
+                                    .style(button::danger),
+                            ]
+                            .spacing(10)
+                            .align_y(Center)
+                            .into()
+                        })
+                )
+                .spacing(5),
+                button(text("Add variable").size(12))
+                    .on_press(Message::AddVariable)
+                    .padding([2, 7])
+                    .style(button::secondary),
+            ]
+            .spacing(10),
+        );
+
+        let memory = section(
+            "Memory",
+            column![
+                text(
+                    "Facts added to the system prompt of every chat. \
+                    Type /remember in a chat to add facts to that chat only."
+                )
+                .size(12)
+                .style(text::secondary),
+                column(
+                    self.settings
+                        .memory
+                        .iter()
+                        .enumerate()
+                        .map(|(index, fact)| {
+                            row![
+                                text_input("My name is...", fact)
+                                    .on_input(move |fact| Message::FactChanged(index, fact))
+                                    .size(14)
+                                    .padding(5),
+                                button(text("Remove").size(12))
+                                    .on_press(Message::RemoveFact(index))
+                                    .padding([2, 7])
+                                    .style(button::danger),
+                            ]
+                            .spacing(10)
+                            .align_y(Center)
+                            .into()
+                        })
+                )
+                .spacing(5),
+                button(text("Add fact").size(12))
+                    .on_press(Message::AddFact)
+                    .padding([2, 7])
+                    .style(button::secondary),
+            ]
+            .spacing(10),
+        );
+
+        let inference = {
+            let inference = &self.settings.inference;
+
+            let field =
+                |label, placeholder, value: Option<u32>, on_input: fn(String) -> Message| {
+                    row![
+                        text(label).size(14).width(Fill),
+                        text_input(
+                            placeholder,
+                            &value.map(|value| value.to_string()).unwrap_or_default()
+                        )
+                        .on_input(on_input)
+                        .font(Font::MONOSPACE)
+                        .size(14)
+                        .padding(5)
+                        .width(150),
+                    ]
+                    .spacing(10)
+                    .align_y(Center)
+                };
+
+            let extra_flags = column![row![
+                text("Extra llama-server flags").size(14).width(Fill),
+                text_input("--flash-attn", &self.settings.extra_flags)
+                    .on_input(Message::ExtraFlagsChanged)
+                    .font(Font::MONOSPACE)
+                    .size(14)
+                    .padding(5)
+                    .width(250),
+            ]
+            .spacing(10)
+            .align_y(Center)]
+            .push_maybe(
+                assistant::parse_flags(&self.settings.extra_flags)
+                    .err()
+                    .map(|error| text(error.to_string()).size(12).style(text::danger)),
+            )
+            .spacing(5);
+
+            section(
+                "Inference",
+                column![
+                    text("Applied the next time a model is launched.")
+                        .size(12)
+                        .style(text::secondary),
+                    row![
+                        text("Detected backend").size(14).width(Fill),
+                        text(self.backend.to_string()).size(14),
+                    ]
+                    .spacing(10),
+                    field("GPU device", "All", inference.gpu, Message::GpuChanged),
+                    field(
+                        "Offloaded layers",
+                        if self.backend == Backend::Metal {
+                            "All"
+                        } else {
+                            "Default"
+                        },
+                        inference.gpu_layers,
+                        Message::GpuLayersChanged
+                    ),
+                    field(
+                        "Context size",
+                        "Model default",
+                        inference.context_size,
+                        Message::ContextSizeChanged
+                    ),
+                    field(
+                        "Threads",
+                        "Default",
+                        inference.threads,
+                        Message::ThreadsChanged
+                    ),
+                    field(
+                        "Batch size",
+                        "Default",
+                        inference.batch_size,
+                        Message::BatchSizeChanged
+                    ),
+                    extra_flags,
+                    field(
+                        "Models kept running",
+                        "None",
+                        Some(self.settings.warm_models).filter(|models| *models > 0),
+                        Message::WarmModelsChanged
+                    ),
+                    text(
+                        "Switching back to a model kept running is instant, \
+                        but every one of them keeps its memory in use."
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                    checkbox(
+                        "Preload the model of a chat when hovering it",
+                        self.settings.preload_on_hover
+                    )
+                    .on_toggle(Message::PreloadOnHoverToggled)
+                    .size(14)
+                    .text_size(14),
+                    text(
+                        "Only models that fit in memory next to the running ones are preloaded, \
+                        and the preload stops if the pointer leaves the chat before it is ready."
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                    checkbox(
+                        "Keep running in the background when the window is closed",
+                        self.settings.run_in_background
+                    )
+                    .on_toggle(Message::RunInBackgroundToggled)
+                    .size(14)
+                    .text_size(14),
+                    text(if tray::SUPPORTED {
+                        "Closing the window hides it in the tray, and the loaded model stays ready \
+                        for the next chat. Use Quit in the tray or the shortcut to stop it."
+                    } else {
+                        "Closing the window minimizes it, and the loaded model stays ready \
+                        for the next chat. Use the Quit shortcut to stop it."
+                    })
+                    .size(12)
+                    .style(text::secondary),
+                    checkbox(
+                        "Summarize earlier messages when switching models",
+                        self.settings.summarize_on_switch
+                    )
+                    .on_toggle(Message::SummarizeOnSwitchToggled)
+                    .size(14)
+                    .text_size(14),
+                    text(
+                        "The new model writes a recap of the chat sized for its context, \
+                        and replies with it instead of the full history."
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                    row![
+                        text("Summarizer").size(14).width(Fill),
+                        pick_list(
+                            self.library.as_slice(),
+                            self.settings.summarizer.as_ref(),
+                            Message::SummarizerSelected
+                        )
+                        .placeholder("None")
+                        .text_size(14)
+                        .width(250),
+                    ]
+                    .push_maybe(self.settings.summarizer.is_some().then(|| {
+                        button(text("Remove").size(12))
+                            .on_press(Message::RemoveSummarizer)
+                            .padding([2, 7])
+                            .style(button::text)
+                    }))
+                    .spacing(10)
+                    .align_y(Center),
+                    text(
+                        "A smaller model from your library kept running to write \
+                        titles while reasoning models focus on replies."
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                    checkbox(
+                        "Let models call tools: web search, page fetch, and calculator",
+                        inference.tool_calling.unwrap_or_default()
+                    )
+                    .on_toggle(Message::ToolCallingToggled)
+                    .size(14)
+                    .text_size(14),
+                    text(
+                        "Needs a model and a llama-server build with native function calling. \
+                        Web requests are listed under Privacy."
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                ]
+                .push_maybe((!self.profiles.is_empty()).then(|| profiles(&self.profiles)))
+                .spacing(10),
+            )
+        };
+
+        let executor = {
+            let executor = &self.settings.executor;
+
+            let status = |kind: executor::Kind, version: Option<String>| {
+                row![
+                    text(kind.to_string()).size(14).width(Fill),
+                    match version {
+                        Some(version) => text(version).style(text::success),
+                        None => text("Not found").style(text::secondary),
+                    }
+                    .size(12)
+                    .font(Font::MONOSPACE),
+                ]
+                .spacing(10)
+                .align_y(Center)
+            };
+
+            let install = match &self.installing {
+                Installing::Idle => None,
+                Installing::Running => Some(text("Downloading llama.cpp...").size(12)),
+                Installing::Failed(error) => Some(text!("{error}").size(12).style(text::danger)),
+            };
+
+            // Hidden until a managed build can be installed, or is found
+            let managed = (self.executors.managed.is_some()
+                || executor::is_installable(self.backend))
+            .then(|| {
+                status(executor::Kind::Managed, self.executors.managed.clone()).push_maybe(
+                    self.executors.managed.is_none().then(|| {
+                        button(text("Install").size(12))
+                            .on_press_maybe(
+                                (!matches!(self.installing, Installing::Running))
+                                    .then_some(Message::InstallManaged),
+                            )
+                            .padding([2, 7])
+                            .style(button::secondary)
+                    }),
+                )
+            });
+
+            let kinds: Vec<_> = executor::Kind::ALL
+                .iter()
+                .copied()
+                .filter(|kind| *kind != executor::Kind::Managed || managed.is_some())
+                .collect();
+
+            let automatic = if managed.is_some() {
+                "Automatic uses the first one available: the system llama-server, \
+                then the managed build, then Docker."
+            } else {
+                "Automatic uses the first one available: the system llama-server, \
+                then Docker."
+            };
+
+            section(
+                "Executor",
+                column![
+                    row![
+                        text("Launch models with").size(14).width(Fill),
+                        pick_list(kinds, Some(executor.kind), Message::ExecutorSelected)
+                            .text_size(14)
+                            .width(250),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                    text(automatic).size(12).style(text::secondary),
+                    status(executor::Kind::System, self.executors.system.clone()),
+                ]
+                .push_maybe(managed)
+                .push(status(
+                    executor::Kind::Docker,
+                    self.executors.docker.clone(),
+                ))
+                .push(
+                    row![
+                        text("Docker image tag").size(14).width(Fill),
+                        text_input(executor::BUILD, &executor.docker_tag)
+                            .on_input(Message::DockerTagChanged)
+                            .font(Font::MONOSPACE)
+                            .size(14)
+                            .padding(5)
+                            .width(250),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                )
+                .push(
+                    row![
+                        match &self.probe {
+                            Probe::Idle => text(""),
+                            Probe::Testing => text("Testing..."),
+                            Probe::Passed(description) => {
+                                text(description).style(text::success)
+                            }
+                            Probe::Failed(error) => text!("{error}").style(text::danger),
+                        }
+                        .size(12),
+                        horizontal_space(),
+                        button(text("Test executor").size(12))
+                            .on_press_maybe(
+                                (!matches!(self.probe, Probe::Testing))
+                                    .then_some(Message::TestExecutor)
+                            )
+                            .padding([2, 7])
+                            .style(button::secondary),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                )
+                .push_maybe(install)
+                .spacing(10),
+            )
+        };
+
+        let remote = {
+            let remote = &self.settings.remote;
+
+            section(
+                "Remote server",
+                column![
+                    checkbox(
+                        "Use a llama-server running on another machine",
+                        remote.enabled
+                    )
+                    .on_toggle(Message::RemoteToggled)
+                    .size(14)
+                    .text_size(14),
+                    text(
+                        "Chats are answered by the model the remote server is running, \
+                        instead of launching one on this machine."
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                    row![
+                        text("Host").size(14).width(Fill),
+                        text_input("desktop.local", &remote.host)
+                            .on_input(Message::RemoteHostChanged)
+                            .font(Font::MONOSPACE)
+                            .size(14)
+                            .padding(5)
+                            .width(250),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                    row![
+                        text("Port").size(14).width(Fill),
+                        text_input("8080", &remote.port.to_string())
+                            .on_input(Message::RemotePortChanged)
+                            .font(Font::MONOSPACE)
+                            .size(14)
+                            .padding(5)
+                            .width(250),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                    checkbox("Tunnel through SSH", remote.tunnel)
+                        .on_toggle(Message::RemoteTunnelToggled)
+                        .size(14)
+                        .text_size(14),
+                    text(
+                        "For servers only listening on the remote machine. The host is \
+                        used as the SSH destination, like user@desktop.local, and needs \
+                        key-based authentication."
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                    row![
+                        match &self.remote {
+                            Connection::Idle => text(""),
+                            Connection::Checking => text("Connecting..."),
+                            Connection::Connected(model) => {
+                                text!("Connected! The server is running {model}.")
+                                    .style(text::success)
+                            }
+                            Connection::Failed(error) => text!("{error}").style(text::danger),
+                        }
+                        .size(12),
+                        horizontal_space(),
+                        button(text("Check connection").size(12))
+                            .on_press_maybe(
+                                (!matches!(self.remote, Connection::Checking)
+                                    && !remote.host.trim().is_empty())
+                                .then_some(Message::CheckRemote)
+                            )
+                            .padding([2, 7])
+                            .style(button::secondary),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                ]
+                .spacing(10),
+            )
+        };
+
+        let shortcuts = section(
+            "Shortcuts",
+            column![
+                column(keymap::Command::ALL.iter().zip(&self.shortcuts).map(
+                    |(command, shortcut)| {
+                        let error = shortcut.parse::<keymap::Shortcut>().err();
+
+                        row![text(command.to_string()).size(14).width(Fill)]
+                            .push_maybe(error.map(|error| text(error).size(12).style(text::danger)))
+                            .push(
+                                text_input("Ctrl+Key", shortcut)
+                                    .on_input(|shortcut| {
+                                        Message::ShortcutChanged(*command, shortcut)
+                                    })
+                                    .font(Font::MONOSPACE)
+                                    .size(14)
+                                    .padding(5)
+                                    .width(150),
+                            )
+                            .spacing(10)
+                            .align_y(Center)
+                            .into()
+                    }
+                ))
+                .spacing(5),
+                row![
+                    text("Ctrl is Cmd on macOS.")
+                        .size(12)
+                        .style(text::secondary),
+                    horizontal_space(),
+                    button(text("Reset to defaults").size(12))
+                        .on_press_maybe(
+                            (self.settings.keymap != Keymap::default())
+                                .then_some(Message::ResetShortcuts)
+                        )
+                        .padding([2, 7])
+                        .style(button::text),
+                ]
+                .align_y(Center),
+            ]
+            .spacing(10),
+        );
+
+        let editor = section(
+            "External editor",
+            column![
+                row![
+                    text("Command").size(14).width(Fill),
+                    text_input("Default app", &self.settings.editor)
+                        .on_input(Message::EditorChanged)
+                        .font(Font::MONOSPACE)
+                        .size(14)
+                        .padding(5)
+                        .width(250),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                text(
+                    "Opens replies and code blocks, followed by the path of the file. \
+                    Saved edits can be pulled back into the chat."
+                )
+                .size(12)
+                .style(text::secondary),
+            ]
+            .spacing(10),
+        );
+
+        let hugging_face = section(
+            "Hugging Face",
+            column![
+                row![
+                    text("Access token").size(14).width(Fill),
+                    text_input(
+                        if self.settings.hugging_face_token_set {
+                            "Stored in the keyring"
+                        } else {
+                            "hf_..."
+                        },
+                        &self.hugging_face_token
+                    )
+                    .on_input(Message::HuggingFaceTokenChanged)
+                    .on_submit(Message::StoreHuggingFaceToken)
+                    .secure(true)
+                    .font(Font::MONOSPACE)
+                    .size(14)
+                    .padding(5)
+                    .width(250),
+                    button(text("Save").size(12))
+                        .on_press_maybe(
+                            (!self.hugging_face_token.trim().is_empty())
+                                .then_some(Message::StoreHuggingFaceToken)
+                        )
+                        .padding([2, 7])
+                        .style(button::secondary),
+                    button(text("Remove").size(12))
+                        .on_press_maybe(
+                            self.settings
+                                .hugging_face_token_set
+                                .then_some(Message::RemoveHuggingFaceToken)
+                        )
+                        .padding([2, 7])
+                        .style(button::danger),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                text(
+                    "Shows gated models in the search and lets you download the ones \
+                    you were granted access to. A read token is enough. \
+                    It is stored in the keyring of the system."
+                )
+                .size(12)
+                .style(text::secondary),
+            ]
+            .push_maybe(
+                self.token_error
+                    .as_ref()
+                    .map(|error| text!("{error}").size(12).style(text::danger)),
+            )
+            .spacing(10),
+        );
+
+        let region = section(
+            "Region",
+            column![
+                row![
+                    text("Dates and numbers").size(14).width(Fill),
+                    pick_list(
+                        Locale::ALL,
+                        Some(self.settings.locale),
+                        Message::LocaleSelected
+                    )
+                    .text_size(14)
+                    .width(250),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                text!(
+                    "Today is {today}; a long reply has {tokens} tokens.",
+                    today = self.settings.locale.date(chrono::Local::now()),
+                    tokens = self.settings.locale.number(12_345)
+                )
+                .size(12)
+                .style(text::secondary),
+            ]
+            .spacing(10),
+        );
+
+        let appearance = section(
+            "Appearance",
+            column![
+                row![
+                    text("Interface scale").size(14).width(Fill),
+                    pick_list(
+                        Scale::ALL,
+                        Some(self.settings.appearance.scale),
+                        Message::ScaleSelected
+                    )
+                    .text_size(14)
+                    .width(250),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                row![
+                    text("Chat font").size(14).width(Fill),
+                    pick_list(
+                        ChatFont::ALL,
+                        Some(self.settings.appearance.chat_font),
+                        Message::ChatFontSelected
+                    )
+                    .text_size(14)
+                    .width(250),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                row![
+                    text("Message text size").size(14).width(Fill),
+                    pick_list(
+                        TextSize::ALL,
+                        Some(self.settings.appearance.text_size),
+                        Message::TextSizeSelected
+                    )
+                    .text_size(14)
+                    .width(250),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                row![
+                    text("Icons").size(14).width(Fill),
+                    pick_list(
+                        Icons::ALL,
+                        Some(self.settings.appearance.icons),
+                        Message::IconsSelected
+                    )
+                    .text_size(14)
+                    .width(250),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                row![
+                    text("Icon size").size(14).width(Fill),
+                    pick_list(
+                        Scale::ALL,
+                        Some(self.settings.appearance.icon_scale),
+                        Message::IconScaleSelected
+                    )
+                    .text_size(14)
+                    .width(250),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                row![
+                    text("High contrast icons are bold symbols that stand out on any background.")
+                        .size(12)
+                        .style(text::secondary)
+                        .width(Fill),
+                    icon::clipboard(),
+                    icon::refresh(),
+                    icon::trash(),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            ]
+            .spacing(10),
+        );
+
+        let theme = {
+            let themes: Vec<_> = iced::Theme::ALL
+                .iter()
+                .map(|theme| Theme::Builtin(theme.to_string()))
+                .chain(
+                    self.settings
+                        .themes
+                        .iter()
+                        .map(|custom| Theme::Custom(custom.name.clone())),
+                )
+                .collect();
+
+            let custom = match &self.settings.theme {
+                Theme::Custom(name) => self
+                    .settings
+                    .themes
+                    .iter()
+                    .find(|custom| custom.name == *name),
+                Theme::Builtin(_) | Theme::System => None,
+            };
+
+            let picker =
+                |label, choices: Vec<Theme>, selected: &Theme, on_select: fn(Theme) -> Message| {
+                    row![
+                        text(label).size(14).width(Fill),
+                        pick_list(choices, Some(selected.clone()), on_select)
+                            .text_size(14)
+                            .width(250),
+                    ]
+                    .spacing(10)
+                    .align_y(Center)
+                };
+
+            let modes = (self.settings.theme == Theme::System).then(|| {
+                column![
+                    picker(
+                        "Light theme",
+                        themes.clone(),
+                        &self.settings.light_theme,
+                        Message::LightThemeSelected,
+                    ),
+                    picker(
+                        "Dark theme",
+                        themes.clone(),
+                        &self.settings.dark_theme,
+                        Message::DarkThemeSelected,
+                    ),
+                ]
+                .spacing(10)
+            });
+
+            let picker = picker(
+                "Theme",
+                std::iter::once(Theme::System)
+                    .chain(themes.iter().cloned())
+                    .collect(),
+                &self.settings.theme,
+                Message::ThemeSelected,
+            );
+
+            let editor = custom.map(|custom| {
+                let colors =
+                    theme::Role::ALL.iter().map(|role| {
+                        let color = custom.color(*role);
+                        let parsed = iced::Color::parse(color);
+
+                        let swatch = container(horizontal_space()).width(20).height(20).style(
+                            move |_theme| container::Style {
+                                background: parsed.map(iced::Background::Color),
+                                border: iced::border::rounded(4)
+                                    .width(1)
+                                    .color(iced::Color::from_rgba(0.5, 0.5, 0.5, 0.5)),
+                                ..container::Style::default()
+                            },
+                        );
+
+                        row![
+                            text(role.to_string()).size(14).width(Fill),
+                            text(if parsed.is_none() {
+                                "Invalid color"
+                            } else {
+                                ""
+                            })
+                            .size(12)
+                            .style(text::danger),
+                            swatch,
+                            text_input("#000000", color)
+                                .on_input(|color| Message::ThemeColorChanged(*role, color))
+                                .size(14)
+                                .font(Font::MONOSPACE)
+                                .width(100),
+                        ]
+                        .spacing(10)
+                        .align_y(Center)
+                        .into()
+                    });
+
+                column![row![
+                    text("Name").size(14).width(Fill),
+                    text_input("My theme", &custom.name)
+                        .on_input(Message::ThemeRenamed)
+                        .size(14)
+                        .width(250),
+                ]
+                .spacing(10)
+                .align_y(Center),]
+                .extend(colors)
+                .spacing(10)
+            });
+
+            let actions = row![text(
+                self.theme_error
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default()
+            )
+            .size(12)
+            .style(text::danger)
+            .width(Fill),]
+            .push_maybe(custom.is_some().then(|| {
+                button(text("Delete").size(12))
+                    .on_press(Message::DeleteTheme)
+                    .padding([2, 7])
+                    .style(button::danger)
+            }))
+            .push(
+                button(text("Import").size(12))
+                    .on_press(Message::ImportTheme)
+                    .padding([2, 7])
+                    .style(button::secondary),
+            )
+            .push(
+                button(text("New custom theme").size(12))
+                    .on_press(Message::NewTheme)
+                    .padding([2, 7])
+                    .style(button::secondary),
+            )
+            .spacing(10)
+            .align_y(Center);
+
+            section(
+                "Theme",
+                column![
+                    picker,
+                    text(
+                        "The system theme switches between a light and a dark theme \
+                        with the mode of your desktop. Custom themes start with the \
+                        colors of the current one and change as you edit them. Themes \
+                        can be imported from TOML files with a name and the five colors \
+                        below."
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                ]
+                .push_maybe(modes)
+                .push_maybe(editor)
+                .push(actions)
+                .spacing(10),
+            )
+        };
+
+        let usage = {
+            let counts: Element<_> = if self.usage.counts.is_empty() {
+                text("No features have been used yet.")
+                    .size(12)
+                    .style(text::secondary)
+                    .into()
+            } else {
+                column(self.usage.counts.iter().map(|(feature, count)| {
+                    row![
+                        text(feature.to_string()).size(14).width(Fill),
+                        text(count).size(14).font(Font::MONOSPACE),
+                    ]
+                    .spacing(10)
+                    .into()
+                }))
+                .spacing(5)
+                .into()
+            };
+
+            section(
+                "Usage",
+                column![
+                    checkbox("Count the features I use", self.settings.record_usage)
+                        .on_toggle(Message::RecordUsageToggled)
+                        .size(14)
+                        .text_size(14),
+                    text(
+                        "Counts are stored on this device and never sent anywhere. \
+                        Nothing about your chats is recorded."
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                    counts,
+                    row![
+                        text(self.usage.since.map_or_else(String::new, |since| format!(
+                            "Since {}",
+                            since.format("%Y-%m-%d")
+                        )))
+                        .size(12)
+                        .style(text::secondary),
+                        horizontal_space(),
+                        button(text("Reset").size(12))
+                            .on_press_maybe(
+                                (!self.usage.counts.is_empty()).then_some(Message::ResetUsage)
+                            )
+                            .padding([2, 7])
+                            .style(button::danger),
+                    ]
+                    .align_y(Center),
+                ]
+                .spacing(10),
+            )
+        };
+
+        let storage = section(
+            "Storage",
+            column![
+                text("Import chats exported from ChatGPT, Ollama, or LM Studio.").size(14),
+                row![
+                    match &self.import {
+                        Import::Idle => text(""),
+                        Import::Running => text("Importing..."),
+                        Import::Done(0) => text("No chats were found."),
+                        Import::Done(1) => text("1 chat was imported."),
+                        Import::Done(imported) => text!("{imported} chats were imported."),
+                        Import::Failed(error) => text!("{error}").style(text::danger),
+                    }
+                    .size(12),
+                    horizontal_space(),
+                    button(text("Import chats...").size(12))
+                        .on_press_maybe(
+                            (!matches!(self.import, Import::Running)).then_some(Message::Import)
+                        )
+                        .padding([2, 7])
+                        .style(button::secondary),
+                ]
+                .align_y(Center),
+                text("Relink chats whose model files were moved or renamed.").size(14),
+                row![
+                    match &self.relink {
+                        Relink::Idle => text(""),
+                        Relink::Running => text("Relinking..."),
+                        Relink::Done(0) => text("Every chat is up to date."),
+                        Relink::Done(1) => text("1 chat was relinked."),
+                        Relink::Done(relinked) => text!("{relinked} chats were relinked."),
+                        Relink::Failed(error) => text!("{error}").style(text::danger),
+                    }
+                    .size(12),
+                    horizontal_space(),
+                    button(text("Relink models").size(12))
+                        .on_press_maybe(
+                            (!matches!(self.relink, Relink::Running)).then_some(Message::Relink)
+                        )
+                        .padding([2, 7])
+                        .style(button::secondary),
+                ]
+                .align_y(Center),
+            ]
+            .spacing(10),
+        );
+
+        let maintenance = {
+            let status: Element<_> = match &self.maintenance {
+                Maintenance::Idle if self.untitled.is_empty() => {
+                    text("Every chat has a title.").size(12).into()
+                }
+                Maintenance::Idle if self.assistant.is_none() => {
+                    text("Open a chat to title the rest with its model.")
+                        .size(12)
+                        .into()
+                }
+                Maintenance::Idle => text!("{} chats have no title.", self.untitled.len())
+                    .size(12)
+                    .into(),
+                Maintenance::Running {
+                    queue,
+                    total,
+                    titled,
+                    ..
+                } => row![
+                    progress_bar(0.0..=*total as f32, (total - queue.len()) as f32)
+                        .height(8)
+                        .width(150),
+                    text!(
+                        "Chat {current} of {total}; {titled} titled so far",
+                        current = total - queue.len()
+                    )
+                    .size(12),
+                ]
+                .spacing(10)
+                .align_y(Center)
+                .into(),
+                Maintenance::Done(1) => text("1 chat was titled.").size(12).into(),
+                Maintenance::Done(titled) => text!("{titled} chats were titled.").size(12).into(),
+            };
+
+            let action = if matches!(self.maintenance, Maintenance::Running { .. }) {
+                button(text("Cancel").size(12))
+                    .on_press(Message::CancelMaintenance)
+                    .padding([2, 7])
+                    .style(button::secondary)
+            } else {
+                button(text("Title chats").size(12))
+                    .on_press_maybe(
+                        (self.assistant.is_some() && !self.untitled.is_empty())
+                            .then_some(Message::TitleChats),
+                    )
+                    .padding([2, 7])
+                    .style(button::secondary)
+            };
+
+            section(
+                "Maintenance",
+                column![
+                    text(
+                        "Write the missing titles of saved chats with the model of the open chat, \
+                        one chat at a time."
+                    )
+                    .size(14),
+                    row![status, horizontal_space(), action].align_y(Center),
+                ]
+                .spacing(10),
+            )
+        };
+
+        let backup = section(
+            "Backup",
+            column![
+                text(
+                    "Back up settings, chats, and memory. Models, API keys, \
+                    and tokens are not included."
+                )
+                .size(14),
+                row![
+                    match &self.backup {
+                        Backup::Idle => text(""),
+                        Backup::Running => text("Working..."),
+                        Backup::Created => text("The backup was created."),
+                        Backup::Restored => text("The backup was restored."),
+                        Backup::Failed(error) => text!("{error}").style(text::danger),
+                    }
+                    .size(12),
+                    horizontal_space(),
+                    button(text("Restore backup...").size(12))
+                        .on_press_maybe(
+                            (!matches!(self.backup, Backup::Running)).then_some(Message::Restore)
+                        )
+                        .padding([2, 7])
+                        .style(button::secondary),
+                    button(text("Create backup...").size(12))
+                        .on_press_maybe(
+                            (!matches!(self.backup, Backup::Running)).then_some(Message::Backup)
+                        )
+                        .padding([2, 7])
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            ]
+            .spacing(10),
+        );
+
+        let search = {
+            let search = &self.settings.search;
+
+            let field =
+                |label, placeholder, value: &str, secure, on_input: fn(String) -> Message| {
+                    row![
+                        text(label).size(14).width(Fill),
+                        text_input(placeholder, value)
+                            .on_input(on_input)
+                            .secure(secure)
+                            .font(Font::MONOSPACE)
+                            .size(14)
+                            .padding(5)
+                            .width(250),
+                    ]
+                    .spacing(10)
+                    .align_y(Center)
+                };
+
+            let configuration = match search.provider {
+                web::Provider::DuckDuckGo => None,
+                web::Provider::Searxng => Some(field(
+                    "Instance",
+                    "http://localhost:8080",
+                    &search.searxng_url,
+                    false,
+                    Message::SearxngUrlChanged,
+                )),
+                web::Provider::Brave => Some(field(
+                    "API key",
+                    "BSA...",
+                    &search.brave_api_key,
+                    true,
+                    Message::BraveApiKeyChanged,
+                )),
+                web::Provider::Tavily => Some(field(
+                    "API key",
+                    "tvly-...",
+                    &search.tavily_api_key,
+                    true,
+                    Message::TavilyApiKeyChanged,
+                )),
+            };
+
+            section(
+                "Web search",
+                column![
+                    text("Used by models that call tools.")
+                        .size(12)
+                        .style(text::secondary),
+                    row![
+                        text("Provider").size(14).width(Fill),
+                        pick_list(
+                            web::Provider::ALL,
+                            Some(search.provider),
+                            Message::SearchProviderSelected
+                        )
+                        .text_size(14)
+                        .width(250),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                ]
+                .push_maybe(configuration)
+                .push(
+                    checkbox("Treat web content as untrusted", search.guard)
+                        .on_toggle(Message::SearchGuardToggled)
+                        .size(14)
+                        .text_size(14),
+                )
+                .push(
+                    text(
+                        "Pages and results are marked as data for the model, and phrases \
+                        that try to pass for instructions are removed.",
+                    )
+                    .size(12)
+                    .style(text::secondary),
+                )
+                .spacing(10),
+            )
+        };
+
+        let servers = section(
+            "MCP servers",
+            column![
+                text(
+                    "Models that call tools can use the tools of these servers. \
+                    Commands are run by the app; URLs must serve streamable HTTP."
+                )
+                .size(12)
+                .style(text::secondary),
+                column(self.settings.mcp.iter().zip(&self.links).enumerate().map(
+                    |(index, (server, link))| {
+                        let placeholder = match server.transport {
+                            mcp::Transport::Stdio => "npx -y @modelcontextprotocol/server-memory",
+                            mcp::Transport::Http => "http://localhost:8000/mcp",
+                        };
+
+                        let status = match link {
+                            Link::Idle => text("Not connected").style(text::secondary),
+                            Link::Connecting => text("Connecting..."),
+                            Link::Connected(tools) if tools.is_empty() => {
+                                text("Connected, without tools").style(text::success)
+                            }
+                            Link::Connected(tools) => text!(
+                                "Tools: {}",
+                                tools
+                                    .iter()
+                                    .map(|tool| tool.original.as_str())
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            )
+                            .style(text::success),
+                            Link::Failed(error) => text!("{error}").style(text::danger),
+                        };
+
+                        column![
+                            row![
+                                text_input("name", &server.name)
+                                    .on_input(move |name| Message::McpNameChanged(index, name))
+                                    .font(Font::MONOSPACE)
+                                    .size(14)
+                                    .padding(5)
+                                    .width(150),
+                                pick_list(
+                                    mcp::Transport::ALL,
+                                    Some(server.transport),
+                                    move |transport| {
+                                        Message::McpTransportSelected(index, transport)
+                                    }
+                                )
+                                .text_size(14)
+                                .width(100),
+                                text_input(placeholder, &server.target)
+                                    .on_input(move |target| Message::McpTargetChanged(
+                                        index, target
+                                    ))
+                                    .font(Font::MONOSPACE)
+                                    .size(14)
+                                    .padding(5),
+                                button(text("Remove").size(12))
+                                    .on_press(Message::RemoveMcpServer(index))
+                                    .padding([2, 7])
+                                    .style(button::danger),
+                            ]
+                            .spacing(10)
+                            .align_y(Center),
+                            row![
+                                status.size(12).width(Fill),
+                                button(text("Connect").size(12))
+                                    .on_press_maybe(
+                                        (!matches!(link, Link::Connecting))
+                                            .then_some(Message::ConnectMcpServer(index))
+                                    )
+                                    .padding([2, 7])
+                                    .style(button::secondary),
+                            ]
+                            .spacing(10)
+                            .align_y(Center),
+                        ]
+                        .spacing(5)
+                        .into()
+                    }
+                ))
+                .spacing(10),
+                button(text("Add server").size(12))
+                    .on_press(Message::AddMcpServer)
+                    .padding([2, 7])
+                    .style(button::secondary),
+            ]
+            .spacing(10),
+        );
+
+        let knowledge = section(
+            "Knowledge",
+            column![
+                text(
+                    "Folders of Markdown and text documents that chats can draw from \
+                    with the Knowledge toggle. They are embedded by a llama-server \
+                    running an embedding model with --embeddings."
+                )
+                .size(12)
+                .style(text::secondary),
+                column(self.settings.knowledge.folders.iter().enumerate().map(
+                    |(index, folder)| {
+                        row![
+                            text(folder.display().to_string())
+                                .size(14)
+                                .font(Font::MONOSPACE)
+                                .width(Fill),
+                            button(text("Remove").size(12))
+                                .on_press(Message::RemoveFolder(index))
+                                .padding([2, 7])
+                                .style(button::danger),
+                        ]
+                        .spacing(10)
+                        .align_y(Center)
+                        .into()
+                    }
+                ))
+                .spacing(5),
+                button(text("Add folder...").size(12))
+                    .on_press(Message::AddFolder)
+                    .padding([2, 7])
+                    .style(button::secondary),
+                row![
+                    text("Embedding server").size(14).width(Fill),
+                    text_input(
+                        "http://localhost:8081",
+                        &self.settings.knowledge.embedding_server
+                    )
+                    .on_input(Message::EmbeddingServerChanged)
+                    .font(Font::MONOSPACE)
+                    .size(14)
+                    .padding(5)
+                    .width(250),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                row![
+                    match &self.indexing {
+                        Indexing::Idle => text(""),
+                        Indexing::Running => text("Indexing..."),
+                        Indexing::Done(summary) => text!(
+                            "{chunks} passages from {documents} documents are indexed.",
+                            chunks = summary.chunks,
+                            documents = summary.documents
+                        ),
+                        Indexing::Failed(error) => text!("{error}").style(text::danger),
+                    }
+                    .size(12),
+                    horizontal_space(),
+                    button(text("Explore embeddings").size(12))
+                        .on_press(Message::OpenEmbeddings)
+                        .padding([2, 7])
+                        .style(button::text),
+                    button(text("Rebuild index").size(12))
+                        .on_press_maybe(
+                            (!matches!(self.indexing, Indexing::Running))
+                                .then_some(Message::BuildIndex)
+                        )
+                        .padding([2, 7])
+                        .style(button::secondary),
+                ]
+                .align_y(Center),
+            ]
+            .spacing(10),
+        );
+
+        let privacy = {
+            let description = text(
+                "Every request sent outside of this machine is recorded. \
+                Chats never leave your computer.",
+            )
+            .size(12)
+            .style(text::secondary);
+
+            let content: Element<_> = if let Some(requests) = &self.requests {
+                let filters = pick_list(
+                    [RequestFilter::All]
+                        .into_iter()
+                        .chain(
+                            audit::Purpose::ALL
+                                .iter()
+                                .copied()
+                                .map(RequestFilter::Purpose),
+                        )
+                        .collect::<Vec<_>>(),
+                    Some(self.request_filter),
+                    Message::RequestFilterChanged,
+                )
+                .text_size(12);
+
+                let requests = column(
+                    requests
+                        .iter()
+                        .filter(|record| self.request_filter.matches(record))
+                        .take(100)
+                        .map(|record| {
+                            column![
+                                text!(
+                                    "{timestamp} · {purpose}{size}",
+                                    timestamp = record.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                                    purpose = record.purpose,
+                                    size = record
+                                        .received
+                                        .map(|bytes| format!(" · {bytes} bytes"))
+                                        .unwrap_or_default(),
+                                )
+                                .size(10)
+                                .style(text::secondary),
+                                text(&record.endpoint).size(12).font(Font::MONOSPACE),
+                            ]
+                            .spacing(2)
+                            .into()
+                        }),
+                )
+                .spacing(10);
+
+                column![
+                    row![
+                        filters,
+                        horizontal_space(),
+                        button(text("Clear log").size(12))
+                            .on_press(Message::ClearRequests)
+                            .padding([2, 7])
+                            .style(button::danger),
+                    ]
+                    .align_y(Center),
+                    scrollable(requests).height(250).spacing(10),
+                ]
+                .spacing(10)
+                .into()
+            } else {
+                button(text("Show requests").size(12))
+                    .on_press(Message::ShowRequests)
+                    .padding([2, 7])
+                    .style(button::secondary)
+                    .into()
+            };
+
+            section("Privacy", column![description, content].spacing(10))
+        };
+
+        let diagnostics = section(
+            "Diagnostics",
+            column![
+                text(
+                    "Copy a summary of your system, the llama.cpp server, and recent errors \
+                    to paste into a bug report. Your home directory is redacted."
+                )
+                .size(12)
+                .style(text::secondary),
+                row![
+                    match self.report {
+                        Report::Idle => text(""),
+                        Report::Gathering => text("Gathering..."),
+                        Report::Copied => text("The report was copied to the clipboard."),
+                    }
+                    .size(12),
+                    horizontal_space(),
+                    button(text("Copy environment report").size(12))
+                        .on_press_maybe(
+                            (!matches!(self.report, Report::Gathering))
+                                .then_some(Message::CopyReport)
+                        )
+                        .padding([2, 7])
+                        .style(button::secondary),
+                ]
+                .align_y(Center),
+                text(
+                    "Export the report together with the recent logs of the app and the \
+                    llama.cpp server, and your settings without any keys or memories."
+                )
+                .size(12)
+                .style(text::secondary),
+                row![
+                    match &self.bundle {
+                        Bundle::Idle => text(""),
+                        Bundle::Exporting => text("Exporting..."),
+                        Bundle::Exported => text("The diagnostics were exported."),
+                        Bundle::Failed(error) => text!("{error}").style(text::danger),
+                    }
+                    .size(12),
+                    horizontal_space(),
+                    button(text("Export diagnostics").size(12))
+                        .on_press_maybe(
+                            (!matches!(self.bundle, Bundle::Exporting))
+                                .then_some(Message::ExportDiagnostics)
+                        )
+                        .padding([2, 7])
+                        .style(button::secondary),
+                ]
+                .align_y(Center),
+            ]
+            .spacing(10),
+        );
+
+        let sections = scrollable(
+            column![
+                assistant,
+                variables,
+                memory,
+                knowledge,
+                inference,
+                executor,
+                remote,
+                search,
+                servers,
+                hugging_face,
+                shortcuts,
+                editor,
+                region,
+                appearance,
+                theme,
+                storage,
+                maintenance,
+                backup,
+                privacy,
+                usage,
+                diagnostics
+            ]
+            .spacing(20)
+            .padding([0, 10]),
+        )
+        .height(Fill)
+        .spacing(10);
+
+        center_x(
+            column![header, sections]
+                .spacing(20)
+                .padding(10)
+                .max_width(600),
+        )
+        .into()
+    }
+}
+
+fn connect(index: usize, server: mcp::Server) -> Task<Message> {
+    Task::perform(mcp::connect(server.clone()), move |result| {
+        Message::McpServerConnected(index, server.clone(), result)
+    })
+}
+
+fn shortcuts(keymap: &Keymap) -> Vec<String> {
+    keymap::Command::ALL
+        .iter()
+        .map(|command| keymap.shortcut(*command).to_string())
+        .collect()
+}
+
+fn section<'a>(title: &'a str, content: impl Into<Element<'a, Message>>) -> Element<'a, Message> {
+    column![
+        text(title).font(Font::MONOSPACE),
+        container(content)
+            .width(Fill)
+            .padding(10)
+            .style(container::rounded_box)
+    ]
+    .spacing(10)
+    .into()
+}
+
+fn profiles(profiles: &[Profile]) -> Element<'_, Message> {
+    column![
+        text(
+            "Models remember the options that last launched them. \
+            They fill in any option left empty above."
+        )
+        .size(12)
+        .style(text::secondary),
+        column(profiles.iter().map(|profile| {
+            let inference = profile.inference;
+
+            let options = [
+                ("GPU", inference.gpu),
+                ("layers", inference.gpu_layers),
+                ("context", inference.context_size),
+                ("threads", inference.threads),
+                ("batch", inference.batch_size),
+            ]
+            .into_iter()
+            .filter_map(|(label, value)| Some(format!("{label} {}", value?)))
+            .chain(
+                inference
+                    .tool_calling
+                    .unwrap_or_default()
+                    .then(|| "tools".to_owned()),
+            )
+            .collect::<Vec<_>>()
+            .join(" · ");
+
+            row![
+                column![
+                    text!(
+                        "{file} ({backend})",
+                        file = profile.file,
+                        backend = profile.backend
+                    )
+                    .size(12)
+                    .font(Font::MONOSPACE),
+                    text(options).size(10).style(text::secondary),
+                ]
+                .spacing(2)
+                .width(Fill),
+                button(text("Reset").size(12))
+                    .on_press_with(|| Message::ResetProfile(profile.clone()))
+                    .padding([2, 7])
+                    .style(button::danger),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into()
+        }))
+        .spacing(5),
+    ]
+    .spacing(10)
+    .into()
+}
+
+/// Parses an optional number, where empty input means unset.
+fn parse_optional(input: &str) -> Option<Option<u32>> {
+    let input = input.trim();
+
+    if input.is_empty() {
+        Some(None)
+    } else {
+        input.parse().ok().map(Some)
+    }
+}
+
+/// The machine running the app, as described in diagnostics.
+fn environment(information: system::Information) -> diagnostics::Environment {
+    diagnostics::Environment {
+        system: format!(
+            "{name} {version} (kernel {kernel})",
+            name = information.system_name.unwrap_or_default(),
+            version = information.system_version.unwrap_or_default(),
+            kernel = information.system_kernel.unwrap_or_default(),
+        ),
+        cpu: information.cpu_brand,
+        memory: information.memory_total,
+        graphics_adapter: information.graphics_adapter,
+        graphics_backend: information.graphics_backend,
+    }
+}
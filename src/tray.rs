@@ -0,0 +1,157 @@
+//! The icon of the app in the system tray.
+//!
+//! Only Linux has one for now, through the StatusNotifierItem protocol
+//! over D-Bus. The other platforms keep minimizing the window instead.
+use iced::{Subscription, Task};
+
+/// An item of the menu of the tray icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Shows the window if it is hidden, and hides it otherwise.
+    Toggle,
+    NewChat,
+    Quit,
+}
+
+/// Whether the app has a tray icon on this platform.
+pub const SUPPORTED: bool = cfg!(target_os = "linux");
+
+/// Adds the icon to the system tray, where it stays until the app quits.
+pub fn show() -> Task<()> {
+    platform::show()
+}
+
+/// Listens to the menu of the tray icon.
+pub fn events() -> Subscription<Event> {
+    Subscription::run(platform::events)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::Event;
+
+    use futures::channel::mpsc;
+    use futures::Stream;
+    use iced::Task;
+    use ksni::TrayMethods;
+
+    /// The tray is shown while its events are listened to.
+    pub fn show() -> Task<()> {
+        Task::none()
+    }
+
+    pub fn events() -> impl Stream<Item = Event> {
+        iced::stream::channel(10, |sender| async move {
+            match (Tray { sender }).spawn().await {
+                Ok(_handle) => futures::future::pending().await,
+                Err(error) => {
+                    let _ = dbg!(error);
+                }
+            }
+        })
+    }
+
+    struct Tray {
+        sender: mpsc::Sender<Event>,
+    }
+
+    impl Event {
+        const ALL: &'static [Self] = &[Self::Toggle, Self::NewChat, Self::Quit];
+
+        fn label(self) -> &'static str {
+            match self {
+                Event::Toggle => "Show/Hide",
+                Event::NewChat => "New Chat",
+                Event::Quit => "Quit",
+            }
+        }
+    }
+
+    /// The size of the icon, in pixels.
+    const SIZE: u32 = 32;
+
+    /// The RGBA pixels of the icon: an ice blue disc.
+    fn pixels() -> Vec<u8> {
+        let center = (SIZE as f32 - 1.0) / 2.0;
+        let radius = SIZE as f32 / 2.0;
+
+        (0..SIZE * SIZE)
+            .flat_map(|index| {
+                let x = (index % SIZE) as f32 - center;
+                let y = (index / SIZE) as f32 - center;
+
+                // Antialiased by the distance to the edge
+                let alpha = (radius - (x * x + y * y).sqrt()).clamp(0.0, 1.0);
+
+                [0x7f, 0xc8, 0xf0, (alpha * 255.0) as u8]
+            })
+            .collect()
+    }
+
+    impl ksni::Tray for Tray {
+        fn id(&self) -> String {
+            env!("CARGO_PKG_NAME").to_owned()
+        }
+
+        fn title(&self) -> String {
+            "Icebreaker".to_owned()
+        }
+
+        fn icon_pixmap(&self) -> Vec<ksni::Icon> {
+            // ARGB instead of RGBA
+            let data = pixels()
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[3], pixel[0], pixel[1], pixel[2]])
+                .collect();
+
+            vec![ksni::Icon {
+                width: SIZE as i32,
+                height: SIZE as i32,
+                data,
+            }]
+        }
+
+        fn activate(&mut self, _x: i32, _y: i32) {
+            let _ = self.sender.try_send(Event::Toggle);
+        }
+
+        fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+            use ksni::menu::{MenuItem, StandardItem};
+
+            let item = |event: Event| -> MenuItem<Self> {
+                StandardItem {
+                    label: event.label().to_owned(),
+                    activate: Box::new(move |tray: &mut Self| {
+                        let _ = tray.sender.try_send(event);
+                    }),
+                    ..StandardItem::default()
+                }
+                .into()
+            };
+
+            Event::ALL
+                .iter()
+                .flat_map(|event| match event {
+                    Event::Quit => vec![MenuItem::Separator, item(*event)],
+                    _ => vec![item(*event)],
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::Event;
+
+    use futures::Stream;
+    use iced::Task;
+
+    pub fn show() -> Task<()> {
+        Task::none()
+    }
+
+    pub fn events() -> impl Stream<Item = Event> {
+        futures::stream::empty()
+    }
+}
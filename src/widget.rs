@@ -1,3 +1,6 @@
+pub mod icon;
+pub mod markdown;
+
 use iced::widget::{container, text, tooltip};
 use iced::Element;
 
@@ -7,7 +10,7 @@ pub mod tip {
 
 pub fn tip<'a, Message: 'a>(
     target: impl Into<Element<'a, Message>>,
-    tip: &'a str,
+    tip: impl text::IntoFragment<'a>,
     position: tip::Position,
 ) -> Element<'a, Message> {
     tooltip(
@@ -0,0 +1,103 @@
+//! The icons of the app, drawn with the [`Appearance`] in the settings.
+//!
+//! The glyphs of the standard set come from the generated icon font.
+use crate::data::appearance::{Appearance, ChatFont, Icons, Scale, TextSize};
+use crate::icon as glyph;
+
+use iced::font;
+use iced::widget::{text, Text};
+use iced::Font;
+
+use std::sync::RwLock;
+
+/// The size of icons at 100% scale; the default text size.
+const SIZE: f32 = 16.0;
+
+static APPEARANCE: RwLock<Appearance> = RwLock::new(Appearance {
+    icons: Icons::Standard,
+    icon_scale: Scale(100),
+    scale: Scale(100),
+    chat_font: ChatFont::Proportional,
+    text_size: TextSize(16),
+});
+
+/// Changes how all icons are drawn from now on.
+pub fn set_appearance(appearance: Appearance) {
+    if let Ok(mut current) = APPEARANCE.write() {
+        *current = appearance;
+    }
+}
+
+/// Scales the given icon size.
+pub fn scaled(size: f32) -> f32 {
+    appearance().icon_scale.apply(size)
+}
+
+pub fn arrow_down<'a>() -> Text<'a> {
+    icon(glyph::arrow_down, "▼")
+}
+
+pub fn arrow_up<'a>() -> Text<'a> {
+    icon(glyph::arrow_up, "▲")
+}
+
+pub fn chat<'a>() -> Text<'a> {
+    icon(glyph::chat, "»")
+}
+
+pub fn clipboard<'a>() -> Text<'a> {
+    icon(glyph::clipboard, "❐")
+}
+
+pub fn clock<'a>() -> Text<'a> {
+    icon(glyph::clock, "◷")
+}
+
+pub fn collapse<'a>() -> Text<'a> {
+    icon(glyph::collapse, "«")
+}
+
+pub fn download<'a>() -> Text<'a> {
+    icon(glyph::download, "↓")
+}
+
+pub fn expand<'a>() -> Text<'a> {
+    icon(glyph::expand, "»")
+}
+
+pub fn heart<'a>() -> Text<'a> {
+    icon(glyph::heart, "♥")
+}
+
+pub fn refresh<'a>() -> Text<'a> {
+    icon(glyph::refresh, "↻")
+}
+
+pub fn trash<'a>() -> Text<'a> {
+    icon(glyph::trash, "✕")
+}
+
+pub fn user<'a>() -> Text<'a> {
+    icon(glyph::user, "●")
+}
+
+fn icon<'a>(glyph: fn() -> Text<'a>, symbol: &'a str) -> Text<'a> {
+    let appearance = appearance();
+
+    let icon = match appearance.icons {
+        Icons::Standard => glyph(),
+        Icons::HighContrast => text(symbol).font(Font {
+            weight: font::Weight::Bold,
+            ..Font::DEFAULT
+        }),
+    };
+
+    icon.size(appearance.icon_scale.apply(SIZE))
+}
+
+fn appearance() -> Appearance {
+    APPEARANCE
+        .read()
+        .map(|appearance| *appearance)
+        .unwrap_or_default()
+}
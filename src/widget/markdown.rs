@@ -0,0 +1,652 @@
+use crate::data::appearance::Appearance;
+use crate::data::exec;
+use crate::data::footnote;
+use crate::widget::icon;
+use crate::widget::tip;
+
+use iced::font;
+use iced::widget::{
+    button, column, container, horizontal_rule, horizontal_space, markdown, rich_text, row,
+    scrollable, text,
+};
+use iced::{padding, Center, Color, Element, Fill, Font, Theme};
+
+/// A Markdown document that can grow while it is being streamed.
+///
+/// Fenced code blocks are kept apart from the surrounding text, so they are
+/// highlighted as soon as their opening fence arrives and can be copied
+/// on their own.
+///
+/// Footnotes are numbered in order of appearance and listed at the end.
+#[derive(Debug, Default)]
+pub struct Content {
+    raw: String,
+    settled: Box<Settled>,
+    blocks: Vec<Block>,
+    notes: Option<Box<Block>>,
+}
+
+/// The segments up to the last closed code block, which further
+/// streaming can no longer change.
+#[derive(Debug, Default)]
+struct Settled {
+    length: usize,
+    segments: Vec<(Kind, String)>,
+    definitions: Vec<footnote::Definition>,
+}
+
+#[derive(Debug)]
+struct Block {
+    kind: Kind,
+    source: String,
+    content: markdown::Content,
+}
+
+impl Block {
+    fn text(source: String) -> Self {
+        Self {
+            kind: Kind::Text,
+            content: markdown::Content::parse(&source),
+            source,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Text,
+    Code {
+        language: Option<String>,
+        code: String,
+    },
+}
+
+impl Content {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(markdown: &str) -> Self {
+        let mut content = Self::new();
+        content.push_str(markdown);
+        content
+    }
+
+    pub fn push_str(&mut self, markdown: &str) {
+        self.raw.push_str(markdown);
+
+        let Split {
+            mut segments,
+            settled,
+            closed,
+        } = split(&self.raw[self.settled.length..]);
+
+        let mut definitions = Vec::new();
+
+        for (index, (kind, source)) in segments.iter_mut().enumerate() {
+            if let Kind::Text = kind {
+                let (body, new_definitions) = footnote::extract(source);
+
+                *source = body;
+
+                if index < closed {
+                    self.settled.definitions.extend(new_definitions);
+                } else {
+                    definitions.extend(new_definitions);
+                }
+            }
+        }
+
+        self.settled.length += settled;
+        self.settled.segments.extend(segments.drain(..closed));
+
+        let segments: Vec<_> = self
+            .settled
+            .segments
+            .iter()
+            .cloned()
+            .chain(segments)
+            .collect();
+
+        let definitions: Vec<_> = self
+            .settled
+            .definitions
+            .iter()
+            .cloned()
+            .chain(definitions)
+            .collect();
+
+        let mut labels: Vec<String> = Vec::new();
+        let segments: Vec<_> = segments
+            .into_iter()
+            .map(|(kind, source)| match kind {
+                Kind::Text => {
+                    let source = number_footnotes(&source, &definitions, &mut labels);
+
+                    (kind, source)
+                }
+                Kind::Code { .. } => (kind, source),
+            })
+            .filter(|(kind, source)| !matches!(kind, Kind::Text) || !source.trim().is_empty())
+            .collect();
+
+        for definition in &definitions {
+            if !labels.contains(&definition.label) {
+                labels.push(definition.label.clone());
+            }
+        }
+
+        let notes = labels
+            .iter()
+            .enumerate()
+            .filter_map(|(index, label)| {
+                let definition = definitions
+                    .iter()
+                    .find(|definition| &definition.label == label)?;
+
+                Some(format!("\\[{}\\] {}\n\n", index + 1, definition.text))
+            })
+            .collect::<String>();
+
+        if notes.is_empty() {
+            self.notes = None;
+        } else if self
+            .notes
+            .as_ref()
+            .is_none_or(|block| block.source != notes)
+        {
+            self.notes = Some(Box::new(Block::text(notes)));
+        }
+
+        self.blocks.truncate(segments.len());
+
+        for (index, (kind, source)) in segments.into_iter().enumerate() {
+            match self.blocks.get_mut(index) {
+                Some(block) if block.source == source => {
+                    block.kind = kind;
+                }
+                Some(block)
+                    if std::mem::discriminant(&block.kind) == std::mem::discriminant(&kind)
+                        && source.starts_with(&block.source) =>
+                {
+                    block.content.push_str(&source[block.source.len()..]);
+                    block.kind = kind;
+                    block.source = source;
+                }
+                _ => {
+                    let block = Block {
+                        kind,
+                        content: markdown::Content::parse(&source),
+                        source,
+                    };
+
+                    if index < self.blocks.len() {
+                        self.blocks[index] = block;
+                    } else {
+                        self.blocks.push(block);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An interaction with the rendered [`Content`].
+#[derive(Debug, Clone)]
+pub enum Interaction {
+    LinkClicked(markdown::Url),
+    Copy(String),
+    Run(exec::Language, String),
+    OpenInEditor(Option<String>, String),
+}
+
+/// Displays the given [`Content`], with a language label, a copy button, and
+/// an edit button on top of every code block.
+///
+/// Code blocks in a language that can be run get a run button as well.
+///
+/// Any occurrence of the given query is highlighted.
+pub fn view<'a>(
+    content: &'a Content,
+    theme: &Theme,
+    appearance: Appearance,
+    query: Option<&str>,
+) -> Element<'a, Interaction> {
+    let settings = markdown::Settings::with_text_size(appearance.text_size.offset(0));
+    let style = markdown::Style::from_palette(theme.palette());
+    let font = appearance.chat_font.font();
+
+    column(content.blocks.iter().map(|block| {
+        let rendered = match block.kind {
+            Kind::Text => items(block.content.items(), settings, style, font, query),
+            Kind::Code { .. } => {
+                items(block.content.items(), settings, style, Font::DEFAULT, query)
+            }
+        }
+        .map(Interaction::LinkClicked);
+
+        match &block.kind {
+            Kind::Text => rendered,
+            Kind::Code { language, code } => {
+                let copy = tip(
+                    button(icon::clipboard().size(icon::scaled(12.0)))
+                        .on_press_with(move || Interaction::Copy(code.clone()))
+                        .padding([2, 7])
+                        .style(button::text),
+                    "Copy code",
+                    tip::Position::Left,
+                );
+
+                let run = language
+                    .as_deref()
+                    .and_then(exec::Language::parse)
+                    .map(|language| {
+                        tip(
+                            button(text("Run").size(12))
+                                .on_press_with(move || Interaction::Run(language, code.clone()))
+                                .padding([2, 7])
+                                .style(button::text),
+                            "Run in a sandbox",
+                            tip::Position::Left,
+                        )
+                    });
+
+                let edit = tip(
+                    button(text("Edit").size(12))
+                        .on_press_with(move || {
+                            Interaction::OpenInEditor(language.clone(), code.clone())
+                        })
+                        .padding([2, 7])
+                        .style(button::text),
+                    "Open in editor",
+                    tip::Position::Left,
+                );
+
+                column![
+                    row![
+                        text(language.as_deref().unwrap_or("code"))
+                            .size(12)
+                            .font(Font::MONOSPACE)
+                            .style(text::secondary),
+                        horizontal_space(),
+                    ]
+                    .push_maybe(run)
+                    .push(edit)
+                    .push(copy)
+                    .align_y(Center),
+                    rendered,
+                ]
+                .spacing(5)
+                .into()
+            }
+        }
+    }))
+    .push_maybe(content.notes.as_ref().map(|notes| {
+        column![
+            horizontal_rule(1),
+            items(
+                notes.content.items(),
+                markdown::Settings::with_text_size(appearance.text_size.offset(-4)),
+                style,
+                font,
+                query,
+            )
+            .map(Interaction::LinkClicked),
+        ]
+        .spacing(10)
+    }))
+    .spacing(settings.text_size)
+    .into()
+}
+
+/// Displays some Markdown items with the given font, highlighting any
+/// occurrence of the given lowercase query.
+///
+/// The items of iced are always drawn with the default font and cannot be
+/// highlighted, so those are laid out here instead.
+pub fn items<'a>(
+    items: &'a [markdown::Item],
+    settings: markdown::Settings,
+    style: markdown::Style,
+    font: Font,
+    query: Option<&str>,
+) -> Element<'a, markdown::Url> {
+    let query = query.filter(|query| !query.is_empty());
+
+    if font == Font::DEFAULT && query.is_none() {
+        return markdown(items, settings, style);
+    }
+
+    let spacing = settings.text_size * 0.625;
+    let marker = Color {
+        a: 0.4,
+        ..style.link_color
+    };
+
+    // Bold and italic spans keep their style, but not their family
+    let spans = |text: &markdown::Text| -> Vec<text::Span<'static, markdown::Url>> {
+        text.spans(style)
+            .iter()
+            .cloned()
+            .map(|span| {
+                let font = match span.font {
+                    Some(styled) if styled.family == font::Family::SansSerif => Font {
+                        family: font.family,
+                        ..styled
+                    },
+                    Some(styled) => styled,
+                    None => font,
+                };
+
+                span.font(font)
+            })
+            .flat_map(|span| match query {
+                Some(query) => highlight(span, query, marker),
+                None => vec![span],
+            })
+            .collect()
+    };
+
+    column(items.iter().enumerate().map(|(i, item)| {
+        match item {
+            markdown::Item::Heading(level, heading) => {
+                use markdown::HeadingLevel;
+
+                container(rich_text(spans(heading)).size(match level {
+                    HeadingLevel::H1 => settings.h1_size,
+                    HeadingLevel::H2 => settings.h2_size,
+                    HeadingLevel::H3 => settings.h3_size,
+                    HeadingLevel::H4 => settings.h4_size,
+                    HeadingLevel::H5 => settings.h5_size,
+                    HeadingLevel::H6 => settings.h6_size,
+                }))
+                .padding(padding::top(if i > 0 {
+                    settings.text_size.0 / 2.0
+                } else {
+                    0.0
+                }))
+                .into()
+            }
+            markdown::Item::Paragraph(paragraph) => {
+                rich_text(spans(paragraph)).size(settings.text_size).into()
+            }
+            markdown::Item::List { start, items: list } => {
+                column(list.iter().enumerate().map(|(n, list)| {
+                    let bullet = match start {
+                        Some(start) => format!("{}.", n as u64 + start),
+                        None => "•".to_owned(),
+                    };
+
+                    row![
+                        text(bullet).size(settings.text_size).font(font),
+                        self::items(list, settings, style, font, query)
+                    ]
+                    .spacing(spacing)
+                    .into()
+                }))
+                .spacing(spacing)
+                .into()
+            }
+            markdown::Item::CodeBlock(code) => container(
+                scrollable(
+                    container(
+                        rich_text(spans(code))
+                            .font(Font::MONOSPACE)
+                            .size(settings.code_size),
+                    )
+                    .padding(settings.text_size.0 / 2.0),
+                )
+                .direction(scrollable::Direction::Horizontal(
+                    scrollable::Scrollbar::default()
+                        .width(settings.text_size.0 / 2.0)
+                        .scroller_width(settings.text_size.0 / 2.0),
+                )),
+            )
+            .width(Fill)
+            .padding(settings.text_size.0 / 2.0)
+            .style(container::dark)
+            .into(),
+        }
+    }))
+    .width(Fill)
+    .spacing(settings.text_size)
+    .into()
+}
+
+/// Splits a span around the occurrences of the given lowercase query, giving
+/// them the background of a marker.
+fn highlight(
+    span: text::Span<'static, markdown::Url>,
+    query: &str,
+    marker: Color,
+) -> Vec<text::Span<'static, markdown::Url>> {
+    let ranges = occurrences(&span.text, query);
+
+    if ranges.is_empty() {
+        return vec![span];
+    }
+
+    let mut spans = Vec::with_capacity(ranges.len() * 2 + 1);
+    let mut offset = 0;
+
+    let part = |range: std::ops::Range<usize>| text::Span {
+        text: span.text[range].to_owned().into(),
+        ..span.clone()
+    };
+
+    for range in ranges {
+        if offset < range.start {
+            spans.push(part(offset..range.start));
+        }
+
+        offset = range.end;
+        spans.push(part(range).background(marker));
+    }
+
+    if offset < span.text.len() {
+        spans.push(part(offset..span.text.len()));
+    }
+
+    spans
+}
+
+/// The byte ranges of the text where the given lowercase query occurs,
+/// ignoring case.
+fn occurrences(text: &str, query: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while let Some(rest) = text.get(start..).filter(|rest| !rest.is_empty()) {
+        let mut query = query.chars().peekable();
+        let mut end = None;
+
+        'chars: for (offset, c) in rest.char_indices() {
+            for lower in c.to_lowercase() {
+                if query.next() != Some(lower) {
+                    break 'chars;
+                }
+            }
+
+            if query.peek().is_none() {
+                end = Some(start + offset + c.len_utf8());
+                break;
+            }
+        }
+
+        match end {
+            Some(end) => {
+                ranges.push(start..end);
+                start = end;
+            }
+            None => {
+                start += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+
+    ranges
+}
+
+/// Replaces the footnote references in some Markdown with their numbers.
+///
+/// References to footnotes with a web address link to it. An unfinished
+/// reference at the end is hidden until the rest of it is streamed.
+fn number_footnotes(
+    markdown: &str,
+    definitions: &[footnote::Definition],
+    labels: &mut Vec<String>,
+) -> String {
+    let markdown = match footnote::unfinished(markdown) {
+        Some(start) => &markdown[..start],
+        None => markdown,
+    };
+
+    let mut numbered = String::with_capacity(markdown.len());
+    let mut offset = 0;
+
+    for (range, label) in footnote::references(markdown) {
+        let number = match labels.iter().position(|candidate| candidate == label) {
+            Some(index) => index + 1,
+            None => {
+                labels.push(label.to_owned());
+                labels.len()
+            }
+        };
+
+        let url = definitions
+            .iter()
+            .find(|definition| definition.label == label)
+            .and_then(footnote::Definition::url);
+
+        numbered.push_str(&markdown[offset..range.start]);
+
+        match url {
+            Some(url) => numbered.push_str(&format!("[\\[{number}\\]]({url})")),
+            None => numbered.push_str(&format!("\\[{number}\\]")),
+        }
+
+        offset = range.end;
+    }
+
+    numbered.push_str(&markdown[offset..]);
+    numbered
+}
+
+/// Markdown split into runs of text and fenced code blocks.
+struct Split {
+    segments: Vec<(Kind, String)>,
+    /// The length of the Markdown up to the last closed code block.
+    settled: usize,
+    /// The amount of segments up to the last closed code block.
+    closed: usize,
+}
+
+/// Splits Markdown into runs of text and fenced code blocks.
+///
+/// A code block without a closing fence extends to the end, which is how
+/// a block looks while it is being streamed.
+fn split(markdown: &str) -> Split {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut code: Option<(Fence, Option<String>, String, String)> = None;
+    let mut offset = 0;
+    let mut settled = 0;
+    let mut closed = 0;
+
+    for line in markdown.split_inclusive('\n') {
+        offset += line.len();
+
+        match &mut code {
+            Some((fence, language, source, code_text)) => {
+                if fence.is_closed_by(line) {
+                    segments.push((
+                        Kind::Code {
+                            language: language.take(),
+                            code: std::mem::take(code_text),
+                        },
+                        std::mem::take(source),
+                    ));
+
+                    code = None;
+
+                    // A closing fence may still grow until its line ends
+                    if line.ends_with('\n') {
+                        settled = offset;
+                        closed = segments.len();
+                    }
+                } else {
+                    source.push_str(line);
+                    code_text.push_str(line);
+                }
+            }
+            None => {
+                if let Some((fence, language)) = Fence::open(line) {
+                    if !text.trim().is_empty() {
+                        segments.push((Kind::Text, std::mem::take(&mut text)));
+                    }
+
+                    code = Some((fence, language, line.to_owned(), String::new()));
+                } else {
+                    text.push_str(line);
+                }
+            }
+        }
+    }
+
+    if let Some((_fence, language, source, code)) = code {
+        segments.push((Kind::Code { language, code }, source));
+    } else if !text.trim().is_empty() {
+        segments.push((Kind::Text, text));
+    }
+
+    Split {
+        segments,
+        settled,
+        closed,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Fence {
+    marker: char,
+    length: usize,
+}
+
+impl Fence {
+    fn open(line: &str) -> Option<(Self, Option<String>)> {
+        let line = line.trim_end();
+        let trimmed = line.trim_start_matches(' ');
+
+        if line.len() - trimmed.len() > 3 {
+            return None;
+        }
+
+        let marker = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+        let length = trimmed.chars().take_while(|c| *c == marker).count();
+
+        if length < 3 {
+            return None;
+        }
+
+        let info = trimmed[length..].trim();
+
+        if marker == '`' && info.contains('`') {
+            return None;
+        }
+
+        let language = info
+            .split_whitespace()
+            .next()
+            .map(str::to_owned)
+            .filter(|language| !language.is_empty());
+
+        Some((Self { marker, length }, language))
+    }
+
+    fn is_closed_by(self, line: &str) -> bool {
+        let trimmed = line.trim();
+        let length = trimmed.chars().take_while(|c| *c == self.marker).count();
+
+        length >= self.length && trimmed.len() == length * self.marker.len_utf8()
+    }
+}